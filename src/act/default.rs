@@ -62,10 +62,10 @@ impl Plugin for ModelSwitchPlugin {
             .get("model")
             .ok_or_else(|| Error::msg("Model name not provided"))?;
 
-        // Validate model exists in supported models
-        if !crate::api::constants::SUPPORTED_MODELS.contains_key(new_model.as_str())
-            && !new_model.starts_with("openrouter/")
-            && !new_model.starts_with("ollama/")
+        // Validate model exists in the model catalogue (or the supported models)
+        if crate::api::catalogue::ModelCatalogue::load_cached()
+            .get_provider_for_model(new_model)
+            .is_err()
         {
             return Err(Error::msg(format!("Unsupported model: {}", new_model)));
         }
@@ -14,7 +14,9 @@
 //! ## Modules
 //! TBD
 
+use crate::api::tools::ToolDefinition;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
 
 // Represents a single parameter for an action
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,6 +43,58 @@ pub struct ActionSchema {
     pub response_format: String, // Or an enum
 }
 
+impl ActionSchema {
+    /// Returns the action as a tool the model can call (see `nyota::api::tools`), with its parameters as a JSON Schema object.
+    pub fn to_tool_definition(&self) -> ToolDefinition {
+        let mut properties = Map::new();
+        for parameter in &self.parameters {
+            let mut property = json!({
+                "type": get_json_schema_type(&parameter.data_type),
+                "description": parameter.description,
+            });
+            if let Some(allowed_values) = &parameter.allowed_values {
+                property["enum"] = json!(allowed_values);
+            }
+            if let Some(default_value) = &parameter.default_value {
+                property["default"] = json!(default_value);
+            }
+            properties.insert(parameter.name.clone(), property);
+        }
+        let required: Vec<&str> = self
+            .parameters
+            .iter()
+            .filter(|parameter| parameter.is_required)
+            .map(|parameter| parameter.name.as_str())
+            .collect();
+
+        ToolDefinition::new(
+            &self.name,
+            &self.description,
+            json!({
+                "type": "object",
+                "properties": Value::Object(properties),
+                "required": required,
+            }),
+        )
+    }
+}
+
+// Maps a parameter data type to its JSON Schema type (custom types, i.e. `address`, are sent as strings)
+fn get_json_schema_type(data_type: &str) -> &'static str {
+    match data_type.to_lowercase().as_str() {
+        "number" | "float" => "number",
+        "integer" | "int" => "integer",
+        "boolean" | "bool" => "boolean",
+        "array" => "array",
+        _ => "string",
+    }
+}
+
+// Returns the built-in actions, which can be offered to the model as tools
+pub fn get_builtin_actions() -> Vec<ActionSchema> {
+    vec![create_get_balance_action()]
+}
+
 // Example usage (for a built-in action)
 pub fn create_get_balance_action() -> ActionSchema {
     ActionSchema {
@@ -86,7 +140,7 @@ pub fn create_get_balance_action() -> ActionSchema {
 }
 
 // Dummy callback function for demonstration
-fn get_balance_callback(params: Vec<String>) -> String {
+fn _get_balance_callback(params: Vec<String>) -> String {
     // In a real implementation, this function would:
     // 1. Parse the parameters (chain, address).
     // 2. Call the appropriate blockchain API.
@@ -0,0 +1,222 @@
+//! # API Attachments
+//! This module contains the local files which can be attached to a message: images and text files.
+//! Images (PNG, JPEG, GIF & WebP) are sent base64-encoded, as OpenAI-style `image_url` content parts, Anthropic `image` blocks
+//! or Ollama `images`, and need a model with vision (see `nyota::api::model_info`).
+//! Any other file is read as text and inlined into the message under a header naming the file, so every model can read it.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use nyota::api::attachment::Attachment;
+//! use nyota::api::conversation::ChatMessage;
+//! use nyota::api::utilities::Adapter;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let adapter = Adapter::new();
+//! let message = ChatMessage::user("What is wrong in this screenshot?").with_attachments(vec![
+//!     Attachment::load("screenshot.png")?,
+//!     Attachment::load("src/main.rs")?,
+//! ]);
+//! let reply = adapter.send_conversation(&[message]).await?;
+//! println!("{}", reply.content);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::api::constants::*;
+use anyhow::{Error, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+/// Attachment is a local file attached to a message.
+/// Attachments are saved along with the messages of a chat session (see `nyota::ctx::session`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Attachment {
+    /// An image, sent to models with vision.
+    Image {
+        /// The file name of the image.
+        name: String,
+        /// The media type of the image (i.e. `image/png`).
+        media_type: String,
+        /// The image data, base64-encoded.
+        data: String,
+    },
+    /// A text file, inlined into the message.
+    Text {
+        /// The file name of the text file.
+        name: String,
+        /// The contents of the text file.
+        content: String,
+    },
+}
+
+impl Attachment {
+    /// Loads the file at the specified path as an attachment: an image if it has an image extension, otherwise a text file.
+    /// Returns an error if the file cannot be read, is too large, or is neither an image nor UTF-8 text.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let name = path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+        let unreadable = |message: &dyn std::fmt::Display| {
+            Error::msg(format!(
+                "🔴[ATTACHMENT] Error: Unable to attach {} | {}",
+                path.display(),
+                message
+            ))
+        };
+
+        let file_size = fs::metadata(path).map_err(|e| unreadable(&e))?.len();
+        let media_type = get_image_media_type(path);
+        let max_bytes = match media_type {
+            Some(_) => MAX_IMAGE_ATTACHMENT_BYTES,
+            None => MAX_TEXT_ATTACHMENT_BYTES,
+        };
+        if file_size > max_bytes {
+            return Err(unreadable(&format!(
+                "{} bytes is over the limit of {} bytes",
+                file_size, max_bytes
+            )));
+        }
+
+        let bytes = fs::read(path).map_err(|e| unreadable(&e))?;
+        match media_type {
+            Some(media_type) => Ok(Self::Image {
+                name,
+                media_type: String::from(media_type),
+                data: STANDARD.encode(bytes),
+            }),
+            None => {
+                let content = String::from_utf8(bytes)
+                    .map_err(|_| unreadable(&"not an image or a UTF-8 text file"))?;
+                Ok(Self::Text { name, content })
+            }
+        }
+    }
+
+    /// Returns the file name of the attachment.
+    pub fn get_name(&self) -> &str {
+        match self {
+            Self::Image { name, .. } | Self::Text { name, .. } => name,
+        }
+    }
+
+    /// Returns true if the attachment is an image.
+    pub fn is_image(&self) -> bool {
+        matches!(self, Self::Image { .. })
+    }
+
+    /// Returns the image as a `data:` URL (i.e. `data:image/png;base64,...`), or `None` for a text file.
+    pub fn get_data_url(&self) -> Option<String> {
+        match self {
+            Self::Image {
+                media_type, data, ..
+            } => Some(format!("data:{};base64,{}", media_type, data)),
+            Self::Text { .. } => None,
+        }
+    }
+}
+
+/// Returns the media type of the file if its extension is one of the supported image formats.
+fn get_image_media_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    IMAGE_MEDIA_TYPES
+        .iter()
+        .find(|(image_extension, _)| *image_extension == extension)
+        .map(|(_, media_type)| *media_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, path::PathBuf};
+
+    /// Returns an empty temporary directory for a test.
+    fn get_test_dir(name: &str) -> Result<PathBuf> {
+        let dir = env::temp_dir().join(format!("nyota-attachment-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    #[test]
+    fn files_are_loaded_as_images_or_text() -> Result<()> {
+        let dir = get_test_dir("load")?;
+        let png = [0x89, b'P', b'N', b'G'];
+        fs::write(dir.join("screenshot.PNG"), png)?;
+        fs::write(dir.join("main.rs"), "fn main() {}")?;
+
+        let image = Attachment::load(dir.join("screenshot.PNG"))?;
+        assert_eq!(
+            image,
+            Attachment::Image {
+                name: String::from("screenshot.PNG"),
+                media_type: String::from("image/png"),
+                data: STANDARD.encode(png),
+            }
+        );
+        assert!(image.is_image());
+        assert_eq!(
+            image.get_data_url(),
+            Some(format!("data:image/png;base64,{}", STANDARD.encode(png)))
+        );
+
+        let text = Attachment::load(dir.join("main.rs"))?;
+        assert_eq!(
+            text,
+            Attachment::Text {
+                name: String::from("main.rs"),
+                content: String::from("fn main() {}"),
+            }
+        );
+        assert!(!text.is_image());
+        assert_eq!(text.get_data_url(), None);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported_files_are_rejected() -> Result<()> {
+        let dir = get_test_dir("reject")?;
+        fs::write(dir.join("data.bin"), [0xff, 0xfe, 0x00])?;
+        let too_large = usize::try_from(MAX_TEXT_ATTACHMENT_BYTES)? + 1;
+        fs::write(dir.join("large.txt"), "a".repeat(too_large))?;
+
+        for (file_name, expected) in [
+            ("data.bin", "not an image or a UTF-8 text file"),
+            ("large.txt", "is over the limit of"),
+            ("missing.txt", "Unable to attach"),
+        ] {
+            let error = Attachment::load(dir.join(file_name))
+                .err()
+                .map(|e| e.to_string())
+                .unwrap_or_default();
+            assert!(error.contains(expected), "{}: {}", file_name, error);
+        }
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn image_media_types_follow_the_extension() {
+        for (file_name, expected) in [
+            ("photo.jpg", Some("image/jpeg")),
+            ("photo.JPEG", Some("image/jpeg")),
+            ("anim.gif", Some("image/gif")),
+            ("image.webp", Some("image/webp")),
+            ("notes.txt", None),
+            ("Makefile", None),
+        ] {
+            assert_eq!(
+                get_image_media_type(Path::new(file_name)),
+                expected,
+                "{}",
+                file_name
+            );
+        }
+    }
+}
@@ -0,0 +1,313 @@
+//! # API Model Catalogue
+//! This module contains the catalogue of models offered by each API provider.
+//! Model lists are fetched from the providers themselves (OpenAI & Anthropic `/v1/models`, OpenRouter `/api/v1/models`,
+//! Ollama `/api/tags` and OpenAI-compatible servers `/models`) and cached on disk (`~/.cache/nyota/models.json`) with a TTL.
+//! While a provider's list is fresh it is authoritative for validating model names; otherwise models are validated
+//! against the built-in `SUPPORTED_MODELS` list, and prefixed models (i.e. `ollama/`) are routed by prefix.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use nyota::api::utilities::{Adapter, ApiProvider};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let mut adapter = Adapter::new();
+//! for model in adapter.list_models(ApiProvider::OLLAMA).await? {
+//!     println!("{}", model); // i.e. `ollama/llama3.2:latest`
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::api::constants::*;
+use crate::api::utilities::ApiProvider;
+use anyhow::{Error, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, fs, path::PathBuf, time::Duration};
+
+/// ModelList is the list of models offered by a single provider, as fetched at a point in time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelList {
+    /// When the list was fetched, in seconds since the Unix epoch.
+    pub fetched_at: i64,
+    /// The model names, as used by nyota (i.e. prefixed with `ollama/` for Ollama models).
+    pub models: Vec<String>,
+}
+
+impl ModelList {
+    /// Creates a new ModelList fetched now.
+    pub fn new(models: Vec<String>) -> Self {
+        Self {
+            fetched_at: Utc::now().timestamp(),
+            models,
+        }
+    }
+
+    /// Returns true if the list was fetched within the specified TTL.
+    pub fn is_fresh(&self, ttl: Duration) -> bool {
+        let age_secs = Utc::now().timestamp().saturating_sub(self.fetched_at);
+        u64::try_from(age_secs).is_ok_and(|age_secs| age_secs < ttl.as_secs())
+    }
+
+    /// Returns true if the list contains the model.
+    /// Ollama model names without a tag match the `:latest` tag (i.e. `ollama/llama3.2` matches `ollama/llama3.2:latest`).
+    pub fn contains(&self, model_name: &str) -> bool {
+        self.models.iter().any(|model| {
+            model == model_name
+                || model
+                    .strip_suffix(OLLAMA_LATEST_TAG)
+                    .is_some_and(|untagged_model| untagged_model == model_name)
+        })
+    }
+}
+
+/// ModelCatalogue holds the cached model list of each provider.
+#[derive(Debug, Clone)]
+pub struct ModelCatalogue {
+    model_lists: HashMap<ApiProvider, ModelList>,
+    ttl: Duration,
+}
+
+impl ModelCatalogue {
+    /// Creates a new, empty ModelCatalogue using the default TTL.
+    pub fn new() -> Self {
+        Self {
+            model_lists: HashMap::new(),
+            ttl: Duration::from_secs(DEFAULT_MODEL_CACHE_TTL_SECS),
+        }
+    }
+
+    /// Creates a new ModelCatalogue from the cache file.
+    /// A missing or unreadable cache file yields an empty catalogue.
+    pub fn load_cached() -> Self {
+        let mut catalogue = Self::new();
+        if let Some(model_lists) = get_cache_file_path()
+            .and_then(|cache_path| fs::read_to_string(cache_path).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+        {
+            catalogue.model_lists = model_lists;
+        }
+        catalogue
+    }
+
+    /// Writes the catalogue to the cache file.
+    pub fn save(&self) -> Result<(), Error> {
+        let cache_path = get_cache_file_path().ok_or_else(|| {
+            Error::msg(
+                "🟡[CATALOGUE] 🔍🗂️ Warning: No cache directory to save the model catalogue.",
+            )
+        })?;
+        if let Some(cache_dir) = cache_path.parent() {
+            fs::create_dir_all(cache_dir)?;
+        }
+        fs::write(
+            &cache_path,
+            serde_json::to_string_pretty(&self.model_lists)?,
+        )
+        .map_err(|e| {
+            Error::msg(format!(
+                "🟡[CATALOGUE] 💔🗂️ Warning: Unable to write {} | {}",
+                cache_path.display(),
+                e
+            ))
+        })
+    }
+
+    /// Returns the cached model list of the provider, if it is still fresh.
+    pub fn get_models(&self, provider: &ApiProvider) -> Option<&[String]> {
+        self.model_lists
+            .get(provider)
+            .filter(|model_list| model_list.is_fresh(self.ttl))
+            .map(|model_list| model_list.models.as_slice())
+    }
+
+    /// Replaces the cached model list of the provider.
+    pub fn set_models(&mut self, provider: ApiProvider, models: Vec<String>) {
+        self.model_lists.insert(provider, ModelList::new(models));
+    }
+
+    /// Sets how long a fetched model list stays fresh.
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = ttl;
+    }
+
+    /// Returns the provider serving the specified model, or an Error if the model is unknown.
+    /// Prefixed models are routed to the provider of their prefix, other models are looked up in the fresh model lists
+    /// and then in the built-in `SUPPORTED_MODELS` list. A model missing from its provider's fresh list is rejected.
+    pub fn get_provider_for_model(&self, model_name: &str) -> Result<ApiProvider, Error> {
+        let provider = get_provider_from_prefix(model_name)
+            .or_else(|| {
+                self.model_lists
+                    .iter()
+                    .find(|(_, model_list)| {
+                        model_list.is_fresh(self.ttl) && model_list.contains(model_name)
+                    })
+                    .map(|(provider, _)| *provider)
+            })
+            .or_else(|| SUPPORTED_MODELS.get(model_name).copied())
+            .ok_or_else(|| {
+                Error::msg(format!(
+                    "🟡[ADAPTER] 💔 Warning: Invalid/Unknown Model | `{}` API Provider Unsupported!",
+                    model_name
+                ))
+            })?;
+
+        match self.model_lists.get(&provider) {
+            Some(model_list)
+                if model_list.is_fresh(self.ttl) && !model_list.contains(model_name) =>
+            {
+                Err(Error::msg(format!(
+                    "🟡[ADAPTER] 💔 Warning: Invalid/Unknown Model | `{}` is not offered by {}",
+                    model_name, provider
+                )))
+            }
+            _ => Ok(provider),
+        }
+    }
+}
+
+impl Default for ModelCatalogue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the path of the model catalogue cache file (i.e. `~/.cache/nyota/models.json`), if the user's cache directory is known.
+pub fn get_cache_file_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|cache_dir| cache_dir.join(CONFIG_DIR_NAME).join(MODEL_CACHE_FILE_NAME))
+}
+
+/// Reads the model names from the body of a provider's model list response.
+/// OpenAI, Anthropic, OpenRouter and OpenAI-compatible servers list models under `data[].id`, Ollama under `models[].name`.
+/// Names of OpenRouter, Ollama and OpenAI-compatible models are given their nyota prefix.
+pub fn parse_model_list(
+    provider: ApiProvider,
+    json_response: &Value,
+) -> Result<Vec<String>, Error> {
+    let (entries, name_field) = match provider {
+        ApiProvider::OLLAMA => (&json_response["models"], "name"),
+        _ => (&json_response["data"], "id"),
+    };
+    let entries = entries.as_array().ok_or_else(|| {
+        Error::msg(format!(
+            "🔴[CATALOGUE] 💔🗂️ Error: {} returned no model list.",
+            provider
+        ))
+    })?;
+    let model_prefix = match provider {
+        ApiProvider::OPENAI | ApiProvider::ANTHROPIC => "",
+        ApiProvider::OPENROUTER => OPENROUTER_MODEL_PREFIX,
+        ApiProvider::OLLAMA => OLLAMA_MODEL_PREFIX,
+        ApiProvider::OPENAI_COMPATIBLE => OPENAI_COMPATIBLE_MODEL_PREFIX,
+    };
+
+    let mut models: Vec<String> = entries
+        .iter()
+        .filter_map(|entry| entry[name_field].as_str())
+        .map(|model| format!("{}{}", model_prefix, model))
+        .collect();
+    models.sort();
+    Ok(models)
+}
+
+/// Returns the provider a model is routed to by its prefix (i.e. `ollama/`), if it has one.
+fn get_provider_from_prefix(model_name: &str) -> Option<ApiProvider> {
+    if model_name.starts_with(OPENROUTER_MODEL_PREFIX) {
+        Some(ApiProvider::OPENROUTER)
+    } else if model_name.starts_with(OLLAMA_MODEL_PREFIX) {
+        Some(ApiProvider::OLLAMA)
+    } else if model_name.starts_with(OPENAI_COMPATIBLE_MODEL_PREFIX) {
+        Some(ApiProvider::OPENAI_COMPATIBLE)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn model_lists_are_parsed_per_provider() -> Result<()> {
+        let cases = [
+            (
+                ApiProvider::OPENAI,
+                json!({"object": "list", "data": [
+                    {"id": "gpt-4o-mini", "object": "model", "created": 1721172741, "owned_by": "system"},
+                    {"id": "gpt-4o", "object": "model", "created": 1715367049, "owned_by": "system"}
+                ]}),
+                vec!["gpt-4o", "gpt-4o-mini"],
+            ),
+            (
+                ApiProvider::ANTHROPIC,
+                json!({"data": [
+                    {"type": "model", "id": "claude-3-5-haiku-20241022", "display_name": "Claude Haiku 3.5", "created_at": "2024-10-22T00:00:00Z"}
+                ], "has_more": false, "first_id": "claude-3-5-haiku-20241022", "last_id": "claude-3-5-haiku-20241022"}),
+                vec!["claude-3-5-haiku-20241022"],
+            ),
+            (
+                ApiProvider::OPENROUTER,
+                json!({"data": [
+                    {"id": "openai/gpt-4o-mini", "canonical_slug": "openai/gpt-4o-mini", "name": "OpenAI: GPT-4o-mini", "context_length": 128000,
+                     "pricing": {"prompt": "0.00000015", "completion": "0.0000006"}},
+                    {"id": "anthropic/claude-3.5-haiku", "name": "Anthropic: Claude 3.5 Haiku", "context_length": 200000}
+                ]}),
+                vec![
+                    "openrouter/anthropic/claude-3.5-haiku",
+                    "openrouter/openai/gpt-4o-mini",
+                ],
+            ),
+            (
+                ApiProvider::OLLAMA,
+                json!({"models": [
+                    {"name": "llama3.2:latest", "model": "llama3.2:latest", "modified_at": "2026-09-30T14:02:11.5Z", "size": 2019393189,
+                     "digest": "a80c4f17acd5", "details": {"format": "gguf", "family": "llama", "parameter_size": "3.2B", "quantization_level": "Q4_K_M"}}
+                ]}),
+                vec!["ollama/llama3.2:latest"],
+            ),
+            (
+                ApiProvider::OPENAI_COMPATIBLE,
+                json!({"object": "list", "data": [{"id": "mistral-7b-instruct", "object": "model", "owned_by": "vllm"}]}),
+                vec!["openai-compatible/mistral-7b-instruct"],
+            ),
+            // Entries without a name are skipped, and an empty list is still a list
+            (
+                ApiProvider::OPENAI,
+                json!({"data": [{"object": "model"}, {"id": "o3-mini"}]}),
+                vec!["o3-mini"],
+            ),
+            (ApiProvider::OLLAMA, json!({"models": []}), vec![]),
+        ];
+        for (provider, json_response, expected) in cases {
+            assert_eq!(
+                parse_model_list(provider, &json_response)?,
+                expected,
+                "{}",
+                provider
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn responses_without_a_model_list_are_errors() {
+        for (provider, json_response) in [
+            (
+                ApiProvider::OPENAI,
+                json!({"error": {"message": "Incorrect API key provided.", "type": "invalid_request_error"}}),
+            ),
+            // Ollama lists its models under `models`, not `data`
+            (ApiProvider::OLLAMA, json!({"data": [{"id": "llama3.2"}]})),
+            (ApiProvider::ANTHROPIC, json!({"data": null})),
+        ] {
+            assert!(
+                parse_model_list(provider, &json_response)
+                    .is_err_and(|e| e.to_string().contains("returned no model list")),
+                "{}",
+                provider
+            );
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! # API Comparison
+//! This module contains the comparison of models: the same conversation sent to several models at once, to set their replies side by side.
+//! Each model is asked by its own Adapter, cloned from the caller's (so it keeps the persona, generation parameters and API keys,
+//! and shares the pooled HTTP client and usage ledger), and every request runs concurrently on the tokio runtime.
+//! Comparisons ask each model itself, so the fallback chain is not used. A model which fails does not stop the others:
+//! every entry holds its own reply or error, with the latency and tokens used.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use nyota::api::compare::compare_models;
+//! use nyota::api::conversation::ChatMessage;
+//! use nyota::api::utilities::Adapter;
+//!
+//! # async fn example() {
+//! let adapter = Adapter::new();
+//! let models = [String::from("gpt-4o-mini"), String::from("claude-3-5-haiku-20241022")];
+//! let entries = compare_models(&adapter, &models, &[ChatMessage::user("Name a prime number.")]).await;
+//! for entry in &entries {
+//!     match &entry.outcome {
+//!         Ok(reply) => println!("{} ({} ms): {}", entry.model, entry.latency.as_millis(), reply.content),
+//!         Err(e) => println!("{}: {}", entry.model, e),
+//!     }
+//! }
+//! # }
+//! ```
+
+use crate::api::conversation::{ChatMessage, LlmResponse};
+use crate::api::fallback::FallbackChain;
+use crate::api::utilities::{Adapter, ApiProvider};
+use anyhow::Error;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+/// ComparisonEntry is the reply of one model to a compared conversation.
+#[derive(Debug)]
+pub struct ComparisonEntry {
+    /// The model asked.
+    pub model: String,
+    /// The provider which offers the model, or `None` if no provider is known to offer it.
+    pub provider: Option<ApiProvider>,
+    /// The time from sending the conversation until the reply was read.
+    pub latency: Duration,
+    /// The reply, or the reason the model did not answer.
+    pub outcome: Result<LlmResponse, Error>,
+}
+
+impl ComparisonEntry {
+    /// Returns the entry as JSON: the provider, model, latency, reply, tool calls, tokens used and cost, or the error.
+    /// A reply given from the response cache is marked `"cached": true`.
+    pub fn to_json(&self) -> Value {
+        let mut entry = json!({
+            "model": self.model,
+            "provider": self.provider.map(|provider| provider.as_str()),
+            "latency_ms": self.latency.as_millis() as u64,
+        });
+        match &self.outcome {
+            Ok(reply) => {
+                entry["content"] = json!(reply.content);
+                if !reply.tool_calls.is_empty() {
+                    entry["tool_calls"] = json!(reply.tool_calls);
+                }
+                entry["usage"] = json!(reply.usage);
+                entry["cost"] = json!(reply.cost);
+                if reply.cached {
+                    entry["cached"] = json!(true);
+                }
+            }
+            Err(e) => entry["error"] = json!(e.to_string()),
+        }
+        entry
+    }
+}
+
+/// Sends the conversation to every model concurrently, each through its own clone of the Adapter, and waits for all of them.
+/// Returns one entry per model, in the order the models were given.
+pub async fn compare_models(
+    api_adapter: &Adapter,
+    models: &[String],
+    messages: &[ChatMessage],
+) -> Vec<ComparisonEntry> {
+    let mut requests = JoinSet::new();
+    for (index, model) in models.iter().enumerate() {
+        let mut model_adapter = api_adapter.clone();
+        model_adapter.set_fallback_chain(FallbackChain::new());
+        let model = model.clone();
+        let messages = messages.to_vec();
+        requests.spawn(async move {
+            let started_at = Instant::now();
+            let (provider, outcome) = match model_adapter.use_model(&model) {
+                Ok(()) => (
+                    Some(model_adapter.get_current_provider()),
+                    model_adapter.send_conversation(&messages).await,
+                ),
+                Err(e) => (None, Err(e)),
+            };
+            let entry = ComparisonEntry {
+                model,
+                provider,
+                latency: started_at.elapsed(),
+                outcome,
+            };
+            (index, entry)
+        });
+    }
+
+    let mut entries: Vec<Option<ComparisonEntry>> = models.iter().map(|_| None).collect();
+    while let Some(joined) = requests.join_next().await {
+        if let Ok((index, entry)) = joined {
+            entries[index] = Some(entry);
+        }
+    }
+    // A request which stopped (i.e. panicked) is reported as such rather than dropped
+    entries
+        .into_iter()
+        .zip(models)
+        .map(|(entry, model)| {
+            entry.unwrap_or_else(|| ComparisonEntry {
+                model: model.clone(),
+                provider: None,
+                latency: Duration::ZERO,
+                outcome: Err(Error::msg(
+                    "🔴[COMPARE] Error: The request stopped before a reply.",
+                )),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::utilities::tests::{
+        get_completion, get_response, get_test_fallback_adapter, serve_responses,
+    };
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn a_model_which_fails_does_not_drop_the_others() -> Result<()> {
+        let (base_url, server) = serve_responses(vec![get_completion("Seven")])?;
+        // OpenRouter rejects the request, and is not retried or fallen back from
+        let (openrouter_url, openrouter_server) = serve_responses(vec![get_response(
+            "400 Bad Request",
+            "",
+            r#"{"error": {"message": "Invalid 'messages'", "type": "invalid_request_error"}}"#,
+        )])?;
+        let adapter = get_test_fallback_adapter(&base_url, &openrouter_url)?;
+        let models = [
+            String::from("openrouter/openai/gpt-4o-mini"),
+            String::from("gpt-4o-mini"),
+            String::from("unknown-model"),
+        ];
+
+        let entries = compare_models(
+            &adapter,
+            &models,
+            &[ChatMessage::user("Name a prime number.")],
+        )
+        .await;
+        let summary: Vec<(&str, Option<ApiProvider>, Option<&str>)> = entries
+            .iter()
+            .map(|entry| {
+                let content = entry
+                    .outcome
+                    .as_ref()
+                    .ok()
+                    .map(|reply| reply.content.as_str());
+                (entry.model.as_str(), entry.provider, content)
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (
+                    "openrouter/openai/gpt-4o-mini",
+                    Some(ApiProvider::OPENROUTER),
+                    None
+                ),
+                ("gpt-4o-mini", Some(ApiProvider::OPENAI), Some("Seven")),
+                ("unknown-model", None, None),
+            ]
+        );
+
+        // Each failure is reported in its own entry
+        let errors: Vec<String> = entries
+            .iter()
+            .map(|entry| {
+                entry.to_json()["error"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string()
+            })
+            .collect();
+        assert!(errors[0].contains("Invalid 'messages'"), "{}", errors[0]);
+        assert!(errors[1].is_empty(), "{}", errors[1]);
+        assert!(errors[2].contains("unknown-model"), "{}", errors[2]);
+
+        assert_eq!(server.join().ok().map(|requests| requests.len()), Some(1));
+        assert_eq!(
+            openrouter_server.join().ok().map(|requests| requests.len()),
+            Some(1)
+        );
+        Ok(())
+    }
+}
@@ -3,34 +3,302 @@
 
 use super::utilities::ApiProvider;
 use std::collections::HashMap;
+use std::ops::RangeInclusive;
 use std::sync::LazyLock;
 
-// *** API URLs for LLM Providers***
-// used by the Adapter for sending requests
-pub const OPENAI_API_URL: &str = "https://api.openai.com/v1/chat/completions";
-pub const ANTHROPIC_API_URL: &str = "https://api.anthropic.com/v1/messages";
-pub const OPENROUTER_API_URL: &str = "https://openrouter.ai/api/v1/chat/completions";
-pub const OLLAMA_API_URL: &str = "http://localhost:11434/api/generate";
+// *** API Base URLs for LLM Providers ***
+// used by the Adapter for sending requests (overridable via env or config file)
+pub const OPENAI_API_BASE_URL: &str = "https://api.openai.com/v1";
+pub const ANTHROPIC_API_BASE_URL: &str = "https://api.anthropic.com/v1";
+pub const OPENROUTER_API_BASE_URL: &str = "https://openrouter.ai/api/v1";
+pub const OLLAMA_API_BASE_URL: &str = "http://localhost:11434";
+pub const OPENAI_COMPATIBLE_API_BASE_URL: &str = "http://localhost:8000/v1";
+
+// *** API Paths for LLM Providers ***
+// appended to the base URLs above
+/// The chat path shared by OpenAI, OpenRouter and OpenAI-compatible servers.
+pub const OPENAI_CHAT_PATH: &str = "/chat/completions";
+/// The chat path of the Anthropic messages API.
+pub const ANTHROPIC_CHAT_PATH: &str = "/messages";
+/// The chat path of the Ollama API.
+pub const OLLAMA_CHAT_PATH: &str = "/api/chat";
+/// The model list path shared by OpenAI, OpenRouter and OpenAI-compatible servers.
+pub const MODELS_PATH: &str = "/models";
+/// The model list path of the Anthropic API (which pages the list, 20 models by default).
+pub const ANTHROPIC_MODELS_PATH: &str = "/models?limit=1000";
+/// The model list path of the Ollama API.
+pub const OLLAMA_MODELS_PATH: &str = "/api/tags";
+/// The embeddings path shared by OpenAI and OpenAI-compatible servers.
+pub const EMBEDDINGS_PATH: &str = "/embeddings";
+/// The embeddings path of the Ollama API (which embeds one text per request).
+pub const OLLAMA_EMBEDDINGS_PATH: &str = "/api/embeddings";
+
+// *** API Endpoint Overrides ***
+/// Environment variables which override the base URL of each provider.
+pub const OPENAI_BASE_URL_ENV: &str = "NYOTA_OPENAI_BASE_URL";
+pub const ANTHROPIC_BASE_URL_ENV: &str = "NYOTA_ANTHROPIC_BASE_URL";
+pub const OPENROUTER_BASE_URL_ENV: &str = "NYOTA_OPENROUTER_BASE_URL";
+pub const OLLAMA_BASE_URL_ENV: &str = "NYOTA_OLLAMA_BASE_URL";
+pub const OPENAI_COMPATIBLE_BASE_URL_ENV: &str = "NYOTA_OPENAI_COMPATIBLE_BASE_URL";
+/// The name of the nyota directory within the user's config directory (i.e. `~/.config/nyota`).
+pub const CONFIG_DIR_NAME: &str = "nyota";
+/// The name of the nyota config file within the nyota config directory.
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+/// The config file table which holds base URL overrides keyed by provider name.
+pub const CONFIG_ENDPOINTS_TABLE: &str = "endpoints";
+
+// *** Model Catalogue ***
+/// The name of the model catalogue cache file within the nyota cache directory (i.e. `~/.cache/nyota`).
+pub const MODEL_CACHE_FILE_NAME: &str = "models.json";
+/// How long a model list fetched from a provider is trusted before it is fetched again.
+pub const DEFAULT_MODEL_CACHE_TTL_SECS: u64 = 24 * 60 * 60;
+/// The tag Ollama gives to models pulled without one.
+pub const OLLAMA_LATEST_TAG: &str = ":latest";
+
+// *** Model Info ***
+/// The model metadata bundled with nyota, used to seed the model info registry.
+pub const BUNDLED_MODEL_INFO: &str = include_str!("models.json");
+/// The name of the model info cache file (metadata refreshed from OpenRouter) within the nyota cache directory.
+pub const MODEL_INFO_CACHE_FILE_NAME: &str = "model_info.json";
+/// The reply size requested from models without metadata (Anthropic requires `max_tokens` on every request).
+pub const DEFAULT_MAX_OUTPUT_TOKENS: u64 = 4096;
+/// The rough number of characters per token used to estimate the size of a conversation.
+pub const CHARS_PER_TOKEN: u64 = 4;
+/// The rough number of tokens taken up by the role and formatting of each message.
+pub const TOKENS_PER_MESSAGE: u64 = 4;
+/// The rough number of tokens taken up by each attached image.
+pub const TOKENS_PER_IMAGE: u64 = 1000;
+
+// *** Retry Policy ***
+// (overridable via the `[retry]` table of the config file or env)
+/// The default time allowed for a whole (non-streamed) request, and between chunks of a streamed reply.
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 120;
+/// The default time allowed to establish a connection with a provider.
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+/// The default number of times a failed request is retried.
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+/// The default delay before the first retry, doubled on every further retry.
+pub const DEFAULT_INITIAL_BACKOFF_MS: u64 = 500;
+/// The default upper bound of the delay between retries (including a `Retry-After` given by the provider).
+pub const DEFAULT_MAX_BACKOFF_MS: u64 = 30_000;
+/// The config file table which holds the retry policy.
+pub const CONFIG_RETRY_TABLE: &str = "retry";
+/// Environment variables which override the retry policy.
+pub const REQUEST_TIMEOUT_ENV: &str = "NYOTA_REQUEST_TIMEOUT_SECS";
+pub const CONNECT_TIMEOUT_ENV: &str = "NYOTA_CONNECT_TIMEOUT_SECS";
+pub const MAX_RETRIES_ENV: &str = "NYOTA_MAX_RETRIES";
+
+// *** Provider Fallback ***
+// (the chain is empty unless set in the `[fallback]` table of the config file or env)
+/// The config file table which holds the fallback chain and model equivalents.
+pub const CONFIG_FALLBACK_TABLE: &str = "fallback";
+/// Environment variable which sets the fallback chain as a comma-separated list of providers.
+pub const FALLBACK_CHAIN_ENV: &str = "NYOTA_FALLBACK_CHAIN";
+/// The built-in groups of equivalent models, each listing the name of the same model on different providers.
+pub const DEFAULT_MODEL_EQUIVALENTS: &[&[(ApiProvider, &str)]] = &[
+    &[
+        (ApiProvider::OPENAI, "gpt-4o"),
+        (ApiProvider::OPENROUTER, "openrouter/openai/gpt-4o"),
+    ],
+    &[
+        (ApiProvider::OPENAI, "gpt-4o-mini"),
+        (ApiProvider::OPENROUTER, "openrouter/openai/gpt-4o-mini"),
+    ],
+    &[
+        (ApiProvider::ANTHROPIC, "claude-3-5-sonnet-20241022"),
+        (
+            ApiProvider::OPENROUTER,
+            "openrouter/anthropic/claude-3.5-sonnet",
+        ),
+    ],
+    &[
+        (ApiProvider::ANTHROPIC, "claude-3-5-haiku-20241022"),
+        (
+            ApiProvider::OPENROUTER,
+            "openrouter/anthropic/claude-3.5-haiku",
+        ),
+    ],
+    &[
+        (ApiProvider::ANTHROPIC, "claude-3-opus-20240229"),
+        (
+            ApiProvider::OPENROUTER,
+            "openrouter/anthropic/claude-3-opus",
+        ),
+    ],
+];
+
+// *** Generation Parameters ***
+// (unset unless set by a persona, the `[generation]` table of the config file, CLI flags or `/set`)
+/// The config file table which holds the generation parameters.
+pub const CONFIG_GENERATION_TABLE: &str = "generation";
+/// The value which unsets a generation parameter (i.e. `/set temperature none`).
+pub const UNSET_PARAM_VALUE: &str = "none";
+/// The prefix of the keys which set raw Ollama `options` (i.e. `/set options.num_ctx 8192`).
+pub const OLLAMA_OPTION_PREFIX: &str = "options.";
+/// The temperatures accepted by OpenAI, OpenRouter, Ollama and OpenAI-compatible servers.
+pub const TEMPERATURE_RANGE: RangeInclusive<f64> = 0.0..=2.0;
+/// The temperatures accepted by Anthropic.
+pub const ANTHROPIC_TEMPERATURE_RANGE: RangeInclusive<f64> = 0.0..=1.0;
+/// The values of `top_p` accepted by every provider.
+pub const TOP_P_RANGE: RangeInclusive<f64> = 0.0..=1.0;
+/// The presence and frequency penalties accepted by every provider that takes them.
+pub const PENALTY_RANGE: RangeInclusive<f64> = -2.0..=2.0;
+/// Name prefixes of OpenAI's reasoning models, which reject sampling settings.
+pub const OPENAI_REASONING_MODEL_PREFIXES: &[&str] = &["o1", "o3", "o4"];
+
+// *** Usage & Cost ***
+// (the daily budget is unset unless set in the `[usage]` table of the config file or env)
+/// The file name of the usage ledger within the user's data directory.
+pub const USAGE_FILE_NAME: &str = "usage.json";
+/// The config file table which holds the usage settings.
+pub const CONFIG_USAGE_TABLE: &str = "usage";
+/// The `[usage]` key which holds the daily budget in USD.
+pub const CONFIG_DAILY_BUDGET_KEY: &str = "daily_budget_usd";
+/// Environment variable which sets the daily budget in USD.
+pub const DAILY_BUDGET_ENV: &str = "NYOTA_DAILY_BUDGET_USD";
+
+// *** Attachments ***
+// (images are sent base64-encoded, other files are inlined as text)
+/// The image file extensions which are attached as images, and their media types.
+pub const IMAGE_MEDIA_TYPES: [(&str, &str); 5] = [
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+];
+/// The largest image which can be attached, in bytes.
+pub const MAX_IMAGE_ATTACHMENT_BYTES: u64 = 20 * 1024 * 1024;
+/// The largest text file which can be attached, in bytes.
+pub const MAX_TEXT_ATTACHMENT_BYTES: u64 = 1024 * 1024;
+
+// *** Structured Output ***
+// (the retry count is set in the `[structured_output]` table of the config file or env)
+/// The number of times a reply which does not match the JSON Schema is asked for again.
+pub const DEFAULT_SCHEMA_RETRIES: u32 = 2;
+/// The config file table which holds the structured output settings.
+pub const CONFIG_STRUCTURED_OUTPUT_TABLE: &str = "structured_output";
+/// The `[structured_output]` key which holds the retry count.
+pub const CONFIG_SCHEMA_RETRIES_KEY: &str = "retries";
+/// Environment variable which sets the retry count of structured output.
+pub const SCHEMA_RETRIES_ENV: &str = "NYOTA_SCHEMA_RETRIES";
+/// The name given to a schema without one (OpenAI requires a name).
+pub const DEFAULT_SCHEMA_NAME: &str = "response";
+/// The instruction which asks providers without native structured output (Anthropic) for JSON, followed by the schema.
+pub const SCHEMA_INSTRUCTION: &str = "Reply only with a JSON value which conforms to the following JSON Schema, without any other text or code fences:";
+/// The message which asks for a reply again after one did not match the schema, followed by the reason.
+pub const SCHEMA_RETRY_PROMPT: &str = "That reply does not conform to the JSON Schema. Reply again with only the corrected JSON value. The problem:";
+/// The JSON Schema keywords replies are validated against; a schema with any other keyword (but the annotations) is rejected.
+pub const SUPPORTED_SCHEMA_KEYWORDS: [&str; 16] = [
+    "type",
+    "enum",
+    "const",
+    "properties",
+    "required",
+    "additionalProperties",
+    "items",
+    "anyOf",
+    "allOf",
+    "oneOf",
+    "minimum",
+    "maximum",
+    "minLength",
+    "maxLength",
+    "minItems",
+    "maxItems",
+];
+/// The JSON Schema keywords which only describe the schema, and are allowed although nothing is validated against them.
+pub const SCHEMA_ANNOTATION_KEYWORDS: [&str; 9] = [
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "deprecated",
+    "readOnly",
+];
+
+// *** Embeddings ***
+// (the embedding model is set in the `[memory]` table of the config file, see `nyota::ctx::memory`)
+/// The embedding model used on OpenAI and OpenAI-compatible servers unless another is set.
+pub const DEFAULT_OPENAI_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+/// The embedding model used on Ollama unless another is set.
+pub const DEFAULT_OLLAMA_EMBEDDING_MODEL: &str = "nomic-embed-text";
+
+// *** API Keys ***
+// (read from env, or else from the encrypted key store in the nyota config directory)
+/// Environment variables which hold the API key of each provider (Ollama takes no key).
+pub const OPENAI_API_KEY_ENV: &str = "OPENAI_API_KEY";
+pub const ANTHROPIC_API_KEY_ENV: &str = "ANTHROPIC_API_KEY";
+pub const OPENROUTER_API_KEY_ENV: &str = "OPENROUTER_API_KEY";
+pub const OPENAI_COMPATIBLE_API_KEY_ENV: &str = "OPENAI_COMPATIBLE_API_KEY";
+/// The prefix of every key issued by each provider, used to validate keys before they are stored.
+/// Anthropic and OpenRouter keys also start with the OpenAI prefix, so the longest matching prefix names the provider.
+pub const API_KEY_PREFIXES: [(ApiProvider, &str); 3] = [
+    (ApiProvider::OPENAI, "sk-"),
+    (ApiProvider::ANTHROPIC, "sk-ant-"),
+    (ApiProvider::OPENROUTER, "sk-or-"),
+];
+/// The shortest key accepted for a provider with a known key prefix.
+pub const MIN_API_KEY_LENGTH: usize = 20;
+/// The number of trailing characters of a key left visible when it is redacted (i.e. `sk-ant-…a1b2`).
+pub const REDACTED_KEY_VISIBLE_CHARS: usize = 4;
+/// The name of the encrypted key store within the nyota config directory.
+pub const KEYSTORE_FILE_NAME: &str = "keys.enc";
+/// The format version written to the key store.
+pub const KEYSTORE_VERSION: u32 = 1;
+/// The length in bytes of the random salt the key store's encryption key is derived with.
+pub const KEYSTORE_SALT_LENGTH: usize = 16;
+/// The length in bytes of the AES-256-GCM nonce.
+pub const KEYSTORE_NONCE_LENGTH: usize = 12;
+/// Environment variable which holds the passphrase of the key store, for scripts (otherwise it is prompted for).
+pub const KEYSTORE_PASSPHRASE_ENV: &str = "NYOTA_KEYSTORE_PASSPHRASE";
+/// The prompt shown when asking for the passphrase of the key store.
+pub const KEYSTORE_PASSPHRASE_PROMPT: &str = "🔐 Key store passphrase: ";
 
 // *** Nyota Default Configuration ***
 // (used if not provided by env)
 /// The default API provider to use if not specified in the environment.
 pub const DEFAULT_PROVIDER: ApiProvider = ApiProvider::OPENAI;
 /// The default AI model to use if not specified in the environment.
-pub const DEFAULT_MODEL: &'static str = "gpt-4o-mini";
+pub const DEFAULT_MODEL: &str = "gpt-4o-mini";
+/// The system prompt of the default persona, sent to providers when a conversation does not include one.
+pub const DEFAULT_SYSTEM_PROMPT: &str = "You are a helpful assistant.";
+
+// *** Personas ***
+// (selected via env, profiles are read from the personas directory)
+/// The name of the built-in persona.
+pub const DEFAULT_PERSONA_NAME: &str = "default";
+/// The directory of persona profiles within the nyota config directory.
+pub const PERSONAS_DIR_NAME: &str = "personas";
+/// The file extension of persona profiles.
+pub const PERSONA_FILE_EXTENSION: &str = "toml";
+/// The persona profile key which holds the system prompt (the other keys are generation parameters).
+pub const PERSONA_SYSTEM_PROMPT_KEY: &str = "system_prompt";
+/// Environment variable which selects the persona of new Adapters.
+pub const PERSONA_ENV: &str = "NYOTA_PERSONA";
 
 // ***Supported API Providers ***
 // Note: Some provider configurations may work for unsupported models
 /// The hardcoded list of supported API providers, used in initialization and validation.
-pub const SUPPORTED_PROVIDERS: [ApiProvider; 4] = [
+pub const SUPPORTED_PROVIDERS: [ApiProvider; 5] = [
     ApiProvider::ANTHROPIC,
     ApiProvider::OPENAI,
     ApiProvider::OPENROUTER,
     ApiProvider::OLLAMA,
+    ApiProvider::OPENAI_COMPATIBLE,
 ];
 
+// *** Model Prefixes ***
+// Models with these prefixes are routed to the matching provider without validation
+pub const OPENROUTER_MODEL_PREFIX: &str = "openrouter/";
+pub const OLLAMA_MODEL_PREFIX: &str = "ollama/";
+pub const OPENAI_COMPATIBLE_MODEL_PREFIX: &str = "openai-compatible/";
+
 // *** Supported Models (non-exhaustive) ***
-// All ollama & openrouter models are also supported
+// All ollama, openrouter & openai-compatible models are also supported
 /// The hardcoded list of supported models, used in initialization and validation.
 /// This list is not exhaustive and may not include all supported models, just those that are statically loaded.
 pub static SUPPORTED_MODELS: LazyLock<HashMap<&str, ApiProvider>> = LazyLock::new(|| {
@@ -0,0 +1,149 @@
+//! # Conversation Types
+//! This module contains the role-tagged message types used to send multi-turn conversations to API providers.
+//! Each provider request builder maps a slice of `ChatMessage` values into its native request format.
+//! Messages may carry attached images and text files (see `nyota::api::attachment`).
+//! A model which calls tools is answered with the assistant message carrying its tool calls, followed by a `Role::Tool` message
+//! with the result of each call (see `nyota::api::tools`); they are mapped to OpenAI-style `tool_calls` and `tool` messages,
+//! Anthropic `tool_use` and `tool_result` blocks, and Ollama `tool_calls` and `tool` messages.
+//! Replies come back as an `LlmResponse`, which also names the provider and model that answered, the tools it called and the tokens used.
+
+use crate::api::attachment::Attachment;
+use crate::api::tools::ToolCall;
+use crate::api::usage::Usage;
+use crate::api::utilities::ApiProvider;
+use serde::{Deserialize, Serialize};
+
+/// Role is an enum that represents the author of a message in a conversation.
+/// The roles mirror those shared by the supported API providers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    /// Instructions which steer the behaviour of the model for the whole conversation.
+    System,
+    /// A message written by the user.
+    User,
+    /// A message written by the model.
+    Assistant,
+    /// The result of a tool the model called, given back to it.
+    Tool,
+}
+
+impl Role {
+    /// Returns the role name as expected by the API providers (i.e. `"system"`, `"user"`, `"assistant"`, `"tool"`).
+    /// Anthropic takes tool results in `user` messages, which its request builder sees to.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::Tool => "tool",
+        }
+    }
+}
+
+/// ChatMessage is a single role-tagged message within a conversation sent to an API provider.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+    /// The files attached to the message.
+    pub attachments: Vec<Attachment>,
+    /// The tools the model asked to call, in an assistant message.
+    pub tool_calls: Vec<ToolCall>,
+    /// The id of the tool call a `Role::Tool` message gives the result of.
+    pub tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    /// Creates a new ChatMessage with the specified role and content.
+    pub fn new(role: Role, content: &str) -> Self {
+        Self {
+            role,
+            content: String::from(content),
+            attachments: Vec::new(),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+
+    /// Returns the message with the specified files attached.
+    pub fn with_attachments(mut self, attachments: Vec<Attachment>) -> Self {
+        self.attachments = attachments;
+        self
+    }
+
+    /// Returns the message with the tools the model asked to call (for an assistant message).
+    pub fn with_tool_calls(mut self, tool_calls: Vec<ToolCall>) -> Self {
+        self.tool_calls = tool_calls;
+        self
+    }
+
+    /// Returns the message text with the attached text files inlined after it, each under a header naming the file.
+    pub fn get_text_content(&self) -> String {
+        let mut text_content = self.content.clone();
+        for attachment in &self.attachments {
+            if let Attachment::Text { name, content } = attachment {
+                text_content.push_str(&format!("\n\n--- {} ---\n{}", name, content));
+            }
+        }
+        text_content
+    }
+
+    /// Returns the images attached to the message.
+    pub fn get_images(&self) -> impl Iterator<Item = &Attachment> {
+        self.attachments
+            .iter()
+            .filter(|attachment| attachment.is_image())
+    }
+
+    /// Creates a new system ChatMessage from a string slice.
+    pub fn system(content: &str) -> Self {
+        Self::new(Role::System, content)
+    }
+
+    /// Creates a new user ChatMessage from a string slice.
+    pub fn user(content: &str) -> Self {
+        Self::new(Role::User, content)
+    }
+
+    /// Creates a new assistant ChatMessage from a string slice.
+    pub fn assistant(content: &str) -> Self {
+        Self::new(Role::Assistant, content)
+    }
+
+    /// Creates a new assistant ChatMessage from a reply, with the tools it asked to call.
+    pub fn from_reply(reply: &LlmResponse) -> Self {
+        Self::assistant(&reply.content).with_tool_calls(reply.tool_calls.clone())
+    }
+
+    /// Creates a new tool ChatMessage with the result of the tool call with the specified id.
+    pub fn tool_result(tool_call_id: &str, content: &str) -> Self {
+        let mut message = Self::new(Role::Tool, content);
+        message.tool_call_id = Some(String::from(tool_call_id));
+        message
+    }
+}
+
+/// LlmResponse is a reply from an API provider to a conversation.
+/// Replies are serialized to be kept in the response cache (see `nyota::api::response_cache`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LlmResponse {
+    /// The reply text (which may be empty if the model only called tools).
+    pub content: String,
+    /// The tools the model asked to call (see `nyota::api::tools`).
+    pub tool_calls: Vec<ToolCall>,
+    /// The provider which answered (which may be a fallback provider).
+    pub provider: ApiProvider,
+    /// The model which answered (which may be a fallback model).
+    pub model: String,
+    /// The tokens used by the reply, if the provider reported them.
+    pub usage: Option<Usage>,
+    /// The cost of the reply in USD, if the usage and the model's prices are known.
+    pub cost: Option<f64>,
+    /// Whether the reply was given from the response cache rather than by the provider.
+    /// The usage and cost of a cached reply are those of the provider's reply, which are not spent again.
+    #[serde(skip)]
+    pub cached: bool,
+    /// The problems which did not prevent the reply, for the caller to show (i.e. a response cache which could not be written).
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+}
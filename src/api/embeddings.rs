@@ -0,0 +1,115 @@
+//! # API Embeddings
+//! This module contains the embeddings used by the Adapter to compare texts by meaning (see `nyota::ctx::memory`).
+//! An embedding is a vector of numbers which a model gives for a text; texts about the same thing get vectors pointing the same way,
+//! which is measured by their cosine similarity.
+//!
+//! Embeddings are requested from OpenAI and OpenAI-compatible servers (`/v1/embeddings`, several texts per request)
+//! and from Ollama (`/api/embeddings`, one text per request). Anthropic and OpenRouter offer no embeddings.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use nyota::api::embeddings::{cosine_similarity, get_default_embedding_model};
+//! use nyota::api::utilities::{Adapter, ApiProvider};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let adapter = Adapter::new();
+//! let model = get_default_embedding_model(ApiProvider::OLLAMA).unwrap_or_default();
+//! let texts = [String::from("The user deploys to Fly.io"), String::from("Where is the app hosted?")];
+//! let embeddings = adapter.embed(ApiProvider::OLLAMA, model, &texts).await?;
+//! println!("{:.2}", cosine_similarity(&embeddings[0], &embeddings[1]));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::api::constants::*;
+use crate::api::error::ApiError;
+use crate::api::utilities::ApiProvider;
+use anyhow::{Error, Result};
+use serde_json::{json, Value};
+
+/// Returns the embedding model used on the provider unless another is set, or `None` if the provider offers no embeddings.
+pub fn get_default_embedding_model(provider: ApiProvider) -> Option<&'static str> {
+    match provider {
+        ApiProvider::OPENAI | ApiProvider::OPENAI_COMPATIBLE => {
+            Some(DEFAULT_OPENAI_EMBEDDING_MODEL)
+        }
+        ApiProvider::OLLAMA => Some(DEFAULT_OLLAMA_EMBEDDING_MODEL),
+        ApiProvider::ANTHROPIC | ApiProvider::OPENROUTER => None,
+    }
+}
+
+/// Formulates the requests which embed the texts with the model: a single request for OpenAI and OpenAI-compatible servers,
+/// and one request per text for Ollama.
+pub fn formulate_embedding_requests(
+    provider: ApiProvider,
+    model: &str,
+    texts: &[String],
+) -> Vec<Value> {
+    match provider {
+        ApiProvider::OLLAMA => texts
+            .iter()
+            .map(|text| {
+                json!({
+                    "model": model.trim_start_matches(OLLAMA_MODEL_PREFIX),
+                    "prompt": text
+                })
+            })
+            .collect(),
+        _ => vec![json!({
+            "model": model.trim_start_matches(OPENAI_COMPATIBLE_MODEL_PREFIX),
+            "input": texts
+        })],
+    }
+}
+
+/// Parses the embeddings from the response of the provider, in the order of the texts of the request.
+/// Returns an `ApiError::InvalidResponse` if the response holds no embeddings.
+pub fn parse_embeddings(provider: ApiProvider, json_response: &Value) -> Result<Vec<Vec<f32>>> {
+    let invalid_response = || {
+        Error::from(ApiError::InvalidResponse {
+            provider,
+            message: String::from("The response holds no embeddings."),
+        })
+    };
+    let to_embedding = |embedding: &Value| -> Option<Vec<f32>> {
+        embedding
+            .as_array()?
+            .iter()
+            .map(|number| number.as_f64().map(|number| number as f32))
+            .collect()
+    };
+    match provider {
+        ApiProvider::OLLAMA => to_embedding(&json_response["embedding"])
+            .map(|embedding| vec![embedding])
+            .ok_or_else(invalid_response),
+        _ => {
+            let mut data: Vec<&Value> = json_response["data"]
+                .as_array()
+                .ok_or_else(invalid_response)?
+                .iter()
+                .collect();
+            // The embeddings are returned with the index of their text, which is their order in practice
+            data.sort_by_key(|item| item["index"].as_u64().unwrap_or_default());
+            data.iter()
+                .map(|item| to_embedding(&item["embedding"]))
+                .collect::<Option<Vec<Vec<f32>>>>()
+                .ok_or_else(invalid_response)
+        }
+    }
+}
+
+/// Returns the cosine similarity of two embeddings, from -1 (opposite) to 1 (the same direction).
+/// Embeddings of different lengths (i.e. from different models) or of zero length have a similarity of 0.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot_product: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let magnitude = |vector: &[f32]| vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let magnitudes = magnitude(a) * magnitude(b);
+    if magnitudes == 0.0 {
+        0.0
+    } else {
+        dot_product / magnitudes
+    }
+}
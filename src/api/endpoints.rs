@@ -0,0 +1,240 @@
+//! # API Endpoints
+//! This module contains the endpoint configuration used by the Adapter to reach each API provider.
+//! Every provider has a base URL which defaults to its public API (or `localhost` for self-hosted servers).
+//! A base URL can be overridden by the `[endpoints]` table of the nyota config files (`~/.config/nyota/config.toml`, or a project's `./nyota.toml`),
+//! and by a `NYOTA_<PROVIDER>_BASE_URL` environment variable, which takes precedence over the config file.
+//!
+//! ## Example
+//! ```toml
+//! [endpoints]
+//! ollama = "http://gpu-box.local:11434"
+//! openai = "https://my-gateway.example.com/v1"
+//! openai-compatible = "http://localhost:1234/v1"
+//! ```
+
+use crate::api::constants::*;
+use crate::api::utilities::ApiProvider;
+use crate::cfg::config::read_config_table;
+use anyhow::{Error, Result};
+use std::{
+    collections::HashMap,
+    env::{self, VarError},
+    path::PathBuf,
+};
+
+/// Endpoints holds the base URL of each supported API provider.
+#[derive(Debug, Clone)]
+pub struct Endpoints {
+    base_urls: HashMap<ApiProvider, String>,
+}
+
+impl Endpoints {
+    /// Creates a new instance of Endpoints using the default base URL of every provider.
+    pub fn new() -> Self {
+        let base_urls = SUPPORTED_PROVIDERS
+            .iter()
+            .map(|provider| (*provider, String::from(get_default_base_url(provider))))
+            .collect();
+        Self { base_urls }
+    }
+
+    /// Creates a new instance of Endpoints with the overrides from the config file and environment variables applied.
+    /// Precedence (lowest to highest): built-in defaults < config file < environment variables.
+    /// Unreadable overrides are reported as warnings and the lower layer is kept.
+    pub fn load() -> Self {
+        let mut endpoints = Self::new();
+
+        match read_endpoints_from_config_file() {
+            Ok(overrides) => {
+                for (provider, base_url) in overrides {
+                    endpoints.set_base_url(provider, &base_url);
+                }
+            }
+            Err(e) => eprintln!("{:?}", e),
+        }
+
+        for provider in SUPPORTED_PROVIDERS.iter() {
+            match get_base_url_from_env(provider) {
+                Ok(Some(base_url)) => endpoints.set_base_url(*provider, &base_url),
+                Ok(None) => {}
+                Err(e) => eprintln!("{:?}", e),
+            }
+        }
+
+        endpoints
+    }
+
+    /// Returns the base URL for the specified ApiProvider enum variant.
+    pub fn get_base_url(&self, provider: &ApiProvider) -> &str {
+        self.base_urls
+            .get(provider)
+            .map(String::as_str)
+            .unwrap_or_else(|| get_default_base_url(provider))
+    }
+
+    /// Sets the base URL (via string slice) for the specified ApiProvider enum variant.
+    /// Trailing slashes are removed so that paths can be appended safely.
+    pub fn set_base_url(&mut self, provider: ApiProvider, base_url: &str) {
+        self.base_urls
+            .insert(provider, String::from(base_url.trim_end_matches('/')));
+    }
+
+    /// Returns the full URL of the chat endpoint for the specified ApiProvider enum variant.
+    pub fn get_chat_url(&self, provider: &ApiProvider) -> String {
+        let chat_path = match provider {
+            ApiProvider::OPENAI | ApiProvider::OPENROUTER | ApiProvider::OPENAI_COMPATIBLE => {
+                OPENAI_CHAT_PATH
+            }
+            ApiProvider::ANTHROPIC => ANTHROPIC_CHAT_PATH,
+            ApiProvider::OLLAMA => OLLAMA_CHAT_PATH,
+        };
+        format!("{}{}", self.get_base_url(provider), chat_path)
+    }
+
+    /// Returns the full URL of the model list endpoint for the specified ApiProvider enum variant.
+    pub fn get_models_url(&self, provider: &ApiProvider) -> String {
+        let models_path = match provider {
+            ApiProvider::OPENAI | ApiProvider::OPENROUTER | ApiProvider::OPENAI_COMPATIBLE => {
+                MODELS_PATH
+            }
+            ApiProvider::ANTHROPIC => ANTHROPIC_MODELS_PATH,
+            ApiProvider::OLLAMA => OLLAMA_MODELS_PATH,
+        };
+        format!("{}{}", self.get_base_url(provider), models_path)
+    }
+
+    /// Returns the full URL of the embeddings endpoint for the specified ApiProvider enum variant,
+    /// or `None` if the provider offers no embeddings (Anthropic and OpenRouter).
+    pub fn get_embeddings_url(&self, provider: &ApiProvider) -> Option<String> {
+        let embeddings_path = match provider {
+            ApiProvider::OPENAI | ApiProvider::OPENAI_COMPATIBLE => EMBEDDINGS_PATH,
+            ApiProvider::OLLAMA => OLLAMA_EMBEDDINGS_PATH,
+            ApiProvider::ANTHROPIC | ApiProvider::OPENROUTER => return None,
+        };
+        Some(format!(
+            "{}{}",
+            self.get_base_url(provider),
+            embeddings_path
+        ))
+    }
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the path of the nyota config file (i.e. `~/.config/nyota/config.toml`), if the user's config directory is known.
+pub fn get_config_file_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|config_dir| config_dir.join(CONFIG_DIR_NAME).join(CONFIG_FILE_NAME))
+}
+
+/// Returns the built-in base URL for the specified ApiProvider enum variant.
+fn get_default_base_url(provider: &ApiProvider) -> &'static str {
+    match provider {
+        ApiProvider::OPENAI => OPENAI_API_BASE_URL,
+        ApiProvider::ANTHROPIC => ANTHROPIC_API_BASE_URL,
+        ApiProvider::OLLAMA => OLLAMA_API_BASE_URL,
+        ApiProvider::OPENROUTER => OPENROUTER_API_BASE_URL,
+        ApiProvider::OPENAI_COMPATIBLE => OPENAI_COMPATIBLE_API_BASE_URL,
+    }
+}
+
+/// Retrieves the base URL override from the environment variables for the specified ApiProvider enum variant.
+/// Returns `None` if the variable is unset or empty.
+fn get_base_url_from_env(provider: &ApiProvider) -> Result<Option<String>, Error> {
+    let env_var = match provider {
+        ApiProvider::OPENAI => OPENAI_BASE_URL_ENV,
+        ApiProvider::ANTHROPIC => ANTHROPIC_BASE_URL_ENV,
+        ApiProvider::OLLAMA => OLLAMA_BASE_URL_ENV,
+        ApiProvider::OPENROUTER => OPENROUTER_BASE_URL_ENV,
+        ApiProvider::OPENAI_COMPATIBLE => OPENAI_COMPATIBLE_BASE_URL_ENV,
+    };
+
+    match env::var(env_var) {
+        Ok(val) if val.trim().is_empty() => Ok(None),
+        Ok(val) => {
+            eprintln!(
+                "🟢[ENV] ✅🌐 Success: {:?} | Base URL set from environment: {}",
+                provider, val
+            );
+            Ok(Some(val))
+        }
+        Err(VarError::NotPresent) => Ok(None),
+        Err(VarError::NotUnicode(_)) => Err(Error::msg(format!(
+            "🟡[ENV] 🔍🌐 Warning: {:?} | `{}` unreadable.",
+            provider, env_var
+        ))),
+    }
+}
+
+/// Reads the base URL overrides from the `[endpoints]` table of the nyota config file.
+/// A missing config file (or a file without an `[endpoints]` table) yields no overrides.
+fn read_endpoints_from_config_file() -> Result<HashMap<ApiProvider, String>, Error> {
+    Ok(read_config_table(CONFIG_ENDPOINTS_TABLE)?
+        .map(get_endpoints_from_table)
+        .unwrap_or_default())
+}
+
+/// Returns the base URL overrides of an `[endpoints]` table.
+/// An entry with an unknown provider or a base URL which is not a string is reported as a warning and skipped,
+/// so that the other overrides still apply.
+fn get_endpoints_from_table(endpoints: toml::Table) -> HashMap<ApiProvider, String> {
+    let mut overrides = HashMap::new();
+    for (provider_name, base_url) in endpoints {
+        let provider: ApiProvider = match provider_name.parse() {
+            Ok(provider) => provider,
+            Err(e) => {
+                eprintln!("{:?}", e);
+                eprintln!(
+                    "🟡[CONFIG] 💔🌐 Warning: Skipping the `{}` entry of `[{}]`.",
+                    provider_name, CONFIG_ENDPOINTS_TABLE
+                );
+                continue;
+            }
+        };
+        match base_url.as_str() {
+            Some(base_url) => {
+                overrides.insert(provider, String::from(base_url));
+            }
+            None => eprintln!(
+                "🟡[CONFIG] 💔🌐 Warning: Base URL for `{}` must be a string.",
+                provider_name
+            ),
+        }
+    }
+    overrides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bad_entries_are_skipped_and_the_rest_kept() -> Result<()> {
+        let endpoints: toml::Table = toml::from_str(
+            r#"
+            ollama = "http://gpu-box.local:11434"
+            olama = "http://typo.local:11434"
+            openai_compatible = "http://localhost:1234/v1"
+            anthropic = 8080
+            "#,
+        )?;
+        let overrides = get_endpoints_from_table(endpoints);
+        assert_eq!(
+            overrides,
+            HashMap::from([
+                (
+                    ApiProvider::OLLAMA,
+                    String::from("http://gpu-box.local:11434")
+                ),
+                (
+                    ApiProvider::OPENAI_COMPATIBLE,
+                    String::from("http://localhost:1234/v1")
+                ),
+            ])
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,448 @@
+//! # API Errors
+//! This module contains the typed error returned by the API layer when a provider call fails.
+//! Adapter methods still return `anyhow::Result`, so callers that need to react to a specific failure
+//! (i.e. a missing key, a 401, a 429 rate limit, a 5xx, a timeout or a refusal) can `downcast_ref::<ApiError>()` the error.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use nyota::api::error::ApiError;
+//! use nyota::api::utilities::Adapter;
+//!
+//! # async fn example() {
+//! let adapter = Adapter::new();
+//! if let Err(e) = adapter.send_to_llm("Hello!").await {
+//!     match e.downcast_ref::<ApiError>() {
+//!         Some(ApiError::RateLimited { retry_after, .. }) => println!("Slow down: {:?}", retry_after),
+//!         Some(api_error) if api_error.is_connection_error() => println!("Offline: {}", api_error),
+//!         _ => println!("{}", e),
+//!     }
+//! }
+//! # }
+//! ```
+
+use crate::api::utilities::ApiProvider;
+use chrono::{DateTime, Utc};
+use reqwest::{header::HeaderMap, StatusCode};
+use serde_json::Value;
+use std::{fmt, time::Duration};
+
+/// ApiError is an enum that represents the ways a call to an API provider can fail.
+/// Every variant carries the provider, and HTTP failures carry the status and the provider's own error message.
+#[derive(Debug)]
+pub enum ApiError {
+    /// No API key is configured for the provider.
+    MissingApiKey { provider: ApiProvider },
+    /// The provider rejected the API key (HTTP 401/403).
+    Unauthorized {
+        provider: ApiProvider,
+        status: u16,
+        message: String,
+    },
+    /// The provider is rate limiting requests (HTTP 429). `retry_after` comes from the `Retry-After` header.
+    RateLimited {
+        provider: ApiProvider,
+        retry_after: Option<Duration>,
+        message: String,
+    },
+    /// The provider failed to handle the request (HTTP 5xx).
+    ServerError {
+        provider: ApiProvider,
+        status: u16,
+        message: String,
+    },
+    /// The provider rejected the request for another reason (i.e. HTTP 400 or 404).
+    RequestFailed {
+        provider: ApiProvider,
+        status: u16,
+        message: String,
+    },
+    /// The provider reported an error part-way through a streamed reply.
+    StreamFailed {
+        provider: ApiProvider,
+        message: String,
+    },
+    /// The request or response timed out.
+    Timeout { provider: ApiProvider },
+    /// The provider could not be reached (i.e. DNS failure, refused connection, dropped connection).
+    Network {
+        provider: ApiProvider,
+        message: String,
+    },
+    /// The provider answered successfully but the reply had no content.
+    EmptyContent { provider: ApiProvider },
+    /// The model refused to answer (or the reply was withheld by a content filter).
+    Refusal {
+        provider: ApiProvider,
+        message: String,
+    },
+    /// The provider answered with a body nyota could not understand.
+    InvalidResponse {
+        provider: ApiProvider,
+        message: String,
+    },
+    /// The generation parameters are out of range or not accepted by the model, so no request was sent (see `nyota::api::generation`).
+    InvalidParams {
+        provider: ApiProvider,
+        message: String,
+    },
+    /// The model does not support a feature the request needs (i.e. tools), so no request was sent.
+    UnsupportedFeature {
+        provider: ApiProvider,
+        model: String,
+        feature: String,
+    },
+    /// The daily budget has been spent, so no request was sent (see `nyota::api::usage`).
+    BudgetExceeded {
+        provider: ApiProvider,
+        spent: f64,
+        budget: f64,
+    },
+}
+
+impl ApiError {
+    /// Creates an ApiError from an unsuccessful HTTP response of the specified provider.
+    /// The provider's error message is extracted from the body (OpenAI, OpenRouter, Anthropic and Ollama error JSON are understood).
+    pub fn from_response(
+        provider: ApiProvider,
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: &str,
+    ) -> Self {
+        let message = extract_error_message(body);
+        let status_code = status.as_u16();
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ApiError::Unauthorized {
+                provider,
+                status: status_code,
+                message,
+            },
+            StatusCode::TOO_MANY_REQUESTS => ApiError::RateLimited {
+                provider,
+                retry_after: parse_retry_after(headers),
+                message,
+            },
+            StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT => {
+                ApiError::Timeout { provider }
+            }
+            status if status.is_server_error() => ApiError::ServerError {
+                provider,
+                status: status_code,
+                message,
+            },
+            _ => ApiError::RequestFailed {
+                provider,
+                status: status_code,
+                message,
+            },
+        }
+    }
+
+    /// Creates an ApiError from a `reqwest` transport error raised while talking to the specified provider.
+    pub fn from_reqwest(provider: ApiProvider, error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            ApiError::Timeout { provider }
+        } else if error.is_decode() {
+            ApiError::InvalidResponse {
+                provider,
+                message: error.to_string(),
+            }
+        } else {
+            ApiError::Network {
+                provider,
+                message: error.to_string(),
+            }
+        }
+    }
+
+    /// Returns the provider the failed call was made to.
+    pub fn get_provider(&self) -> ApiProvider {
+        match self {
+            ApiError::MissingApiKey { provider }
+            | ApiError::Unauthorized { provider, .. }
+            | ApiError::RateLimited { provider, .. }
+            | ApiError::ServerError { provider, .. }
+            | ApiError::RequestFailed { provider, .. }
+            | ApiError::StreamFailed { provider, .. }
+            | ApiError::Timeout { provider }
+            | ApiError::Network { provider, .. }
+            | ApiError::EmptyContent { provider }
+            | ApiError::Refusal { provider, .. }
+            | ApiError::InvalidResponse { provider, .. }
+            | ApiError::InvalidParams { provider, .. }
+            | ApiError::UnsupportedFeature { provider, .. }
+            | ApiError::BudgetExceeded { provider, .. } => *provider,
+        }
+    }
+
+    /// Returns the HTTP status returned by the provider, if the failure was an HTTP error.
+    pub fn get_status(&self) -> Option<u16> {
+        match self {
+            ApiError::Unauthorized { status, .. }
+            | ApiError::ServerError { status, .. }
+            | ApiError::RequestFailed { status, .. } => Some(*status),
+            ApiError::RateLimited { .. } => Some(StatusCode::TOO_MANY_REQUESTS.as_u16()),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the provider could not be reached at all (no key, network failure or timeout),
+    /// as opposed to the provider answering with an error.
+    pub fn is_connection_error(&self) -> bool {
+        matches!(
+            self,
+            ApiError::MissingApiKey { .. } | ApiError::Timeout { .. } | ApiError::Network { .. }
+        )
+    }
+
+    /// Returns true if the turn should be retried on the next provider of the fallback chain
+    /// (no key, rejected key, network failure, timeout, rate limit or server error).
+    /// Failures caused by the request or the reply itself (i.e. a 400, a refusal or a failed stream) are not retried elsewhere.
+    pub fn is_fallback_error(&self) -> bool {
+        matches!(
+            self,
+            ApiError::MissingApiKey { .. }
+                | ApiError::Unauthorized { .. }
+                | ApiError::RateLimited { .. }
+                | ApiError::ServerError { .. }
+                | ApiError::Timeout { .. }
+                | ApiError::Network { .. }
+        )
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::MissingApiKey { provider } => write!(
+                f,
+                "No API key is configured for {}. Add it to your .env file.",
+                provider
+            ),
+            ApiError::Unauthorized {
+                provider,
+                status,
+                message,
+            } => write!(
+                f,
+                "{} rejected the API key (HTTP {}): {}",
+                provider, status, message
+            ),
+            ApiError::RateLimited {
+                provider,
+                retry_after: Some(retry_after),
+                message,
+            } => write!(
+                f,
+                "{} is rate limiting requests, retry in {}s: {}",
+                provider,
+                retry_after.as_secs(),
+                message
+            ),
+            ApiError::RateLimited {
+                provider, message, ..
+            } => write!(f, "{} is rate limiting requests: {}", provider, message),
+            ApiError::ServerError {
+                provider,
+                status,
+                message,
+            } => write!(
+                f,
+                "{} had a server error (HTTP {}): {}",
+                provider, status, message
+            ),
+            ApiError::RequestFailed {
+                provider,
+                status,
+                message,
+            } => write!(
+                f,
+                "{} rejected the request (HTTP {}): {}",
+                provider, status, message
+            ),
+            ApiError::StreamFailed { provider, message } => {
+                write!(f, "{} failed mid-reply: {}", provider, message)
+            }
+            ApiError::Timeout { provider } => write!(f, "{} timed out.", provider),
+            ApiError::Network { provider, message } => {
+                write!(f, "Could not reach {}: {}", provider, message)
+            }
+            ApiError::EmptyContent { provider } => {
+                write!(f, "{} returned an empty reply.", provider)
+            }
+            ApiError::Refusal { provider, message } => {
+                write!(f, "The {} model declined to answer: {}", provider, message)
+            }
+            ApiError::InvalidResponse { provider, message } => {
+                write!(
+                    f,
+                    "{} sent a reply nyota could not read: {}",
+                    provider, message
+                )
+            }
+            ApiError::InvalidParams { provider, message } => {
+                write!(
+                    f,
+                    "Invalid generation parameters for {}: {}",
+                    provider, message
+                )
+            }
+            ApiError::UnsupportedFeature {
+                provider,
+                model,
+                feature,
+            } => write!(f, "{} ({}) does not support {}.", model, provider, feature),
+            ApiError::BudgetExceeded {
+                provider,
+                spent,
+                budget,
+            } => write!(
+                f,
+                "Daily budget spent (${:.2} of ${:.2}), nothing was sent to {}.",
+                spent, budget, provider
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// Extracts the human-readable message from a provider error body.
+/// Understands `{"error": {"message": ...}}` (OpenAI, OpenRouter, Anthropic) and `{"error": "..."}` (Ollama),
+/// and falls back to the raw body for anything else.
+pub fn extract_error_message(body: &str) -> String {
+    let Ok(json) = serde_json::from_str::<Value>(body) else {
+        return String::from(body.trim());
+    };
+
+    let error = &json["error"];
+    if let Some(message) = error["message"].as_str() {
+        return String::from(message);
+    }
+    if let Some(message) = error.as_str() {
+        return String::from(message);
+    }
+    if let Some(message) = json["message"].as_str() {
+        return String::from(message);
+    }
+    String::from(body.trim())
+}
+
+/// Parses the `Retry-After` header, given either as a number of seconds or as an HTTP date.
+pub fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let retry_after = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+    if let Ok(seconds) = retry_after.trim().parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds.max(0.0)).ok();
+    }
+    let retry_at = DateTime::parse_from_rfc2822(retry_after.trim()).ok()?;
+    (retry_at.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderValue, RETRY_AFTER};
+
+    #[test]
+    fn error_messages_are_extracted_per_provider() {
+        for (body, expected) in [
+            // OpenAI
+            (
+                r#"{"error": {"message": "Incorrect API key provided: sk-abc. You can find your API key at https://platform.openai.com/account/api-keys.", "type": "invalid_request_error", "param": null, "code": "invalid_api_key"}}"#,
+                "Incorrect API key provided: sk-abc. You can find your API key at https://platform.openai.com/account/api-keys.",
+            ),
+            // Anthropic
+            (
+                r#"{"type":"error","error":{"type":"rate_limit_error","message":"Number of request tokens has exceeded your per-minute rate limit"},"request_id":"req_011CUEf1g2h3"}"#,
+                "Number of request tokens has exceeded your per-minute rate limit",
+            ),
+            // OpenRouter
+            (
+                r#"{"error":{"message":"No endpoints found for openai/gpt-0.","code":404},"user_id":"user_2abc"}"#,
+                "No endpoints found for openai/gpt-0.",
+            ),
+            // Ollama
+            (
+                r#"{"error":"model \"llama9\" not found, try pulling it first"}"#,
+                r#"model "llama9" not found, try pulling it first"#,
+            ),
+            // A top-level message (i.e. some OpenAI-compatible servers)
+            (
+                r#"{"object":"error","message":"The model `mistral` does not exist.","type":"NotFoundError","code":404}"#,
+                "The model `mistral` does not exist.",
+            ),
+        ] {
+            assert_eq!(extract_error_message(body), expected, "{}", body);
+        }
+    }
+
+    #[test]
+    fn other_error_bodies_are_kept_as_they_are() {
+        for (body, expected) in [
+            // A gateway in front of the provider answers with HTML
+            (
+                "  <html><body><h1>502 Bad Gateway</h1></body></html>\n",
+                "<html><body><h1>502 Bad Gateway</h1></body></html>",
+            ),
+            (
+                "upstream connect error or disconnect/reset before headers",
+                "upstream connect error or disconnect/reset before headers",
+            ),
+            // A body cut short
+            (
+                r#"{"error": {"message": "Rate lim"#,
+                r#"{"error": {"message": "Rate lim"#,
+            ),
+            // JSON without a message
+            (r#"{"detail": "Not Found"}"#, r#"{"detail": "Not Found"}"#),
+            (r#"{"error": {"code": 500}}"#, r#"{"error": {"code": 500}}"#),
+            ("", ""),
+        ] {
+            assert_eq!(extract_error_message(body), expected, "{:?}", body);
+        }
+    }
+
+    fn get_headers(retry_after: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Ok(retry_after) = HeaderValue::from_str(retry_after) {
+            headers.insert(RETRY_AFTER, retry_after);
+        }
+        headers
+    }
+
+    #[test]
+    fn retry_after_is_read_in_seconds() {
+        for (retry_after, expected) in [
+            ("120", Some(Duration::from_secs(120))),
+            (" 3 ", Some(Duration::from_secs(3))),
+            ("0", Some(Duration::ZERO)),
+            ("1.5", Some(Duration::from_millis(1500))),
+            ("-5", Some(Duration::ZERO)),
+            ("soon", None),
+        ] {
+            assert_eq!(
+                parse_retry_after(&get_headers(retry_after)),
+                expected,
+                "{}",
+                retry_after
+            );
+        }
+        assert_eq!(parse_retry_after(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn retry_after_is_read_as_an_http_date() {
+        let retry_at = (Utc::now() + chrono::Duration::seconds(30)).to_rfc2822();
+        let retry_after = parse_retry_after(&get_headers(&retry_at));
+        assert!(
+            retry_after.is_some_and(|retry_after| retry_after > Duration::from_secs(25)
+                && retry_after <= Duration::from_secs(30)),
+            "{:?}",
+            retry_after
+        );
+        // A date already passed is not waited for
+        assert_eq!(
+            parse_retry_after(&get_headers("Wed, 21 Oct 2015 07:28:00 GMT")),
+            None
+        );
+    }
+}
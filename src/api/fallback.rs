@@ -0,0 +1,295 @@
+//! # API Fallback
+//! This module contains the provider fallback chain used by the Adapter when the current provider cannot answer.
+//! If a turn fails because a provider has no key, rejects the key, cannot be reached, times out, rate limits or has a server error,
+//! the turn is retried on the next provider of the chain with the equivalent model on that provider.
+//! Equivalent models are looked up in groups of model names (one per provider); if the current model is not in a group,
+//! the provider's default fallback model is used, and providers with neither are skipped.
+//! The chain is empty (no fallback) unless set in the `[fallback]` table of the nyota config file
+//! or by the `NYOTA_FALLBACK_CHAIN` environment variable (i.e. `anthropic,openrouter,ollama`).
+//!
+//! ## Example
+//! ```toml
+//! [fallback]
+//! chain = ["anthropic", "openrouter", "ollama"]
+//!
+//! [fallback.default_models]
+//! ollama = "ollama/llama3.2"
+//!
+//! [[fallback.equivalents]]
+//! anthropic = "claude-3-5-haiku-20241022"
+//! openrouter = "openrouter/anthropic/claude-3.5-haiku"
+//! ollama = "ollama/llama3.1:8b"
+//! ```
+
+use crate::api::constants::*;
+use crate::api::utilities::ApiProvider;
+use crate::cfg::config::read_config_table;
+use anyhow::{Error, Result};
+use std::{
+    collections::HashMap,
+    env::{self, VarError},
+};
+
+/// FallbackChain holds the ordered list of providers to fall back to and the models to use on each of them.
+#[derive(Debug, Clone)]
+pub struct FallbackChain {
+    providers: Vec<ApiProvider>,
+    /// Groups of equivalent models, each mapping a provider to its name for the model.
+    equivalents: Vec<HashMap<ApiProvider, String>>,
+    /// The model used on a provider when the current model has no equivalent there.
+    default_models: HashMap<ApiProvider, String>,
+}
+
+impl FallbackChain {
+    /// Creates a new instance of FallbackChain with no fallback providers and the built-in model equivalents.
+    pub fn new() -> Self {
+        let equivalents = DEFAULT_MODEL_EQUIVALENTS
+            .iter()
+            .map(|group| {
+                group
+                    .iter()
+                    .map(|(provider, model)| (*provider, String::from(*model)))
+                    .collect()
+            })
+            .collect();
+        Self {
+            providers: Vec::new(),
+            equivalents,
+            default_models: HashMap::new(),
+        }
+    }
+
+    /// Creates a new instance of FallbackChain with the settings from the config file and environment variables applied.
+    /// Precedence (lowest to highest): built-in defaults < config file < environment variables.
+    /// Unreadable settings are reported as warnings and the lower layer is kept.
+    pub fn load() -> Self {
+        let mut fallback_chain = Self::new();
+
+        if let Err(e) = fallback_chain.apply_config_file() {
+            eprintln!("{:?}", e);
+        }
+
+        match get_fallback_chain_from_env() {
+            Ok(Some(providers)) => fallback_chain.providers = providers,
+            Ok(None) => {}
+            Err(e) => eprintln!("{:?}", e),
+        }
+
+        fallback_chain
+    }
+
+    /// Returns the ordered list of providers to fall back to.
+    pub fn get_providers(&self) -> &[ApiProvider] {
+        &self.providers
+    }
+
+    /// Sets the ordered list of providers to fall back to.
+    pub fn set_providers(&mut self, providers: Vec<ApiProvider>) {
+        self.providers = providers;
+    }
+
+    /// Adds a group of equivalent models, checked before any existing group.
+    pub fn add_equivalents(&mut self, group: HashMap<ApiProvider, String>) {
+        self.equivalents.insert(0, group);
+    }
+
+    /// Sets the model used on the specified provider when the current model has no equivalent there.
+    pub fn set_default_model(&mut self, provider: ApiProvider, model: &str) {
+        self.default_models.insert(provider, String::from(model));
+    }
+
+    /// Returns the name of the specified model on the specified provider, if one is known.
+    pub fn get_equivalent_model(&self, model: &str, provider: &ApiProvider) -> Option<&str> {
+        self.equivalents
+            .iter()
+            .filter(|group| group.values().any(|equivalent| equivalent == model))
+            .find_map(|group| group.get(provider))
+            .or_else(|| self.default_models.get(provider))
+            .map(String::as_str)
+    }
+
+    /// Returns the providers and models to try for a turn on the specified provider and model:
+    /// the current provider first, then every provider of the chain which has a model to use.
+    pub fn get_targets(&self, provider: ApiProvider, model: &str) -> Vec<(ApiProvider, String)> {
+        let mut targets = vec![(provider, String::from(model))];
+        for fallback_provider in self.providers.iter() {
+            if targets
+                .iter()
+                .any(|(target, _)| target == fallback_provider)
+            {
+                continue;
+            }
+            if let Some(fallback_model) = self.get_equivalent_model(model, fallback_provider) {
+                targets.push((*fallback_provider, String::from(fallback_model)));
+            }
+        }
+        targets
+    }
+
+    /// Applies the settings from the `[fallback]` table of the nyota config file.
+    fn apply_config_file(&mut self) -> Result<(), Error> {
+        let Some(fallback_table) = read_config_table(CONFIG_FALLBACK_TABLE)? else {
+            return Ok(());
+        };
+
+        if let Some(chain) = fallback_table.get("chain") {
+            let provider_names = chain.as_array().ok_or_else(|| {
+                Error::msg("🟡[CONFIG] 💔🔀 Warning: Fallback `chain` must be a list of providers.")
+            })?;
+            self.providers = provider_names
+                .iter()
+                .map(|provider_name| {
+                    provider_name
+                        .as_str()
+                        .ok_or_else(|| {
+                            Error::msg(
+                                "🟡[CONFIG] 💔🔀 Warning: Fallback `chain` must be a list of providers.",
+                            )
+                        })?
+                        .parse()
+                })
+                .collect::<Result<Vec<ApiProvider>, Error>>()?;
+        }
+
+        if let Some(default_models) = fallback_table.get("default_models") {
+            let default_models = default_models.as_table().ok_or_else(|| {
+                Error::msg("🟡[CONFIG] 💔🔀 Warning: Fallback `default_models` must be a table.")
+            })?;
+            self.default_models = read_provider_models(default_models)?;
+        }
+
+        if let Some(equivalents) = fallback_table.get("equivalents") {
+            let groups = equivalents.as_array().ok_or_else(|| {
+                Error::msg(
+                    "🟡[CONFIG] 💔🔀 Warning: Fallback `equivalents` must be a list of tables.",
+                )
+            })?;
+            // Groups from the config file take precedence over the built-in groups
+            for group in groups.iter().rev() {
+                let group = group.as_table().ok_or_else(|| {
+                    Error::msg(
+                        "🟡[CONFIG] 💔🔀 Warning: Fallback `equivalents` must be a list of tables.",
+                    )
+                })?;
+                self.add_equivalents(read_provider_models(group)?);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for FallbackChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads a table of model names keyed by provider name.
+fn read_provider_models(table: &toml::Table) -> Result<HashMap<ApiProvider, String>, Error> {
+    table
+        .iter()
+        .map(|(provider_name, model)| {
+            let provider: ApiProvider = provider_name.parse()?;
+            let model = model.as_str().ok_or_else(|| {
+                Error::msg(format!(
+                    "🟡[CONFIG] 💔🔀 Warning: Fallback model for `{}` must be a string.",
+                    provider_name
+                ))
+            })?;
+            Ok((provider, String::from(model)))
+        })
+        .collect()
+}
+
+/// Retrieves the fallback chain from the `NYOTA_FALLBACK_CHAIN` environment variable (a comma-separated list of providers).
+/// Returns `None` if the variable is unset or empty.
+fn get_fallback_chain_from_env() -> Result<Option<Vec<ApiProvider>>, Error> {
+    match env::var(FALLBACK_CHAIN_ENV) {
+        Ok(val) if val.trim().is_empty() => Ok(None),
+        Ok(val) => val
+            .split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<ApiProvider>, Error>>()
+            .map(Some),
+        Err(VarError::NotPresent) => Ok(None),
+        Err(VarError::NotUnicode(_)) => Err(Error::msg(format!(
+            "🟡[ENV] 🔍🔀 Warning: `{}` unreadable.",
+            FALLBACK_CHAIN_ENV
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_follow_the_chain_order() {
+        let mut fallback_chain = FallbackChain::new();
+        fallback_chain.set_providers(vec![
+            ApiProvider::ANTHROPIC,
+            ApiProvider::OPENAI,
+            ApiProvider::OPENROUTER,
+            ApiProvider::OLLAMA,
+        ]);
+        fallback_chain.add_equivalents(HashMap::from([
+            (ApiProvider::OPENAI, String::from("gpt-test")),
+            (ApiProvider::ANTHROPIC, String::from("claude-test")),
+            (ApiProvider::OPENROUTER, String::from("openrouter/test")),
+        ]));
+        fallback_chain.set_default_model(ApiProvider::OLLAMA, "ollama/llama3.2");
+
+        // The current provider comes first, and is not tried again where it is in the chain
+        assert_eq!(
+            fallback_chain.get_targets(ApiProvider::OPENAI, "gpt-test"),
+            [
+                (ApiProvider::OPENAI, String::from("gpt-test")),
+                (ApiProvider::ANTHROPIC, String::from("claude-test")),
+                (ApiProvider::OPENROUTER, String::from("openrouter/test")),
+                (ApiProvider::OLLAMA, String::from("ollama/llama3.2")),
+            ]
+        );
+        // Providers without an equivalent or default model are skipped
+        assert_eq!(
+            fallback_chain.get_targets(ApiProvider::OPENAI, "gpt-unknown"),
+            [
+                (ApiProvider::OPENAI, String::from("gpt-unknown")),
+                (ApiProvider::OLLAMA, String::from("ollama/llama3.2")),
+            ]
+        );
+    }
+
+    #[test]
+    fn added_equivalents_take_precedence_over_the_built_in_ones() {
+        let mut fallback_chain = FallbackChain::new();
+        assert_eq!(
+            fallback_chain.get_equivalent_model("gpt-4o-mini", &ApiProvider::OPENROUTER),
+            Some("openrouter/openai/gpt-4o-mini")
+        );
+
+        fallback_chain.add_equivalents(HashMap::from([
+            (ApiProvider::OPENAI, String::from("gpt-4o-mini")),
+            (
+                ApiProvider::OPENROUTER,
+                String::from("openrouter/mirror/gpt-4o-mini"),
+            ),
+        ]));
+        assert_eq!(
+            fallback_chain.get_equivalent_model("gpt-4o-mini", &ApiProvider::OPENROUTER),
+            Some("openrouter/mirror/gpt-4o-mini")
+        );
+    }
+
+    #[test]
+    fn an_empty_chain_only_targets_the_current_provider() {
+        let fallback_chain = FallbackChain::new();
+        assert_eq!(
+            fallback_chain.get_targets(ApiProvider::ANTHROPIC, "claude-3-5-sonnet-20241022"),
+            [(
+                ApiProvider::ANTHROPIC,
+                String::from("claude-3-5-sonnet-20241022")
+            )]
+        );
+    }
+}
@@ -0,0 +1,376 @@
+//! # API Generation Parameters
+//! This module contains the sampling settings sent with each request: temperature, top_p, seed, max tokens, stop sequences,
+//! presence/frequency penalties and raw Ollama `options`.
+//! Settings are layered: the persona's (see `nyota::api::persona`) < the `[generation]` table of the config file < CLI flags
+//! and `/set` in the chat, and are translated into each provider's parameter names when a request is formulated.
+//! Settings a provider or model does not accept are rejected before the request is sent (i.e. `temperature` for OpenAI o1/o3 models,
+//! or `seed` for Anthropic), except on fallback providers where they are dropped so the fallback can still answer.
+//!
+//! ## Example
+//! ```toml
+//! [generation]
+//! temperature = 0.7
+//! max_tokens = 1024
+//! stop = ["<END>"]
+//!
+//! [generation.options] # Ollama only
+//! num_ctx = 8192
+//! ```
+
+use crate::api::constants::*;
+use crate::api::error::ApiError;
+use crate::api::utilities::ApiProvider;
+use crate::cfg::config::read_config_table;
+use anyhow::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::ops::RangeInclusive;
+
+/// GenerationParams holds the sampling settings of a request. Unset settings are left to the provider's defaults.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenerationParams {
+    /// The sampling temperature.
+    pub temperature: Option<f64>,
+    /// The nucleus sampling probability mass.
+    pub top_p: Option<f64>,
+    /// The seed for deterministic sampling.
+    pub seed: Option<u64>,
+    /// The maximum number of tokens in a reply.
+    pub max_tokens: Option<u64>,
+    /// Sequences which end the reply when generated.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    /// Penalises tokens which already appear in the reply.
+    pub presence_penalty: Option<f64>,
+    /// Penalises tokens by how often they already appear in the reply.
+    pub frequency_penalty: Option<f64>,
+    /// Raw Ollama `options` (i.e. `num_ctx`), sent as given and ignored by other providers.
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub options: Map<String, Value>,
+}
+
+impl GenerationParams {
+    /// Creates a new GenerationParams with every setting left to the provider's defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new GenerationParams from the `[generation]` table of the config file.
+    /// An unreadable table is reported as a warning and leaves every setting unset.
+    pub fn load() -> Self {
+        match read_generation_params_from_config_file() {
+            Ok(generation_params) => generation_params,
+            Err(e) => {
+                eprintln!("{:?}", e);
+                Self::new()
+            }
+        }
+    }
+
+    /// Returns these settings with the settings set in `overrides` taking precedence.
+    pub fn merge(&self, overrides: &GenerationParams) -> Self {
+        let mut options = self.options.clone();
+        options.extend(overrides.options.clone());
+        Self {
+            temperature: overrides.temperature.or(self.temperature),
+            top_p: overrides.top_p.or(self.top_p),
+            seed: overrides.seed.or(self.seed),
+            max_tokens: overrides.max_tokens.or(self.max_tokens),
+            stop: if overrides.stop.is_empty() {
+                self.stop.clone()
+            } else {
+                overrides.stop.clone()
+            },
+            presence_penalty: overrides.presence_penalty.or(self.presence_penalty),
+            frequency_penalty: overrides.frequency_penalty.or(self.frequency_penalty),
+            options,
+        }
+    }
+
+    /// Sets a single setting from text (i.e. `/set temperature 0.7`); `none` unsets it.
+    /// Stop sequences are given as a comma-separated list, and Ollama `options` under the `options.` prefix (i.e. `options.num_ctx`).
+    /// Returns an error for any other key, so that a mistyped setting is not sent as an Ollama option.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        let value = value.trim();
+        let is_unset = value.eq_ignore_ascii_case(UNSET_PARAM_VALUE);
+        match key {
+            "temperature" => self.temperature = parse_param(key, value, is_unset)?,
+            "top_p" => self.top_p = parse_param(key, value, is_unset)?,
+            "seed" => self.seed = parse_param(key, value, is_unset)?,
+            "max_tokens" => self.max_tokens = parse_param(key, value, is_unset)?,
+            "presence_penalty" => self.presence_penalty = parse_param(key, value, is_unset)?,
+            "frequency_penalty" => self.frequency_penalty = parse_param(key, value, is_unset)?,
+            "stop" if is_unset => self.stop.clear(),
+            "stop" => {
+                self.stop = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|stop| !stop.is_empty())
+                    .map(String::from)
+                    .collect()
+            }
+            _ => {
+                let option_key = key
+                    .strip_prefix(OLLAMA_OPTION_PREFIX)
+                    .filter(|option_key| !option_key.is_empty())
+                    .ok_or_else(|| {
+                        Error::msg(format!(
+                            "🟡[GENERATION] 💔🎛️ Warning: Unknown setting `{}` (Ollama options are set as `{}<name>`).",
+                            key, OLLAMA_OPTION_PREFIX
+                        ))
+                    })?;
+                if is_unset {
+                    self.options.remove(option_key);
+                } else {
+                    let option = serde_json::from_str(value).unwrap_or_else(|_| json!(value));
+                    self.options.insert(String::from(option_key), option);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns an `ApiError::InvalidParams` if a setting is out of range or not accepted by the provider and model.
+    pub fn validate(&self, provider: ApiProvider, model: &str) -> Result<(), Error> {
+        let invalid = |message: String| Error::from(ApiError::InvalidParams { provider, message });
+
+        check_range(
+            "temperature",
+            self.temperature,
+            get_temperature_range(provider),
+        )
+        .map_err(invalid)?;
+        check_range("top_p", self.top_p, TOP_P_RANGE).map_err(invalid)?;
+        check_range("presence_penalty", self.presence_penalty, PENALTY_RANGE).map_err(invalid)?;
+        check_range("frequency_penalty", self.frequency_penalty, PENALTY_RANGE).map_err(invalid)?;
+        if self.max_tokens == Some(0) {
+            return Err(invalid(String::from("max_tokens must be at least 1")));
+        }
+
+        let unsupported = self.get_unsupported(provider, model);
+        if !unsupported.is_empty() {
+            return Err(invalid(format!(
+                "{} not accepted by {}",
+                unsupported.join(", "),
+                model
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns these settings without the ones the provider and model do not accept, and with the temperature clamped to the provider's range.
+    pub fn get_supported(&self, provider: ApiProvider, model: &str) -> Self {
+        let mut supported = self.clone();
+        supported.temperature = self.temperature.map(|temperature| {
+            let temperature_range = get_temperature_range(provider);
+            temperature.clamp(*temperature_range.start(), *temperature_range.end())
+        });
+        for unsupported in self.get_unsupported(provider, model) {
+            match unsupported {
+                "temperature" => supported.temperature = None,
+                "top_p" => supported.top_p = None,
+                "seed" => supported.seed = None,
+                "presence_penalty" => supported.presence_penalty = None,
+                "frequency_penalty" => supported.frequency_penalty = None,
+                _ => {}
+            }
+        }
+        supported
+    }
+
+    /// Returns the settings under the parameter names of the provider, for the top level of its request
+    /// (or, for Ollama, for its `options`). `max_tokens` is left out for Anthropic, which sends it separately.
+    pub fn to_provider_params(&self, provider: ApiProvider) -> Map<String, Value> {
+        let (max_tokens_key, stop_key) = match provider {
+            // OpenAI's reasoning models reject the older `max_tokens`
+            ApiProvider::OPENAI => (Some("max_completion_tokens"), "stop"),
+            ApiProvider::ANTHROPIC => (None, "stop_sequences"),
+            ApiProvider::OLLAMA => (Some("num_predict"), "stop"),
+            ApiProvider::OPENROUTER | ApiProvider::OPENAI_COMPATIBLE => {
+                (Some("max_tokens"), "stop")
+            }
+        };
+
+        let mut params = Map::new();
+        if let Some(temperature) = self.temperature {
+            params.insert(String::from("temperature"), json!(temperature));
+        }
+        if let Some(top_p) = self.top_p {
+            params.insert(String::from("top_p"), json!(top_p));
+        }
+        if let Some(seed) = self.seed {
+            params.insert(String::from("seed"), json!(seed));
+        }
+        if let (Some(max_tokens_key), Some(max_tokens)) = (max_tokens_key, self.max_tokens) {
+            params.insert(String::from(max_tokens_key), json!(max_tokens));
+        }
+        if !self.stop.is_empty() {
+            params.insert(String::from(stop_key), json!(self.stop));
+        }
+        if let Some(presence_penalty) = self.presence_penalty {
+            params.insert(String::from("presence_penalty"), json!(presence_penalty));
+        }
+        if let Some(frequency_penalty) = self.frequency_penalty {
+            params.insert(String::from("frequency_penalty"), json!(frequency_penalty));
+        }
+        if provider == ApiProvider::OLLAMA {
+            params.extend(self.options.clone());
+        }
+        params
+    }
+
+    /// Returns the names of the settings which are set but not accepted by the provider and model:
+    /// Anthropic takes no seed or penalties, and OpenAI reasoning models take no sampling settings.
+    fn get_unsupported(&self, provider: ApiProvider, model: &str) -> Vec<&'static str> {
+        let mut unsupported = Vec::new();
+        let is_reasoning_model = matches!(provider, ApiProvider::OPENAI | ApiProvider::OPENROUTER)
+            && is_openai_reasoning_model(model);
+        if is_reasoning_model {
+            if self.temperature.is_some() {
+                unsupported.push("temperature");
+            }
+            if self.top_p.is_some() {
+                unsupported.push("top_p");
+            }
+        }
+        if provider == ApiProvider::ANTHROPIC && self.seed.is_some() {
+            unsupported.push("seed");
+        }
+        if provider == ApiProvider::ANTHROPIC || is_reasoning_model {
+            if self.presence_penalty.is_some() {
+                unsupported.push("presence_penalty");
+            }
+            if self.frequency_penalty.is_some() {
+                unsupported.push("frequency_penalty");
+            }
+        }
+        unsupported
+    }
+}
+
+/// Returns the temperatures accepted by the provider.
+fn get_temperature_range(provider: ApiProvider) -> RangeInclusive<f64> {
+    match provider {
+        ApiProvider::ANTHROPIC => ANTHROPIC_TEMPERATURE_RANGE,
+        _ => TEMPERATURE_RANGE,
+    }
+}
+
+/// Returns true if the model is one of OpenAI's reasoning models (i.e. `o1`, `o3-mini`, `openrouter/openai/o1`).
+fn is_openai_reasoning_model(model: &str) -> bool {
+    let model_name = model.rsplit('/').next().unwrap_or(model);
+    OPENAI_REASONING_MODEL_PREFIXES
+        .iter()
+        .any(|prefix| model_name.starts_with(prefix))
+}
+
+/// Returns a message if the setting is set outside of its range.
+fn check_range(name: &str, value: Option<f64>, range: RangeInclusive<f64>) -> Result<(), String> {
+    match value {
+        Some(value) if !range.contains(&value) => Err(format!(
+            "{} must be between {} and {}, got {}",
+            name,
+            range.start(),
+            range.end(),
+            value
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Parses the value of a single setting, or unsets it.
+fn parse_param<T: std::str::FromStr>(
+    key: &str,
+    value: &str,
+    is_unset: bool,
+) -> Result<Option<T>, Error> {
+    if is_unset {
+        return Ok(None);
+    }
+    value.parse().map(Some).map_err(|_| {
+        Error::msg(format!(
+            "🟡[GENERATION] 💔🎛️ Warning: Invalid value `{}` for {}.",
+            value, key
+        ))
+    })
+}
+
+/// Reads the sampling settings from the `[generation]` table of the nyota config file.
+fn read_generation_params_from_config_file() -> Result<GenerationParams, Error> {
+    let Some(generation_table) = read_config_table(CONFIG_GENERATION_TABLE)? else {
+        return Ok(GenerationParams::new());
+    };
+    toml::Value::Table(generation_table)
+        .try_into()
+        .map_err(|e| {
+            Error::msg(format!(
+                "🟡[CONFIG] 💔🎛️ Warning: Invalid `[{}]` table | {}",
+                CONFIG_GENERATION_TABLE, e
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_are_set_and_unset_from_text() -> Result<()> {
+        let mut generation_params = GenerationParams::new();
+        generation_params.set("temperature", "0.7")?;
+        generation_params.set("seed", " 42 ")?;
+        generation_params.set("stop", "<END>, ###,")?;
+        assert_eq!(generation_params.temperature, Some(0.7));
+        assert_eq!(generation_params.seed, Some(42));
+        assert_eq!(generation_params.stop, vec!["<END>", "###"]);
+
+        generation_params.set("temperature", "none")?;
+        generation_params.set("stop", "NONE")?;
+        assert_eq!(generation_params.temperature, None);
+        assert!(generation_params.stop.is_empty());
+        assert!(generation_params.set("max_tokens", "lots").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn ollama_options_need_their_prefix() -> Result<()> {
+        let mut generation_params = GenerationParams::new();
+        generation_params.set("options.num_ctx", "8192")?;
+        generation_params.set("options.mirostat_tau", "5.0")?;
+        generation_params.set("options.tfs", "high")?;
+        assert_eq!(
+            Value::Object(generation_params.options.clone()),
+            json!({"num_ctx": 8192, "mirostat_tau": 5.0, "tfs": "high"})
+        );
+        generation_params.set("options.tfs", "none")?;
+        assert!(!generation_params.options.contains_key("tfs"));
+
+        for key in ["temprature", "num_ctx", "options.", "top-p"] {
+            let set = generation_params.set(key, "1");
+            assert!(
+                set.is_err_and(|e| e.to_string().contains("Unknown setting")),
+                "`{}` was accepted",
+                key
+            );
+        }
+        assert_eq!(generation_params.options.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn ollama_options_are_only_sent_to_ollama() -> Result<()> {
+        let mut generation_params = GenerationParams::new();
+        generation_params.set("options.num_ctx", "8192")?;
+        generation_params.set("max_tokens", "256")?;
+        assert_eq!(
+            Value::Object(generation_params.to_provider_params(ApiProvider::OLLAMA)),
+            json!({"num_predict": 256, "num_ctx": 8192})
+        );
+        assert_eq!(
+            Value::Object(generation_params.to_provider_params(ApiProvider::OPENAI)),
+            json!({"max_completion_tokens": 256})
+        );
+        Ok(())
+    }
+}
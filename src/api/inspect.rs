@@ -0,0 +1,53 @@
+//! # API Inspection
+//! This module contains the types used to inspect raw request/response round trips with API providers.
+//! They are produced by `Adapter::inspect_conversation` and displayed by development mode, which shows exactly
+//! what was sent to a provider, what came back, and what nyota extracted from it.
+
+use crate::api::tools::ToolCall;
+use crate::api::usage::Usage;
+use crate::api::utilities::ApiProvider;
+use anyhow::Error;
+use reqwest::{header::HeaderMap, StatusCode};
+use serde_json::Value;
+use std::time::Duration;
+
+/// RawExchange records a single request/response round trip with an API provider.
+pub struct RawExchange {
+    /// The provider the request was sent to.
+    pub provider: ApiProvider,
+    /// The model named in the request.
+    pub model: String,
+    /// The full URL the request was posted to.
+    pub url: String,
+    /// The exact JSON body produced by `formulate_request`.
+    pub request_body: Value,
+    /// The time from sending the request until the whole response body was read.
+    pub latency: Duration,
+    /// The raw response, or the error raised if the request could not be sent or read.
+    pub outcome: Result<RawResponse, Error>,
+}
+
+/// RawResponse holds an HTTP response from an API provider exactly as it was received.
+pub struct RawResponse {
+    /// The HTTP status returned by the provider.
+    pub status: StatusCode,
+    /// The HTTP headers returned by the provider.
+    pub headers: HeaderMap,
+    /// The response body as text.
+    pub raw_body: String,
+    /// The reply text extracted from the body by the provider's response parser, or the reason it could not be.
+    pub parsed: Result<String, Error>,
+    /// The tools the model asked to call.
+    pub tool_calls: Vec<ToolCall>,
+    /// The tokens used by the reply, if the provider reported them.
+    pub usage: Option<Usage>,
+    /// The problems which did not prevent the reply (i.e. a usage ledger which could not be written).
+    pub warnings: Vec<String>,
+}
+
+impl RawResponse {
+    /// Returns the response body as JSON, or `None` if the body is not valid JSON.
+    pub fn get_body_json(&self) -> Option<Value> {
+        serde_json::from_str(&self.raw_body).ok()
+    }
+}
@@ -0,0 +1,571 @@
+//! # API Key Store
+//! This module contains the encrypted key store, which keeps API keys on disk for users who would rather not export them in the environment.
+//! The keys are held in a single file in the nyota config directory (`~/.config/nyota/keys.enc`), encrypted with AES-256-GCM
+//! under a key derived from a passphrase with Argon2id. The passphrase is read from `NYOTA_KEYSTORE_PASSPHRASE`, or else prompted for.
+//! Keys set in the environment (or `.env`) take precedence over stored keys, so the store only fills in the keys missing from the environment.
+//! The file lists the providers whose keys it holds in the clear, so that it is only unlocked (and the passphrase asked for)
+//! when one of those keys is missing from the environment.
+//!
+//! Keys are validated against the prefix each provider issues (`sk-`, `sk-ant-`, `sk-or-`) before they are stored,
+//! and are redacted (see `redact_api_key`) wherever they could be printed.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use nyota::api::keystore::KeyStore;
+//! use nyota::api::utilities::ApiProvider;
+//!
+//! # fn example() -> anyhow::Result<()> {
+//! let passphrase = "correct horse battery staple";
+//! let mut key_store = KeyStore::open(passphrase)?;
+//! key_store.set(ApiProvider::ANTHROPIC, "sk-ant-REDACTED")?;
+//! key_store.save(passphrase)?;
+//! println!("{:?}", key_store); // the keys are redacted
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::api::constants::*;
+use crate::api::utilities::ApiProvider;
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Key, Nonce,
+};
+use anyhow::{Error, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    env::{self, VarError},
+    fmt, fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// KeyStore holds the API keys kept in the encrypted key store, decrypted in memory.
+#[derive(Clone, Default)]
+pub struct KeyStore {
+    keys: HashMap<ApiProvider, String>,
+}
+
+/// The key store file as written to disk: the key derivation settings, and the encrypted keys.
+#[derive(Serialize, Deserialize)]
+struct KeyStoreFile {
+    version: u32,
+    /// The Argon2id memory cost in KiB.
+    memory_kib: u32,
+    /// The Argon2id number of passes.
+    iterations: u32,
+    /// The Argon2id degree of parallelism.
+    parallelism: u32,
+    /// The salt, base64-encoded.
+    salt: String,
+    /// The AES-256-GCM nonce, base64-encoded.
+    nonce: String,
+    /// The providers whose keys are stored, in the clear, so that the store is only unlocked when one of them is missing from the environment.
+    /// `None` in files written before the list was kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    providers: Option<Vec<ApiProvider>>,
+    /// The keys as a JSON object keyed by provider name, encrypted and base64-encoded.
+    ciphertext: String,
+}
+
+impl KeyStore {
+    /// Creates a new, empty KeyStore.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if a key store file exists.
+    pub fn exists() -> bool {
+        get_keystore_file_path().is_some_and(|keystore_path| keystore_path.is_file())
+    }
+
+    /// Returns the providers whose keys are in the key store file, read from its header without the passphrase, or `None` if there is no key store.
+    /// A file written before the header listed them (or whose header cannot be read) may hold the key of any provider which takes one,
+    /// except the optional OpenAI-compatible key.
+    pub fn get_stored_providers() -> Option<Vec<ApiProvider>> {
+        let keystore_path = get_keystore_file_path().filter(|path| path.is_file())?;
+        let stored_providers = fs::read_to_string(keystore_path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<KeyStoreFile>(&contents).ok())
+            .and_then(|keystore_file| keystore_file.providers);
+        Some(stored_providers.unwrap_or_else(|| {
+            SUPPORTED_PROVIDERS
+                .into_iter()
+                .filter(|provider| {
+                    get_api_key_env_var(provider).is_some()
+                        && *provider != ApiProvider::OPENAI_COMPATIBLE
+                })
+                .collect()
+        }))
+    }
+
+    /// Opens the key store file with the passphrase. A missing file yields an empty KeyStore.
+    /// Returns an error if the file cannot be read, or the passphrase does not decrypt it.
+    pub fn open(passphrase: &str) -> Result<Self, Error> {
+        match get_keystore_file_path() {
+            Some(keystore_path) => Self::open_file(&keystore_path, passphrase),
+            None => Ok(Self::new()),
+        }
+    }
+
+    /// Opens the key store file at the path with the passphrase. A missing file yields an empty KeyStore.
+    pub fn open_file(keystore_path: &Path, passphrase: &str) -> Result<Self, Error> {
+        if !keystore_path.is_file() {
+            return Ok(Self::new());
+        }
+        let unreadable = |message: &dyn fmt::Display| {
+            Error::msg(format!(
+                "🔴[KEYSTORE] Error: Unable to open {} | {}",
+                keystore_path.display(),
+                message
+            ))
+        };
+
+        let contents = fs::read_to_string(keystore_path).map_err(|e| unreadable(&e))?;
+        let keystore_file: KeyStoreFile =
+            serde_json::from_str(&contents).map_err(|e| unreadable(&e))?;
+        if keystore_file.version != KEYSTORE_VERSION {
+            return Err(unreadable(&format!(
+                "unsupported version {}",
+                keystore_file.version
+            )));
+        }
+        let params = Params::new(
+            keystore_file.memory_kib,
+            keystore_file.iterations,
+            keystore_file.parallelism,
+            None,
+        )
+        .map_err(|e| unreadable(&e))?;
+        let salt = STANDARD
+            .decode(&keystore_file.salt)
+            .map_err(|e| unreadable(&e))?;
+        let nonce = STANDARD
+            .decode(&keystore_file.nonce)
+            .map_err(|e| unreadable(&e))?;
+        let ciphertext = STANDARD
+            .decode(&keystore_file.ciphertext)
+            .map_err(|e| unreadable(&e))?;
+        if nonce.len() != KEYSTORE_NONCE_LENGTH {
+            return Err(unreadable(&"damaged nonce"));
+        }
+
+        let cipher = get_cipher(passphrase, &salt, params)?;
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+            .map_err(|_| unreadable(&"wrong passphrase, or the file is damaged"))?;
+        let keys = serde_json::from_slice(&plaintext).map_err(|e| unreadable(&e))?;
+        Ok(Self { keys })
+    }
+
+    /// Opens the key store file with the passphrase from the environment, or else prompts for it on the terminal.
+    pub fn unlock() -> Result<Self, Error> {
+        let passphrase = read_passphrase(KEYSTORE_PASSPHRASE_PROMPT)?;
+        Self::open(&passphrase)
+    }
+
+    /// Encrypts the keys with the passphrase and writes them to the key store file, readable only by the user.
+    /// A fresh salt and nonce are used on every save.
+    pub fn save(&self, passphrase: &str) -> Result<(), Error> {
+        let keystore_path = get_keystore_file_path().ok_or_else(|| {
+            Error::msg("🔴[KEYSTORE] Error: No config directory to save the key store.")
+        })?;
+        self.save_to_file(&keystore_path, passphrase)
+    }
+
+    /// Encrypts the keys with the passphrase and writes them to the key store file at the path, readable only by the user.
+    /// The keys are written to a temporary file beside it, which is synced and then renamed over the key store,
+    /// so that a crash or a full disk mid-write leaves the previous key store whole.
+    pub fn save_to_file(&self, keystore_path: &Path, passphrase: &str) -> Result<(), Error> {
+        let unwritable = |message: &dyn fmt::Display| {
+            Error::msg(format!(
+                "🔴[KEYSTORE] Error: Unable to write {} | {}",
+                keystore_path.display(),
+                message
+            ))
+        };
+
+        let params = Params::default();
+        let mut salt = [0u8; KEYSTORE_SALT_LENGTH];
+        let mut nonce = [0u8; KEYSTORE_NONCE_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut nonce);
+        let cipher = get_cipher(passphrase, &salt, params.clone())?;
+        let plaintext = serde_json::to_vec(&self.keys)?;
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_ref())
+            .map_err(|e| unwritable(&e))?;
+        let keystore_file = KeyStoreFile {
+            version: KEYSTORE_VERSION,
+            memory_kib: params.m_cost(),
+            iterations: params.t_cost(),
+            parallelism: params.p_cost(),
+            salt: STANDARD.encode(salt),
+            nonce: STANDARD.encode(nonce),
+            providers: Some(
+                SUPPORTED_PROVIDERS
+                    .into_iter()
+                    .filter(|provider| self.keys.contains_key(provider))
+                    .collect(),
+            ),
+            ciphertext: STANDARD.encode(ciphertext),
+        };
+
+        if let Some(config_dir) = keystore_path.parent() {
+            fs::create_dir_all(config_dir).map_err(|e| unwritable(&e))?;
+        }
+        let temp_path = keystore_path.with_extension("enc.tmp");
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&temp_path).map_err(|e| unwritable(&e))?;
+        // The mode only applies to a file being created, so a leftover temporary file is narrowed too
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))
+                .map_err(|e| unwritable(&e))?;
+        }
+        file.write_all(serde_json::to_string_pretty(&keystore_file)?.as_bytes())
+            .map_err(|e| unwritable(&e))?;
+        file.sync_all().map_err(|e| unwritable(&e))?;
+        fs::rename(&temp_path, keystore_path).map_err(|e| unwritable(&e))
+    }
+
+    /// Returns the stored API key for the specified ApiProvider enum variant.
+    pub fn get(&self, provider: &ApiProvider) -> Option<&String> {
+        self.keys.get(provider)
+    }
+
+    /// Returns every stored API key, keyed by provider.
+    pub fn get_keys(&self) -> &HashMap<ApiProvider, String> {
+        &self.keys
+    }
+
+    /// Stores the API key for the specified ApiProvider enum variant, replacing any stored before.
+    /// Returns an error if the key is not valid for the provider (see `validate_api_key`).
+    pub fn set(&mut self, provider: ApiProvider, api_key: &str) -> Result<(), Error> {
+        validate_api_key(&provider, api_key)?;
+        self.keys.insert(provider, String::from(api_key.trim()));
+        Ok(())
+    }
+
+    /// Removes the stored API key for the specified ApiProvider enum variant. Returns true if one was stored.
+    pub fn remove(&mut self, provider: &ApiProvider) -> bool {
+        self.keys.remove(provider).is_some()
+    }
+}
+
+impl fmt::Debug for KeyStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyStore")
+            .field("keys", &redact_api_keys(&self.keys))
+            .finish()
+    }
+}
+
+/// Returns the path of the key store (i.e. `~/.config/nyota/keys.enc`), if the user's config directory is known.
+pub fn get_keystore_file_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|config_dir| config_dir.join(CONFIG_DIR_NAME).join(KEYSTORE_FILE_NAME))
+}
+
+/// Returns the environment variable which holds the API key of the specified ApiProvider enum variant, or `None` for Ollama.
+pub fn get_api_key_env_var(provider: &ApiProvider) -> Option<&'static str> {
+    match provider {
+        ApiProvider::OPENAI => Some(OPENAI_API_KEY_ENV),
+        ApiProvider::ANTHROPIC => Some(ANTHROPIC_API_KEY_ENV),
+        ApiProvider::OLLAMA => None,
+        ApiProvider::OPENROUTER => Some(OPENROUTER_API_KEY_ENV),
+        ApiProvider::OPENAI_COMPATIBLE => Some(OPENAI_COMPATIBLE_API_KEY_ENV),
+    }
+}
+
+/// Checks that the API key has the form of a key issued by the specified ApiProvider enum variant:
+/// the provider's prefix (`sk-` for OpenAI, `sk-ant-` for Anthropic, `sk-or-` for OpenRouter), a plausible length and no whitespace.
+/// Ollama takes no key, and OpenAI-compatible servers accept any key without whitespace.
+pub fn validate_api_key(provider: &ApiProvider, api_key: &str) -> Result<(), Error> {
+    let api_key = api_key.trim();
+    let invalid = |message: &str| {
+        Error::msg(format!(
+            "🟡[KEYSTORE] 💔🔑 Warning: {:?} | API Key invalid: {}",
+            provider, message
+        ))
+    };
+
+    if *provider == ApiProvider::OLLAMA {
+        return Err(invalid("ollama takes no API key"));
+    }
+    if api_key.is_empty() {
+        return Err(invalid("the key is empty"));
+    }
+    if api_key.chars().any(char::is_whitespace) {
+        return Err(invalid("the key contains whitespace"));
+    }
+    let Some(expected_prefix) = get_api_key_prefix(provider) else {
+        return Ok(());
+    };
+    match get_provider_for_api_key(api_key) {
+        Some(key_provider) if key_provider == *provider => {}
+        Some(key_provider) => {
+            return Err(invalid(&format!(
+                "the key looks like an {} key",
+                key_provider
+            )))
+        }
+        None => {
+            return Err(invalid(&format!(
+                "the key should start with `{}`",
+                expected_prefix
+            )))
+        }
+    }
+    if api_key.len() < MIN_API_KEY_LENGTH {
+        return Err(invalid("the key is too short"));
+    }
+    Ok(())
+}
+
+/// Returns the API key with all but its provider prefix and last few characters hidden (i.e. `sk-ant-…a1b2`), so it can be printed.
+pub fn redact_api_key(api_key: &str) -> String {
+    let prefix = get_provider_for_api_key(api_key)
+        .and_then(|provider| get_api_key_prefix(&provider))
+        .unwrap_or_default();
+    let char_count = api_key.chars().count();
+    // Short keys are hidden entirely, so that the visible characters never give away most of a key
+    if char_count < MIN_API_KEY_LENGTH {
+        return format!("{}…", prefix);
+    }
+    let suffix: String = api_key
+        .chars()
+        .skip(char_count - REDACTED_KEY_VISIBLE_CHARS)
+        .collect();
+    format!("{}…{}", prefix, suffix)
+}
+
+/// Returns the API keys redacted (see `redact_api_key`), keyed by provider.
+pub fn redact_api_keys(api_keys: &HashMap<ApiProvider, String>) -> HashMap<ApiProvider, String> {
+    api_keys
+        .iter()
+        .map(|(provider, api_key)| (*provider, redact_api_key(api_key)))
+        .collect()
+}
+
+/// Reads the key store passphrase from the environment, or else prompts for it on the terminal without echoing it.
+pub fn read_passphrase(prompt: &str) -> Result<String, Error> {
+    let passphrase = match env::var(KEYSTORE_PASSPHRASE_ENV) {
+        Ok(passphrase) => passphrase,
+        Err(VarError::NotPresent) => rpassword::prompt_password(prompt).map_err(|e| {
+            Error::msg(format!(
+                "🟡[KEYSTORE] 🔒 Warning: Key store locked | Unable to prompt for the passphrase ({}), set {}",
+                e, KEYSTORE_PASSPHRASE_ENV
+            ))
+        })?,
+        Err(VarError::NotUnicode(_)) => {
+            return Err(Error::msg(format!(
+                "🟡[KEYSTORE] 🔒 Warning: Key store locked | {} unreadable.",
+                KEYSTORE_PASSPHRASE_ENV
+            )))
+        }
+    };
+    if passphrase.is_empty() {
+        return Err(Error::msg(
+            "🟡[KEYSTORE] 🔒 Warning: Key store locked | The passphrase is empty.",
+        ));
+    }
+    Ok(passphrase)
+}
+
+/// Returns the key prefix issued by the specified ApiProvider enum variant, if it has one.
+fn get_api_key_prefix(provider: &ApiProvider) -> Option<&'static str> {
+    API_KEY_PREFIXES
+        .iter()
+        .find(|(key_provider, _)| key_provider == provider)
+        .map(|(_, prefix)| *prefix)
+}
+
+/// Returns the provider whose key prefix the API key starts with, preferring the longest prefix.
+fn get_provider_for_api_key(api_key: &str) -> Option<ApiProvider> {
+    API_KEY_PREFIXES
+        .iter()
+        .filter(|(_, prefix)| api_key.starts_with(prefix))
+        .max_by_key(|(_, prefix)| prefix.len())
+        .map(|(provider, _)| *provider)
+}
+
+/// Derives the AES-256-GCM cipher from the passphrase and salt with Argon2id.
+fn get_cipher(passphrase: &str, salt: &[u8], params: Params) -> Result<Aes256Gcm, Error> {
+    let mut derived_key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut derived_key)
+        .map_err(|e| {
+            Error::msg(format!(
+                "🔴[KEYSTORE] Error: Unable to derive the encryption key | {}",
+                e
+            ))
+        })?;
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&derived_key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSPHRASE: &str = "correct horse battery staple";
+
+    /// Returns a key store path in a fresh temporary directory of the test.
+    fn get_test_keystore_path(test_name: &str) -> PathBuf {
+        let test_dir = env::temp_dir().join(format!(
+            "nyota-keystore-{}-{}",
+            test_name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&test_dir);
+        test_dir.join(KEYSTORE_FILE_NAME)
+    }
+
+    #[test]
+    fn keys_are_read_back_with_the_passphrase() -> Result<()> {
+        let keystore_path = get_test_keystore_path("round-trip");
+        let mut key_store = KeyStore::new();
+        key_store.set(ApiProvider::ANTHROPIC, "sk-ant-REDACTED")?;
+        key_store.set(ApiProvider::OPENROUTER, " sk-or-v1-0123456789abcdef ")?;
+        key_store.save_to_file(&keystore_path, PASSPHRASE)?;
+
+        let contents = fs::read_to_string(&keystore_path)?;
+        assert!(!contents.contains("0123456789abcdef"));
+        let keystore_file: KeyStoreFile = serde_json::from_str(&contents)?;
+        assert_eq!(
+            keystore_file.providers,
+            Some(vec![ApiProvider::ANTHROPIC, ApiProvider::OPENROUTER])
+        );
+        let opened = KeyStore::open_file(&keystore_path, PASSPHRASE)?;
+        assert_eq!(opened.get_keys(), key_store.get_keys());
+        assert_eq!(
+            opened.get(&ApiProvider::OPENROUTER).map(String::as_str),
+            Some("sk-or-v1-0123456789abcdef")
+        );
+        assert!(!keystore_path.with_extension("enc.tmp").exists());
+        let _ = fs::remove_dir_all(keystore_path.parent().unwrap_or(&keystore_path));
+        Ok(())
+    }
+
+    #[test]
+    fn a_wrong_passphrase_is_rejected() -> Result<()> {
+        let keystore_path = get_test_keystore_path("wrong-passphrase");
+        let mut key_store = KeyStore::new();
+        key_store.set(ApiProvider::OPENAI, "sk-proj-0123456789abcdef")?;
+        key_store.save_to_file(&keystore_path, PASSPHRASE)?;
+
+        let opened = KeyStore::open_file(&keystore_path, "correct horse battery stapler");
+        assert!(opened.is_err_and(|e| e.to_string().contains("wrong passphrase")));
+        // A missing key store opens empty, whatever the passphrase
+        assert!(
+            KeyStore::open_file(&keystore_path.with_extension("missing"), "")?
+                .get_keys()
+                .is_empty()
+        );
+        let _ = fs::remove_dir_all(keystore_path.parent().unwrap_or(&keystore_path));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saving_narrows_the_permissions_of_an_existing_key_store() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        let keystore_path = get_test_keystore_path("permissions");
+        if let Some(test_dir) = keystore_path.parent() {
+            fs::create_dir_all(test_dir)?;
+        }
+        fs::write(&keystore_path, "{}")?;
+        fs::set_permissions(&keystore_path, fs::Permissions::from_mode(0o644))?;
+
+        KeyStore::new().save_to_file(&keystore_path, PASSPHRASE)?;
+        let mode = fs::metadata(&keystore_path)?.permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let _ = fs::remove_dir_all(keystore_path.parent().unwrap_or(&keystore_path));
+        Ok(())
+    }
+
+    #[test]
+    fn api_keys_are_validated_per_provider() {
+        for (provider, api_key, problem) in [
+            (ApiProvider::OPENAI, "sk-proj-0123456789abcdef", None),
+            (
+                ApiProvider::ANTHROPIC,
+                "sk-ant-REDACTED",
+                None,
+            ),
+            (ApiProvider::OPENROUTER, "sk-or-v1-0123456789abcdef", None),
+            (ApiProvider::OPENAI_COMPATIBLE, "local-key", None),
+            (
+                ApiProvider::OLLAMA,
+                "sk-0123456789abcdef0123",
+                Some("ollama takes no API key"),
+            ),
+            (ApiProvider::OPENAI, "  ", Some("the key is empty")),
+            (
+                ApiProvider::OPENAI,
+                "sk-0123456789 abcdef",
+                Some("the key contains whitespace"),
+            ),
+            (
+                ApiProvider::OPENAI,
+                "pk-0123456789abcdef0123",
+                Some("the key should start with `sk-`"),
+            ),
+            (
+                ApiProvider::OPENAI,
+                "sk-ant-REDACTED",
+                Some("the key looks like an"),
+            ),
+            (
+                ApiProvider::ANTHROPIC,
+                "sk-ant-0123",
+                Some("the key is too short"),
+            ),
+        ] {
+            let validated = validate_api_key(&provider, api_key);
+            match problem {
+                None => assert!(validated.is_ok(), "{}: {:?}", api_key, validated),
+                Some(problem) => assert!(
+                    validated
+                        .as_ref()
+                        .is_err_and(|e| e.to_string().contains(problem)),
+                    "{}: {:?}",
+                    api_key,
+                    validated
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn api_keys_are_redacted_to_their_prefix_and_last_characters() {
+        for (api_key, expected) in [
+            ("sk-ant-REDACTED", "sk-ant-…cdef"),
+            ("sk-or-v1-0123456789abcdef", "sk-or-…cdef"),
+            ("sk-proj-0123456789abcdef", "sk-…cdef"),
+            ("local-0123456789abcdef-key", "…-key"),
+            // Short keys are hidden entirely
+            ("sk-ant-0123", "sk-ant-…"),
+            ("secret", "…"),
+        ] {
+            assert_eq!(redact_api_key(api_key), expected, "{}", api_key);
+        }
+        let redacted = redact_api_keys(&HashMap::from([(
+            ApiProvider::OPENAI,
+            String::from("sk-proj-0123456789abcdef"),
+        )]));
+        assert_eq!(
+            redacted.get(&ApiProvider::OPENAI).map(String::as_str),
+            Some("sk-…cdef")
+        );
+    }
+}
@@ -11,14 +11,53 @@
 //! Though ostensibly it resembles the singleton pattern when a default adapter is instanciated. The idea was to have multiple adapters at use per program instance.
 //!
 //! ## Example
-//! ```rust
-//! use nyota::api::Adapter;
+//! ```rust,no_run
+//! use nyota::api::conversation::ChatMessage;
+//! use nyota::api::utilities::Adapter;
 //!
+//! # async fn example() -> anyhow::Result<()> {
 //! // Initialize an adapter
 //! let adapter = Adapter::new();
 //!
 //! // Send Message to the Currently-in focus LLM
-//! let response = adapter.send_to_llm(String::from(user_msg)).await;
+//! let response = adapter.send_to_llm("What does nyota mean?").await?;
+//!
+//! // Send a multi-turn conversation so that follow-up questions keep their context
+//! let conversation = vec![
+//!     ChatMessage::user("What does nyota mean?"),
+//!     ChatMessage::assistant(&response),
+//!     ChatMessage::user("How is it pronounced?"),
+//! ];
+//! let follow_up = adapter.send_conversation(&conversation).await?;
+//! println!("{} ({:?})", follow_up.content, follow_up.usage);
+//!
+//! // Stream the reply back chunk by chunk as it is generated
+//! let mut stream = adapter.stream_conversation(&conversation).await?;
+//! while let Some(chunk) = stream.next_chunk().await? {
+//!     print!("{}", chunk);
+//! }
+//! # Ok(())
+//! # }
+//! ```
 
+pub mod attachment;
+pub mod catalogue;
+pub mod compare;
 pub mod constants;
+pub mod conversation;
+pub mod embeddings;
+pub mod endpoints;
+pub mod error;
+pub mod fallback;
+pub mod generation;
+pub mod inspect;
+pub mod keystore;
+pub mod model_info;
+pub mod persona;
+pub mod response_cache;
+pub mod retry;
+pub mod stream;
+pub mod structured;
+pub mod tools;
+pub mod usage;
 pub mod utilities;
@@ -0,0 +1,267 @@
+//! # API Model Info
+//! This module contains the metadata nyota keeps about each model: context window, maximum output, pricing and capabilities.
+//! The registry is seeded from a file bundled with nyota (`src/api/models.json`) and can be refreshed from OpenRouter's model list,
+//! which is cached on disk (`~/.cache/nyota/model_info.json`). Refreshed entries are keyed by their OpenRouter name (i.e. `openrouter/openai/gpt-4o`).
+//! The metadata is used to size `max_tokens` in requests and to warn before a conversation overflows the model's context window.
+
+use crate::api::constants::*;
+use crate::api::conversation::ChatMessage;
+use anyhow::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, fs, path::PathBuf};
+
+/// Capabilities holds the features a model supports beyond plain text chat.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// The model accepts images.
+    #[serde(default)]
+    pub vision: bool,
+    /// The model supports tool (function) calling.
+    #[serde(default)]
+    pub tools: bool,
+    /// The model supports a JSON output mode.
+    #[serde(default)]
+    pub json_mode: bool,
+    /// The model is a reasoning model.
+    #[serde(default)]
+    pub reasoning: bool,
+}
+
+/// ModelInfo holds the metadata of a single model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    /// The model name, as used by nyota.
+    pub model: String,
+    /// The size of the context window in tokens (prompt and reply).
+    pub context_length: Option<u64>,
+    /// The maximum number of tokens the model can produce in a reply.
+    pub max_output_tokens: Option<u64>,
+    /// The price of a prompt token in USD.
+    pub input_price: Option<f64>,
+    /// The price of a reply token in USD.
+    pub output_price: Option<f64>,
+    /// The features the model supports.
+    #[serde(default)]
+    pub capabilities: Capabilities,
+}
+
+/// ModelRegistry holds the metadata of every known model, keyed by model name.
+#[derive(Debug, Clone)]
+pub struct ModelRegistry {
+    models: HashMap<String, ModelInfo>,
+}
+
+impl ModelRegistry {
+    /// Creates a new instance of ModelRegistry seeded from the bundled model file.
+    /// A bundled file which cannot be read (i.e. after a bad edit) is reported as an error and leaves the registry empty.
+    pub fn new() -> Self {
+        let bundled_models = parse_bundled_model_info().unwrap_or_else(|e| {
+            eprintln!("{:?}", e);
+            Vec::new()
+        });
+        let mut registry = Self {
+            models: HashMap::new(),
+        };
+        registry.insert_all(bundled_models);
+        registry
+    }
+
+    /// Creates a new instance of ModelRegistry seeded from the bundled model file, with the metadata last refreshed from OpenRouter added.
+    /// A missing or unreadable cache file leaves the bundled metadata only.
+    pub fn load() -> Self {
+        let mut registry = Self::new();
+        if let Some(cached_models) = get_cache_file_path()
+            .and_then(|cache_path| fs::read_to_string(cache_path).ok())
+            .and_then(|contents| serde_json::from_str::<Vec<ModelInfo>>(&contents).ok())
+        {
+            registry.insert_all(cached_models);
+        }
+        registry
+    }
+
+    /// Returns the metadata of the specified model, if known.
+    pub fn get(&self, model: &str) -> Option<&ModelInfo> {
+        self.models.get(model)
+    }
+
+    /// Adds (or replaces) the metadata of a model.
+    pub fn insert(&mut self, model_info: ModelInfo) {
+        self.models.insert(model_info.model.clone(), model_info);
+    }
+
+    /// Replaces the OpenRouter metadata with the models of an OpenRouter model list response, and writes them to the cache file.
+    /// Returns the number of models read.
+    pub fn refresh_from_openrouter(&mut self, json_response: &Value) -> Result<usize, Error> {
+        let openrouter_models = parse_openrouter_model_info(json_response)?;
+        let model_count = openrouter_models.len();
+
+        let cache_path = get_cache_file_path().ok_or_else(|| {
+            Error::msg("🟡[MODELS] 🔍📇 Warning: No cache directory to save the model info.")
+        })?;
+        if let Some(cache_dir) = cache_path.parent() {
+            fs::create_dir_all(cache_dir)?;
+        }
+        fs::write(&cache_path, serde_json::to_string(&openrouter_models)?).map_err(|e| {
+            Error::msg(format!(
+                "🟡[MODELS] 💔📇 Warning: Unable to write {} | {}",
+                cache_path.display(),
+                e
+            ))
+        })?;
+
+        self.models
+            .retain(|model, _| !model.starts_with(OPENROUTER_MODEL_PREFIX));
+        self.insert_all(openrouter_models);
+        Ok(model_count)
+    }
+
+    fn insert_all(&mut self, models: Vec<ModelInfo>) {
+        for model_info in models {
+            self.insert(model_info);
+        }
+    }
+}
+
+impl Default for ModelRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parses the model file bundled with nyota (`src/api/models.json`).
+fn parse_bundled_model_info() -> Result<Vec<ModelInfo>, Error> {
+    serde_json::from_str(BUNDLED_MODEL_INFO).map_err(|e| {
+        Error::msg(format!(
+            "🔴[MODELS] 💔📇 Error: The bundled model info is invalid, so no model metadata is known | {}",
+            e
+        ))
+    })
+}
+
+/// Returns the path of the model info cache file (i.e. `~/.cache/nyota/model_info.json`), if the user's cache directory is known.
+pub fn get_cache_file_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|cache_dir| {
+        cache_dir
+            .join(CONFIG_DIR_NAME)
+            .join(MODEL_INFO_CACHE_FILE_NAME)
+    })
+}
+
+/// Returns a rough estimate of the number of tokens a conversation takes up in the context window.
+/// Providers tokenize differently, so this only counts characters (about `CHARS_PER_TOKEN` per token, attached text files and tool-call arguments included)
+/// plus a small overhead per message and `TOKENS_PER_IMAGE` per attached image.
+pub fn estimate_tokens(messages: &[ChatMessage]) -> u64 {
+    messages
+        .iter()
+        .map(|msg| {
+            let tool_call_chars: usize = msg
+                .tool_calls
+                .iter()
+                .map(|tool_call| tool_call.name.len() + tool_call.arguments.to_string().len())
+                .sum();
+            (msg.get_text_content().chars().count() + tool_call_chars) as u64 / CHARS_PER_TOKEN
+                + TOKENS_PER_MESSAGE
+                + msg.get_images().count() as u64 * TOKENS_PER_IMAGE
+        })
+        .sum()
+}
+
+/// Returns the number of tokens to allow for the reply to a conversation:
+/// the model's maximum output, reduced to what is left of its context window after the conversation.
+/// Models without metadata get `DEFAULT_MAX_OUTPUT_TOKENS`.
+pub fn get_max_output_tokens(model_info: Option<&ModelInfo>, messages: &[ChatMessage]) -> u64 {
+    let Some(model_info) = model_info else {
+        return DEFAULT_MAX_OUTPUT_TOKENS;
+    };
+    let max_output_tokens = model_info
+        .max_output_tokens
+        .unwrap_or(DEFAULT_MAX_OUTPUT_TOKENS);
+    match model_info.context_length {
+        Some(context_length) => max_output_tokens
+            .min(context_length.saturating_sub(estimate_tokens(messages)))
+            .max(1),
+        None => max_output_tokens,
+    }
+}
+
+/// Reads the model metadata from the body of an OpenRouter model list response (`data[]`).
+fn parse_openrouter_model_info(json_response: &Value) -> Result<Vec<ModelInfo>, Error> {
+    let entries = json_response["data"]
+        .as_array()
+        .ok_or_else(|| Error::msg("🔴[MODELS] 💔📇 Error: OpenRouter returned no model list."))?;
+
+    let models = entries
+        .iter()
+        .filter_map(|entry| {
+            let model_id = entry["id"].as_str()?;
+            let input_modalities = entry["architecture"]["input_modalities"]
+                .as_array()
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let supported_parameters = entry["supported_parameters"]
+                .as_array()
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let supports = |parameter: &str| {
+                supported_parameters
+                    .iter()
+                    .any(|supported| supported.as_str() == Some(parameter))
+            };
+
+            Some(ModelInfo {
+                model: format!("{}{}", OPENROUTER_MODEL_PREFIX, model_id),
+                context_length: entry["context_length"].as_u64(),
+                max_output_tokens: entry["top_provider"]["max_completion_tokens"].as_u64(),
+                input_price: parse_price(&entry["pricing"]["prompt"]),
+                output_price: parse_price(&entry["pricing"]["completion"]),
+                capabilities: Capabilities {
+                    vision: input_modalities
+                        .iter()
+                        .any(|modality| modality.as_str() == Some("image")),
+                    tools: supports("tools"),
+                    json_mode: supports("response_format") || supports("structured_outputs"),
+                    reasoning: supports("reasoning") || supports("include_reasoning"),
+                },
+            })
+        })
+        .collect();
+    Ok(models)
+}
+
+/// Reads an OpenRouter price, which is given as a string of USD per token (i.e. `"0.0000025"`).
+fn parse_price(price: &Value) -> Option<f64> {
+    match price {
+        Value::String(price) => price.parse().ok(),
+        Value::Number(price) => price.as_f64(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_model_info_parses() -> Result<()> {
+        let bundled_models = parse_bundled_model_info()?;
+        assert!(!bundled_models.is_empty());
+        for model_info in &bundled_models {
+            assert!(!model_info.model.is_empty());
+            assert!(
+                model_info
+                    .context_length
+                    .is_none_or(|context_length| context_length > 0),
+                "{} has an empty context window",
+                model_info.model
+            );
+        }
+        // Every bundled model is known to a new registry, once
+        let registry = ModelRegistry::new();
+        assert_eq!(registry.models.len(), bundled_models.len());
+        for model_info in &bundled_models {
+            assert_eq!(registry.get(&model_info.model), Some(model_info));
+        }
+        Ok(())
+    }
+}
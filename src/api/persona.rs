@@ -0,0 +1,149 @@
+//! # API Personas
+//! This module contains the persona profiles an Adapter can take on: a named system prompt with its own sampling settings.
+//! Personas are TOML files in the nyota personas directory (`~/.config/nyota/personas/<name>.toml`), named after the file.
+//! Besides `system_prompt`, a profile takes the generation parameters (see `nyota::api::generation`), which settings from the config file,
+//! CLI flags and `/set` take precedence over.
+//! The built-in `default` persona uses the `DEFAULT_SYSTEM_PROMPT` and the providers' own sampling defaults,
+//! and can be overridden by a `default.toml`. The persona of a new Adapter is the `model.persona` setting of the config
+//! (see `nyota::cfg::config`), which the `NYOTA_PERSONA` environment variable overrides; `/persona` switches it in the chat.
+//!
+//! ## Example
+//! ```toml
+//! # ~/.config/nyota/personas/reviewer.toml
+//! system_prompt = "You are a meticulous Rust code reviewer. Be terse."
+//! temperature = 0.2
+//! top_p = 0.9
+//! max_tokens = 2048
+//! stop = ["<END>"]
+//! ```
+
+use crate::api::constants::*;
+use crate::api::generation::GenerationParams;
+use anyhow::{Error, Result};
+use std::{fs, path::PathBuf};
+
+/// Persona is a named system prompt with the sampling settings sent along with it.
+/// Unset settings are left to the provider's defaults.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Persona {
+    /// The persona name (the file name of its profile, without the `.toml` extension).
+    pub name: String,
+    /// The system prompt sent when the conversation has no system message of its own.
+    pub system_prompt: String,
+    /// The sampling settings of the persona.
+    pub generation_params: GenerationParams,
+}
+
+impl Persona {
+    /// Creates the built-in default persona.
+    pub fn new() -> Self {
+        Self {
+            name: String::from(DEFAULT_PERSONA_NAME),
+            system_prompt: String::from(DEFAULT_SYSTEM_PROMPT),
+            generation_params: GenerationParams::new(),
+        }
+    }
+
+    /// Loads the persona with the specified name from the personas directory.
+    /// The `default` persona falls back to the built-in one if it has no profile.
+    pub fn load(name: &str) -> Result<Self, Error> {
+        if name.is_empty() || name.contains(['/', '\\', '.']) {
+            return Err(Error::msg(format!(
+                "🟡[PERSONA] 💔🎭 Warning: Invalid persona name `{}`.",
+                name
+            )));
+        }
+        let profile_path = get_personas_dir_path()
+            .map(|personas_dir| personas_dir.join(format!("{}.{}", name, PERSONA_FILE_EXTENSION)))
+            .filter(|profile_path| profile_path.exists());
+        let Some(profile_path) = profile_path else {
+            if name == DEFAULT_PERSONA_NAME {
+                return Ok(Self::new());
+            }
+            return Err(Error::msg(format!(
+                "🟡[PERSONA] 🔍🎭 Warning: No persona named `{}` (available: {}).",
+                name,
+                list_personas().join(", ")
+            )));
+        };
+
+        let contents = fs::read_to_string(&profile_path).map_err(|e| {
+            Error::msg(format!(
+                "🟡[PERSONA] 🔍🎭 Warning: Unable to read {} | {}",
+                profile_path.display(),
+                e
+            ))
+        })?;
+        let invalid_persona = |e: &dyn std::fmt::Display| {
+            Error::msg(format!(
+                "🟡[PERSONA] 💔🎭 Warning: Invalid persona in {} | {}",
+                profile_path.display(),
+                e
+            ))
+        };
+        let mut profile: toml::Table = contents.parse().map_err(|e| invalid_persona(&e))?;
+
+        // Everything but the system prompt is a generation parameter
+        let system_prompt = match profile.remove(PERSONA_SYSTEM_PROMPT_KEY) {
+            Some(toml::Value::String(system_prompt)) => system_prompt,
+            Some(_) => {
+                return Err(invalid_persona(&format!(
+                    "`{}` must be a string",
+                    PERSONA_SYSTEM_PROMPT_KEY
+                )))
+            }
+            None => String::from(DEFAULT_SYSTEM_PROMPT),
+        };
+        let generation_params = toml::Value::Table(profile)
+            .try_into()
+            .map_err(|e| invalid_persona(&e))?;
+
+        Ok(Self {
+            name: String::from(name),
+            system_prompt,
+            generation_params,
+        })
+    }
+
+    /// Loads the named persona, or the default persona if it cannot be loaded (which is reported as a warning).
+    pub fn load_or_default(name: &str) -> Self {
+        Self::load(name).unwrap_or_else(|e| {
+            eprintln!("{:?}", e);
+            eprintln!(
+                "🟡[PERSONA] 🎭 Warning: Using Default Persona - `{}`",
+                DEFAULT_PERSONA_NAME
+            );
+            Self::new()
+        })
+    }
+}
+
+impl Default for Persona {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the names of the available personas: the built-in default and every profile in the personas directory, sorted.
+pub fn list_personas() -> Vec<String> {
+    let mut persona_names = vec![String::from(DEFAULT_PERSONA_NAME)];
+    if let Some(profiles) =
+        get_personas_dir_path().and_then(|personas_dir| fs::read_dir(personas_dir).ok())
+    {
+        persona_names.extend(profiles.filter_map(|profile| {
+            let profile_path = profile.ok()?.path();
+            if profile_path.extension()? != PERSONA_FILE_EXTENSION {
+                return None;
+            }
+            Some(String::from(profile_path.file_stem()?.to_str()?))
+        }));
+    }
+    persona_names.sort();
+    persona_names.dedup();
+    persona_names
+}
+
+/// Returns the path of the personas directory (i.e. `~/.config/nyota/personas`), if the user's config directory is known.
+pub fn get_personas_dir_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|config_dir| config_dir.join(CONFIG_DIR_NAME).join(PERSONAS_DIR_NAME))
+}
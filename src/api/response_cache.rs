@@ -0,0 +1,340 @@
+//! # API Response Cache
+//! This module contains the response cache of the Adapter, which saves the tokens of requests sent again word for word
+//! (i.e. by scripts and CI jobs running the same task). Each request is keyed by its provider and model, and the hash of the
+//! endpoint it is sent to and the request as formulated for the provider (the messages, system prompt, generation parameters,
+//! tools and response schema);
+//! a request whose reply is cached is answered from the cache without being sent, and its usage is not recorded again.
+//!
+//! The replies are kept in the fast cache (see `nyota::ctx::cache`): in Redis if it can be reached, so that they are shared
+//! between runs, and otherwise in memory for the rest of the run. The cache is opt-in: it is on if `responses = true` is set in
+//! the `[cache]` table of the nyota config file, or if `--cache-ttl` is given, and off with `--no-cache`.
+//! Replies are kept for the `response_ttl_secs` of the `[cache]` table (or `--cache-ttl`). Streamed replies are not cached.
+//! The cache is read and written on the blocking threads of the runtime, as Redis is queried synchronously.
+//! A cache which cannot be read or written does not fail the request: the problem is returned with the reply as a warning.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use nyota::api::conversation::ChatMessage;
+//! use nyota::api::response_cache::ResponseCache;
+//! use nyota::api::utilities::Adapter;
+//! use nyota::ctx::cache::CacheSettings;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let mut adapter = Adapter::new();
+//! adapter.set_response_cache(Some(ResponseCache::connect(&CacheSettings::read()?)));
+//!
+//! let messages = [ChatMessage::user("Summarise the release notes.")];
+//! adapter.send_conversation(&messages).await?;
+//! let reply = adapter.send_conversation(&messages).await?;
+//! assert!(reply.cached);
+//! for warning in &reply.warnings {
+//!     eprintln!("{}", warning);
+//! }
+//! if let Some(response_cache) = adapter.get_response_cache() {
+//!     println!("{}", response_cache.get_stats());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::api::conversation::LlmResponse;
+use crate::api::utilities::ApiProvider;
+use crate::ctx::cache::{get_json, set_json, Cache, CacheKey, CacheSettings, FastCache};
+use anyhow::{Error, Result};
+use serde_json::Value;
+use std::{
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+use tokio::task;
+
+/// CacheStats holds the number of requests answered from the response cache and sent to the providers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    /// The requests answered from the cache.
+    pub hits: u64,
+    /// The requests sent to the providers, their replies not being cached.
+    pub misses: u64,
+    /// The tokens the cached replies used when they were first answered, saved by the hits.
+    pub saved_tokens: u64,
+}
+
+impl CacheStats {
+    /// Returns the share of the requests answered from the cache, from 0 to 1 (0 if there were none).
+    pub fn get_hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            requests => self.hits as f64 / requests as f64,
+        }
+    }
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} hit{}, {} miss{} ({:.0}% hit rate, {} tokens saved)",
+            self.hits,
+            if self.hits == 1 { "" } else { "s" },
+            self.misses,
+            if self.misses == 1 { "" } else { "es" },
+            self.get_hit_rate() * 100.0,
+            self.saved_tokens
+        )
+    }
+}
+
+/// ResponseCache keeps the replies of the models in the fast cache, and gives them again for identical requests.
+/// It is a shared handle: clones keep their replies in the same cache for the same time to live, and count them in the same stats
+/// (see `nyota::api::compare`, which asks several models at once through clones of the Adapter).
+#[derive(Clone)]
+pub struct ResponseCache {
+    state: Arc<Mutex<CacheState>>,
+}
+
+/// The fast cache the replies are kept in, the time they are kept, and the stats of the requests looked up in it.
+struct CacheState {
+    cache: FastCache,
+    ttl: Duration,
+    stats: CacheStats,
+}
+
+impl ResponseCache {
+    /// Creates a new ResponseCache which keeps the replies in the cache for the time to live.
+    pub fn new(cache: FastCache, ttl: Duration) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(CacheState {
+                cache,
+                ttl,
+                stats: CacheStats::default(),
+            })),
+        }
+    }
+
+    /// Connects to the fast cache of the settings, keeping the replies for their response TTL.
+    /// If the Redis server of the settings cannot be reached, the replies are kept in memory (see `get_fallback_reason`).
+    pub fn connect(cache_settings: &CacheSettings) -> Self {
+        Self::new(
+            FastCache::connect(cache_settings),
+            cache_settings.response_ttl,
+        )
+    }
+
+    /// Returns the cached reply of the model to the request sent to the chat URL, marked as cached, and counts the lookup as a hit or a miss.
+    /// Returns an error if the cache cannot be read, which is counted as a miss.
+    pub async fn get(
+        &self,
+        provider: ApiProvider,
+        chat_url: &str,
+        model: &str,
+        request: &Value,
+    ) -> Result<Option<LlmResponse>> {
+        let key = CacheKey::response(provider, chat_url, model, request);
+        let response_cache = self.clone();
+        task::spawn_blocking(move || {
+            let mut state = response_cache.lock()?;
+            let reply = get_json::<LlmResponse>(&mut state.cache, &key);
+            match reply {
+                Ok(Some(mut reply)) => {
+                    state.stats.hits += 1;
+                    state.stats.saved_tokens +=
+                        reply.usage.map_or(0, |usage| usage.get_total_tokens());
+                    reply.cached = true;
+                    Ok(Some(reply))
+                }
+                reply => {
+                    state.stats.misses += 1;
+                    reply
+                }
+            }
+        })
+        .await?
+    }
+
+    /// Keeps the reply of the model to the request sent to the chat URL for the time to live.
+    pub async fn store(
+        &self,
+        provider: ApiProvider,
+        chat_url: &str,
+        model: &str,
+        request: &Value,
+        reply: &LlmResponse,
+    ) -> Result<()> {
+        let key = CacheKey::response(provider, chat_url, model, request);
+        let response_cache = self.clone();
+        let reply = reply.clone();
+        task::spawn_blocking(move || {
+            let mut state = response_cache.lock()?;
+            let ttl = state.ttl;
+            set_json(&mut state.cache, &key, &reply, ttl)
+        })
+        .await?
+    }
+
+    /// Returns the number of requests answered from the cache and sent to the providers so far.
+    pub fn get_stats(&self) -> CacheStats {
+        self.lock().map(|state| state.stats).unwrap_or_default()
+    }
+
+    /// Returns the time a reply is kept.
+    pub fn get_ttl(&self) -> Duration {
+        self.lock().map(|state| state.ttl).unwrap_or_default()
+    }
+
+    /// Sets the time a reply is kept by every clone of the cache, for the replies cached from then on.
+    pub fn set_ttl(&self, ttl: Duration) {
+        if let Ok(mut state) = self.lock() {
+            state.ttl = ttl;
+        }
+    }
+
+    /// Returns why the replies are kept in memory rather than Redis, if a Redis server was set but could not be used.
+    pub fn get_fallback_reason(&self) -> Option<String> {
+        self.lock()
+            .ok()
+            .and_then(|state| state.cache.get_fallback_reason().map(String::from))
+    }
+
+    /// Returns the name of the backend the replies are kept in (i.e. `redis`).
+    pub fn get_backend_name(&self) -> String {
+        self.lock()
+            .map(|state| state.cache.get_backend_name())
+            .unwrap_or_default()
+    }
+
+    /// Locks the cache and its stats.
+    fn lock(&self) -> Result<MutexGuard<'_, CacheState>> {
+        self.state
+            .lock()
+            .map_err(|_| Error::msg("🔴[CACHE] 💔🗃️ Error: Response cache unavailable."))
+    }
+}
+
+impl fmt::Debug for ResponseCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseCache")
+            .field("backend", &self.get_backend_name())
+            .field("ttl", &self.get_ttl())
+            .field("stats", &self.get_stats())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::usage::Usage;
+    use serde_json::json;
+    use std::num::NonZeroUsize;
+
+    const CHAT_URL: &str = "https://api.openai.com/v1/chat/completions";
+
+    /// Creates a ResponseCache kept in memory, which keeps the replies for a minute.
+    fn get_test_cache() -> ResponseCache {
+        ResponseCache::new(
+            FastCache::in_memory(NonZeroUsize::MIN.saturating_add(15)),
+            Duration::from_secs(60),
+        )
+    }
+
+    /// Creates a reply of the model with the content, which used 10 tokens.
+    fn get_reply(model: &str, content: &str) -> LlmResponse {
+        LlmResponse {
+            content: String::from(content),
+            tool_calls: Vec::new(),
+            provider: ApiProvider::OPENAI,
+            model: String::from(model),
+            usage: Some(Usage {
+                input_tokens: 7,
+                output_tokens: 3,
+            }),
+            cost: None,
+            cached: false,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Formulates the request of a single user message to the model.
+    fn get_request(model: &str, prompt: &str) -> Value {
+        json!({"model": model, "messages": [{"role": "user", "content": prompt}]})
+    }
+
+    #[tokio::test]
+    async fn lookups_are_counted_as_hits_and_misses() -> Result<()> {
+        let response_cache = get_test_cache();
+        let request = get_request("gpt-4o-mini", "What is the capital of Kenya?");
+
+        let missed = response_cache
+            .get(ApiProvider::OPENAI, CHAT_URL, "gpt-4o-mini", &request)
+            .await?;
+        assert_eq!(missed, None);
+        let reply = get_reply("gpt-4o-mini", "Nairobi");
+        response_cache
+            .store(
+                ApiProvider::OPENAI,
+                CHAT_URL,
+                "gpt-4o-mini",
+                &request,
+                &reply,
+            )
+            .await?;
+        let cached_reply = response_cache
+            .get(ApiProvider::OPENAI, CHAT_URL, "gpt-4o-mini", &request)
+            .await?;
+
+        assert_eq!(
+            cached_reply,
+            Some(LlmResponse {
+                cached: true,
+                ..reply
+            })
+        );
+        assert_eq!(
+            response_cache.get_stats(),
+            CacheStats {
+                hits: 1,
+                misses: 1,
+                saved_tokens: 10,
+            }
+        );
+        assert_eq!(response_cache.get_stats().get_hit_rate(), 0.5);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn replies_are_kept_apart_per_model_and_chat_url() -> Result<()> {
+        let response_cache = get_test_cache();
+        let request = get_request("gpt-4o-mini", "What is the capital of Kenya?");
+        response_cache
+            .store(
+                ApiProvider::OPENAI,
+                CHAT_URL,
+                "gpt-4o-mini",
+                &request,
+                &get_reply("gpt-4o-mini", "Nairobi"),
+            )
+            .await?;
+
+        for (chat_url, model) in [
+            (CHAT_URL, "gpt-4o"),
+            ("http://localhost:8080/v1/chat/completions", "gpt-4o-mini"),
+        ] {
+            let reply = response_cache
+                .get(ApiProvider::OPENAI, chat_url, model, &request)
+                .await?;
+            assert_eq!(reply, None, "{} {}", chat_url, model);
+        }
+        assert_eq!(response_cache.get_stats().misses, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn clones_share_the_ttl() {
+        let response_cache = get_test_cache();
+        let clone = response_cache.clone();
+
+        clone.set_ttl(Duration::from_secs(5));
+        assert_eq!(response_cache.get_ttl(), Duration::from_secs(5));
+    }
+}
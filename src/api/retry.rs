@@ -0,0 +1,268 @@
+//! # API Retry Policy
+//! This module contains the timeout and retry policy applied by the Adapter to every provider call.
+//! Requests which time out, fail to connect, or are answered with a 408, 429 or 5xx status are retried
+//! with exponential backoff and jitter. A `Retry-After` header sent by the provider takes precedence over the backoff.
+//! The policy can be set in the `[retry]` table of the nyota config file (`~/.config/nyota/config.toml`),
+//! and the timeouts and retry count by `NYOTA_REQUEST_TIMEOUT_SECS`, `NYOTA_CONNECT_TIMEOUT_SECS` and `NYOTA_MAX_RETRIES`.
+//!
+//! ## Example
+//! ```toml
+//! [retry]
+//! request_timeout_secs = 300   # local models can be slow
+//! connect_timeout_secs = 5
+//! max_retries = 5
+//! initial_backoff_ms = 250
+//! max_backoff_ms = 10000
+//! ```
+
+use crate::api::constants::*;
+use crate::cfg::config::read_config_table;
+use anyhow::{Error, Result};
+use rand::Rng;
+use reqwest::{Client, StatusCode};
+use std::{
+    env::{self, VarError},
+    time::Duration,
+};
+
+/// RetryPolicy holds the timeouts, retry count and backoff used for provider calls.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// The time allowed for a whole (non-streamed) request, and between chunks of a streamed reply.
+    pub request_timeout: Duration,
+    /// The time allowed to establish a connection.
+    pub connect_timeout: Duration,
+    /// The number of times a failed request is retried (0 disables retries).
+    pub max_retries: u32,
+    /// The delay before the first retry, doubled on every further retry.
+    pub initial_backoff: Duration,
+    /// The upper bound of the delay between retries.
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Creates a new instance of RetryPolicy using the default timeouts and backoff.
+    pub fn new() -> Self {
+        Self {
+            request_timeout: Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECS),
+            connect_timeout: Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS),
+            max_retries: DEFAULT_MAX_RETRIES,
+            initial_backoff: Duration::from_millis(DEFAULT_INITIAL_BACKOFF_MS),
+            max_backoff: Duration::from_millis(DEFAULT_MAX_BACKOFF_MS),
+        }
+    }
+
+    /// Creates a new instance of RetryPolicy with the overrides from the config file and environment variables applied.
+    /// Precedence (lowest to highest): built-in defaults < config file < environment variables.
+    /// Unreadable overrides are reported as warnings and the lower layer is kept.
+    pub fn load() -> Self {
+        let mut retry_policy = Self::new();
+
+        if let Err(e) = retry_policy.apply_config_file() {
+            eprintln!("{:?}", e);
+        }
+
+        match read_u64_from_env(REQUEST_TIMEOUT_ENV) {
+            Ok(Some(seconds)) => retry_policy.request_timeout = Duration::from_secs(seconds),
+            Ok(None) => {}
+            Err(e) => eprintln!("{:?}", e),
+        }
+        match read_u64_from_env(CONNECT_TIMEOUT_ENV) {
+            Ok(Some(seconds)) => retry_policy.connect_timeout = Duration::from_secs(seconds),
+            Ok(None) => {}
+            Err(e) => eprintln!("{:?}", e),
+        }
+        match read_u64_from_env(MAX_RETRIES_ENV) {
+            Ok(Some(retries)) => {
+                retry_policy.max_retries = u32::try_from(retries).unwrap_or(u32::MAX)
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("{:?}", e),
+        }
+
+        retry_policy
+    }
+
+    /// Builds the pooled HTTP client shared by every request of an Adapter.
+    /// The connect timeout applies to every request, and the request timeout bounds the wait between chunks of a response.
+    pub fn build_client(&self) -> Result<Client, Error> {
+        Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .read_timeout(self.request_timeout)
+            .build()
+            .map_err(|e| {
+                Error::msg(format!(
+                    "🔴[ADAPTER] 🚫🔌 Error: Unable to build HTTP client | {}",
+                    e
+                ))
+            })
+    }
+
+    /// Returns true if a request answered with the specified status should be retried.
+    pub fn is_retryable_status(status: StatusCode) -> bool {
+        status == StatusCode::REQUEST_TIMEOUT
+            || status == StatusCode::TOO_MANY_REQUESTS
+            || status.is_server_error()
+    }
+
+    /// Returns true if a request which failed with the specified transport error should be retried.
+    pub fn is_retryable_error(error: &reqwest::Error) -> bool {
+        error.is_timeout() || error.is_connect()
+    }
+
+    /// Returns the delay before the specified retry (starting at 1).
+    /// A `Retry-After` given by the provider is used as is (capped at `max_backoff`);
+    /// otherwise the backoff doubles with every retry and a random jitter of up to half the delay is added.
+    pub fn get_backoff(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_backoff);
+        }
+        let exponential_backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_backoff);
+        let jitter_ms =
+            rand::thread_rng().gen_range(0..=exponential_backoff.as_millis() as u64 / 2);
+        (exponential_backoff + Duration::from_millis(jitter_ms)).min(self.max_backoff)
+    }
+
+    /// Applies the overrides from the `[retry]` table of the nyota config file.
+    fn apply_config_file(&mut self) -> Result<(), Error> {
+        let Some(retry_table) = read_config_table(CONFIG_RETRY_TABLE)? else {
+            return Ok(());
+        };
+
+        for (setting, value) in retry_table {
+            let value = value
+                .as_integer()
+                .and_then(|value| u64::try_from(value).ok())
+                .ok_or_else(|| {
+                    Error::msg(format!(
+                        "🟡[CONFIG] 💔🔁 Warning: Retry setting `{}` must be a positive integer.",
+                        setting
+                    ))
+                })?;
+            match setting.as_str() {
+                "request_timeout_secs" => self.request_timeout = Duration::from_secs(value),
+                "connect_timeout_secs" => self.connect_timeout = Duration::from_secs(value),
+                "max_retries" => self.max_retries = u32::try_from(value).unwrap_or(u32::MAX),
+                "initial_backoff_ms" => self.initial_backoff = Duration::from_millis(value),
+                "max_backoff_ms" => self.max_backoff = Duration::from_millis(value),
+                _ => {
+                    return Err(Error::msg(format!(
+                        "🟡[CONFIG] 💔🔁 Warning: Unknown retry setting `{}`",
+                        setting
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads a non-negative integer from the specified environment variable.
+/// Returns `None` if the variable is unset or empty.
+fn read_u64_from_env(env_var: &str) -> Result<Option<u64>, Error> {
+    match env::var(env_var) {
+        Ok(val) if val.trim().is_empty() => Ok(None),
+        Ok(val) => val.trim().parse().map(Some).map_err(|_| {
+            Error::msg(format!(
+                "🟡[ENV] 💔🔁 Warning: `{}` must be a positive integer, got `{}`",
+                env_var, val
+            ))
+        }),
+        Err(VarError::NotPresent) => Ok(None),
+        Err(VarError::NotUnicode(_)) => Err(Error::msg(format!(
+            "🟡[ENV] 🔍🔁 Warning: `{}` unreadable.",
+            env_var
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_policy(initial_backoff_ms: u64, max_backoff_ms: u64) -> RetryPolicy {
+        RetryPolicy {
+            initial_backoff: Duration::from_millis(initial_backoff_ms),
+            max_backoff: Duration::from_millis(max_backoff_ms),
+            ..RetryPolicy::new()
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_with_every_retry_plus_up_to_half_in_jitter() {
+        let retry_policy = get_policy(100, 60_000);
+        for (retry, exponential_backoff_ms) in [(1, 100), (2, 200), (3, 400), (4, 800), (5, 1600)] {
+            for _ in 0..50 {
+                let backoff = retry_policy.get_backoff(retry, None);
+                assert!(
+                    backoff >= Duration::from_millis(exponential_backoff_ms)
+                        && backoff <= Duration::from_millis(exponential_backoff_ms * 3 / 2),
+                    "retry {} waited {:?}",
+                    retry,
+                    backoff
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn backoff_is_capped_at_max_backoff() {
+        let retry_policy = get_policy(100, 1000);
+        for retry in [4, 5, 10, 64, u32::MAX] {
+            for _ in 0..50 {
+                let backoff = retry_policy.get_backoff(retry, None);
+                assert!(
+                    backoff <= Duration::from_millis(1000),
+                    "retry {} waited {:?}",
+                    retry,
+                    backoff
+                );
+            }
+        }
+        // Once the exponential backoff reaches the cap, the jitter cannot push it past it
+        assert_eq!(
+            retry_policy.get_backoff(10, None),
+            Duration::from_millis(1000)
+        );
+    }
+
+    #[test]
+    fn retry_after_takes_precedence_over_the_backoff() {
+        let retry_policy = get_policy(100, 1000);
+        assert_eq!(
+            retry_policy.get_backoff(3, Some(Duration::from_millis(250))),
+            Duration::from_millis(250)
+        );
+        assert_eq!(
+            retry_policy.get_backoff(1, Some(Duration::ZERO)),
+            Duration::ZERO
+        );
+        assert_eq!(
+            retry_policy.get_backoff(1, Some(Duration::from_secs(120))),
+            Duration::from_millis(1000)
+        );
+    }
+
+    #[test]
+    fn only_timeouts_rate_limits_and_server_errors_are_retried() {
+        for status in [408, 429, 500, 502, 503, 504] {
+            assert!(RetryPolicy::is_retryable_status(
+                StatusCode::from_u16(status).unwrap_or_default()
+            ));
+        }
+        for status in [200, 400, 401, 403, 404, 422] {
+            assert!(!RetryPolicy::is_retryable_status(
+                StatusCode::from_u16(status).unwrap_or_default()
+            ));
+        }
+    }
+}
@@ -0,0 +1,508 @@
+//! # API Streaming
+//! This module contains the streaming response reader for interfacing with various AI API providers.
+//! Streamed replies arrive as incremental text chunks rather than one JSON body:
+//! OpenAI, OpenRouter & OpenAI-compatible servers send Server-Sent Events (`data:` lines), Anthropic sends `content_block_delta` events,
+//! and Ollama sends newline-delimited JSON objects.
+//! The token usage of a streamed reply arrives with its final events and is recorded to the usage ledger once the stream finishes.
+
+use crate::api::error::{extract_error_message, ApiError};
+use crate::api::model_info::ModelInfo;
+use crate::api::usage::{parse_usage, Usage, UsageLedger};
+use crate::api::utilities::ApiProvider;
+use anyhow::{Error, Result};
+use reqwest::Response;
+use serde_json::Value;
+use std::collections::VecDeque;
+
+/// StreamEvent is an enum that represents the meaning of a single line of a streamed provider response.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    /// An incremental piece of the reply text.
+    Text(String),
+    /// The token usage of the reply (OpenAI & OpenRouter send it in a final chunk, and Anthropic splits it over its events).
+    Usage(Usage),
+    /// The provider has signalled the end of the reply, with the token usage if it sends it in the same line (Ollama's `done` line).
+    Done(Option<Usage>),
+    /// The line carries no reply text (keep-alives, comments, metadata events).
+    Skip,
+}
+
+/// ResponseStream reads a streamed response from an API provider and yields the reply text chunk by chunk.
+/// It is created by `Adapter::stream_conversation` and consumed by calling `next_chunk` until it returns `None`.
+pub struct ResponseStream {
+    provider: ApiProvider,
+    model: String,
+    response: Response,
+    /// Bytes received from the provider which do not yet form a complete line.
+    buffer: Vec<u8>,
+    /// Text chunks parsed from complete lines but not yet handed to the caller.
+    pending: VecDeque<String>,
+    finished: bool,
+    usage: Option<Usage>,
+    model_info: Option<ModelInfo>,
+    /// The ledger the usage is recorded to once the stream finishes (taken when recorded).
+    usage_ledger: Option<UsageLedger>,
+    /// The problems which did not prevent the reply (i.e. a usage ledger which could not be written).
+    warnings: Vec<String>,
+}
+
+impl ResponseStream {
+    /// Creates a new ResponseStream from a successful streamed response of the specified ApiProvider and model.
+    pub fn new(provider: ApiProvider, model: &str, response: Response) -> Self {
+        Self {
+            provider,
+            model: String::from(model),
+            response,
+            buffer: Vec::new(),
+            pending: VecDeque::new(),
+            finished: false,
+            usage: None,
+            model_info: None,
+            usage_ledger: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Records the usage of the reply to the ledger once the stream finishes, priced with the model's metadata.
+    pub fn with_usage_ledger(
+        mut self,
+        usage_ledger: UsageLedger,
+        model_info: Option<ModelInfo>,
+    ) -> Self {
+        self.usage_ledger = Some(usage_ledger);
+        self.model_info = model_info;
+        self
+    }
+
+    /// Returns the provider answering the reply (which may be a fallback provider).
+    pub fn get_provider(&self) -> ApiProvider {
+        self.provider
+    }
+
+    /// Returns the model answering the reply (which may be a fallback model).
+    pub fn get_model(&self) -> &str {
+        &self.model
+    }
+
+    /// Returns the token usage of the reply so far, if the provider has reported it.
+    pub fn get_usage(&self) -> Option<Usage> {
+        self.usage
+    }
+
+    /// Returns the cost of the reply so far in USD, if the usage and the model's prices are known.
+    pub fn get_cost(&self) -> Option<f64> {
+        self.usage?.get_cost(self.model_info.as_ref())
+    }
+
+    /// Returns the problems which did not prevent the reply, for the caller to show once the stream finishes
+    /// (i.e. a usage ledger which could not be written).
+    pub fn get_warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Returns the next text chunk of the reply, or `None` once the provider has finished the reply.
+    /// An `ApiError` is returned if the connection fails or the provider reports an error mid-stream.
+    pub async fn next_chunk(&mut self) -> Result<Option<String>> {
+        loop {
+            if let Some(chunk) = self.pending.pop_front() {
+                return Ok(Some(chunk));
+            }
+            if self.finished {
+                self.record_usage();
+                return Ok(None);
+            }
+
+            let next_bytes = self
+                .response
+                .chunk()
+                .await
+                .map_err(|e| ApiError::from_reqwest(self.provider, e))?;
+            match next_bytes {
+                Some(bytes) => {
+                    self.buffer.extend_from_slice(&bytes);
+                    self.drain_complete_lines()?;
+                }
+                None => {
+                    // The connection closed - whatever is left in the buffer is the final line
+                    let remainder = std::mem::take(&mut self.buffer);
+                    self.handle_line(&String::from_utf8_lossy(&remainder))?;
+                    self.finished = true;
+                }
+            }
+        }
+    }
+
+    /// Reads the remainder of the stream and returns the complete reply text.
+    pub async fn collect_text(mut self) -> Result<String> {
+        let mut text = String::new();
+        while let Some(chunk) = self.next_chunk().await? {
+            text.push_str(&chunk);
+        }
+        Ok(text)
+    }
+
+    /// Records the usage to the ledger, once.
+    fn record_usage(&mut self) {
+        if let (Some(usage_ledger), Some(usage)) = (self.usage_ledger.take(), self.usage) {
+            // The reply has already been delivered - an unwritable ledger must not turn it into an error
+            if let Err(e) = usage_ledger.record(&self.model, usage, self.get_cost()) {
+                self.warnings.push(format!("{}", e));
+            }
+        }
+    }
+
+    /// Parses every complete (newline-terminated) line held in the buffer.
+    fn drain_complete_lines(&mut self) -> Result<()> {
+        for line in take_complete_lines(&mut self.buffer) {
+            self.handle_line(&line)?;
+        }
+        Ok(())
+    }
+
+    /// Parses a single line and queues any reply text it contains.
+    fn handle_line(&mut self, line: &str) -> Result<()> {
+        match parse_stream_line(self.provider, line)? {
+            StreamEvent::Text(text) if !text.is_empty() => self.pending.push_back(text),
+            StreamEvent::Usage(usage) => self.usage.get_or_insert_with(Usage::default).merge(usage),
+            StreamEvent::Done(usage) => {
+                if let Some(usage) = usage {
+                    self.usage.get_or_insert_with(Usage::default).merge(usage);
+                }
+                self.finished = true;
+            }
+            StreamEvent::Text(_) | StreamEvent::Skip => {}
+        }
+        Ok(())
+    }
+}
+
+/// Removes the complete (newline-terminated) lines from the buffer and returns them, leaving the partial last line in it.
+/// Lines are only decoded once complete, so a line (or a character) split between two chunks is read whole.
+fn take_complete_lines(buffer: &mut Vec<u8>) -> Vec<String> {
+    let mut lines = Vec::new();
+    while let Some(newline) = buffer.iter().position(|byte| *byte == b'\n') {
+        let line: Vec<u8> = buffer.drain(..=newline).collect();
+        lines.push(String::from(String::from_utf8_lossy(&line)));
+    }
+    lines
+}
+
+/// Parses a single line of a streamed response from the specified API provider into a StreamEvent.
+pub fn parse_stream_line(provider: ApiProvider, line: &str) -> Result<StreamEvent, Error> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(StreamEvent::Skip);
+    }
+
+    let stream_event = match provider {
+        ApiProvider::OPENAI | ApiProvider::OPENROUTER | ApiProvider::OPENAI_COMPATIBLE => {
+            parse_openai_stream_line(line)
+        }
+        ApiProvider::ANTHROPIC => parse_anthropic_stream_line(line),
+        ApiProvider::OLLAMA => parse_ollama_stream_line(line),
+    };
+
+    stream_event.map_err(|stream_error| {
+        Error::from(match stream_error {
+            StreamLineError::Invalid(e) => ApiError::InvalidResponse {
+                provider,
+                message: e.to_string(),
+            },
+            StreamLineError::Reported(message) => ApiError::StreamFailed { provider, message },
+        })
+    })
+}
+
+/// StreamLineError is the reason a single streamed line could not be turned into a StreamEvent.
+enum StreamLineError {
+    /// The line is not valid JSON.
+    Invalid(serde_json::Error),
+    /// The provider reported an error in the line.
+    Reported(String),
+}
+
+impl From<serde_json::Error> for StreamLineError {
+    fn from(e: serde_json::Error) -> Self {
+        StreamLineError::Invalid(e)
+    }
+}
+
+/// Parses a Server-Sent Events line from OpenAI, OpenRouter or an OpenAI-compatible server.
+/// Lines starting with `:` are comments (OpenRouter sends these as keep-alives).
+fn parse_openai_stream_line(line: &str) -> Result<StreamEvent, StreamLineError> {
+    let Some(data) = line.strip_prefix("data:") else {
+        return Ok(StreamEvent::Skip);
+    };
+    let data = data.trim();
+    if data == "[DONE]" {
+        return Ok(StreamEvent::Done(None));
+    }
+
+    let json: Value = serde_json::from_str(data)?;
+    if json.get("error").is_some() {
+        return Err(StreamLineError::Reported(extract_error_message(data)));
+    }
+    match json["choices"][0]["delta"]["content"].as_str() {
+        Some(text) if !text.is_empty() => Ok(StreamEvent::Text(String::from(text))),
+        _ => match parse_usage(ApiProvider::OPENAI, &json) {
+            Some(usage) => Ok(StreamEvent::Usage(usage)),
+            None => Ok(StreamEvent::Skip),
+        },
+    }
+}
+
+/// Parses a Server-Sent Events line from Anthropic.
+/// Only the `data:` lines are read, as each carries its own event `type`.
+fn parse_anthropic_stream_line(line: &str) -> Result<StreamEvent, StreamLineError> {
+    let Some(data) = line.strip_prefix("data:") else {
+        return Ok(StreamEvent::Skip);
+    };
+
+    let json: Value = serde_json::from_str(data.trim())?;
+    match json["type"].as_str() {
+        Some("content_block_delta") => match json["delta"]["text"].as_str() {
+            Some(text) => Ok(StreamEvent::Text(String::from(text))),
+            None => Ok(StreamEvent::Skip),
+        },
+        Some("message_start") => match parse_usage(ApiProvider::ANTHROPIC, &json["message"]) {
+            Some(usage) => Ok(StreamEvent::Usage(usage)),
+            None => Ok(StreamEvent::Skip),
+        },
+        Some("message_delta") => match parse_usage(ApiProvider::ANTHROPIC, &json) {
+            Some(usage) => Ok(StreamEvent::Usage(usage)),
+            None => Ok(StreamEvent::Skip),
+        },
+        Some("message_stop") => Ok(StreamEvent::Done(None)),
+        Some("error") => Err(StreamLineError::Reported(extract_error_message(
+            data.trim(),
+        ))),
+        _ => Ok(StreamEvent::Skip),
+    }
+}
+
+/// Parses a newline-delimited JSON line from Ollama.
+fn parse_ollama_stream_line(line: &str) -> Result<StreamEvent, StreamLineError> {
+    let json: Value = serde_json::from_str(line)?;
+    if let Some(error) = json["error"].as_str() {
+        return Err(StreamLineError::Reported(String::from(error)));
+    }
+    if json["done"].as_bool() == Some(true) {
+        return Ok(StreamEvent::Done(parse_usage(ApiProvider::OLLAMA, &json)));
+    }
+    match json["message"]["content"].as_str() {
+        Some(text) => Ok(StreamEvent::Text(String::from(text))),
+        None => Ok(StreamEvent::Skip),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_usage(input_tokens: u64, output_tokens: u64) -> Usage {
+        Usage {
+            input_tokens,
+            output_tokens,
+        }
+    }
+
+    #[test]
+    fn stream_lines_are_parsed_per_provider() -> Result<()> {
+        let cases = [
+            // OpenAI
+            (
+                ApiProvider::OPENAI,
+                r#"data: {"id":"chatcmpl-AbC1","object":"chat.completion.chunk","created":1760778764,"model":"gpt-4o-mini-2024-07-18","choices":[{"index":0,"delta":{"content":"Nairobi"},"logprobs":null,"finish_reason":null}],"usage":null}"#,
+                StreamEvent::Text(String::from("Nairobi")),
+            ),
+            (
+                ApiProvider::OPENAI,
+                r#"data: {"id":"chatcmpl-AbC1","object":"chat.completion.chunk","created":1760778764,"model":"gpt-4o-mini-2024-07-18","choices":[{"index":0,"delta":{},"logprobs":null,"finish_reason":"stop"}],"usage":null}"#,
+                StreamEvent::Skip,
+            ),
+            (
+                ApiProvider::OPENAI,
+                r#"data: {"id":"chatcmpl-AbC1","object":"chat.completion.chunk","created":1760778764,"model":"gpt-4o-mini-2024-07-18","choices":[],"usage":{"prompt_tokens":14,"completion_tokens":3,"total_tokens":17}}"#,
+                StreamEvent::Usage(get_usage(14, 3)),
+            ),
+            (ApiProvider::OPENAI, "data: [DONE]", StreamEvent::Done(None)),
+            (
+                ApiProvider::OPENAI,
+                "data:[DONE]\r\n",
+                StreamEvent::Done(None),
+            ),
+            (ApiProvider::OPENAI, "", StreamEvent::Skip),
+            // OpenRouter
+            (
+                ApiProvider::OPENROUTER,
+                ": OPENROUTER PROCESSING",
+                StreamEvent::Skip,
+            ),
+            (
+                ApiProvider::OPENROUTER,
+                r#"data: {"id":"gen-1760778764-xYz","provider":"OpenAI","model":"openai/gpt-4o-mini","object":"chat.completion.chunk","created":1760778764,"choices":[{"index":0,"delta":{"role":"assistant","content":" is"},"finish_reason":null,"native_finish_reason":null,"logprobs":null}]}"#,
+                StreamEvent::Text(String::from(" is")),
+            ),
+            (
+                ApiProvider::OPENROUTER,
+                r#"data: {"id":"gen-1760778764-xYz","provider":"OpenAI","model":"openai/gpt-4o-mini","object":"chat.completion.chunk","created":1760778764,"choices":[{"index":0,"delta":{"role":"assistant","content":""},"finish_reason":null,"native_finish_reason":null,"logprobs":null}],"usage":{"prompt_tokens":14,"completion_tokens":3,"total_tokens":17,"cost":0.0000039}}"#,
+                StreamEvent::Usage(get_usage(14, 3)),
+            ),
+            (
+                ApiProvider::OPENROUTER,
+                "data: [DONE]",
+                StreamEvent::Done(None),
+            ),
+            // Anthropic
+            (
+                ApiProvider::ANTHROPIC,
+                "event: content_block_delta",
+                StreamEvent::Skip,
+            ),
+            (
+                ApiProvider::ANTHROPIC,
+                r#"data: {"type":"message_start","message":{"id":"msg_01XFDUDYJgAACzvnptvVoYEL","type":"message","role":"assistant","content":[],"model":"claude-3-5-haiku-20241022","stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":25,"output_tokens":1}}}"#,
+                StreamEvent::Usage(get_usage(25, 1)),
+            ),
+            (
+                ApiProvider::ANTHROPIC,
+                r#"data: {"type":"ping"}"#,
+                StreamEvent::Skip,
+            ),
+            (
+                ApiProvider::ANTHROPIC,
+                r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Nairobi"}}"#,
+                StreamEvent::Text(String::from("Nairobi")),
+            ),
+            (
+                ApiProvider::ANTHROPIC,
+                r#"data: {"type":"message_delta","delta":{"stop_reason":"end_turn","stop_sequence":null},"usage":{"output_tokens":15}}"#,
+                StreamEvent::Usage(get_usage(0, 15)),
+            ),
+            (
+                ApiProvider::ANTHROPIC,
+                r#"data: {"type":"message_stop"}"#,
+                StreamEvent::Done(None),
+            ),
+            // Ollama
+            (
+                ApiProvider::OLLAMA,
+                r#"{"model":"llama3.2","created_at":"2026-10-18T09:12:40.12Z","message":{"role":"assistant","content":"Nairobi"},"done":false}"#,
+                StreamEvent::Text(String::from("Nairobi")),
+            ),
+        ];
+        for (provider, line, expected) in cases {
+            assert_eq!(parse_stream_line(provider, line)?, expected, "{}", line);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn stream_error_lines_fail_the_reply() {
+        let cases = [
+            (
+                ApiProvider::OPENAI,
+                r#"data: {"error":{"message":"The server had an error while processing your request. Sorry about that!","type":"server_error","param":null,"code":null}}"#,
+                "The server had an error while processing your request. Sorry about that!",
+            ),
+            (
+                ApiProvider::OPENROUTER,
+                r#"data: {"id":"cmpl-abc123","object":"chat.completion.chunk","created":1760778764,"model":"openai/gpt-4o-mini","provider":"OpenAI","error":{"code":"server_error","message":"Provider disconnected unexpectedly"},"choices":[{"index":0,"delta":{"content":""},"finish_reason":"error"}]}"#,
+                "Provider disconnected unexpectedly",
+            ),
+            (
+                ApiProvider::ANTHROPIC,
+                r#"data: {"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+                "Overloaded",
+            ),
+            (
+                ApiProvider::OLLAMA,
+                r#"{"error":"model runner has unexpectedly stopped"}"#,
+                "model runner has unexpectedly stopped",
+            ),
+        ];
+        for (provider, line, expected) in cases {
+            let error = parse_stream_line(provider, line).err();
+            assert!(
+                matches!(
+                    error.as_ref().and_then(|e| e.downcast_ref::<ApiError>()),
+                    Some(ApiError::StreamFailed { message, .. }) if message == expected
+                ),
+                "{}: {:?}",
+                line,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_stream_lines_are_invalid_responses() {
+        for (provider, line) in [
+            // A line cut short, as when the connection drops mid-event
+            (
+                ApiProvider::OPENAI,
+                r#"data: {"choices":[{"delta":{"content":"Nai"#,
+            ),
+            (ApiProvider::ANTHROPIC, "data: not json"),
+            (ApiProvider::OLLAMA, "<html>502 Bad Gateway</html>"),
+        ] {
+            let error = parse_stream_line(provider, line).err();
+            assert!(
+                matches!(
+                    error.as_ref().and_then(|e| e.downcast_ref::<ApiError>()),
+                    Some(ApiError::InvalidResponse { .. })
+                ),
+                "{}: {:?}",
+                line,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn lines_split_between_chunks_are_read_whole() -> Result<()> {
+        // "Nairobi ☀" arrives over three chunks, which split an event, the `[DONE]` line and the sun's three bytes
+        let sun = "☀".as_bytes();
+        let chunks: Vec<Vec<u8>> = vec![
+            [
+                br#"data: {"choices":[{"delta":{"content":"Nairobi "#.as_slice(),
+                &sun[..1],
+            ]
+            .concat(),
+            [&sun[1..], br#""}}]}"#.as_slice(), b"\n\ndata: [DO"].concat(),
+            b"NE]\n".to_vec(),
+        ];
+        let mut buffer = Vec::new();
+        let mut events = Vec::new();
+        for chunk in chunks {
+            buffer.extend_from_slice(&chunk);
+            for line in take_complete_lines(&mut buffer) {
+                events.push(parse_stream_line(ApiProvider::OPENAI, &line)?);
+            }
+        }
+        assert_eq!(
+            events,
+            vec![
+                StreamEvent::Text(String::from("Nairobi ☀")),
+                StreamEvent::Skip,
+                StreamEvent::Done(None),
+            ]
+        );
+        assert!(buffer.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn ollama_done_line_ends_the_reply_with_its_usage() -> Result<()> {
+        let done_line = r#"{"model":"llama3.2","created_at":"2026-10-18T09:12:44.51Z","message":{"role":"assistant","content":""},"done_reason":"stop","done":true,"total_duration":4883583458,"load_duration":1334875,"prompt_eval_count":26,"prompt_eval_duration":342546000,"eval_count":282,"eval_duration":4535599000}"#;
+        assert_eq!(
+            parse_stream_line(ApiProvider::OLLAMA, done_line)?,
+            StreamEvent::Done(Some(get_usage(26, 282)))
+        );
+        assert_eq!(
+            parse_stream_line(ApiProvider::OLLAMA, r#"{"model":"llama3.2","done":true}"#)?,
+            StreamEvent::Done(None)
+        );
+        Ok(())
+    }
+}
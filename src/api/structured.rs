@@ -0,0 +1,673 @@
+//! # API Structured Output
+//! This module contains structured output: replies which are a JSON value conforming to a JSON Schema, for scripting.
+//! The schema is sent as OpenAI-style `response_format: json_schema` (OpenAI, OpenRouter & OpenAI-compatible servers)
+//! and Ollama `format`. Anthropic has no native equivalent, so it is asked for the JSON in the system prompt.
+//! Every reply is parsed and validated against the schema before it is returned; a reply which does not conform is sent back
+//! with the problem and asked for again, up to the configured number of retries (the `[structured_output]` table of the config file
+//! or `NYOTA_SCHEMA_RETRIES`).
+//!
+//! Validation covers the common JSON Schema keywords: `type`, `enum`, `const`, `properties`, `required`, `additionalProperties`,
+//! `items`, `anyOf`, `allOf`, `oneOf`, `minimum`, `maximum`, `minLength`, `maxLength`, `minItems` and `maxItems`,
+//! besides annotations such as `title` and `description`. A schema with any other keyword (i.e. `$ref`, `pattern` or `format`)
+//! is rejected before anything is sent, rather than letting replies through which do not conform to it.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use nyota::api::conversation::ChatMessage;
+//! use nyota::api::structured::ResponseSchema;
+//! use nyota::api::utilities::Adapter;
+//! use serde_json::json;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let adapter = Adapter::new();
+//! let schema = ResponseSchema::new(
+//!     "city",
+//!     json!({
+//!         "type": "object",
+//!         "properties": {"name": {"type": "string"}, "population": {"type": "integer"}},
+//!         "required": ["name", "population"]
+//!     }),
+//! );
+//! let reply = adapter
+//!     .send_structured(&[ChatMessage::user("The largest city in Kenya?")], &schema)
+//!     .await?;
+//! println!("{}", reply.value["name"]);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ```toml
+//! [structured_output]
+//! retries = 3
+//! ```
+
+use crate::api::constants::*;
+use crate::api::conversation::LlmResponse;
+use crate::api::utilities::ApiProvider;
+use crate::cfg::config::read_config_table;
+use anyhow::{Error, Result};
+use serde_json::{json, Value};
+use std::{
+    env::{self, VarError},
+    fs,
+    path::Path,
+};
+
+/// ResponseSchema is the JSON Schema a reply must conform to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseSchema {
+    /// The schema name (letters, digits, `_` and `-`), which OpenAI requires.
+    pub name: String,
+    /// The JSON Schema.
+    pub schema: Value,
+}
+
+/// StructuredResponse is a reply which conforms to a JSON Schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructuredResponse {
+    /// The JSON value of the reply.
+    pub value: Value,
+    /// The reply it was read from.
+    pub response: LlmResponse,
+}
+
+impl ResponseSchema {
+    /// Creates a new ResponseSchema with the specified name and JSON Schema.
+    pub fn new(name: &str, schema: Value) -> Self {
+        Self {
+            name: get_schema_name(name),
+            schema,
+        }
+    }
+
+    /// Loads a JSON Schema from a file, named after the file (or its `title`, if it has one).
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let invalid_schema = |message: &dyn std::fmt::Display| {
+            Error::msg(format!(
+                "🔴[SCHEMA] Error: Unable to use JSON Schema {} | {}",
+                path.display(),
+                message
+            ))
+        };
+        let contents = fs::read_to_string(path).map_err(|e| invalid_schema(&e))?;
+        let schema: Value = serde_json::from_str(&contents).map_err(|e| invalid_schema(&e))?;
+        if !schema.is_object() {
+            return Err(invalid_schema(&"a JSON Schema must be an object"));
+        }
+        check_keywords(&schema, "#").map_err(|e| invalid_schema(&e))?;
+
+        let name = schema["title"]
+            .as_str()
+            .or_else(|| path.file_stem().and_then(|file_stem| file_stem.to_str()))
+            .unwrap_or(DEFAULT_SCHEMA_NAME)
+            .to_string();
+        Ok(Self::new(&name, schema))
+    }
+
+    /// Parses a reply as JSON and validates it against the schema.
+    /// Code fences around the JSON (i.e. ```` ```json ````) are ignored. Returns the reason if the reply does not conform.
+    pub fn parse_reply(&self, reply: &str) -> Result<Value, String> {
+        let value: Value = serde_json::from_str(strip_code_fences(reply))
+            .map_err(|e| format!("the reply is not valid JSON ({})", e))?;
+        self.validate(&value)?;
+        Ok(value)
+    }
+
+    /// Validates a JSON value against the schema, returning where and why it does not conform.
+    pub fn validate(&self, value: &Value) -> Result<(), String> {
+        validate_value(value, &self.schema, "$")
+    }
+
+    /// Returns an error naming the first keyword of the schema which replies cannot be validated against, if it has one.
+    pub fn check_keywords(&self) -> Result<(), Error> {
+        check_keywords(&self.schema, "#").map_err(|e| {
+            Error::msg(format!(
+                "🔴[SCHEMA] Error: Unable to use JSON Schema `{}` | {}",
+                self.name, e
+            ))
+        })
+    }
+}
+
+/// Returns the message which asks for a reply again after the last one did not conform to the schema, for the problem found.
+pub fn get_retry_prompt(problem: &str) -> String {
+    format!("{} {}", SCHEMA_RETRY_PROMPT, problem)
+}
+
+/// Adds the schema to a request formulated for the specified provider: as `response_format` (OpenAI, OpenRouter & OpenAI-compatible servers),
+/// `format` (Ollama), or an instruction appended to the system prompt (Anthropic).
+pub fn add_response_schema_to_request(
+    request: &mut Value,
+    provider: ApiProvider,
+    response_schema: &ResponseSchema,
+) {
+    match provider {
+        ApiProvider::OPENAI | ApiProvider::OPENROUTER | ApiProvider::OPENAI_COMPATIBLE => {
+            request["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": {"name": response_schema.name, "schema": response_schema.schema}
+            });
+        }
+        ApiProvider::OLLAMA => request["format"] = response_schema.schema.clone(),
+        ApiProvider::ANTHROPIC => {
+            let instruction = format!("{}\n{}", SCHEMA_INSTRUCTION, response_schema.schema);
+            request["system"] = match request["system"].as_str() {
+                Some(system_prompt) => json!(format!("{}\n\n{}", system_prompt, instruction)),
+                None => json!(instruction),
+            };
+        }
+    }
+}
+
+/// Loads the number of times a reply which does not conform to the schema is asked for again.
+/// Precedence (lowest to highest): `DEFAULT_SCHEMA_RETRIES` < config file < environment variable.
+/// Unreadable settings are reported as warnings and the lower layer is kept.
+pub fn load_schema_retries() -> u32 {
+    let mut schema_retries = DEFAULT_SCHEMA_RETRIES;
+    match read_schema_retries_from_config_file() {
+        Ok(Some(retries)) => schema_retries = retries,
+        Ok(None) => {}
+        Err(e) => eprintln!("{:?}", e),
+    }
+    match get_schema_retries_from_env() {
+        Ok(Some(retries)) => schema_retries = retries,
+        Ok(None) => {}
+        Err(e) => eprintln!("{:?}", e),
+    }
+    schema_retries
+}
+
+/// Returns the name with every character OpenAI does not accept in a schema name replaced by `_`.
+fn get_schema_name(name: &str) -> String {
+    let schema_name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(64)
+        .collect();
+    if schema_name.is_empty() {
+        String::from(DEFAULT_SCHEMA_NAME)
+    } else {
+        schema_name
+    }
+}
+
+/// Returns the reply without the code fences some models wrap JSON in.
+fn strip_code_fences(reply: &str) -> &str {
+    let reply = reply.trim();
+    let Some(fenced) = reply.strip_prefix("```") else {
+        return reply;
+    };
+    // Drop the language tag line (i.e. `json`) and the closing fence
+    let fenced = fenced.split_once('\n').map_or("", |(_, body)| body);
+    fenced
+        .trim_end()
+        .strip_suffix("```")
+        .unwrap_or(fenced)
+        .trim()
+}
+
+/// Checks that every keyword of a JSON Schema, and of the schemas within it, is one replies are validated against or an annotation.
+/// Returns the keyword and where it is in the schema (i.e. `#/properties/email`) otherwise.
+fn check_keywords(schema: &Value, path: &str) -> Result<(), String> {
+    let schema = match schema {
+        Value::Bool(_) => return Ok(()),
+        Value::Object(schema) => schema,
+        _ => {
+            return Err(format!(
+                "{}: a JSON Schema must be an object or a boolean",
+                path
+            ))
+        }
+    };
+    for (keyword, value) in schema {
+        if SCHEMA_ANNOTATION_KEYWORDS.contains(&keyword.as_str()) {
+            continue;
+        }
+        if !SUPPORTED_SCHEMA_KEYWORDS.contains(&keyword.as_str()) {
+            return Err(format!(
+                "{}: the keyword `{}` is not supported, so replies could not be checked against it",
+                path, keyword
+            ));
+        }
+        let keyword_path = format!("{}/{}", path, keyword);
+        match (keyword.as_str(), value) {
+            ("properties", Value::Object(properties)) => {
+                for (property, property_schema) in properties {
+                    check_keywords(property_schema, &format!("{}/{}", keyword_path, property))?;
+                }
+            }
+            ("additionalProperties" | "items", _) => check_keywords(value, &keyword_path)?,
+            ("anyOf" | "allOf" | "oneOf", Value::Array(subschemas)) => {
+                for (index, subschema) in subschemas.iter().enumerate() {
+                    check_keywords(subschema, &format!("{}/{}", keyword_path, index))?;
+                }
+            }
+            ("properties" | "anyOf" | "allOf" | "oneOf", _) => {
+                return Err(format!(
+                    "{}: `{}` has the wrong type",
+                    keyword_path, keyword
+                ))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Validates a JSON value against a JSON Schema, at the specified path of the reply (i.e. `$.cities[0].name`).
+fn validate_value(value: &Value, schema: &Value, path: &str) -> Result<(), String> {
+    // `true` (and `{}`) accepts anything, `false` nothing
+    let schema = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => return Err(format!("{}: no value is allowed here", path)),
+        Value::Object(schema) => schema,
+        _ => return Ok(()),
+    };
+    let fail = |message: String| Err(format!("{}: {}", path, message));
+
+    if let Some(schema_type) = schema.get("type") {
+        let types: Vec<&str> = match schema_type {
+            Value::String(schema_type) => vec![schema_type.as_str()],
+            Value::Array(schema_types) => schema_types.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|schema_type| is_type(value, schema_type)) {
+            return fail(format!(
+                "expected {}, got {}",
+                types.join(" or "),
+                get_type_name(value)
+            ));
+        }
+    }
+    if let Some(Value::Array(allowed_values)) = schema.get("enum") {
+        if !allowed_values.contains(value) {
+            return fail(format!(
+                "{} is not one of {}",
+                value,
+                Value::Array(allowed_values.clone())
+            ));
+        }
+    }
+    if let Some(constant) = schema.get("const") {
+        if constant != value {
+            return fail(format!("expected {}, got {}", constant, value));
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for key in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(key) {
+                        return fail(format!("missing required property `{}`", key));
+                    }
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (key, property_value) in object {
+                let property_path = format!("{}.{}", path, key);
+                match properties.and_then(|properties| properties.get(key)) {
+                    Some(property_schema) => {
+                        validate_value(property_value, property_schema, &property_path)?
+                    }
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            return fail(format!("unexpected property `{}`", key))
+                        }
+                        Some(additional_schema) => {
+                            validate_value(property_value, additional_schema, &property_path)?
+                        }
+                        None => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            check_bound(items.len() as f64, schema, "minItems", "maxItems", "items")
+                .or_else(fail)?;
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    validate_value(item, item_schema, &format!("{}[{}]", path, index))?;
+                }
+            }
+        }
+        Value::String(string) => {
+            check_bound(
+                string.chars().count() as f64,
+                schema,
+                "minLength",
+                "maxLength",
+                "characters",
+            )
+            .or_else(fail)?;
+        }
+        Value::Number(number) => {
+            if let Some(number) = number.as_f64() {
+                check_bound(number, schema, "minimum", "maximum", "").or_else(fail)?;
+            }
+        }
+        Value::Bool(_) | Value::Null => {}
+    }
+
+    if let Some(Value::Array(all_of)) = schema.get("allOf") {
+        for subschema in all_of {
+            validate_value(value, subschema, path)?;
+        }
+    }
+    if let Some(Value::Array(any_of)) = schema.get("anyOf") {
+        let errors: Vec<String> = any_of
+            .iter()
+            .filter_map(|subschema| validate_value(value, subschema, path).err())
+            .collect();
+        if errors.len() == any_of.len() && !any_of.is_empty() {
+            return fail(format!("matches none of anyOf ({})", errors.join("; ")));
+        }
+    }
+    if let Some(Value::Array(one_of)) = schema.get("oneOf") {
+        let matches = one_of
+            .iter()
+            .filter(|subschema| validate_value(value, subschema, path).is_ok())
+            .count();
+        if matches != 1 {
+            return fail(format!("matches {} of oneOf, expected exactly 1", matches));
+        }
+    }
+    Ok(())
+}
+
+/// Returns a message if the size (or value) is below the schema's minimum or above its maximum.
+fn check_bound(
+    size: f64,
+    schema: &serde_json::Map<String, Value>,
+    min_keyword: &str,
+    max_keyword: &str,
+    unit: &str,
+) -> Result<(), String> {
+    let unit = if unit.is_empty() {
+        String::new()
+    } else {
+        format!(" {}", unit)
+    };
+    if let Some(minimum) = schema.get(min_keyword).and_then(Value::as_f64) {
+        if size < minimum {
+            return Err(format!(
+                "{}{} is below the {} of {}",
+                size, unit, min_keyword, minimum
+            ));
+        }
+    }
+    if let Some(maximum) = schema.get(max_keyword).and_then(Value::as_f64) {
+        if size > maximum {
+            return Err(format!(
+                "{}{} is above the {} of {}",
+                size, unit, max_keyword, maximum
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Returns true if the value is of the JSON Schema type (an `integer` is any number without a fraction).
+fn is_type(value: &Value, schema_type: &str) -> bool {
+    match schema_type {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value
+            .as_f64()
+            .is_some_and(|number| value.is_i64() || value.is_u64() || number.fract() == 0.0),
+        _ => true,
+    }
+}
+
+/// Returns the JSON Schema type name of the value.
+fn get_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Object(_) => "object",
+        Value::Array(_) => "array",
+        Value::String(_) => "string",
+        Value::Bool(_) => "boolean",
+        Value::Null => "null",
+        Value::Number(number) if number.is_f64() => "number",
+        Value::Number(_) => "integer",
+    }
+}
+
+/// Reads the retry count from the `[structured_output]` table of the nyota config file.
+fn read_schema_retries_from_config_file() -> Result<Option<u32>, Error> {
+    let Some(structured_output_table) = read_config_table(CONFIG_STRUCTURED_OUTPUT_TABLE)? else {
+        return Ok(None);
+    };
+    match structured_output_table.get(CONFIG_SCHEMA_RETRIES_KEY) {
+        Some(toml::Value::Integer(retries)) if *retries >= 0 => {
+            Ok(Some(u32::try_from(*retries).unwrap_or(u32::MAX)))
+        }
+        Some(_) => Err(Error::msg(format!(
+            "🟡[CONFIG] 💔🧾 Warning: `{}` must be a positive integer.",
+            CONFIG_SCHEMA_RETRIES_KEY
+        ))),
+        None => Ok(None),
+    }
+}
+
+/// Retrieves the retry count from the `NYOTA_SCHEMA_RETRIES` environment variable.
+/// Returns `None` if the variable is unset or empty.
+fn get_schema_retries_from_env() -> Result<Option<u32>, Error> {
+    match env::var(SCHEMA_RETRIES_ENV) {
+        Ok(val) if val.trim().is_empty() => Ok(None),
+        Ok(val) => val.trim().parse().map(Some).map_err(|_| {
+            Error::msg(format!(
+                "🟡[ENV] 💔🧾 Warning: `{}` must be a positive integer, got `{}`",
+                SCHEMA_RETRIES_ENV, val
+            ))
+        }),
+        Err(VarError::NotPresent) => Ok(None),
+        Err(VarError::NotUnicode(_)) => Err(Error::msg(format!(
+            "🟡[ENV] 🔍🧾 Warning: `{}` unreadable.",
+            SCHEMA_RETRIES_ENV
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_validated_against_the_supported_keywords() {
+        let city = json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string", "minLength": 1, "maxLength": 20},
+                "population": {"type": "integer", "minimum": 0},
+                "kind": {"enum": ["capital", "town"]},
+                "country": {"const": "Kenya"},
+                "districts": {"type": "array", "items": {"type": "string"}, "minItems": 1, "maxItems": 2},
+                "area": {"anyOf": [{"type": "number"}, {"type": "null"}]},
+                "code": {"oneOf": [{"type": "string"}, {"type": "integer"}]},
+                "elevation": {"allOf": [{"type": "number"}, {"maximum": 5000}]}
+            },
+            "required": ["name", "population"],
+            "additionalProperties": false
+        });
+        let table = [
+            (json!({"name": "Nairobi", "population": 4397073}), Ok(())),
+            (
+                json!({"name": "Nairobi", "population": 4.397e6, "kind": "capital", "country": "Kenya",
+                       "districts": ["Westlands"], "area": null, "code": 47, "elevation": 1795}),
+                Ok(()),
+            ),
+            (
+                json!({"name": "Nairobi"}),
+                Err("$: missing required property `population`"),
+            ),
+            (
+                json!({"name": 1, "population": 1}),
+                Err("$.name: expected string, got integer"),
+            ),
+            (
+                json!({"name": "", "population": 1}),
+                Err("$.name: 0 characters is below the minLength of 1"),
+            ),
+            (
+                json!({"name": "N", "population": 1.5}),
+                Err("$.population: expected integer, got number"),
+            ),
+            (
+                json!({"name": "N", "population": -1}),
+                Err("$.population: -1 is below the minimum of 0"),
+            ),
+            (
+                json!({"name": "N", "population": 1, "kind": "city"}),
+                Err(r#"$.kind: "city" is not one of ["capital","town"]"#),
+            ),
+            (
+                json!({"name": "N", "population": 1, "country": "Uganda"}),
+                Err(r#"$.country: expected "Kenya", got "Uganda""#),
+            ),
+            (
+                json!({"name": "N", "population": 1, "districts": []}),
+                Err("$.districts: 0 items is below the minItems of 1"),
+            ),
+            (
+                json!({"name": "N", "population": 1, "districts": ["a", 2]}),
+                Err("$.districts[1]: expected string, got integer"),
+            ),
+            (
+                json!({"name": "N", "population": 1, "area": "big"}),
+                Err("$.area: matches none of anyOf"),
+            ),
+            (
+                json!({"name": "N", "population": 1, "code": true}),
+                Err("$.code: matches 0 of oneOf, expected exactly 1"),
+            ),
+            (
+                json!({"name": "N", "population": 1, "elevation": 6000}),
+                Err("$.elevation: 6000 is above the maximum of 5000"),
+            ),
+            (
+                json!({"name": "N", "population": 1, "mayor": "X"}),
+                Err("$: unexpected property `mayor`"),
+            ),
+            (json!(["Nairobi"]), Err("$: expected object, got array")),
+        ];
+        for (value, expected) in table {
+            let validated = validate_value(&value, &city, "$");
+            match expected {
+                Ok(()) => assert_eq!(validated, Ok(()), "{}", value),
+                Err(expected) => assert!(
+                    validated.as_ref().is_err_and(|e| e.starts_with(expected)),
+                    "{} gave {:?}, expected {}",
+                    value,
+                    validated,
+                    expected
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn boolean_schemas_accept_everything_or_nothing() {
+        assert_eq!(validate_value(&json!({"a": 1}), &json!(true), "$"), Ok(()));
+        assert_eq!(validate_value(&json!(1), &json!({}), "$"), Ok(()));
+        assert!(validate_value(&json!(1), &json!(false), "$").is_err());
+        let closed = json!({"type": "object", "additionalProperties": {"type": "integer"}});
+        assert_eq!(validate_value(&json!({"a": 1}), &closed, "$"), Ok(()));
+        assert!(validate_value(&json!({"a": "1"}), &closed, "$").is_err());
+    }
+
+    #[test]
+    fn code_fences_are_stripped_from_replies() {
+        for (reply, expected) in [
+            (r#"{"a": 1}"#, r#"{"a": 1}"#),
+            ("  {\"a\": 1}\n", r#"{"a": 1}"#),
+            ("```json\n{\"a\": 1}\n```", r#"{"a": 1}"#),
+            ("```\n[1, 2]\n```\n", "[1, 2]"),
+            ("```json\n{\"a\": 1}", r#"{"a": 1}"#),
+            ("```", ""),
+        ] {
+            assert_eq!(strip_code_fences(reply), expected, "{:?}", reply);
+        }
+        let schema = ResponseSchema::new("numbers", json!({"type": "array"}));
+        assert_eq!(
+            schema.parse_reply("```json\n[1, 2]\n```"),
+            Ok(json!([1, 2]))
+        );
+        assert!(schema
+            .parse_reply("Here you go: [1, 2]")
+            .is_err_and(|e| e.starts_with("the reply is not valid JSON")));
+    }
+
+    #[test]
+    fn schemas_with_unsupported_keywords_are_rejected() {
+        for (schema, keyword) in [
+            (json!({"$ref": "https://example.com/city.json"}), "$ref"),
+            (
+                json!({"$defs": {"city": {"type": "string"}}, "type": "object"}),
+                "$defs",
+            ),
+            (
+                json!({"type": "object", "properties": {"email": {"type": "string", "format": "email"}}}),
+                "format",
+            ),
+            (
+                json!({"type": "object", "properties": {"code": {"type": "string", "pattern": "^[A-Z]{3}$"}}}),
+                "pattern",
+            ),
+            (
+                json!({"type": "number", "exclusiveMinimum": 0}),
+                "exclusiveMinimum",
+            ),
+            (
+                json!({"type": "array", "prefixItems": [{"type": "string"}]}),
+                "prefixItems",
+            ),
+            (
+                json!({"anyOf": [{"type": "string"}, {"not": {"type": "null"}}]}),
+                "not",
+            ),
+            (
+                json!({"type": "array", "items": {"type": "integer", "multipleOf": 2}}),
+                "multipleOf",
+            ),
+        ] {
+            let checked = ResponseSchema::new("test", schema.clone()).check_keywords();
+            assert!(
+                checked.is_err_and(|e| e.to_string().contains(&format!("`{}`", keyword))),
+                "{} was not rejected for `{}`",
+                schema,
+                keyword
+            );
+        }
+        let schema = json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "city",
+            "description": "A city and its population",
+            "type": "object",
+            "properties": {"name": {"type": "string", "description": "The name"}, "tags": {"items": true}},
+            "required": ["name"]
+        });
+        assert!(ResponseSchema::new("city", schema).check_keywords().is_ok());
+    }
+
+    #[test]
+    fn retry_prompt_gives_the_problem() {
+        assert_eq!(
+            get_retry_prompt("$: missing required property `name`"),
+            format!(
+                "{} $: missing required property `name`",
+                SCHEMA_RETRY_PROMPT
+            )
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! # API Tools
+//! This module contains native tool (function) calling: the tools offered to a model and the calls it asks for.
+//! Tool definitions are usually generated from the action schemas (see `nyota::act`), so the model can pick an action and its parameters.
+//! They are serialized as OpenAI, OpenRouter, OpenAI-compatible & Ollama `tools` (`{"type": "function", "function": {...}}`)
+//! and Anthropic `tools` (`{"name", "description", "input_schema"}`). Tool calls are parsed from OpenAI-style `tool_calls`,
+//! Anthropic `tool_use` content blocks and Ollama `message.tool_calls`, and returned apart from the reply text.
+//! The model is given the results by sending the conversation again with the reply (`ChatMessage::from_reply`) and a
+//! `ChatMessage::tool_result` for each call (see `nyota::api::conversation`). Streamed replies are not offered tools.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use nyota::act::create_get_balance_action;
+//! use nyota::api::conversation::ChatMessage;
+//! use nyota::api::utilities::Adapter;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let mut adapter = Adapter::new();
+//! adapter.set_tools(vec![create_get_balance_action().to_tool_definition()]);
+//!
+//! let mut conversation = vec![ChatMessage::user("What's the balance of 0xabc on ethereum?")];
+//! let reply = adapter.send_conversation(&conversation).await?;
+//! conversation.push(ChatMessage::from_reply(&reply));
+//! for tool_call in &reply.tool_calls {
+//!     println!("{}({})", tool_call.name, tool_call.arguments); // i.e. get_balance({"address":"0xabc","chain":"ethereum"})
+//!     conversation.push(ChatMessage::tool_result(&tool_call.id, "1.5 ETH"));
+//! }
+//! let answer = adapter.send_conversation(&conversation).await?;
+//! println!("{}", answer.content);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::api::error::ApiError;
+use crate::api::utilities::ApiProvider;
+use anyhow::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// ToolDefinition describes a tool the model may call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolDefinition {
+    /// The tool name the model calls it by.
+    pub name: String,
+    /// What the tool does, which the model uses to decide when to call it.
+    pub description: String,
+    /// The JSON Schema of the tool's arguments (an `object` schema).
+    pub parameters: Value,
+}
+
+impl ToolDefinition {
+    /// Creates a new ToolDefinition from its name, description and the JSON Schema of its arguments.
+    pub fn new(name: &str, description: &str, parameters: Value) -> Self {
+        Self {
+            name: String::from(name),
+            description: String::from(description),
+            parameters,
+        }
+    }
+
+    /// Returns the tool in the request format of the specified provider.
+    pub fn to_provider_json(&self, provider: ApiProvider) -> Value {
+        match provider {
+            ApiProvider::ANTHROPIC => json!({
+                "name": self.name,
+                "description": self.description,
+                "input_schema": self.parameters
+            }),
+            ApiProvider::OPENAI
+            | ApiProvider::OPENROUTER
+            | ApiProvider::OPENAI_COMPATIBLE
+            | ApiProvider::OLLAMA => json!({
+                "type": "function",
+                "function": {
+                    "name": self.name,
+                    "description": self.description,
+                    "parameters": self.parameters
+                }
+            }),
+        }
+    }
+}
+
+/// ToolCall is a request from the model to call a tool.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// The id of the call given by the provider (Ollama gives none, so its calls are numbered).
+    pub id: String,
+    /// The name of the tool to call.
+    pub name: String,
+    /// The arguments of the call, as a JSON object.
+    pub arguments: Value,
+}
+
+impl ToolCall {
+    /// Returns the call in the request format of the specified provider, for the assistant message which made it:
+    /// an OpenAI-style `tool_calls` entry (with the arguments as a JSON string), an Anthropic `tool_use` block,
+    /// or an Ollama `tool_calls` entry (with the arguments as an object).
+    pub fn to_provider_json(&self, provider: ApiProvider) -> Value {
+        match provider {
+            ApiProvider::ANTHROPIC => json!({
+                "type": "tool_use",
+                "id": self.id,
+                "name": self.name,
+                "input": self.arguments
+            }),
+            ApiProvider::OLLAMA => json!({
+                "function": {"name": self.name, "arguments": self.arguments}
+            }),
+            ApiProvider::OPENAI | ApiProvider::OPENROUTER | ApiProvider::OPENAI_COMPATIBLE => {
+                json!({
+                    "id": self.id,
+                    "type": "function",
+                    "function": {"name": self.name, "arguments": self.arguments.to_string()}
+                })
+            }
+        }
+    }
+}
+
+/// Adds the tools to a request formulated for the specified provider. An empty list leaves the request as it is.
+pub fn add_tools_to_request(request: &mut Value, provider: ApiProvider, tools: &[ToolDefinition]) {
+    if tools.is_empty() {
+        return;
+    }
+    request["tools"] = Value::Array(
+        tools
+            .iter()
+            .map(|tool| tool.to_provider_json(provider))
+            .collect(),
+    );
+}
+
+/// Parses the tool calls from a successful response body of the specified provider.
+/// Returns an empty list if the model called no tools, or an `ApiError::InvalidResponse` if a call cannot be read.
+pub fn parse_tool_calls(
+    provider: ApiProvider,
+    json_response: &Value,
+) -> Result<Vec<ToolCall>, Error> {
+    let invalid_call = |message: &str| {
+        Error::from(ApiError::InvalidResponse {
+            provider,
+            message: format!("Unreadable tool call: {}", message),
+        })
+    };
+
+    match provider {
+        ApiProvider::OPENAI | ApiProvider::OPENROUTER | ApiProvider::OPENAI_COMPATIBLE => {
+            let Some(tool_calls) = json_response["choices"][0]["message"]["tool_calls"].as_array()
+            else {
+                return Ok(Vec::new());
+            };
+            tool_calls
+                .iter()
+                .map(|tool_call| {
+                    let function = &tool_call["function"];
+                    let name = function["name"]
+                        .as_str()
+                        .ok_or_else(|| invalid_call("no function name"))?;
+                    // OpenAI sends the arguments as a JSON string
+                    let arguments = match &function["arguments"] {
+                        Value::String(arguments) if arguments.trim().is_empty() => json!({}),
+                        Value::String(arguments) => serde_json::from_str(arguments)
+                            .map_err(|e| invalid_call(&e.to_string()))?,
+                        arguments => arguments.clone(),
+                    };
+                    Ok(ToolCall {
+                        id: String::from(tool_call["id"].as_str().unwrap_or_default()),
+                        name: String::from(name),
+                        arguments,
+                    })
+                })
+                .collect()
+        }
+        ApiProvider::ANTHROPIC => {
+            let Some(content_blocks) = json_response["content"].as_array() else {
+                return Ok(Vec::new());
+            };
+            content_blocks
+                .iter()
+                .filter(|block| block["type"].as_str() == Some("tool_use"))
+                .map(|block| {
+                    let name = block["name"]
+                        .as_str()
+                        .ok_or_else(|| invalid_call("no tool name"))?;
+                    Ok(ToolCall {
+                        id: String::from(block["id"].as_str().unwrap_or_default()),
+                        name: String::from(name),
+                        arguments: block["input"].clone(),
+                    })
+                })
+                .collect()
+        }
+        ApiProvider::OLLAMA => {
+            let Some(tool_calls) = json_response["message"]["tool_calls"].as_array() else {
+                return Ok(Vec::new());
+            };
+            tool_calls
+                .iter()
+                .enumerate()
+                .map(|(call_index, tool_call)| {
+                    let function = &tool_call["function"];
+                    let name = function["name"]
+                        .as_str()
+                        .ok_or_else(|| invalid_call("no function name"))?;
+                    Ok(ToolCall {
+                        id: format!("call_{}", call_index),
+                        name: String::from(name),
+                        arguments: function["arguments"].clone(),
+                    })
+                })
+                .collect()
+        }
+    }
+}
@@ -0,0 +1,504 @@
+//! # API Usage
+//! This module contains the token usage and cost accounting of provider calls.
+//! Every reply carries the tokens it used, parsed from the provider's `usage` block
+//! (OpenAI/OpenRouter `prompt_tokens`/`completion_tokens`, Anthropic `input_tokens`/`output_tokens`, Ollama `prompt_eval_count`/`eval_count`).
+//! Usage is priced with the model metadata (see `nyota::api::model_info`) and recorded per day and per model in a ledger
+//! persisted under the user's data directory (`~/.local/share/nyota/usage.json`).
+//! Each record is merged into the ledger as it is on disk, under a lock on `usage.lock`, so that nyota processes running at once
+//! do not overwrite each other's usage; a ledger which cannot be read is left as it is rather than overwritten.
+//! An optional daily budget (`[usage] daily_budget_usd` in the config file or `NYOTA_DAILY_BUDGET_USD`) blocks further requests once spent.
+
+use crate::api::constants::*;
+use crate::api::model_info::ModelInfo;
+use crate::api::utilities::ApiProvider;
+use crate::cfg::config::read_config_table;
+use anyhow::{Error, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    env::{self, VarError},
+    fs::{self, File},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Usage holds the number of tokens used by a single reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Usage {
+    /// The tokens of the prompt (the whole conversation sent).
+    pub input_tokens: u64,
+    /// The tokens of the reply.
+    pub output_tokens: u64,
+}
+
+impl Usage {
+    /// Returns the total number of tokens used.
+    pub fn get_total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+
+    /// Returns the cost of the usage in USD, if the model's prices are known.
+    pub fn get_cost(&self, model_info: Option<&ModelInfo>) -> Option<f64> {
+        let model_info = model_info?;
+        let input_price = model_info.input_price?;
+        let output_price = model_info.output_price?;
+        Some(self.input_tokens as f64 * input_price + self.output_tokens as f64 * output_price)
+    }
+
+    /// Merges usage reported in several parts of a streamed reply, keeping the largest count of each kind.
+    pub fn merge(&mut self, other: Usage) {
+        self.input_tokens = self.input_tokens.max(other.input_tokens);
+        self.output_tokens = self.output_tokens.max(other.output_tokens);
+    }
+}
+
+/// Parses the usage block of a response body (or of a streamed event) from the specified provider.
+/// Returns `None` if the body reports no usage.
+pub fn parse_usage(provider: ApiProvider, json_response: &Value) -> Option<Usage> {
+    let (input_tokens, output_tokens) = match provider {
+        ApiProvider::OPENAI | ApiProvider::OPENROUTER | ApiProvider::OPENAI_COMPATIBLE => (
+            &json_response["usage"]["prompt_tokens"],
+            &json_response["usage"]["completion_tokens"],
+        ),
+        ApiProvider::ANTHROPIC => (
+            &json_response["usage"]["input_tokens"],
+            &json_response["usage"]["output_tokens"],
+        ),
+        ApiProvider::OLLAMA => (
+            &json_response["prompt_eval_count"],
+            &json_response["eval_count"],
+        ),
+    };
+    if input_tokens.is_null() && output_tokens.is_null() {
+        return None;
+    }
+    Some(Usage {
+        input_tokens: input_tokens.as_u64().unwrap_or_default(),
+        output_tokens: output_tokens.as_u64().unwrap_or_default(),
+    })
+}
+
+/// ModelUsage holds the usage accumulated for a single model.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct ModelUsage {
+    /// The number of replies.
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// The cost in USD (replies from models without prices count as free).
+    pub cost: f64,
+}
+
+impl ModelUsage {
+    /// Adds the usage and cost of a reply.
+    pub fn add(&mut self, usage: Usage, cost: Option<f64>) {
+        self.requests += 1;
+        self.input_tokens += usage.input_tokens;
+        self.output_tokens += usage.output_tokens;
+        self.cost += cost.unwrap_or_default();
+    }
+
+    /// Returns the total number of tokens used.
+    pub fn get_total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+/// UsageTotals accumulates usage per model (i.e. over a chat session or a day).
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct UsageTotals {
+    models: BTreeMap<String, ModelUsage>,
+}
+
+impl UsageTotals {
+    /// Creates a new, empty UsageTotals.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the usage and cost of a reply from the specified model.
+    pub fn add(&mut self, model: &str, usage: Usage, cost: Option<f64>) {
+        self.models
+            .entry(String::from(model))
+            .or_default()
+            .add(usage, cost);
+    }
+
+    /// Returns the usage of every model, ordered by model name.
+    pub fn get_models(&self) -> &BTreeMap<String, ModelUsage> {
+        &self.models
+    }
+
+    /// Returns the usage summed over every model.
+    pub fn get_total(&self) -> ModelUsage {
+        self.models
+            .values()
+            .fold(ModelUsage::default(), |mut total, model_usage| {
+                total.requests += model_usage.requests;
+                total.input_tokens += model_usage.input_tokens;
+                total.output_tokens += model_usage.output_tokens;
+                total.cost += model_usage.cost;
+                total
+            })
+    }
+}
+
+/// UsageLedger records usage per day (`YYYY-MM-DD`, local time) and per model, persisted to the usage file.
+/// It is a shared handle: clones record to the same ledger, so streamed replies can record their usage once they finish.
+#[derive(Debug, Clone, Default)]
+pub struct UsageLedger {
+    days: Arc<Mutex<BTreeMap<String, UsageTotals>>>,
+    daily_budget: Option<f64>,
+}
+
+impl UsageLedger {
+    /// Creates a new UsageLedger from the usage file, with the daily budget from the config file and environment variables.
+    /// A missing or unreadable usage file yields an empty ledger; an invalid usage file and unreadable budgets are reported as warnings.
+    pub fn load() -> Self {
+        let days = get_usage_file_path()
+            .and_then(|usage_path| {
+                let contents = fs::read_to_string(&usage_path).ok()?;
+                serde_json::from_str(&contents)
+                    .map_err(|e| {
+                        eprintln!(
+                            "🟡[USAGE] 💔📊 Warning: {} is not a valid usage ledger | {}",
+                            usage_path.display(),
+                            e
+                        )
+                    })
+                    .ok()
+            })
+            .unwrap_or_default();
+
+        let mut daily_budget = match read_daily_budget_from_config_file() {
+            Ok(daily_budget) => daily_budget,
+            Err(e) => {
+                eprintln!("{:?}", e);
+                None
+            }
+        };
+        match get_daily_budget_from_env() {
+            Ok(Some(budget)) => daily_budget = Some(budget),
+            Ok(None) => {}
+            Err(e) => eprintln!("{:?}", e),
+        }
+
+        Self {
+            days: Arc::new(Mutex::new(days)),
+            daily_budget,
+        }
+    }
+
+    /// Records the usage and cost of a reply from the specified model under today's date, and writes the ledger to the usage file.
+    /// The usage is merged into the usage file as it is on disk (see `record_to_file`), and the ledger is updated with what other
+    /// nyota processes recorded since it was loaded. If the usage file cannot be written, the usage is only kept for this run.
+    pub fn record(&self, model: &str, usage: Usage, cost: Option<f64>) -> Result<(), Error> {
+        let mut days = self
+            .days
+            .lock()
+            .map_err(|_| Error::msg("🔴[USAGE] 💔📊 Error: Usage ledger unavailable."))?;
+
+        let usage_path = get_usage_file_path().ok_or_else(|| {
+            Error::msg("🟡[USAGE] 🔍📊 Warning: No data directory to save the usage ledger.")
+        });
+        match usage_path
+            .and_then(|usage_path| record_to_file(&usage_path, &get_today(), model, usage, cost))
+        {
+            Ok(recorded_days) => {
+                *days = recorded_days;
+                Ok(())
+            }
+            Err(e) => {
+                days.entry(get_today()).or_default().add(model, usage, cost);
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns the usage recorded today.
+    pub fn get_today(&self) -> UsageTotals {
+        self.days
+            .lock()
+            .ok()
+            .and_then(|days| days.get(&get_today()).cloned())
+            .unwrap_or_default()
+    }
+
+    /// Returns the daily budget in USD, if one is set.
+    pub fn get_daily_budget(&self) -> Option<f64> {
+        self.daily_budget
+    }
+
+    /// Sets (or clears) the daily budget in USD.
+    pub fn set_daily_budget(&mut self, daily_budget: Option<f64>) {
+        self.daily_budget = daily_budget;
+    }
+
+    /// Returns the amount spent today and the daily budget, if the budget has been spent.
+    pub fn get_exceeded_budget(&self) -> Option<(f64, f64)> {
+        let daily_budget = self.daily_budget?;
+        let spent = self.get_today().get_total().cost;
+        (spent >= daily_budget).then_some((spent, daily_budget))
+    }
+}
+
+/// Returns the path of the usage ledger (i.e. `~/.local/share/nyota/usage.json`), if the user's data directory is known.
+pub fn get_usage_file_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|data_dir| data_dir.join(CONFIG_DIR_NAME).join(USAGE_FILE_NAME))
+}
+
+/// Adds the usage and cost of a reply from the model under the date to the usage file, and returns the ledger as written.
+/// The file is read, merged and written while holding an exclusive lock on the lock file next to it (`usage.lock`),
+/// so that the records of nyota processes running at once are all kept. The ledger is written to a temporary file first
+/// and moved over the usage file, so that it is never left half written.
+/// Returns an error, leaving the file as it is, if it exists but is not a valid ledger.
+pub fn record_to_file(
+    usage_path: &Path,
+    date: &str,
+    model: &str,
+    usage: Usage,
+    cost: Option<f64>,
+) -> Result<BTreeMap<String, UsageTotals>, Error> {
+    let to_write_error = |e: std::io::Error| {
+        Error::msg(format!(
+            "🟡[USAGE] 💔📊 Warning: Unable to write {} | {}",
+            usage_path.display(),
+            e
+        ))
+    };
+    if let Some(data_dir) = usage_path.parent() {
+        fs::create_dir_all(data_dir).map_err(to_write_error)?;
+    }
+    // The lock is released when the lock file is closed
+    let lock_file = File::create(usage_path.with_extension("lock")).map_err(to_write_error)?;
+    lock_file.lock().map_err(to_write_error)?;
+
+    let mut days: BTreeMap<String, UsageTotals> = match fs::read_to_string(usage_path) {
+        Ok(contents) => serde_json::from_str(&contents).map_err(|e| {
+            Error::msg(format!(
+                "🟡[USAGE] 💔📊 Warning: {} is not a valid usage ledger, so it is not overwritten; fix or remove it to record usage again | {}",
+                usage_path.display(),
+                e
+            ))
+        })?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => {
+            return Err(Error::msg(format!(
+                "🟡[USAGE] 💔📊 Warning: Unable to read {} | {}",
+                usage_path.display(),
+                e
+            )))
+        }
+    };
+    days.entry(String::from(date))
+        .or_default()
+        .add(model, usage, cost);
+
+    let temp_path = usage_path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_string_pretty(&days)?).map_err(to_write_error)?;
+    fs::rename(&temp_path, usage_path).map_err(to_write_error)?;
+    Ok(days)
+}
+
+/// Returns today's date (local time) as used for the ledger keys.
+fn get_today() -> String {
+    Local::now().format("%Y-%m-%d").to_string()
+}
+
+/// Reads the daily budget from the `[usage]` table of the nyota config file.
+fn read_daily_budget_from_config_file() -> Result<Option<f64>, Error> {
+    let Some(usage_table) = read_config_table(CONFIG_USAGE_TABLE)? else {
+        return Ok(None);
+    };
+    match usage_table.get(CONFIG_DAILY_BUDGET_KEY) {
+        Some(toml::Value::Float(budget)) => Ok(Some(*budget)),
+        Some(toml::Value::Integer(budget)) => Ok(Some(*budget as f64)),
+        Some(_) => Err(Error::msg(format!(
+            "🟡[CONFIG] 💔📊 Warning: `{}` must be a number of USD.",
+            CONFIG_DAILY_BUDGET_KEY
+        ))),
+        None => Ok(None),
+    }
+}
+
+/// Retrieves the daily budget from the `NYOTA_DAILY_BUDGET_USD` environment variable.
+/// Returns `None` if the variable is unset or empty.
+fn get_daily_budget_from_env() -> Result<Option<f64>, Error> {
+    match env::var(DAILY_BUDGET_ENV) {
+        Ok(val) if val.trim().is_empty() => Ok(None),
+        Ok(val) => val.trim().parse().map(Some).map_err(|_| {
+            Error::msg(format!(
+                "🟡[ENV] 💔📊 Warning: `{}` must be a number of USD, got `{}`",
+                DAILY_BUDGET_ENV, val
+            ))
+        }),
+        Err(VarError::NotPresent) => Ok(None),
+        Err(VarError::NotUnicode(_)) => Err(Error::msg(format!(
+            "🟡[ENV] 🔍📊 Warning: `{}` unreadable.",
+            DAILY_BUDGET_ENV
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Returns a usage file path in a fresh temporary directory of the test.
+    fn get_test_usage_path(test_name: &str) -> PathBuf {
+        let test_dir =
+            env::temp_dir().join(format!("nyota-usage-{}-{}", test_name, std::process::id()));
+        let _ = fs::remove_dir_all(&test_dir);
+        test_dir.join(USAGE_FILE_NAME)
+    }
+
+    fn get_usage(input_tokens: u64, output_tokens: u64) -> Usage {
+        Usage {
+            input_tokens,
+            output_tokens,
+        }
+    }
+
+    #[test]
+    fn usage_is_parsed_per_provider() {
+        let cases = [
+            // OpenAI
+            (
+                ApiProvider::OPENAI,
+                json!({"id": "chatcmpl-AbC1", "object": "chat.completion", "model": "gpt-4o-mini-2024-07-18",
+                       "choices": [{"index": 0, "message": {"role": "assistant", "content": "Nairobi."}, "finish_reason": "stop"}],
+                       "usage": {"prompt_tokens": 14, "completion_tokens": 3, "total_tokens": 17,
+                                 "prompt_tokens_details": {"cached_tokens": 0, "audio_tokens": 0}}}),
+                Some(get_usage(14, 3)),
+            ),
+            // OpenRouter
+            (
+                ApiProvider::OPENROUTER,
+                json!({"id": "gen-1760778764-xYz", "provider": "OpenAI", "model": "openai/gpt-4o-mini",
+                       "choices": [{"message": {"role": "assistant", "content": "Nairobi."}}],
+                       "usage": {"prompt_tokens": 14, "completion_tokens": 3, "total_tokens": 17, "cost": 0.0000039}}),
+                Some(get_usage(14, 3)),
+            ),
+            // Anthropic
+            (
+                ApiProvider::ANTHROPIC,
+                json!({"id": "msg_01XFDUDYJgAACzvnptvVoYEL", "type": "message", "role": "assistant",
+                       "content": [{"type": "text", "text": "Nairobi."}], "model": "claude-3-5-haiku-20241022", "stop_reason": "end_turn",
+                       "usage": {"input_tokens": 25, "cache_creation_input_tokens": 0, "cache_read_input_tokens": 0, "output_tokens": 6}}),
+                Some(get_usage(25, 6)),
+            ),
+            // Anthropic's message_delta event only counts the output
+            (
+                ApiProvider::ANTHROPIC,
+                json!({"type": "message_delta", "delta": {"stop_reason": "end_turn"}, "usage": {"output_tokens": 15}}),
+                Some(get_usage(0, 15)),
+            ),
+            // Ollama
+            (
+                ApiProvider::OLLAMA,
+                json!({"model": "llama3.2", "message": {"role": "assistant", "content": "Nairobi."}, "done": true,
+                       "total_duration": 4883583458u64, "prompt_eval_count": 26, "eval_count": 282}),
+                Some(get_usage(26, 282)),
+            ),
+            // Replies without usage
+            (
+                ApiProvider::OPENAI,
+                json!({"choices": [{"delta": {"content": "Nai"}}], "usage": null}),
+                None,
+            ),
+            (ApiProvider::OPENAI_COMPATIBLE, json!({"usage": {}}), None),
+            (
+                ApiProvider::OPENROUTER,
+                json!({"choices": [{"message": {"content": "Nairobi."}}]}),
+                None,
+            ),
+            (ApiProvider::ANTHROPIC, json!({"usage": {}}), None),
+            // A cached Ollama prompt is not evaluated again, so only the output is counted
+            (
+                ApiProvider::OLLAMA,
+                json!({"model": "llama3.2", "done": true, "eval_count": 9}),
+                Some(get_usage(0, 9)),
+            ),
+            (
+                ApiProvider::OLLAMA,
+                json!({"model": "llama3.2", "done": true}),
+                None,
+            ),
+            (ApiProvider::OLLAMA, json!({}), None),
+        ];
+        for (provider, json_response, expected) in cases {
+            assert_eq!(
+                parse_usage(provider, &json_response),
+                expected,
+                "{}",
+                json_response
+            );
+        }
+    }
+
+    #[test]
+    fn records_are_merged_into_the_ledger_on_disk() -> Result<()> {
+        let usage_path = get_test_usage_path("merge");
+        // Two processes which each loaded an empty ledger record one after the other
+        record_to_file(
+            &usage_path,
+            "2026-10-18",
+            "gpt-4o-mini",
+            get_usage(10, 5),
+            Some(0.5),
+        )?;
+        let days = record_to_file(
+            &usage_path,
+            "2026-10-18",
+            "claude-3-5-haiku",
+            get_usage(20, 10),
+            None,
+        )?;
+        record_to_file(
+            &usage_path,
+            "2026-10-19",
+            "gpt-4o-mini",
+            get_usage(1, 1),
+            Some(0.25),
+        )?;
+
+        let on_disk: BTreeMap<String, UsageTotals> =
+            serde_json::from_str(&fs::read_to_string(&usage_path)?)?;
+        assert_eq!(on_disk.len(), 2);
+        let day = on_disk.get("2026-10-18").cloned().unwrap_or_default();
+        assert_eq!(day.get_models().len(), 2);
+        assert_eq!(day.get_total().get_total_tokens(), 45);
+        assert_eq!(day.get_total().cost, 0.5);
+        assert_eq!(days.get("2026-10-18"), Some(&day));
+        let _ = fs::remove_dir_all(usage_path.parent().unwrap_or(&usage_path));
+        Ok(())
+    }
+
+    #[test]
+    fn an_invalid_ledger_is_not_overwritten() -> Result<()> {
+        let usage_path = get_test_usage_path("invalid");
+        if let Some(test_dir) = usage_path.parent() {
+            fs::create_dir_all(test_dir)?;
+        }
+        fs::write(&usage_path, "{\"2026-10-18\": {\"models\": ")?;
+
+        let recorded = record_to_file(
+            &usage_path,
+            "2026-10-18",
+            "gpt-4o-mini",
+            get_usage(10, 5),
+            None,
+        );
+        assert!(recorded.is_err_and(|e| e.to_string().contains("not a valid usage ledger")));
+        assert_eq!(
+            fs::read_to_string(&usage_path)?,
+            "{\"2026-10-18\": {\"models\": "
+        );
+        let _ = fs::remove_dir_all(usage_path.parent().unwrap_or(&usage_path));
+        Ok(())
+    }
+}
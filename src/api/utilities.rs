@@ -1,60 +1,205 @@
 //! # API Utilities
 //! This module contains api utilities for interfacing with various AI API providers.
+//! Tools are offered to `send_conversation` only; streamed (`stream_conversation`) and structured replies never call tools.
 
+use crate::api::attachment::Attachment;
+use crate::api::catalogue::{parse_model_list, ModelCatalogue};
 use crate::api::constants::*;
+use crate::api::conversation::{ChatMessage, LlmResponse, Role};
+use crate::api::embeddings::{formulate_embedding_requests, parse_embeddings};
+use crate::api::endpoints::Endpoints;
+use crate::api::error::{parse_retry_after, ApiError};
+use crate::api::fallback::FallbackChain;
+use crate::api::generation::GenerationParams;
+use crate::api::inspect::{RawExchange, RawResponse};
+use crate::api::keystore::{get_api_key_env_var, redact_api_keys, validate_api_key, KeyStore};
+use crate::api::model_info::{get_max_output_tokens, ModelInfo, ModelRegistry};
+use crate::api::persona::Persona;
+use crate::api::response_cache::ResponseCache;
+use crate::api::retry::RetryPolicy;
+use crate::api::stream::ResponseStream;
+use crate::api::structured::{
+    add_response_schema_to_request, get_retry_prompt, load_schema_retries, ResponseSchema,
+    StructuredResponse,
+};
+use crate::api::tools::{add_tools_to_request, parse_tool_calls, ToolCall, ToolDefinition};
+use crate::api::usage::{parse_usage, Usage, UsageLedger};
+use crate::cfg::config::{Config, ConfigSource};
+use crate::ctx::cache::{get_json, set_json, CacheKey, CacheSettings, RedisCache};
 use anyhow::{Error, Result};
-use reqwest::{Client, Response};
+use reqwest::{header::HeaderValue, Client, RequestBuilder, Response};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::{
     collections::HashMap,
     env::{self, VarError},
+    fmt,
+    str::FromStr,
+    time::Instant,
 };
+use tokio::task;
 
 /// ApiProvider is an enum that represents the various AI API providers supported by Nyota.
 /// Each variant corresponds to a specific API provider. However, some models may work with multiple providers.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Providers are serialized by their lowercase name (see `as_str`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
 pub enum ApiProvider {
+    #[serde(rename = "openai")]
     OPENAI,
+    #[serde(rename = "anthropic")]
     ANTHROPIC,
+    #[serde(rename = "ollama")]
     OLLAMA,
+    #[serde(rename = "openrouter")]
     OPENROUTER,
+    /// Any server exposing the OpenAI chat completions API (i.e. vLLM, LM Studio, llama.cpp).
+    #[serde(rename = "openai-compatible")]
+    OPENAI_COMPATIBLE,
+}
+
+impl ApiProvider {
+    /// Returns the lowercase name of the provider, as used in config files (i.e. `"openai-compatible"`).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiProvider::OPENAI => "openai",
+            ApiProvider::ANTHROPIC => "anthropic",
+            ApiProvider::OLLAMA => "ollama",
+            ApiProvider::OPENROUTER => "openrouter",
+            ApiProvider::OPENAI_COMPATIBLE => "openai-compatible",
+        }
+    }
+}
+
+impl Default for ApiProvider {
+    fn default() -> Self {
+        DEFAULT_PROVIDER
+    }
+}
+
+impl fmt::Display for ApiProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
 }
-/// Adapter acts as an interface which tracks global API state and retains API keys gathered from the environmental variables.
+
+impl FromStr for ApiProvider {
+    type Err = Error;
+
+    /// Parses a provider from its lowercase name (case-insensitive, `_` and `-` are interchangeable).
+    fn from_str(provider_name: &str) -> Result<Self, Self::Err> {
+        let normalized_name = provider_name.trim().to_lowercase().replace('_', "-");
+        SUPPORTED_PROVIDERS
+            .iter()
+            .find(|provider| provider.as_str() == normalized_name)
+            .copied()
+            .ok_or_else(|| {
+                Error::msg(format!(
+                    "🟡[ADAPTER] 💔 Warning: Unknown API Provider `{}`",
+                    provider_name
+                ))
+            })
+    }
+}
+/// Adapter acts as an interface which tracks global API state and retains API keys gathered from the environmental variables
+/// (and from the encrypted key store, see `nyota::api::keystore`). API keys are redacted from its debug output.
 /// Even though a default Adapter is provided, it is recommended to create a new instance for each chat session.
 /// The intended use is to have multiple instances of Adapters instanciated.  This allows for easy switching between API providers and models dynamically between simultaneous chat sessions.
-#[derive(Debug)]
+/// Each Adapter keeps a pooled HTTP client, so connections to a provider are reused between turns.
+/// Cloned Adapters share the pooled HTTP client, the usage ledger and the response cache (see `nyota::api::compare`, which asks several models at once).
+#[derive(Clone)]
 pub struct Adapter {
     api_keys: HashMap<ApiProvider, String>,
+    endpoints: Endpoints,
+    retry_policy: RetryPolicy,
+    fallback_chain: FallbackChain,
+    catalogue: ModelCatalogue,
+    model_registry: ModelRegistry,
+    usage_ledger: UsageLedger,
+    response_cache: Option<ResponseCache>,
+    cache_settings: CacheSettings,
+    persona: Persona,
+    generation_params: GenerationParams,
+    tools: Vec<ToolDefinition>,
+    schema_retries: u32,
+    client: Client,
     current_provider: ApiProvider,
     current_model: String,
 }
 
 impl Adapter {
-    /// Creates a new instance of Adapter.
-    /// The Adapter is initialized with API keys from the environment variables.
-    /// If no model is specified, the DEFAULT_MODEL is used.
-    /// If no provider is specified, the DEFAULT_PROVIDER is used.
+    /// Creates a new instance of Adapter, with the config loaded from the config files and environment variables (see `with_config`).
     pub fn new() -> Self {
+        Self::with_config(&Config::load())
+    }
+
+    /// Creates a new instance of Adapter from the config (see `nyota::cfg::config`).
+    /// The Adapter is initialized with API keys from the environment variables.
+    /// Keys missing from the environment are read from the encrypted key store, if it holds any of them (prompting for its passphrase unless it is set in env).
+    /// The model, the provider used if the model is not known to any provider, and the persona are taken from the `[model]` settings
+    /// (see `nyota::api::persona`); an unknown model falls back to the DEFAULT_MODEL.
+    /// Provider base URLs, the retry policy, the generation parameters, the daily budget and the structured output retries are loaded
+    /// from the config files and environment variables
+    /// (see `nyota::api::endpoints`, `nyota::api::retry`, `nyota::api::generation`, `nyota::api::usage` and `nyota::api::structured`).
+    /// Replies are cached if the `[cache]` table enables it (see `nyota::api::response_cache`).
+    pub fn with_config(config: &Config) -> Self {
         // Get All API Keys from Environment
         let mut api_keys = HashMap::new();
+        let mut missing_key_warnings = Vec::new();
         for provider in SUPPORTED_PROVIDERS.iter() {
             match get_api_key_from_env(provider) {
                 Ok(api_key) => {
                     api_keys.insert(*provider, api_key);
                 }
-                Err(e) => {
-                    eprintln!("{:?}", e);
+                Err(e) => missing_key_warnings.push((*provider, e)),
+            }
+        }
+
+        // Fill in the API Keys Missing from the Environment from the Key Store
+        // It is only unlocked if it holds a key missing from the environment; if it cannot be unlocked, the keys stay missing
+        let is_key_missing = |api_keys: &HashMap<ApiProvider, String>, provider: &ApiProvider| {
+            get_api_key_env_var(provider).is_some()
+                && api_keys.get(provider).is_none_or(String::is_empty)
+        };
+        if KeyStore::get_stored_providers().is_some_and(|stored_providers| {
+            stored_providers
+                .iter()
+                .any(|provider| is_key_missing(&api_keys, provider))
+        }) {
+            match KeyStore::unlock() {
+                Ok(key_store) => {
+                    for provider in SUPPORTED_PROVIDERS.iter() {
+                        match key_store.get(provider) {
+                            Some(api_key) if is_key_missing(&api_keys, provider) => {
+                                eprintln!(
+                                    "🟢[KEYSTORE] ✅🔑 Success: {:?} | API Key Loaded.",
+                                    provider
+                                );
+                                api_keys.insert(*provider, api_key.clone());
+                            }
+                            _ => {}
+                        }
+                    }
                 }
+                Err(e) => eprintln!("{:?}", e),
+            }
+        }
+        for (provider, e) in missing_key_warnings {
+            if !api_keys.contains_key(&provider) {
+                eprintln!("{:?}", e);
             }
         }
 
-        // Get Default AI Model from Environment
-        // If not found, use the default model
-        let current_model = match get_ai_model_from_env() {
+        // Load the Model Catalogue Cached from Earlier Runs (used to validate model names)
+        let catalogue = ModelCatalogue::load_cached();
+
+        // Get Default AI Model from the Config
+        // If not supported, use the default model
+        let current_model = match get_default_model(config, &catalogue) {
             Ok(current_model) => current_model,
             Err(e) => {
                 eprintln!("{:?}", e);
-                println!(
+                eprintln!(
                     "🟡[ADAPTER] 🧩 Warning: Using Default Model - `{}`",
                     DEFAULT_MODEL
                 );
@@ -63,28 +208,58 @@ impl Adapter {
         };
 
         // Get the API Provider from the Specified Model
-        // If not found, use the default provider
-        let current_provider = match Self::get_api_provider_from_model(&current_model) {
-            Ok(provider) => *provider,
+        // If not found, use the default provider from the config
+        let current_provider = match catalogue.get_provider_for_model(&current_model) {
+            Ok(provider) => provider,
             Err(e) => {
                 eprintln!("{:?}", e);
-                println!(
+                eprintln!(
                     "🟡[ADAPTER] 🧩 Warning: Using Default Provider {:#?}",
-                    DEFAULT_PROVIDER
+                    config.model.provider
                 );
-                DEFAULT_PROVIDER
+                config.model.provider
             }
         };
 
+        // Build the Shared HTTP Client from the Retry Policy
+        // If it cannot be built, fall back to a client without timeouts
+        let retry_policy = RetryPolicy::load();
+        let client = retry_policy.build_client().unwrap_or_else(|e| {
+            eprintln!("{:?}", e);
+            Client::new()
+        });
+
+        // Connect to the Response Cache, if Enabled
+        let (cache_settings, cache_warnings) = CacheSettings::load();
+        for e in cache_warnings {
+            eprintln!("{:?}", e);
+        }
+        let response_cache = cache_settings
+            .cache_responses
+            .then(|| ResponseCache::connect(&cache_settings));
+
         Self {
             api_keys,
+            endpoints: Endpoints::load(),
+            retry_policy,
+            fallback_chain: FallbackChain::load(),
+            catalogue,
+            model_registry: ModelRegistry::load(),
+            usage_ledger: UsageLedger::load(),
+            response_cache,
+            cache_settings,
+            persona: Persona::load_or_default(&config.model.persona),
+            generation_params: GenerationParams::load(),
+            tools: Vec::new(),
+            schema_retries: load_schema_retries(),
+            client,
             current_provider,
             current_model,
         }
     }
 
     /// Returns the current API provider as an ApiProvider enum variant.
-    pub fn _get_current_provider(&self) -> ApiProvider {
+    pub fn get_current_provider(&self) -> ApiProvider {
         self.current_provider
     }
 
@@ -98,6 +273,39 @@ impl Adapter {
         self.api_keys.get(provider)
     }
 
+    /// Returns the endpoints (base URLs) used to reach each API provider.
+    pub fn get_endpoints(&self) -> &Endpoints {
+        &self.endpoints
+    }
+
+    /// Sets the base URL (via string slice) used to reach the specified ApiProvider enum variant.
+    /// Useful for pointing an Adapter at a gateway, a self-hosted server or a local mock server.
+    pub fn set_base_url(&mut self, provider: ApiProvider, base_url: &str) {
+        self.endpoints.set_base_url(provider, base_url);
+    }
+
+    /// Returns the timeout and retry policy applied to provider calls.
+    pub fn get_retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    /// Sets the timeout and retry policy applied to provider calls, rebuilding the shared HTTP client to match.
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) -> Result<()> {
+        self.client = retry_policy.build_client()?;
+        self.retry_policy = retry_policy;
+        Ok(())
+    }
+
+    /// Returns the chain of providers a turn falls back to when the current provider cannot answer.
+    pub fn get_fallback_chain(&self) -> &FallbackChain {
+        &self.fallback_chain
+    }
+
+    /// Sets the chain of providers a turn falls back to when the current provider cannot answer.
+    pub fn set_fallback_chain(&mut self, fallback_chain: FallbackChain) {
+        self.fallback_chain = fallback_chain;
+    }
+
     /// Sets the current API provider from an ApiProvider enum variant.
     pub fn _set_current_provider(&mut self, provider: ApiProvider) {
         // add validation for provider
@@ -110,266 +318,1670 @@ impl Adapter {
         self.current_model = model;
     }
 
+    /// Switches to the specified AI model, and to the API provider which offers it (see `nyota::api::catalogue`).
+    /// Returns an error if no provider is known to offer the model.
+    pub fn use_model(&mut self, model: &str) -> Result<()> {
+        self.current_provider = self.catalogue.get_provider_for_model(model)?;
+        self.current_model = String::from(model);
+        Ok(())
+    }
+
+    /// Switches to the specified API provider, with the model equivalent to the current one on it (see `nyota::api::fallback`).
+    /// Returns an error if the provider has neither a model equivalent to the current one nor a default fallback model.
+    pub fn use_provider(&mut self, provider: ApiProvider) -> Result<()> {
+        if provider == self.current_provider {
+            return Ok(());
+        }
+        let model = self
+            .fallback_chain
+            .get_equivalent_model(&self.current_model, &provider)
+            .ok_or_else(|| {
+                Error::msg(format!(
+                    "🟡[ADAPTER] 🧩 Warning: No {} model equivalent to {:#?}, keeping {}.",
+                    provider, self.current_model, self.current_provider
+                ))
+            })?;
+        self.current_model = String::from(model);
+        self.current_provider = provider;
+        Ok(())
+    }
+
     /// Sets the API key (via String) for the specified ApiProvider enum variant.
-    pub fn _set_api_key(&mut self, provider: ApiProvider, key: String) {
-        // add validation for api key (HOW?)
+    /// Returns an error if the key does not have the form of a key issued by the provider (see `nyota::api::keystore::validate_api_key`).
+    pub fn set_api_key(&mut self, provider: ApiProvider, key: String) -> Result<()> {
+        validate_api_key(&provider, &key)?;
         self.api_keys.insert(provider, key);
+        Ok(())
     }
 
-    /// Returns an API Provider enum variant or an Error from the specified model name.
-    /// If the model name is not found in the supported models list, an error is returned.
-    /// If the model name is found, the corresponding API Provider is returned.
-    /// All models prefixed with "ollama/" or "openrouter/" are default supported.
-    fn get_api_provider_from_model(model_name: &str) -> Result<&ApiProvider, Error> {
-        if model_name.starts_with("openrouter/") {
-            return Ok(&ApiProvider::OPENROUTER);
-        } else if model_name.starts_with("ollama/") {
-            return Ok(&ApiProvider::OLLAMA);
+    /// Returns the catalogue of models offered by each API provider, as cached so far.
+    pub fn get_catalogue(&self) -> &ModelCatalogue {
+        &self.catalogue
+    }
+
+    /// Returns the catalogue mutably (i.e. to store a model list fetched by a clone of the Adapter).
+    pub fn get_catalogue_mut(&mut self) -> &mut ModelCatalogue {
+        &mut self.catalogue
+    }
+
+    /// Returns the metadata (context window, pricing, capabilities) of the specified model, if known.
+    pub fn get_model_info(&self, model: &str) -> Option<&ModelInfo> {
+        self.model_registry.get(model)
+    }
+
+    /// Refreshes the model metadata from OpenRouter's model list, and caches it on disk.
+    /// Returns the number of models read.
+    pub async fn refresh_model_info(&mut self) -> Result<usize> {
+        let provider = ApiProvider::OPENROUTER;
+        // OpenRouter lists its models without a key, but a key is sent if there is one
+        let api_key = self
+            .get_api_key(&provider)
+            .map(String::as_str)
+            .unwrap_or_default();
+        let submission = with_auth_headers(
+            self.client
+                .get(self.endpoints.get_models_url(&provider))
+                .timeout(self.retry_policy.request_timeout),
+            &provider,
+            api_key,
+        );
+        let response = submission
+            .send()
+            .await
+            .map_err(|e| ApiError::from_reqwest(provider, e))?;
+        if !response.status().is_success() {
+            return Err(get_error_from_response(provider, response).await);
         }
+        let json: Value = response
+            .json()
+            .await
+            .map_err(|e| ApiError::from_reqwest(provider, e))?;
+        self.model_registry.refresh_from_openrouter(&json)
+    }
 
-        SUPPORTED_MODELS.get(model_name).ok_or_else(|| {
-            Error::msg(format!(
-                "🟡[ADAPTER] 💔 Warning: Invalid/Unknown Model | `{}` API Provider Unsupported!",
-                model_name
-            ))
+    /// Returns the models offered by the specified API provider.
+    /// The cached list is used while it is fresh; otherwise the list shared in the Redis server of the `[cache]` table is used
+    /// (see `nyota::ctx::cache`), and failing that the list is fetched from the provider (see `refresh_models`) and shared there.
+    /// The Redis server only saves requests: if it cannot be reached, the list is fetched from the provider as if it were not set.
+    pub async fn list_models(&mut self, provider: ApiProvider) -> Result<Vec<String>> {
+        if let Some(models) = self.catalogue.get_models(&provider) {
+            return Ok(models.to_vec());
+        }
+
+        // Redis is queried synchronously, so it is queried on the blocking threads of the runtime
+        let key = CacheKey::ModelList(provider);
+        let cache_settings = self.cache_settings.clone();
+        let shared_key = key.clone();
+        let (shared_cache, shared_models) = task::spawn_blocking(move || {
+            let mut shared_cache = cache_settings.redis_url.as_ref().and_then(|redis_url| {
+                RedisCache::connect(redis_url, cache_settings.redis_timeout).ok()
+            });
+            let shared_models = shared_cache.as_mut().and_then(|redis_cache| {
+                get_json::<Vec<String>>(redis_cache, &shared_key)
+                    .ok()
+                    .flatten()
+            });
+            (shared_cache, shared_models)
         })
+        .await?;
+        if let Some(models) = shared_models {
+            self.catalogue.set_models(provider, models.clone());
+            self.catalogue.save()?;
+            return Ok(models);
+        }
+
+        let models = self.refresh_models(provider).await?;
+        if let Some(mut redis_cache) = shared_cache {
+            let ttl = self.cache_settings.get_ttl(&key);
+            let shared_models = models.clone();
+            let _ =
+                task::spawn_blocking(move || set_json(&mut redis_cache, &key, &shared_models, ttl))
+                    .await;
+        }
+        Ok(models)
+    }
+
+    /// Fetches the models offered by the specified API provider, and stores them in the catalogue and its cache file.
+    pub async fn refresh_models(&mut self, provider: ApiProvider) -> Result<Vec<String>> {
+        let api_key = self
+            .get_api_key(&provider)
+            .ok_or(ApiError::MissingApiKey { provider })?;
+        let submission = with_auth_headers(
+            self.client
+                .get(self.endpoints.get_models_url(&provider))
+                .timeout(self.retry_policy.request_timeout),
+            &provider,
+            api_key,
+        );
+        let response = submission
+            .send()
+            .await
+            .map_err(|e| ApiError::from_reqwest(provider, e))?;
+        if !response.status().is_success() {
+            return Err(get_error_from_response(provider, response).await);
+        }
+        let json: Value = response
+            .json()
+            .await
+            .map_err(|e| ApiError::from_reqwest(provider, e))?;
+
+        let models = parse_model_list(provider, &json)?;
+        self.catalogue.set_models(provider, models.clone());
+        self.catalogue.save()?;
+        Ok(models)
     }
 
     /// Sends a test request to the current API provider using the Adapter's current AI model.
     pub async fn send_test_request(&self, msg: &str) -> Result<()> {
-        // println!(
-        //     "🟢[ADAPTER] 🚀📡 Sending Test Request to API Provider {:#?}...",
-        //     self.current_provider
-        // );
-        let request = formulate_request(self.current_provider, &self.current_model, msg).await;
-        self.send_request(&request, &self.current_provider).await?;
-        // println!("{}", parse_response(self.current_provider, response).await?);
+        let request = self.formulate_request_to(
+            self.current_provider,
+            &self.current_model,
+            &[ChatMessage::user(msg)],
+            &[],
+            false,
+            true,
+        )?;
+        let response = self.send_request(&request, &self.current_provider).await?;
+        if !response.status().is_success() {
+            return Err(get_error_from_response(self.current_provider, response).await);
+        }
         Ok(())
     }
 
     /// Sends a message as a string slice to the current API provider using the Adapter's current AI model.
     /// Returns the response from the API provider as a string.
     pub async fn send_to_llm(&self, msg: &str) -> Result<String> {
-        let request = formulate_request(self.current_provider, &self.current_model, msg).await;
-        let response = self.send_request(&request, &self.current_provider).await?;
-        Ok(parse_response(self.current_provider, response).await?)
+        let reply = self.send_conversation(&[ChatMessage::user(msg)]).await?;
+        Ok(reply.content)
+    }
+
+    /// Sends a conversation (a slice of role-tagged messages, oldest first) to the current API provider using the Adapter's current AI model.
+    /// The full transcript is mapped into the provider's native request format so that follow-up questions retain their context.
+    /// If the provider cannot answer, the conversation is retried on the fallback chain (see `nyota::api::fallback`).
+    /// The tools set on the Adapter are offered to the model, and the tools it asks to call are returned apart from the reply text.
+    /// Returns the reply with the provider and model which answered it and the tokens it used, which are recorded to the usage ledger.
+    /// If replies are cached, a request sent before is answered from the response cache (see `nyota::api::response_cache`).
+    pub async fn send_conversation(&self, messages: &[ChatMessage]) -> Result<LlmResponse> {
+        self.send_conversation_with(messages, &self.tools, None)
+            .await
+    }
+
+    /// Sends a conversation to the current API provider and returns a reply which conforms to the JSON Schema (see `nyota::api::structured`).
+    /// The schema is sent in the provider's native format, and the reply is validated before it is returned:
+    /// a reply which does not conform is sent back with the problem and asked for again, up to the schema retry count.
+    /// Returns an `ApiError::InvalidResponse` if no reply conforms. Tools are not offered to structured replies.
+    /// A schema with keywords replies cannot be validated against is rejected before anything is sent (see `ResponseSchema::check_keywords`).
+    pub async fn send_structured(
+        &self,
+        messages: &[ChatMessage],
+        response_schema: &ResponseSchema,
+    ) -> Result<StructuredResponse> {
+        response_schema.check_keywords()?;
+        let mut conversation = messages.to_vec();
+        let mut retries = 0;
+        loop {
+            let response = self
+                .send_conversation_with(&conversation, &[], Some(response_schema))
+                .await?;
+            match response_schema.parse_reply(&response.content) {
+                Ok(value) => return Ok(StructuredResponse { value, response }),
+                Err(problem) if retries < self.schema_retries => {
+                    retries += 1;
+                    conversation.push(ChatMessage::assistant(&response.content));
+                    conversation.push(ChatMessage::user(&get_retry_prompt(&problem)));
+                }
+                Err(problem) => {
+                    let attempts = retries + 1;
+                    return Err(Error::from(ApiError::InvalidResponse {
+                        provider: response.provider,
+                        message: format!(
+                            "No reply conformed to the JSON Schema `{}` in {} attempt{} ({})",
+                            response_schema.name,
+                            attempts,
+                            if attempts == 1 { "" } else { "s" },
+                            problem
+                        ),
+                    }));
+                }
+            }
+        }
+    }
+
+    /// Returns the number of times `send_structured` asks again for a reply which does not conform to the schema.
+    pub fn get_schema_retries(&self) -> u32 {
+        self.schema_retries
+    }
+
+    /// Sets the number of times `send_structured` asks again for a reply which does not conform to the schema (0 disables retries).
+    pub fn set_schema_retries(&mut self, schema_retries: u32) {
+        self.schema_retries = schema_retries;
+    }
+
+    /// Sends a conversation on the fallback chain, offering the tools and asking for a reply which conforms to the schema, if any.
+    async fn send_conversation_with(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+        response_schema: Option<&ResponseSchema>,
+    ) -> Result<LlmResponse> {
+        self.check_budget()?;
+        let mut last_error = None;
+        for (target, (provider, model)) in self
+            .fallback_chain
+            .get_targets(self.current_provider, &self.current_model)
+            .into_iter()
+            .enumerate()
+        {
+            let mut request = match self.formulate_request_to(
+                provider,
+                &model,
+                messages,
+                tools,
+                false,
+                target > 0,
+            ) {
+                Ok(request) => request,
+                // A fallback which cannot take the request is skipped
+                Err(e) if target > 0 => {
+                    last_error = Some(e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if let Some(response_schema) = response_schema {
+                add_response_schema_to_request(&mut request, provider, response_schema);
+            }
+            let chat_url = self.endpoints.get_chat_url(&provider);
+            // A cache which cannot be read or written does not fail the request, and is returned as a warning with the reply
+            let mut cache_warnings = Vec::new();
+            if let Some(response_cache) = &self.response_cache {
+                match response_cache
+                    .get(provider, &chat_url, &model, &request)
+                    .await
+                {
+                    Ok(Some(cached_reply)) => return Ok(cached_reply),
+                    Ok(None) => {}
+                    Err(e) => cache_warnings.push(format!("{}", e)),
+                }
+            }
+            let reply = match self.send_request(&request, &provider).await {
+                Ok(response) => self.read_reply(provider, &model, response).await,
+                Err(e) => Err(e),
+            };
+            match reply {
+                Err(e) if is_fallback_error(&e) => last_error = Some(e),
+                Ok(mut reply) => {
+                    if let Some(response_cache) = &self.response_cache {
+                        if let Err(e) = response_cache
+                            .store(provider, &chat_url, &model, &request, &reply)
+                            .await
+                        {
+                            cache_warnings.push(format!("{}", e));
+                        }
+                    }
+                    reply.warnings.extend(cache_warnings);
+                    return Ok(reply);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| Error::msg("🔴[ADAPTER] Error: No provider to send to.")))
+    }
+
+    /// Sends a conversation to the current API provider and streams the reply back as it is generated.
+    /// If the provider cannot answer, the conversation is retried on the fallback chain (see `nyota::api::fallback`).
+    /// Returns a ResponseStream which yields incremental text chunks until the reply is complete,
+    /// and reports the provider and model which actually answered and the tokens used (recorded to the usage ledger once it finishes).
+    /// Tools are not offered to streamed replies; use `send_conversation` to let the model call tools.
+    pub async fn stream_conversation(&self, messages: &[ChatMessage]) -> Result<ResponseStream> {
+        self.check_budget()?;
+        let mut last_error = None;
+        for (target, (provider, model)) in self
+            .fallback_chain
+            .get_targets(self.current_provider, &self.current_model)
+            .into_iter()
+            .enumerate()
+        {
+            let request = match self.formulate_request_to(
+                provider,
+                &model,
+                messages,
+                &[],
+                true,
+                target > 0,
+            ) {
+                Ok(request) => request,
+                // A fallback which cannot take the request is skipped
+                Err(e) if target > 0 => {
+                    last_error = Some(e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let stream = match self.send_request(&request, &provider).await {
+                Ok(response) if response.status().is_success() => Ok(ResponseStream::new(
+                    provider, &model, response,
+                )
+                .with_usage_ledger(
+                    self.usage_ledger.clone(),
+                    self.get_model_info(&model).cloned(),
+                )),
+                Ok(response) => Err(get_error_from_response(provider, response).await),
+                Err(e) => Err(e),
+            };
+            match stream {
+                Err(e) if is_fallback_error(&e) => last_error = Some(e),
+                stream => return stream,
+            }
+        }
+        Err(last_error.unwrap_or_else(|| Error::msg("🔴[ADAPTER] Error: No provider to send to.")))
+    }
+
+    /// Sends a conversation to the current API provider and records the raw round trip for inspection.
+    /// The returned RawExchange holds the exact request body, the HTTP status, headers and body returned,
+    /// the text extracted by the provider's response parser, and the latency of the call.
+    /// Failures to reach the provider are recorded in the exchange rather than returned, so the request can still be inspected.
+    pub async fn inspect_conversation(&self, messages: &[ChatMessage]) -> RawExchange {
+        let provider = self.current_provider;
+        // Invalid generation parameters are still sent in the body shown, so the request can be inspected
+        let generation_params = self.get_effective_generation_params();
+        let mut request_body = formulate_request(
+            provider,
+            &self.current_model,
+            self.get_model_info(&self.current_model),
+            &self.persona.system_prompt,
+            &generation_params,
+            messages,
+            false,
+        );
+        add_tools_to_request(&mut request_body, provider, &self.tools);
+
+        let started_at = Instant::now();
+        let sent = match self
+            .check_budget()
+            .and_then(|_| generation_params.validate(provider, &self.current_model))
+            .and_then(|_| {
+                self.check_capabilities(provider, &self.current_model, messages, &self.tools)
+            }) {
+            Ok(()) => self.send_request(&request_body, &provider).await,
+            Err(e) => Err(e),
+        };
+        let outcome = match sent {
+            Ok(response) => {
+                let status = response.status();
+                let headers = response.headers().clone();
+                match response.text().await {
+                    Ok(raw_body) => {
+                        let mut usage = None;
+                        let mut tool_calls = Vec::new();
+                        let parsed = if status.is_success() {
+                            match serde_json::from_str::<Value>(&raw_body) {
+                                Ok(json) => {
+                                    usage = parse_usage(provider, &json);
+                                    match parse_tool_calls(provider, &json) {
+                                        Ok(calls) => {
+                                            tool_calls = calls;
+                                            read_reply_text(provider, json, &tool_calls).await
+                                        }
+                                        Err(e) => Err(e),
+                                    }
+                                }
+                                Err(e) => Err(Error::from(ApiError::InvalidResponse {
+                                    provider,
+                                    message: e.to_string(),
+                                })),
+                            }
+                        } else {
+                            Err(Error::from(ApiError::from_response(
+                                provider, status, &headers, &raw_body,
+                            )))
+                        };
+                        let mut warnings = Vec::new();
+                        if let Some(usage) = usage {
+                            self.record_usage(&self.current_model, usage, &mut warnings);
+                        }
+                        Ok(RawResponse {
+                            status,
+                            headers,
+                            raw_body,
+                            parsed,
+                            tool_calls,
+                            usage,
+                            warnings,
+                        })
+                    }
+                    Err(e) => Err(Error::from(ApiError::from_reqwest(provider, e))),
+                }
+            }
+            Err(e) => Err(e),
+        };
+
+        RawExchange {
+            provider,
+            model: String::from(&self.current_model),
+            url: self.endpoints.get_chat_url(&provider),
+            request_body,
+            latency: started_at.elapsed(),
+            outcome,
+        }
+    }
+
+    /// Returns the persona the Adapter speaks as.
+    pub fn get_persona(&self) -> &Persona {
+        &self.persona
+    }
+
+    /// Sets the persona the Adapter speaks as.
+    pub fn set_persona(&mut self, persona: Persona) {
+        self.persona = persona;
+    }
+
+    /// Switches to the persona with the specified name (see `nyota::api::persona`).
+    pub fn use_persona(&mut self, name: &str) -> Result<()> {
+        self.persona = Persona::load(name)?;
+        Ok(())
+    }
+
+    /// Returns the generation parameters set on the Adapter (from the config file, CLI flags or `/set`).
+    pub fn get_generation_params(&self) -> &GenerationParams {
+        &self.generation_params
+    }
+
+    /// Sets the generation parameters of the Adapter, which take precedence over the persona's.
+    pub fn set_generation_params(&mut self, generation_params: GenerationParams) {
+        self.generation_params = generation_params;
+    }
+
+    /// Returns the generation parameters sent with requests: the persona's, overridden by those set on the Adapter.
+    pub fn get_effective_generation_params(&self) -> GenerationParams {
+        self.persona
+            .generation_params
+            .merge(&self.generation_params)
     }
 
-    /// Sends a request to the specified API provider using the JSON Value type from Serde.
+    /// Returns the tools offered to the model by `send_conversation`.
+    pub fn get_tools(&self) -> &[ToolDefinition] {
+        &self.tools
+    }
+
+    /// Sets the tools offered to the model by `send_conversation` (see `nyota::api::tools`). An empty list offers none.
+    pub fn set_tools(&mut self, tools: Vec<ToolDefinition>) {
+        self.tools = tools;
+    }
+
+    /// Returns an `ApiError::UnsupportedFeature` if the attachments cannot be sent to the current model (i.e. images to a text-only model).
+    pub fn check_attachments(&self, attachments: &[Attachment]) -> Result<()> {
+        let message = ChatMessage::user("").with_attachments(attachments.to_vec());
+        self.check_capabilities(self.current_provider, &self.current_model, &[message], &[])
+    }
+
+    /// Returns the usage ledger, which holds the tokens used and money spent per day and per model.
+    pub fn get_usage_ledger(&self) -> &UsageLedger {
+        &self.usage_ledger
+    }
+
+    /// Embeds the texts with the embedding model of the provider (see `nyota::api::embeddings`), and records the tokens used to the usage ledger.
+    /// Returns one embedding per text, in their order, or an `ApiError::UnsupportedFeature` if the provider offers no embeddings.
+    pub async fn embed(
+        &self,
+        provider: ApiProvider,
+        model: &str,
+        texts: &[String],
+    ) -> Result<Vec<Vec<f32>>> {
+        let embeddings_url = self
+            .endpoints
+            .get_embeddings_url(&provider)
+            .ok_or_else(|| ApiError::UnsupportedFeature {
+                provider,
+                model: String::from(model),
+                feature: String::from("embeddings"),
+            })?;
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for request in formulate_embedding_requests(provider, model, texts) {
+            let response = self
+                .send_request_to(&embeddings_url, &request, &provider)
+                .await?;
+            if !response.status().is_success() {
+                return Err(get_error_from_response(provider, response).await);
+            }
+            let json: Value = response
+                .json()
+                .await
+                .map_err(|e| ApiError::from_reqwest(provider, e))?;
+            // A ledger which cannot be written is shown by the warnings of the replies, which write to it as well
+            if let Some(usage) = parse_usage(provider, &json) {
+                self.record_usage(model, usage, &mut Vec::new());
+            }
+            embeddings.extend(parse_embeddings(provider, &json)?);
+        }
+        if embeddings.len() != texts.len() {
+            return Err(Error::from(ApiError::InvalidResponse {
+                provider,
+                message: format!(
+                    "{} embeddings were returned for {} texts.",
+                    embeddings.len(),
+                    texts.len()
+                ),
+            }));
+        }
+        Ok(embeddings)
+    }
+
+    /// Returns the settings of the fast cache, which the response cache and the shared model lists are kept in.
+    pub fn get_cache_settings(&self) -> &CacheSettings {
+        &self.cache_settings
+    }
+
+    /// Returns the response cache, if replies are cached.
+    pub fn get_response_cache(&self) -> Option<&ResponseCache> {
+        self.response_cache.as_ref()
+    }
+
+    /// Sets the response cache the replies are kept in, or `None` to stop caching them.
+    pub fn set_response_cache(&mut self, response_cache: Option<ResponseCache>) {
+        self.response_cache = response_cache;
+    }
+
+    /// Formulates the request of a conversation to a provider and model, with the persona's system prompt and the effective generation parameters.
+    /// Parameters are validated for the configured provider, and trimmed to what a fallback provider accepts.
+    /// The tools are offered to the model, and images are only sent to it, unless it is known not to support them.
+    fn formulate_request_to(
+        &self,
+        provider: ApiProvider,
+        model: &str,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+        stream: bool,
+        is_fallback: bool,
+    ) -> Result<Value> {
+        self.check_capabilities(provider, model, messages, tools)?;
+        let mut generation_params = self.get_effective_generation_params();
+        if is_fallback {
+            generation_params = generation_params.get_supported(provider, model);
+        } else {
+            generation_params.validate(provider, model)?;
+        }
+        let mut request = formulate_request(
+            provider,
+            model,
+            self.get_model_info(model),
+            &self.persona.system_prompt,
+            &generation_params,
+            messages,
+            stream,
+        );
+        add_tools_to_request(&mut request, provider, tools);
+        Ok(request)
+    }
+
+    /// Returns an `ApiError::UnsupportedFeature` if images are attached to the conversation, or tools offered, and the model is known not to support them.
+    /// Models missing from the model registry are assumed to support both.
+    fn check_capabilities(
+        &self,
+        provider: ApiProvider,
+        model: &str,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+    ) -> Result<()> {
+        let Some(model_info) = self.get_model_info(model) else {
+            return Ok(());
+        };
+        let unsupported_feature = if !model_info.capabilities.vision
+            && messages.iter().any(|msg| msg.get_images().next().is_some())
+        {
+            Some("images")
+        } else if !model_info.capabilities.tools && !tools.is_empty() {
+            Some("tools")
+        } else {
+            None
+        };
+        match unsupported_feature {
+            Some(feature) => Err(Error::from(ApiError::UnsupportedFeature {
+                provider,
+                model: String::from(model),
+                feature: String::from(feature),
+            })),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns an `ApiError::BudgetExceeded` if the daily budget has been spent.
+    fn check_budget(&self) -> Result<()> {
+        match self.usage_ledger.get_exceeded_budget() {
+            Some((spent, budget)) => Err(Error::from(ApiError::BudgetExceeded {
+                provider: self.current_provider,
+                spent,
+                budget,
+            })),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads the reply to a conversation from a provider response, and records its usage to the usage ledger.
+    async fn read_reply(
+        &self,
+        provider: ApiProvider,
+        model: &str,
+        api_response: Response,
+    ) -> Result<LlmResponse> {
+        if !api_response.status().is_success() {
+            return Err(get_error_from_response(provider, api_response).await);
+        }
+        let json: Value = api_response
+            .json()
+            .await
+            .map_err(|e| ApiError::from_reqwest(provider, e))?;
+        let usage = parse_usage(provider, &json);
+        let tool_calls = parse_tool_calls(provider, &json)?;
+        let content = read_reply_text(provider, json, &tool_calls).await?;
+
+        let mut warnings = Vec::new();
+        let cost = usage.and_then(|usage| self.record_usage(model, usage, &mut warnings));
+        Ok(LlmResponse {
+            content,
+            tool_calls,
+            provider,
+            model: String::from(model),
+            usage,
+            cost,
+            cached: false,
+            warnings,
+        })
+    }
+
+    /// Records the usage of a reply from the specified model to the usage ledger, and returns its cost if the model's prices are known.
+    /// The reply has already been received, so a ledger which cannot be written is added to the warnings rather than failing it.
+    fn record_usage(&self, model: &str, usage: Usage, warnings: &mut Vec<String>) -> Option<f64> {
+        let cost = usage.get_cost(self.get_model_info(model));
+        if let Err(e) = self.usage_ledger.record(model, usage, cost) {
+            warnings.push(format!("{}", e));
+        }
+        cost
+    }
+
+    /// Sends a request to the chat endpoint of the specified API provider (see `send_request_to`).
     async fn send_request(
         &self,
         request: &Value,
         provider: &ApiProvider,
     ) -> Result<Response, Error> {
-        let client = Client::new();
-        let api_key = self.get_api_key(provider).ok_or_else(|| {
-            Error::msg(format!(
-                "🔴[ADAPTER] Error: No valid API key found for provider {:#?}",
-                provider
-            ))
+        self.send_request_to(&self.endpoints.get_chat_url(provider), request, provider)
+            .await
+    }
+
+    /// Sends a request to an endpoint of the specified API provider using the JSON Value type from Serde.
+    /// Timeouts, failed connections and 408/429/5xx responses are retried according to the Adapter's RetryPolicy.
+    /// Once the retries are exhausted, the last response (or error) is returned.
+    async fn send_request_to(
+        &self,
+        url: &str,
+        request: &Value,
+        provider: &ApiProvider,
+    ) -> Result<Response, Error> {
+        let api_key = self.get_api_key(provider).ok_or(ApiError::MissingApiKey {
+            provider: *provider,
         })?;
-        let submission = match provider {
-            ApiProvider::OPENAI => client
-                .post(OPENAI_API_URL)
-                .header("Content-Type", "application/json")
-                .header("Authorization", format!("Bearer {}", api_key)),
-            ApiProvider::ANTHROPIC => client
-                .post(ANTHROPIC_API_URL)
-                .header("x-api-key", api_key)
-                .header("anthropic-version", "2023-06-01")
-                .header("content-type", "application/json"),
-            ApiProvider::OLLAMA => client.post(OLLAMA_API_URL),
-            ApiProvider::OPENROUTER => client
-                .post(OPENROUTER_API_URL)
-                .header("Content-Type", "application/json")
-                .header("Authorization", format!("Bearer {}", api_key)),
-        };
-        submission.json(&request).send().await.map_err(|e| {
-            Error::msg(format!(
-                "🔴[ADAPTER] 🚫🔌 Error: Failed to send request to API Provider {:#?} | {:?}",
-                provider, e
-            ))
-        })
+        // Streamed replies are only bounded by the read timeout between chunks
+        let is_streamed = request["stream"].as_bool().unwrap_or(false);
+
+        let mut retry = 0;
+        loop {
+            let submission = with_auth_headers(self.client.post(url), provider, api_key);
+            let submission = if is_streamed {
+                submission
+            } else {
+                submission.timeout(self.retry_policy.request_timeout)
+            };
+
+            let can_retry = retry < self.retry_policy.max_retries;
+            let retry_after = match submission.json(&request).send().await {
+                Ok(response)
+                    if can_retry && RetryPolicy::is_retryable_status(response.status()) =>
+                {
+                    parse_retry_after(response.headers())
+                }
+                Ok(response) => return Ok(response),
+                Err(e) if can_retry && RetryPolicy::is_retryable_error(&e) => None,
+                Err(e) => return Err(Error::from(ApiError::from_reqwest(*provider, e))),
+            };
+
+            retry += 1;
+            tokio::time::sleep(self.retry_policy.get_backoff(retry, retry_after)).await;
+        }
+    }
+}
+
+impl Default for Adapter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Adapter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Adapter")
+            .field("api_keys", &redact_api_keys(&self.api_keys))
+            .field("endpoints", &self.endpoints)
+            .field("retry_policy", &self.retry_policy)
+            .field("fallback_chain", &self.fallback_chain)
+            .field("catalogue", &self.catalogue)
+            .field("model_registry", &self.model_registry)
+            .field("usage_ledger", &self.usage_ledger)
+            .field("response_cache", &self.response_cache)
+            .field("cache_settings", &self.cache_settings)
+            .field("persona", &self.persona)
+            .field("generation_params", &self.generation_params)
+            .field("tools", &self.tools)
+            .field("schema_retries", &self.schema_retries)
+            .field("client", &self.client)
+            .field("current_provider", &self.current_provider)
+            .field("current_model", &self.current_model)
+            .finish()
     }
 }
 
 /// Retrieves the API key from the environment variables for the specified ApiProvider enum variant.
 /// If the API key is empty, undetected, or unreadable, a warning is returned.
 /// In the instance that a provider does not require an API key, an empty string is returned.
+/// OpenAI-compatible servers only need a key if `OPENAI_COMPATIBLE_API_KEY` is set.
+/// A key which does not have the form of the provider's keys is still used, with a warning.
 fn get_api_key_from_env(selected_provider: &ApiProvider) -> Result<String, Error> {
-    let api_name = match selected_provider {
-        ApiProvider::OPENAI => env::var("OPENAI_API_KEY"),
-        ApiProvider::ANTHROPIC => env::var("ANTHROPIC_API_KEY"),
-        ApiProvider::OLLAMA => Ok(String::from("")),
-        ApiProvider::OPENROUTER => env::var("OPENROUTER_API_KEY"),
+    let api_name = match (selected_provider, get_api_key_env_var(selected_provider)) {
+        (ApiProvider::OPENAI_COMPATIBLE, Some(env_var)) => match env::var(env_var) {
+            Err(VarError::NotPresent) => return Ok(String::from("")),
+            key => key,
+        },
+        (_, Some(env_var)) => env::var(env_var),
+        (_, None) => Ok(String::from("")),
     };
 
     match api_name {
         Ok(val) if val.is_empty() && selected_provider != &ApiProvider::OLLAMA => {
-            return Err(Error::msg(format!(
+            Err(Error::msg(format!(
                 "🟡[ENV] 🚫🔑 Warning:  {:?} | API Key is empty.",
                 selected_provider
-            )));
+            )))
         }
         Ok(val) => {
-            if selected_provider == &ApiProvider::OLLAMA {
+            if val.is_empty() {
                 return Ok(val);
             }
-            println!(
+            eprintln!(
                 "🟢[ENV] ✅🔑 Success: {:?} | API Key Loaded.",
                 selected_provider
             );
+            if let Err(e) = validate_api_key(selected_provider, &val) {
+                eprintln!("{:?}", e);
+            }
             Ok(val)
         }
-        Err(VarError::NotPresent) => {
-            return Err(Error::msg(format!(
-                "🟡[ENV] 🔍🔑 Warning: {:?} | API Key undetected.",
-                selected_provider,
-            )));
-        }
-        Err(VarError::NotUnicode(_)) => {
-            return Err(Error::msg(format!(
-                "🟡[ENV] 🔍🔑 Warning: {:?} | API Key unreadable.",
-                selected_provider
-            )));
-        }
+        Err(VarError::NotPresent) => Err(Error::msg(format!(
+            "🟡[ENV] 🔍🔑 Warning: {:?} | API Key undetected.",
+            selected_provider,
+        ))),
+        Err(VarError::NotUnicode(_)) => Err(Error::msg(format!(
+            "🟡[ENV] 🔍🔑 Warning: {:?} | API Key unreadable.",
+            selected_provider
+        ))),
     }
 }
 
-/// Retrieves the default AI model from the environment variables.
-/// Set from the NYOTA_DEFAULT_AI_MODEL environment variable.
-/// If not set, an error is returned.
-fn get_ai_model_from_env() -> Result<String, Error> {
-    let model_name = env::var("NYOTA_DEFAULT_AI_MODEL");
-    match model_name {
-        Ok(val) if val.is_empty() => {
-            return Err(Error::msg(
-                "🟡[ENV] 🚫🔩 Warning: Default AI Model was not set.",
-            ));
-        }
-        Ok(val)
-            if !SUPPORTED_MODELS.contains_key(val.as_str())
-                && !val.starts_with("openrouter/")
-                && !val.starts_with("ollama/") =>
-        {
-            return Err(Error::msg(format!(
-                "🟡[ENV] 💔🔩 Warning: Specified Default AI Model {:#?} is not supported.",
-                val
-            )));
-        }
-        Ok(val) => {
-            println!(
-                "🟢[ENV] ⚙️🔩 Success: Default AI Model Set from environment: {}",
-                val
-            );
-            return Ok(val);
-        }
-        Err(VarError::NotPresent) => {
-            return Err(Error::msg(
-                "🟡[ENV] 🔍🔩 Warning: Default AI Model undetected.",
-            ));
-        }
-        Err(VarError::NotUnicode(_)) => {
-            return Err(Error::msg(
-                "🟡[ENV] 🔍🔩 Warning: Default AI Model unreadable",
-            ));
-        }
-    };
+/// Retrieves the default AI model from the config, where it is set by `[model] default` or the NYOTA_DEFAULT_AI_MODEL environment variable.
+/// If the model is unknown to the model catalogue, an error is returned.
+fn get_default_model(config: &Config, catalogue: &ModelCatalogue) -> Result<String, Error> {
+    let model_name = &config.model.default_model;
+    if catalogue.get_provider_for_model(model_name).is_err() {
+        return Err(Error::msg(format!(
+            "🟡[CONFIG] 💔🔩 Warning: Specified Default AI Model {:#?} is not supported.",
+            model_name
+        )));
+    }
+    match config.get_source("model.default") {
+        ConfigSource::Default => {}
+        source => eprintln!(
+            "🟢[CONFIG] ⚙️🔩 Success: Default AI Model Set from {}: {}",
+            source, model_name
+        ),
+    }
+    Ok(model_name.clone())
 }
 
 /// Formulates a JSON Value Serde type to be sent to the specified API provider.
-async fn formulate_request(provider: ApiProvider, model: &str, msg: &str) -> Value {
-    let req: Value;
-    match provider {
+/// The conversation is mapped into the provider's native format, with the system prompt if the conversation has no system message:
+/// OpenAI, OpenRouter, OpenAI-compatible servers & Ollama (`/api/chat`) take the system prompt as the first of the `messages`,
+/// and Anthropic takes system messages as a top-level `system` field.
+/// The generation parameters are sent as top-level fields, except for Ollama which takes them in `options` (see `nyota::api::generation`).
+/// Anthropic requires `max_tokens`, which is sized from the model info (see `nyota::api::model_info`) to fit the context window.
+/// If `stream` is set, the provider is asked to stream the reply back incrementally.
+/// OpenAI and OpenRouter are asked to report the token usage of streamed replies (Anthropic and Ollama always do);
+/// OpenAI-compatible servers are not, as some reject the extra field.
+fn formulate_request(
+    provider: ApiProvider,
+    model: &str,
+    model_info: Option<&ModelInfo>,
+    system_prompt: &str,
+    generation_params: &GenerationParams,
+    messages: &[ChatMessage],
+    stream: bool,
+) -> Value {
+    let mut req = match provider {
         ApiProvider::OPENAI => {
-            req = json!({
+            let mut req = json!({
                 "model": model,
-               "store": true,
-                "stream": false,
-                "messages": [
-                    {"role": "system", "content": "You are a helpful assistant."},
-                    {"role": "user", "content": msg}
-                ]
+                "store": true,
+                "stream": stream,
+                "messages": with_system_prompt(provider, messages, system_prompt)
             });
+            if stream {
+                req["stream_options"] = json!({"include_usage": true});
+            }
+            req
         }
         ApiProvider::ANTHROPIC => {
-            req = json!({
-            "model": model,
-            "max_tokens": 1024,
-            "messages": [{"role": "user", "content": msg}]
+            let max_output_tokens = get_max_output_tokens(model_info, messages);
+            let mut req = json!({
+                "model": model,
+                "max_tokens": generation_params.max_tokens.map_or(max_output_tokens, |max_tokens| max_tokens.min(max_output_tokens)),
+                "stream": stream,
+                "messages": to_json_messages(provider, messages.iter().filter(|msg| msg.role != Role::System))
             });
+            let conversation_system_prompt = messages
+                .iter()
+                .filter(|msg| msg.role == Role::System)
+                .map(|msg| msg.content.as_str())
+                .collect::<Vec<&str>>()
+                .join("\n\n");
+            if !conversation_system_prompt.is_empty() {
+                req["system"] = json!(conversation_system_prompt);
+            } else if !system_prompt.is_empty() {
+                req["system"] = json!(system_prompt);
+            }
+            req
         }
         ApiProvider::OLLAMA => {
-            req = json!({
-              "model": model.trim_start_matches("ollama/"),
-              "prompt": msg,
-              "stream": false
+            let mut req = json!({
+                "model": model.trim_start_matches(OLLAMA_MODEL_PREFIX),
+                "messages": with_system_prompt(provider, messages, system_prompt),
+                "stream": stream
             });
+            let options = generation_params.to_provider_params(provider);
+            if !options.is_empty() {
+                req["options"] = Value::Object(options);
+            }
+            req
         }
-        ApiProvider::OPENROUTER => {
-            req = json!({
-                "model": model.trim_start_matches("openrouter/"),
-                "messages": [
-                    {"role":"system", "content": "You are a helpful assistant."},
-                    {"role":"user", "content": msg}
-                ]
-            })
+        ApiProvider::OPENROUTER => json!({
+            "model": model.trim_start_matches(OPENROUTER_MODEL_PREFIX),
+            "stream": stream,
+            "usage": {"include": true},
+            "messages": with_system_prompt(provider, messages, system_prompt)
+        }),
+        ApiProvider::OPENAI_COMPATIBLE => json!({
+            "model": model.trim_start_matches(OPENAI_COMPATIBLE_MODEL_PREFIX),
+            "stream": stream,
+            "messages": with_system_prompt(provider, messages, system_prompt)
+        }),
+    };
+
+    // Ollama takes its generation parameters in `options` (above)
+    if provider != ApiProvider::OLLAMA {
+        if let Some(req) = req.as_object_mut() {
+            req.extend(generation_params.to_provider_params(provider));
+        }
+    }
+    req
+}
+
+/// Maps role-tagged messages into the `{"role": ..., "content": ...}` JSON objects shared by the API providers.
+fn to_json_messages<'a>(
+    provider: ApiProvider,
+    messages: impl Iterator<Item = &'a ChatMessage>,
+) -> Vec<Value> {
+    messages.map(|msg| to_json_message(provider, msg)).collect()
+}
+
+/// Maps a role-tagged message into JSON, with its attached text files inlined into the content.
+/// Attached images are sent as OpenAI-style `image_url` content parts, Anthropic `image` blocks (before the text, as Anthropic advises)
+/// or Ollama `images`. Messages without images keep plain string content.
+/// Tool calls and tool results are mapped by `to_json_tool_message`.
+fn to_json_message(provider: ApiProvider, msg: &ChatMessage) -> Value {
+    if msg.role == Role::Tool || !msg.tool_calls.is_empty() {
+        return to_json_tool_message(provider, msg);
+    }
+    let text_content = msg.get_text_content();
+    let images: Vec<&Attachment> = msg.get_images().collect();
+    if images.is_empty() {
+        return json!({"role": msg.role.as_str(), "content": text_content});
+    }
+
+    let text_part =
+        (!text_content.is_empty()).then(|| json!({"type": "text", "text": text_content}));
+    match provider {
+        ApiProvider::ANTHROPIC => {
+            let mut content: Vec<Value> = images
+                .iter()
+                .filter_map(|image| match image {
+                    Attachment::Image {
+                        media_type, data, ..
+                    } => Some(json!({
+                        "type": "image",
+                        "source": {"type": "base64", "media_type": media_type, "data": data}
+                    })),
+                    Attachment::Text { .. } => None,
+                })
+                .collect();
+            content.extend(text_part);
+            json!({"role": msg.role.as_str(), "content": content})
+        }
+        ApiProvider::OLLAMA => {
+            let images: Vec<&str> = images
+                .iter()
+                .filter_map(|image| match image {
+                    Attachment::Image { data, .. } => Some(data.as_str()),
+                    Attachment::Text { .. } => None,
+                })
+                .collect();
+            json!({"role": msg.role.as_str(), "content": text_content, "images": images})
+        }
+        ApiProvider::OPENAI | ApiProvider::OPENROUTER | ApiProvider::OPENAI_COMPATIBLE => {
+            let mut content: Vec<Value> = text_part.into_iter().collect();
+            content.extend(images.iter().filter_map(|image| {
+                let data_url = image.get_data_url()?;
+                Some(json!({"type": "image_url", "image_url": {"url": data_url}}))
+            }));
+            json!({"role": msg.role.as_str(), "content": content})
+        }
+    }
+}
+
+/// Maps an assistant message which called tools, or the result of a tool call, into JSON.
+/// Tool calls are sent as OpenAI-style and Ollama `tool_calls` or Anthropic `tool_use` blocks after the text (see `ToolCall::to_provider_json`).
+/// Results are sent as OpenAI-style `tool` messages naming the call, Anthropic `tool_result` blocks in a `user` message
+/// (which Anthropic combines with the other results of the turn), or Ollama `tool` messages, which answer the calls in order.
+fn to_json_tool_message(provider: ApiProvider, msg: &ChatMessage) -> Value {
+    let text_content = msg.get_text_content();
+    if msg.role == Role::Tool {
+        let tool_call_id = msg.tool_call_id.as_deref().unwrap_or_default();
+        return match provider {
+            ApiProvider::ANTHROPIC => json!({
+                "role": "user",
+                "content": [{"type": "tool_result", "tool_use_id": tool_call_id, "content": text_content}]
+            }),
+            ApiProvider::OLLAMA => json!({"role": "tool", "content": text_content}),
+            ApiProvider::OPENAI | ApiProvider::OPENROUTER | ApiProvider::OPENAI_COMPATIBLE => {
+                json!({"role": "tool", "tool_call_id": tool_call_id, "content": text_content})
+            }
+        };
+    }
+
+    let tool_calls: Vec<Value> = msg
+        .tool_calls
+        .iter()
+        .map(|tool_call| tool_call.to_provider_json(provider))
+        .collect();
+    match provider {
+        ApiProvider::ANTHROPIC => {
+            // Anthropic rejects empty text blocks
+            let mut content: Vec<Value> = (!text_content.is_empty())
+                .then(|| json!({"type": "text", "text": text_content}))
+                .into_iter()
+                .collect();
+            content.extend(tool_calls);
+            json!({"role": msg.role.as_str(), "content": content})
         }
+        ApiProvider::OLLAMA => {
+            json!({"role": msg.role.as_str(), "content": text_content, "tool_calls": tool_calls})
+        }
+        ApiProvider::OPENAI | ApiProvider::OPENROUTER | ApiProvider::OPENAI_COMPATIBLE => {
+            // OpenAI takes no content (rather than an empty one) alongside the calls
+            let content = (!text_content.is_empty()).then_some(text_content);
+            json!({"role": msg.role.as_str(), "content": content, "tool_calls": tool_calls})
+        }
+    }
+}
+
+/// Maps role-tagged messages into JSON, prepending the system prompt if the conversation has no system message.
+fn with_system_prompt(
+    provider: ApiProvider,
+    messages: &[ChatMessage],
+    system_prompt: &str,
+) -> Vec<Value> {
+    let mut json_messages = Vec::with_capacity(messages.len() + 1);
+    if !system_prompt.is_empty() && !messages.iter().any(|msg| msg.role == Role::System) {
+        json_messages.push(json!({"role": "system", "content": system_prompt}));
     }
-    return req;
+    json_messages.extend(to_json_messages(provider, messages.iter()));
+    json_messages
 }
 
 /// Public interface for parsing a response from the API provider and returning the relevant content as a string.
 /// Converts API response to a JSON Value and then parses the response based on the API provider.
+/// Unsuccessful responses are returned as an `ApiError` carrying the HTTP status and the provider's error message.
 pub async fn parse_response(model: ApiProvider, api_response: Response) -> Result<String, Error> {
     if api_response.status().is_success() {
-        let json: Value = api_response.json().await?;
+        let json: Value = api_response
+            .json()
+            .await
+            .map_err(|e| ApiError::from_reqwest(model, e))?;
+        parse_response_json(model, json).await
+    } else {
+        Err(get_error_from_response(model, api_response).await)
+    }
+}
 
-        match model {
-            ApiProvider::OPENAI => parse_openai_response(json).await,
-            ApiProvider::ANTHROPIC => parse_anthropic_response(json).await,
-            ApiProvider::OLLAMA => parse_ollama_response(json).await,
-            ApiProvider::OPENROUTER => parse_openrouter_response(json).await,
+/// Public interface for parsing a successful JSON response body from the API provider and returning the relevant content as a string.
+pub async fn parse_response_json(model: ApiProvider, json: Value) -> Result<String, Error> {
+    match model {
+        ApiProvider::OPENAI | ApiProvider::OPENROUTER | ApiProvider::OPENAI_COMPATIBLE => {
+            parse_openai_response(model, json).await
         }
-    } else {
-        let error_text = api_response.text().await?;
-        Err(Error::msg(format!("API error: {}", error_text)))
+        ApiProvider::ANTHROPIC => parse_anthropic_response(json).await,
+        ApiProvider::OLLAMA => parse_ollama_response(json).await,
     }
 }
 
-/// Parses a JSON response from OpenAI and returns the relevant content as a string.
-async fn parse_openai_response(json_response: Value) -> Result<String, Error> {
-    // DEBUG println!("{:#?}", json_response);
-    let content = json_response["choices"][0]["message"]["content"]
-        .as_str()
-        .ok_or_else(|| Error::msg("No content found in response!"))?;
+/// Parses the reply text from a successful JSON response body which asked for the specified tool calls.
+/// A reply which only calls tools may have no text, so an empty reply is only an error if no tools were called.
+async fn read_reply_text(
+    provider: ApiProvider,
+    json: Value,
+    tool_calls: &[ToolCall],
+) -> Result<String, Error> {
+    match parse_response_json(provider, json).await {
+        Err(e)
+            if !tool_calls.is_empty()
+                && matches!(
+                    e.downcast_ref::<ApiError>(),
+                    Some(ApiError::EmptyContent { .. })
+                ) =>
+        {
+            Ok(String::new())
+        }
+        reply => reply,
+    }
+}
 
-    Ok(String::from(content))
+/// Adds the authentication headers expected by the specified API provider to a request.
+/// Ollama takes no key, and OpenAI-compatible servers are only sent a key if one is configured.
+/// The key headers are marked sensitive, so they are redacted from the debug output of the request.
+fn with_auth_headers(
+    submission: RequestBuilder,
+    provider: &ApiProvider,
+    api_key: &str,
+) -> RequestBuilder {
+    match provider {
+        ApiProvider::ANTHROPIC => {
+            let submission = match HeaderValue::from_str(api_key) {
+                Ok(mut key_header) => {
+                    key_header.set_sensitive(true);
+                    submission.header("x-api-key", key_header)
+                }
+                // The invalid key is reported when the request is sent
+                Err(_) => submission.header("x-api-key", api_key),
+            };
+            submission.header("anthropic-version", "2023-06-01")
+        }
+        ApiProvider::OLLAMA => submission,
+        ApiProvider::OPENAI_COMPATIBLE if api_key.is_empty() => submission,
+        ApiProvider::OPENAI | ApiProvider::OPENROUTER | ApiProvider::OPENAI_COMPATIBLE => {
+            submission.bearer_auth(api_key)
+        }
+    }
+}
+
+/// Returns true if the error is an `ApiError` which should be retried on the next provider of the fallback chain.
+fn is_fallback_error(error: &Error) -> bool {
+    error
+        .downcast_ref::<ApiError>()
+        .is_some_and(ApiError::is_fallback_error)
+}
+
+/// Reads an unsuccessful response from the API provider into an `ApiError`.
+async fn get_error_from_response(provider: ApiProvider, api_response: Response) -> Error {
+    let status = api_response.status();
+    let headers = api_response.headers().clone();
+    match api_response.text().await {
+        Ok(body) => Error::from(ApiError::from_response(provider, status, &headers, &body)),
+        Err(e) => Error::from(ApiError::from_reqwest(provider, e)),
+    }
+}
+
+/// Parses a JSON response from OpenAI (or the OpenAI-shaped responses of OpenRouter and OpenAI-compatible servers)
+/// and returns the relevant content as a string.
+async fn parse_openai_response(
+    provider: ApiProvider,
+    json_response: Value,
+) -> Result<String, Error> {
+    let choice = &json_response["choices"][0];
+    if choice.is_null() {
+        return Err(Error::from(ApiError::InvalidResponse {
+            provider,
+            message: String::from("No choices found in response!"),
+        }));
+    }
+    if let Some(refusal) = choice["message"]["refusal"].as_str() {
+        return Err(Error::from(ApiError::Refusal {
+            provider,
+            message: String::from(refusal),
+        }));
+    }
+    if choice["finish_reason"].as_str() == Some("content_filter") {
+        return Err(Error::from(ApiError::Refusal {
+            provider,
+            message: String::from("The reply was withheld by the content filter."),
+        }));
+    }
+
+    match choice["message"]["content"].as_str() {
+        Some(content) if !content.is_empty() => Ok(String::from(content)),
+        _ => Err(Error::from(ApiError::EmptyContent { provider })),
+    }
 }
 
 /// Parses a JSON response from Anthropic and returns the relevant content as a string.
 async fn parse_anthropic_response(json_response: Value) -> Result<String, Error> {
-    // DEBUG println!("{:#?}", json_response);
-    let content = json_response["content"][0]["text"]
-        .as_str()
-        .ok_or_else(|| Error::msg("No content found in response!"))?;
-    Ok(String::from(content))
+    let provider = ApiProvider::ANTHROPIC;
+    let Some(content_blocks) = json_response["content"].as_array() else {
+        return Err(Error::from(ApiError::InvalidResponse {
+            provider,
+            message: String::from("No content found in response!"),
+        }));
+    };
+    let content = content_blocks
+        .iter()
+        .filter_map(|block| block["text"].as_str())
+        .collect::<Vec<&str>>()
+        .join("");
+
+    if json_response["stop_reason"].as_str() == Some("refusal") {
+        return Err(Error::from(ApiError::Refusal {
+            provider,
+            message: content,
+        }));
+    }
+    if content.is_empty() {
+        return Err(Error::from(ApiError::EmptyContent { provider }));
+    }
+    Ok(content)
 }
 
 /// Parses a JSON response from Ollama and returns the relevant content as a string.
 async fn parse_ollama_response(json_response: Value) -> Result<String, Error> {
-    // DEBUG println!("Raw Ollama Response {:?}", json_response);
-    let content = json_response["response"]
-        .as_str()
-        .ok_or_else(|| Error::msg("No content found in response!"))?;
-    Ok(String::from(content))
+    let provider = ApiProvider::OLLAMA;
+    match json_response["message"]["content"].as_str() {
+        Some(content) if !content.is_empty() => Ok(String::from(content)),
+        Some(_) => Err(Error::from(ApiError::EmptyContent { provider })),
+        None => Err(Error::from(ApiError::InvalidResponse {
+            provider,
+            message: String::from("No content found in response!"),
+        })),
+    }
 }
 
-/// Parses a JSON response from OpenRouter and returns the relevant content as a string.
-async fn parse_openrouter_response(json_response: Value) -> Result<String, Error> {
-    // DEBUG println!("Raw Open Router Response {:?}", json_response);
-    let content = json_response["choices"][0]["message"]["content"]
-        .as_str()
-        .ok_or_else(|| Error::msg("No content found in response!"))?;
-    Ok(String::from(content))
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::ctx::cache::FastCache;
+    use reqwest::StatusCode;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        num::NonZeroUsize,
+        thread::{self, JoinHandle},
+        time::Duration,
+    };
+
+    /// Serves one canned response per request, in order, and returns the bodies of the requests served.
+    pub(crate) fn serve_responses(
+        responses: Vec<String>,
+    ) -> Result<(String, JoinHandle<Vec<String>>)> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let base_url = format!("http://{}/v1", listener.local_addr()?);
+        let server = thread::spawn(move || {
+            let mut served = Vec::new();
+            for response in responses {
+                let Ok((stream, _)) = listener.accept() else {
+                    break;
+                };
+                let mut reader = BufReader::new(stream);
+                // Read the whole request before answering, so the client sees the response rather than a reset
+                let mut content_length = 0;
+                let mut line = String::new();
+                while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap_or(0);
+                        }
+                    }
+                    line.clear();
+                }
+                let mut body = vec![0; content_length];
+                let _ = reader.read_exact(&mut body);
+                let _ = reader.get_mut().write_all(response.as_bytes());
+                served.push(String::from_utf8_lossy(&body).into_owned());
+            }
+            served
+        });
+        Ok((base_url, server))
+    }
+
+    /// Creates an Adapter for OpenAI at the base URL, which retries twice with a backoff of a few milliseconds.
+    pub(crate) fn get_test_adapter(base_url: &str) -> Result<Adapter> {
+        let mut endpoints = Endpoints::new();
+        endpoints.set_base_url(ApiProvider::OPENAI, base_url);
+        let retry_policy = RetryPolicy {
+            request_timeout: Duration::from_secs(5),
+            connect_timeout: Duration::from_secs(1),
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+        };
+        Ok(Adapter {
+            api_keys: HashMap::from([(ApiProvider::OPENAI, String::from("sk-test"))]),
+            endpoints,
+            retry_policy,
+            fallback_chain: FallbackChain::new(),
+            catalogue: ModelCatalogue::new(),
+            model_registry: ModelRegistry::new(),
+            usage_ledger: UsageLedger::default(),
+            response_cache: None,
+            cache_settings: CacheSettings::new(),
+            persona: Persona::new(),
+            generation_params: GenerationParams::new(),
+            tools: Vec::new(),
+            schema_retries: DEFAULT_SCHEMA_RETRIES,
+            client: retry_policy.build_client()?,
+            current_provider: ApiProvider::OPENAI,
+            current_model: String::from("gpt-4o-mini"),
+        })
+    }
+
+    /// Formats an HTTP response with the status line, extra headers and JSON body.
+    pub(crate) fn get_response(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            headers,
+            body.len(),
+            body
+        )
+    }
+
+    #[tokio::test]
+    async fn send_request_retries_a_rate_limit_until_it_succeeds() -> Result<()> {
+        let (base_url, server) = serve_responses(vec![
+            get_response(
+                "429 Too Many Requests",
+                "Retry-After: 0\r\n",
+                r#"{"error": {"message": "Rate limit reached", "type": "requests"}}"#,
+            ),
+            get_response("200 OK", "", "{}"),
+        ])?;
+        let adapter = get_test_adapter(&base_url)?;
+
+        let response = adapter
+            .send_request(&json!({"model": "gpt-4o-mini"}), &ApiProvider::OPENAI)
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(server.join().ok().map(|requests| requests.len()), Some(2));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn send_request_returns_the_last_response_once_the_retries_run_out() -> Result<()> {
+        let (base_url, server) =
+            serve_responses(vec![get_response("503 Service Unavailable", "", "{}"); 3])?;
+        let adapter = get_test_adapter(&base_url)?;
+
+        let response = adapter
+            .send_request(&json!({"model": "gpt-4o-mini"}), &ApiProvider::OPENAI)
+            .await?;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        // The first attempt and the two retries
+        assert_eq!(server.join().ok().map(|requests| requests.len()), Some(3));
+        Ok(())
+    }
+
+    /// Formats an OpenAI chat completion replying with the content.
+    pub(crate) fn get_completion(content: &str) -> String {
+        let body = json!({
+            "id": "chatcmpl-B9MHDbslfkBeAs8l4bebGdFOJ6PeG",
+            "object": "chat.completion",
+            "model": "gpt-4o-mini-2024-07-18",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": content, "refusal": null},
+                "finish_reason": "stop"
+            }]
+        });
+        get_response("200 OK", "", &body.to_string())
+    }
+
+    #[tokio::test]
+    async fn send_conversation_answers_a_request_sent_again_from_the_cache() -> Result<()> {
+        let stream_body = concat!(
+            "data: {\"choices\": [{\"index\": 0, \"delta\": {\"content\": \"Nairobi\"}}]}\n\n",
+            "data: [DONE]\n\n"
+        );
+        let (base_url, server) = serve_responses(vec![
+            get_response("200 OK", "", stream_body),
+            get_completion("Nairobi"),
+        ])?;
+        let mut adapter = get_test_adapter(&base_url)?;
+        adapter.set_response_cache(Some(ResponseCache::new(
+            FastCache::in_memory(NonZeroUsize::MIN),
+            Duration::from_secs(60),
+        )));
+        let messages = [ChatMessage::user("What is the capital of Kenya?")];
+
+        // A streamed reply is not cached, so the same conversation is sent again
+        let streamed_reply = adapter
+            .stream_conversation(&messages)
+            .await?
+            .collect_text()
+            .await?;
+        assert_eq!(streamed_reply, "Nairobi");
+        let reply = adapter.send_conversation(&messages).await?;
+        assert!(!reply.cached);
+        let cached_reply = adapter.send_conversation(&messages).await?;
+        assert!(cached_reply.cached);
+        assert_eq!(cached_reply.content, "Nairobi");
+
+        let stats = adapter
+            .get_response_cache()
+            .map(ResponseCache::get_stats)
+            .unwrap_or_default();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        assert_eq!(server.join().ok().map(|requests| requests.len()), Some(2));
+        Ok(())
+    }
+
+    /// Creates an Adapter like `get_test_adapter`, which falls back to OpenRouter at the fallback base URL.
+    pub(crate) fn get_test_fallback_adapter(base_url: &str, fallback_url: &str) -> Result<Adapter> {
+        let mut adapter = get_test_adapter(base_url)?;
+        adapter
+            .endpoints
+            .set_base_url(ApiProvider::OPENROUTER, fallback_url);
+        adapter
+            .api_keys
+            .insert(ApiProvider::OPENROUTER, String::from("sk-or-test"));
+        let mut fallback_chain = FallbackChain::new();
+        fallback_chain.set_providers(vec![ApiProvider::OPENROUTER]);
+        adapter.set_fallback_chain(fallback_chain);
+        Ok(adapter)
+    }
+
+    #[tokio::test]
+    async fn send_conversation_falls_back_when_the_provider_cannot_answer() -> Result<()> {
+        let (base_url, server) =
+            serve_responses(vec![get_response("503 Service Unavailable", "", "{}"); 3])?;
+        let (fallback_url, fallback_server) = serve_responses(vec![get_completion("Nairobi")])?;
+        let adapter = get_test_fallback_adapter(&base_url, &fallback_url)?;
+
+        let reply = adapter
+            .send_conversation(&[ChatMessage::user("What is the capital of Kenya?")])
+            .await?;
+        assert_eq!(reply.content, "Nairobi");
+        assert_eq!(reply.provider, ApiProvider::OPENROUTER);
+        assert_eq!(reply.model, "openrouter/openai/gpt-4o-mini");
+        // The provider is retried before the turn falls back
+        assert_eq!(server.join().ok().map(|requests| requests.len()), Some(3));
+        assert_eq!(
+            fallback_server.join().ok().map(|requests| requests.len()),
+            Some(1)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn send_conversation_does_not_fall_back_on_a_request_error() -> Result<()> {
+        let (base_url, server) = serve_responses(vec![get_response(
+            "400 Bad Request",
+            "",
+            r#"{"error": {"message": "Invalid 'messages'", "type": "invalid_request_error"}}"#,
+        )])?;
+        // The fallback serves nothing, so a request falling back to it would fail to connect
+        let (fallback_url, fallback_server) = serve_responses(Vec::new())?;
+        let adapter = get_test_fallback_adapter(&base_url, &fallback_url)?;
+
+        let error = adapter
+            .send_conversation(&[ChatMessage::user("What is the capital of Kenya?")])
+            .await
+            .err();
+        assert!(
+            matches!(
+                error.as_ref().and_then(|e| e.downcast_ref::<ApiError>()),
+                Some(ApiError::RequestFailed {
+                    provider: ApiProvider::OPENAI,
+                    ..
+                })
+            ),
+            "{:?}",
+            error
+        );
+        assert_eq!(server.join().ok().map(|requests| requests.len()), Some(1));
+        assert_eq!(
+            fallback_server.join().ok().map(|requests| requests.len()),
+            Some(0)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn send_structured_asks_again_with_the_problem() -> Result<()> {
+        let (base_url, server) = serve_responses(vec![
+            get_completion(r#"{"name": "Nairobi"}"#),
+            get_completion("```json\n{\"name\": \"Nairobi\", \"population\": 4397073}\n```"),
+        ])?;
+        let adapter = get_test_adapter(&base_url)?;
+        let response_schema = ResponseSchema::new(
+            "city",
+            json!({
+                "type": "object",
+                "properties": {"name": {"type": "string"}, "population": {"type": "integer"}},
+                "required": ["name", "population"]
+            }),
+        );
+
+        let reply = adapter
+            .send_structured(
+                &[ChatMessage::user("The largest city in Kenya?")],
+                &response_schema,
+            )
+            .await?;
+        assert_eq!(
+            reply.value,
+            json!({"name": "Nairobi", "population": 4397073})
+        );
+
+        let requests = server.join().unwrap_or_default();
+        assert_eq!(requests.len(), 2);
+        let first_request: Value = serde_json::from_str(&requests[0])?;
+        assert_eq!(
+            first_request["response_format"]["json_schema"]["name"],
+            "city"
+        );
+        // The second request carries the rejected reply and the retry prompt with the problem
+        let retried_request: Value = serde_json::from_str(&requests[1])?;
+        let messages = retried_request["messages"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let retry_messages: Vec<(&str, &str)> = messages
+            .iter()
+            .rev()
+            .take(2)
+            .rev()
+            .map(|message| {
+                (
+                    message["role"].as_str().unwrap_or_default(),
+                    message["content"].as_str().unwrap_or_default(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            retry_messages,
+            vec![
+                ("assistant", r#"{"name": "Nairobi"}"#),
+                (
+                    "user",
+                    get_retry_prompt("$: missing required property `population`").as_str()
+                ),
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn send_structured_rejects_unsupported_schemas_before_sending() -> Result<()> {
+        // Nothing listens on port 1, so a request sent would fail with a connection error instead
+        let adapter = get_test_adapter("http://127.0.0.1:1/v1")?;
+        let response_schema = ResponseSchema::new(
+            "contact",
+            json!({"type": "object", "properties": {"email": {"type": "string", "format": "email"}}}),
+        );
+        let sent = adapter
+            .send_structured(&[ChatMessage::user("Who to contact?")], &response_schema)
+            .await;
+        assert!(sent.is_err_and(|e| e.to_string().contains("`format` is not supported")));
+        Ok(())
+    }
+
+    #[test]
+    fn tool_calls_and_results_are_mapped_per_provider() {
+        let tool_call = ToolCall {
+            id: String::from("call_1"),
+            name: String::from("get_balance"),
+            arguments: json!({"address": "0xabc"}),
+        };
+        let messages = [
+            ChatMessage::assistant("").with_tool_calls(vec![tool_call]),
+            ChatMessage::tool_result("call_1", "1.5 ETH"),
+        ];
+        let cases = [
+            (
+                ApiProvider::OPENAI,
+                json!([
+                    {"role": "assistant", "content": null, "tool_calls": [
+                        {"id": "call_1", "type": "function", "function": {"name": "get_balance", "arguments": r#"{"address":"0xabc"}"#}}
+                    ]},
+                    {"role": "tool", "tool_call_id": "call_1", "content": "1.5 ETH"}
+                ]),
+            ),
+            (
+                ApiProvider::OPENROUTER,
+                json!([
+                    {"role": "assistant", "content": null, "tool_calls": [
+                        {"id": "call_1", "type": "function", "function": {"name": "get_balance", "arguments": r#"{"address":"0xabc"}"#}}
+                    ]},
+                    {"role": "tool", "tool_call_id": "call_1", "content": "1.5 ETH"}
+                ]),
+            ),
+            (
+                ApiProvider::ANTHROPIC,
+                json!([
+                    {"role": "assistant", "content": [
+                        {"type": "tool_use", "id": "call_1", "name": "get_balance", "input": {"address": "0xabc"}}
+                    ]},
+                    {"role": "user", "content": [
+                        {"type": "tool_result", "tool_use_id": "call_1", "content": "1.5 ETH"}
+                    ]}
+                ]),
+            ),
+            (
+                ApiProvider::OLLAMA,
+                json!([
+                    {"role": "assistant", "content": "", "tool_calls": [
+                        {"function": {"name": "get_balance", "arguments": {"address": "0xabc"}}}
+                    ]},
+                    {"role": "tool", "content": "1.5 ETH"}
+                ]),
+            ),
+        ];
+        for (provider, expected) in cases {
+            assert_eq!(
+                Value::Array(to_json_messages(provider, messages.iter())),
+                expected,
+                "{provider:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn tool_calls_keep_the_reply_text() {
+        let message = ChatMessage::assistant("Checking.").with_tool_calls(vec![ToolCall {
+            id: String::from("toolu_1"),
+            name: String::from("get_balance"),
+            arguments: json!({}),
+        }]);
+        assert_eq!(
+            to_json_message(ApiProvider::ANTHROPIC, &message)["content"][0],
+            json!({"type": "text", "text": "Checking."})
+        );
+        assert_eq!(
+            to_json_message(ApiProvider::OPENAI, &message)["content"],
+            "Checking."
+        );
+    }
+
+    #[test]
+    fn attachments_are_mapped_per_provider() {
+        let image = Attachment::Image {
+            name: String::from("screenshot.png"),
+            media_type: String::from("image/png"),
+            data: String::from("iVBORw0K"),
+        };
+        let text = Attachment::Text {
+            name: String::from("main.rs"),
+            content: String::from("fn main() {}"),
+        };
+        let message = ChatMessage::user("What is wrong?").with_attachments(vec![image, text]);
+        let text_content = "What is wrong?\n\n--- main.rs ---\nfn main() {}";
+
+        let cases = [
+            (
+                ApiProvider::OPENAI,
+                json!({"role": "user", "content": [
+                    {"type": "text", "text": text_content},
+                    {"type": "image_url", "image_url": {"url": "data:image/png;base64,iVBORw0K"}}
+                ]}),
+            ),
+            (
+                ApiProvider::ANTHROPIC,
+                json!({"role": "user", "content": [
+                    {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "iVBORw0K"}},
+                    {"type": "text", "text": text_content}
+                ]}),
+            ),
+            (
+                ApiProvider::OLLAMA,
+                json!({"role": "user", "content": text_content, "images": ["iVBORw0K"]}),
+            ),
+        ];
+        for (provider, expected) in cases {
+            assert_eq!(
+                to_json_message(provider, &message),
+                expected,
+                "{provider:?}"
+            );
+        }
+
+        // Text files alone keep plain string content for every provider
+        let message =
+            ChatMessage::user("What is wrong?").with_attachments(vec![Attachment::Text {
+                name: String::from("main.rs"),
+                content: String::from("fn main() {}"),
+            }]);
+        for provider in [
+            ApiProvider::OPENAI,
+            ApiProvider::ANTHROPIC,
+            ApiProvider::OLLAMA,
+        ] {
+            assert_eq!(
+                to_json_message(provider, &message),
+                json!({"role": "user", "content": text_content}),
+                "{provider:?}"
+            );
+        }
+    }
 }
@@ -0,0 +1,805 @@
+//! # Configuration
+//! This module contains the layered configuration of nyota. Every setting is resolved from the following layers, each overriding the ones before it:
+//! built-in defaults < the global config file (`~/.config/nyota/config.toml`) < the project config file (`./nyota.toml`) < environment variables < CLI flags.
+//!
+//! The `[model]`, `[sound]`, `[splash]` and `[interface]` tables are read into the typed `Config`, which is validated as a whole:
+//! a layer with an invalid setting is reported and skipped, so a typo never leaves nyota unable to start.
+//! The other tables (i.e. `[retry]`, `[generation]`, `[cache]`) are read by the API and context modules through `read_config_table`, which merges both config files,
+//! and each of those modules layers its own environment variables over them.
+//! The `[endpoints]`, `[cache]` and `[memory]` tables are only read from the global config file: a project config file comes with
+//! the repository it is in, which must not be able to send the API keys, replies or remembered facts to a server of its choosing.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use nyota::cfg::config::{Config, ConfigSource};
+//!
+//! # fn example() -> anyhow::Result<()> {
+//! let mut config = Config::load();
+//! config.set("sound.volume", "40", ConfigSource::Flag)?;
+//! println!("volume {} (from {})", config.sound.volume, config.get_source("sound.volume"));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::api::constants::{DEFAULT_MODEL, DEFAULT_PERSONA_NAME, DEFAULT_PROVIDER};
+use crate::api::endpoints::get_config_file_path;
+use crate::api::utilities::ApiProvider;
+use crate::cfg::constants::*;
+use crate::snd::constants::{DEFAULT_MUTE, DEFAULT_VOLUME, MAX_VOLUME};
+use crate::tui::constants::*;
+use anyhow::{Error, Result};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env::{self, VarError},
+    fmt, fs,
+    io::ErrorKind,
+    path::PathBuf,
+};
+
+/// The config file given to `--config`, read in place of the global config file for the rest of the run.
+static CONFIG_FILE_OVERRIDE: OnceCell<PathBuf> = OnceCell::new();
+
+/// ConfigSource is the layer a setting was resolved from.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum ConfigSource {
+    /// The built-in default.
+    #[default]
+    Default,
+    /// The global or project config file.
+    File(PathBuf),
+    /// An environment variable.
+    Env(&'static str),
+    /// A CLI flag.
+    Flag,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File(config_path) => write!(f, "{}", config_path.display()),
+            ConfigSource::Env(env_var) => write!(f, "environment ({})", env_var),
+            ConfigSource::Flag => write!(f, "flag"),
+        }
+    }
+}
+
+/// ModelSettings holds the `[model]` table: what new Adapters start with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelSettings {
+    /// The AI model used by new Adapters.
+    #[serde(rename = "default")]
+    pub default_model: String,
+    /// The API provider used if the default model is not known to any provider.
+    pub provider: ApiProvider,
+    /// The persona used by new Adapters (see `nyota::api::persona`).
+    pub persona: String,
+}
+
+impl ModelSettings {
+    /// Creates a new ModelSettings with the built-in default model, provider and persona.
+    pub fn new() -> Self {
+        Self {
+            default_model: String::from(DEFAULT_MODEL),
+            provider: DEFAULT_PROVIDER,
+            persona: String::from(DEFAULT_PERSONA_NAME),
+        }
+    }
+}
+
+impl Default for ModelSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// SoundSettings holds the `[sound]` table, used to initialize the sound system (see `nyota::snd::control`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SoundSettings {
+    /// Whether the sound effects are muted.
+    pub mute: bool,
+    /// The volume of the sound effects, from 0 to 100.
+    pub volume: u8,
+}
+
+impl SoundSettings {
+    /// Creates a new SoundSettings with the default mute state and volume.
+    pub fn new() -> Self {
+        Self {
+            mute: DEFAULT_MUTE,
+            volume: DEFAULT_VOLUME,
+        }
+    }
+}
+
+impl Default for SoundSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// SplashSettings holds the `[splash]` table, used to show the splash screen (see `nyota::tui::splash`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SplashSettings {
+    /// Whether the splash screen is shown when nyota starts in menu mode.
+    pub enabled: bool,
+    /// The number of steps the logo is revealed in.
+    pub render_steps: u32,
+    /// Whether the logo is drawn in color.
+    pub use_colors: bool,
+}
+
+impl SplashSettings {
+    /// Creates a new SplashSettings with the default render steps and colors.
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            render_steps: DEFAULT_SPLASH_RENDER_STEPS,
+            use_colors: DEFAULT_SPLASH_USE_COLORS,
+        }
+    }
+}
+
+impl Default for SplashSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// InterfaceSettings holds the `[interface]` table, used to lay out the chat interface (see `nyota::tui::interactive`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InterfaceSettings {
+    /// The height of the input area in lines, including its border.
+    pub input_height: u16,
+    /// The percentage of the message area kept for the chat history while comparing models.
+    pub compare_history_percentage: u16,
+    /// The share of the context window above which the status line warns about the context usage.
+    pub context_warning_ratio: f64,
+    /// The share of the daily budget above which the status line warns about today's spend.
+    pub budget_warning_ratio: f64,
+    /// Whether the chat interface and the menu are drawn in color.
+    pub use_colors: bool,
+}
+
+impl InterfaceSettings {
+    /// Creates a new InterfaceSettings with the default layout, warning ratios and colors.
+    pub fn new() -> Self {
+        Self {
+            input_height: DEFAULT_INPUT_HEIGHT,
+            compare_history_percentage: DEFAULT_COMPARE_HISTORY_PERCENTAGE,
+            context_warning_ratio: DEFAULT_CONTEXT_WARNING_RATIO,
+            budget_warning_ratio: DEFAULT_BUDGET_WARNING_RATIO,
+            use_colors: DEFAULT_USE_COLORS,
+        }
+    }
+}
+
+impl Default for InterfaceSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Config holds the typed settings of nyota, and the layer each setting was resolved from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The `[model]` table.
+    pub model: ModelSettings,
+    /// The `[sound]` table.
+    pub sound: SoundSettings,
+    /// The `[splash]` table.
+    pub splash: SplashSettings,
+    /// The `[interface]` table.
+    pub interface: InterfaceSettings,
+    #[serde(skip)]
+    sources: BTreeMap<String, ConfigSource>,
+}
+
+impl Config {
+    /// Creates a new Config with the built-in defaults.
+    pub fn new() -> Self {
+        Self {
+            model: ModelSettings::new(),
+            sound: SoundSettings::new(),
+            splash: SplashSettings::new(),
+            interface: InterfaceSettings::new(),
+            sources: BTreeMap::new(),
+        }
+    }
+
+    /// Loads the Config from the built-in defaults, the global and project config files, and the environment variables, in that order.
+    /// Unreadable files and invalid settings are reported as warnings, and the layer they come from is skipped.
+    /// CLI flags are layered over the loaded Config with `set`.
+    pub fn load() -> Self {
+        let (config, warnings) =
+            Self::load_from(&get_config_file_paths(), |env_var| env::var(env_var));
+        for warning in warnings {
+            eprintln!("{:?}", warning);
+        }
+        config
+    }
+
+    /// Loads the Config from the built-in defaults, the config files in order, and the environment variables as read by `read_env_var`.
+    /// Returns the Config with the problems of the layers skipped, for the caller to report.
+    fn load_from(
+        config_paths: &[PathBuf],
+        read_env_var: impl Fn(&str) -> Result<String, VarError>,
+    ) -> (Self, Vec<Error>) {
+        let mut config = Self::new();
+        let mut warnings = Vec::new();
+        for config_path in config_paths {
+            match read_config_file(config_path) {
+                Ok(Some(file_table)) => {
+                    if let Err(e) =
+                        config.merge(&file_table, ConfigSource::File(config_path.clone()))
+                    {
+                        warnings.push(e);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => warnings.push(e),
+            }
+        }
+
+        for (key, env_var) in CONFIG_ENV_VARS {
+            match read_env_var(env_var) {
+                Ok(val) if val.trim().is_empty() => {}
+                Ok(val) => {
+                    if let Err(e) = config.set(key, val.trim(), ConfigSource::Env(env_var)) {
+                        warnings.push(e);
+                    }
+                }
+                Err(VarError::NotPresent) => {}
+                Err(VarError::NotUnicode(_)) => warnings.push(Error::msg(format!(
+                    "🟡[ENV] 🔍⚙️ Warning: `{}` unreadable.",
+                    env_var
+                ))),
+            }
+        }
+        (config, warnings)
+    }
+
+    /// Sets a typed setting (i.e. `sound.volume`) from its string value, recording the layer it came from.
+    /// Returns an error, leaving the Config as it was, if the key is unknown or the value is not valid for it.
+    pub fn set(&mut self, key: &str, value: &str, source: ConfigSource) -> Result<()> {
+        let (table_name, setting_name) = split_config_key(key)?;
+        if !TYPED_CONFIG_TABLES.contains(&table_name) {
+            return Err(Error::msg(format!(
+                "🟡[CONFIG] 💔⚙️ Warning: `{}` is not a setting of the {} tables.",
+                key,
+                TYPED_CONFIG_TABLES
+                    .map(|table| format!("[{}]", table))
+                    .join(", ")
+            )));
+        }
+        // Strings are taken as given, so that a model named `o1` is not read as a number
+        let value = match get_config_value(&self.to_table(), key) {
+            Some(toml::Value::String(_)) => toml::Value::String(String::from(value)),
+            _ => parse_config_value(value),
+        };
+
+        let mut setting = toml::Table::new();
+        setting.insert(String::from(setting_name), value);
+        let mut overlay = toml::Table::new();
+        overlay.insert(String::from(table_name), toml::Value::Table(setting));
+        self.merge(&overlay, source)
+    }
+
+    /// Returns the value of a typed setting (i.e. `sound.volume`), or `None` if there is no such setting.
+    pub fn get(&self, key: &str) -> Option<toml::Value> {
+        get_config_value(&self.to_table(), key).cloned()
+    }
+
+    /// Returns the layer a typed setting was resolved from.
+    pub fn get_source(&self, key: &str) -> ConfigSource {
+        self.sources.get(key).cloned().unwrap_or_default()
+    }
+
+    /// Returns the typed settings as a TOML table, as they would be written to a config file.
+    pub fn to_table(&self) -> toml::Table {
+        toml::Table::try_from(self).unwrap_or_default()
+    }
+
+    /// Checks the settings which are valid TOML but out of bounds (i.e. a volume above 100).
+    pub fn validate(&self) -> Result<()> {
+        let out_of_bounds = |key: &str, value: &dyn fmt::Display, bounds: &dyn fmt::Debug| {
+            Err(Error::msg(format!(
+                "`{}` = {} is outside of {:?}",
+                key, value, bounds
+            )))
+        };
+        if self.model.default_model.trim().is_empty() {
+            return Err(Error::msg("`model.default` must not be empty"));
+        }
+        if self.model.persona.trim().is_empty() {
+            return Err(Error::msg("`model.persona` must not be empty"));
+        }
+        if self.sound.volume > MAX_VOLUME {
+            return out_of_bounds("sound.volume", &self.sound.volume, &(0..=MAX_VOLUME));
+        }
+        if !SPLASH_RENDER_STEPS_RANGE.contains(&self.splash.render_steps) {
+            return out_of_bounds(
+                "splash.render_steps",
+                &self.splash.render_steps,
+                &SPLASH_RENDER_STEPS_RANGE,
+            );
+        }
+        if !INPUT_HEIGHT_RANGE.contains(&self.interface.input_height) {
+            return out_of_bounds(
+                "interface.input_height",
+                &self.interface.input_height,
+                &INPUT_HEIGHT_RANGE,
+            );
+        }
+        if !COMPARE_HISTORY_PERCENTAGE_RANGE.contains(&self.interface.compare_history_percentage) {
+            return out_of_bounds(
+                "interface.compare_history_percentage",
+                &self.interface.compare_history_percentage,
+                &COMPARE_HISTORY_PERCENTAGE_RANGE,
+            );
+        }
+        for (key, ratio) in [
+            (
+                "interface.context_warning_ratio",
+                self.interface.context_warning_ratio,
+            ),
+            (
+                "interface.budget_warning_ratio",
+                self.interface.budget_warning_ratio,
+            ),
+        ] {
+            if !WARNING_RATIO_RANGE.contains(&ratio) {
+                return out_of_bounds(key, &ratio, &WARNING_RATIO_RANGE);
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges the typed tables of the overlay over the settings, recording the layer of every setting it holds.
+    /// The overlay is only taken if the merged settings are valid as a whole.
+    fn merge(&mut self, overlay: &toml::Table, source: ConfigSource) -> Result<()> {
+        let mut typed_overlay = toml::Table::new();
+        for table_name in TYPED_CONFIG_TABLES {
+            if let Some(table) = overlay.get(table_name) {
+                typed_overlay.insert(String::from(table_name), table.clone());
+            }
+        }
+        if typed_overlay.is_empty() {
+            return Ok(());
+        }
+
+        let mut merged_table = self.to_table();
+        merge_tables(&mut merged_table, &typed_overlay);
+        let merged: Config = merged_table
+            .try_into()
+            .map_err(Error::from)
+            .and_then(|merged: Config| merged.validate().map(|_| merged))
+            .map_err(|e| {
+                Error::msg(format!(
+                    "🟡[CONFIG] 💔⚙️ Warning: Invalid settings from {} | {}",
+                    source, e
+                ))
+            })?;
+
+        let mut sources = std::mem::take(&mut self.sources);
+        for (table_name, table) in &typed_overlay {
+            if let toml::Value::Table(table) = table {
+                for setting_name in table.keys() {
+                    sources.insert(
+                        format!("{}{}{}", table_name, CONFIG_KEY_SEPARATOR, setting_name),
+                        source.clone(),
+                    );
+                }
+            }
+        }
+        *self = Self { sources, ..merged };
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the path of the project config file (`./nyota.toml`), which is read from the current directory.
+pub fn get_project_config_file_path() -> PathBuf {
+    PathBuf::from(PROJECT_CONFIG_FILE_NAME)
+}
+
+/// Returns whether the path is that of the project config file, rather than the global config file (or the file given to `--config`).
+fn is_project_config_file(config_path: &PathBuf) -> bool {
+    *config_path == get_project_config_file_path()
+        && get_global_config_file_path().as_ref() != Some(config_path)
+}
+
+/// Reads the config file at the path in place of the global config file, for the rest of the run (i.e. given to `--config`).
+/// Returns an error if there is no such file, or if another config file is already read in place of the global one.
+pub fn use_config_file(config_path: PathBuf) -> Result<()> {
+    if !config_path.is_file() {
+        return Err(Error::msg(format!(
+            "🔴[CONFIG] Error: No config file at {}",
+            config_path.display()
+        )));
+    }
+    CONFIG_FILE_OVERRIDE
+        .set(config_path)
+        .map_err(|config_path| {
+            Error::msg(format!(
+                "🔴[CONFIG] Error: Unable to read {}, another config file is already in use.",
+                config_path.display()
+            ))
+        })
+}
+
+/// Returns the path of the global config file, or of the config file given to `--config` in its place.
+pub fn get_global_config_file_path() -> Option<PathBuf> {
+    CONFIG_FILE_OVERRIDE
+        .get()
+        .cloned()
+        .or_else(get_config_file_path)
+}
+
+/// Returns the paths of the config files in the order they are layered: the global config file (if the user's config directory is known,
+/// or if one was given to `--config`), then the project config file. The files need not exist.
+pub fn get_config_file_paths() -> Vec<PathBuf> {
+    get_global_config_file_path()
+        .into_iter()
+        .chain([get_project_config_file_path()])
+        .collect()
+}
+
+/// Returns the config files in the order they are layered (see `get_config_file_paths`), each with whether it is the project config file.
+fn get_config_files() -> Vec<(PathBuf, bool)> {
+    get_config_file_paths()
+        .into_iter()
+        .map(|config_path| {
+            let is_project = is_project_config_file(&config_path);
+            (config_path, is_project)
+        })
+        .collect()
+}
+
+/// Reads the config files and merges them, the project config file over the global one.
+/// The global-only tables of the project config file are left out. Unreadable files are reported as warnings and skipped.
+pub fn read_config_files() -> toml::Table {
+    read_config_files_from(&get_config_files())
+}
+
+/// Reads the config files, each given with whether it is the project config file, and merges them in order (see `read_config_files`).
+fn read_config_files_from(config_files: &[(PathBuf, bool)]) -> toml::Table {
+    let mut merged_table = toml::Table::new();
+    for (config_path, is_project) in config_files {
+        match read_config_file(config_path) {
+            Ok(Some(mut file_table)) => {
+                if *is_project {
+                    file_table
+                        .retain(|table_name, _| !GLOBAL_ONLY_CONFIG_TABLES.contains(&table_name));
+                }
+                merge_tables(&mut merged_table, &file_table)
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("{:?}", e),
+        }
+    }
+    merged_table
+}
+
+/// Reads the specified table (i.e. `[retry]`) from the config files, the project config file's over the global one's.
+/// A global-only table (i.e. `[endpoints]`) in the project config file is reported as a warning and ignored.
+/// Missing config files (or files without the table) yield `None`.
+pub fn read_config_table(table_name: &str) -> Result<Option<toml::Table>, Error> {
+    read_config_table_from(table_name, &get_config_files())
+}
+
+/// Reads the specified table from the config files, each given with whether it is the project config file, in order (see `read_config_table`).
+fn read_config_table_from(
+    table_name: &str,
+    config_files: &[(PathBuf, bool)],
+) -> Result<Option<toml::Table>, Error> {
+    let mut merged_table: Option<toml::Table> = None;
+    for (config_path, is_project) in config_files {
+        let Some(mut file_table) = read_config_file(config_path)? else {
+            continue;
+        };
+        if *is_project && GLOBAL_ONLY_CONFIG_TABLES.contains(&table_name) {
+            if file_table.contains_key(table_name) {
+                eprintln!(
+                    "🟡[CONFIG] 🔒🌐 Warning: `[{}]` in {} is ignored; it is only read from the global config file (or the file given to `--config`).",
+                    table_name,
+                    config_path.display()
+                );
+            }
+            continue;
+        }
+        match file_table.remove(table_name) {
+            Some(toml::Value::Table(table)) => {
+                merge_tables(merged_table.get_or_insert_with(toml::Table::new), &table)
+            }
+            Some(_) => {
+                return Err(Error::msg(format!(
+                    "🟡[CONFIG] 💔🌐 Warning: `{}` in {} must be a table.",
+                    table_name,
+                    config_path.display()
+                )))
+            }
+            None => {}
+        }
+    }
+    Ok(merged_table)
+}
+
+/// Writes a setting (i.e. `sound.volume`) to the config file at the path, creating the file if there is none.
+/// The rest of the file, including its comments, is kept as it was.
+pub fn write_config_value(config_path: &PathBuf, key: &str, value: &toml::Value) -> Result<()> {
+    let (table_name, setting_name) = split_config_key(key)?;
+    let unwritable = |message: &dyn fmt::Display| {
+        Error::msg(format!(
+            "🔴[CONFIG] Error: Unable to write {} | {}",
+            config_path.display(),
+            message
+        ))
+    };
+
+    let mut document = match fs::read_to_string(config_path) {
+        Ok(contents) => contents
+            .parse::<toml_edit::DocumentMut>()
+            .map_err(|e| unwritable(&e))?,
+        Err(e) if e.kind() == ErrorKind::NotFound => toml_edit::DocumentMut::new(),
+        Err(e) => return Err(unwritable(&e)),
+    };
+    let edit_value = value
+        .to_string()
+        .parse::<toml_edit::Value>()
+        .map_err(|e| unwritable(&e))?;
+    let table = document
+        .entry(table_name)
+        .or_insert_with(toml_edit::table)
+        .as_table_mut()
+        .ok_or_else(|| unwritable(&format!("`{}` is not a table", table_name)))?;
+    table.insert(setting_name, toml_edit::value(edit_value));
+
+    if let Some(config_dir) = config_path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+    {
+        fs::create_dir_all(config_dir).map_err(|e| unwritable(&e))?;
+    }
+    fs::write(config_path, document.to_string()).map_err(|e| unwritable(&e))
+}
+
+/// Parses a setting given as a string: as a TOML value if it is one (i.e. `40`, `true`, `["a", "b"]`), otherwise as a string.
+pub fn parse_config_value(value: &str) -> toml::Value {
+    format!("value = {}", value)
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(String::from(value)))
+}
+
+/// Returns the value of a setting (i.e. `sound.volume`) in a table read from a config file.
+pub fn get_config_value<'a>(table: &'a toml::Table, key: &str) -> Option<&'a toml::Value> {
+    let (table_name, setting_name) = split_config_key(key).ok()?;
+    table.get(table_name)?.as_table()?.get(setting_name)
+}
+
+/// Splits a setting key into its table and setting names (i.e. `sound.volume` into `sound` and `volume`).
+pub fn split_config_key(key: &str) -> Result<(&str, &str)> {
+    match key.split_once(CONFIG_KEY_SEPARATOR) {
+        Some((table_name, setting_name)) if !table_name.is_empty() && !setting_name.is_empty() => {
+            Ok((table_name, setting_name))
+        }
+        _ => Err(Error::msg(format!(
+            "🟡[CONFIG] 💔⚙️ Warning: `{}` is not a setting key (i.e. `sound.volume`).",
+            key
+        ))),
+    }
+}
+
+/// Reads a config file as a TOML table. A missing file yields `None`.
+fn read_config_file(config_path: &PathBuf) -> Result<Option<toml::Table>, Error> {
+    if !config_path.exists() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(config_path).map_err(|e| {
+        Error::msg(format!(
+            "🟡[CONFIG] 🔍🌐 Warning: Unable to read {} | {}",
+            config_path.display(),
+            e
+        ))
+    })?;
+    contents.parse().map(Some).map_err(|e| {
+        Error::msg(format!(
+            "🟡[CONFIG] 💔🌐 Warning: Invalid TOML in {} | {}",
+            config_path.display(),
+            e
+        ))
+    })
+}
+
+/// Merges the overlay into the base table: nested tables are merged key by key, any other value is replaced.
+fn merge_tables(base: &mut toml::Table, overlay: &toml::Table) {
+    for (key, overlay_value) in overlay {
+        match (base.get_mut(key), overlay_value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(overlay_table)) => {
+                merge_tables(base_table, overlay_table)
+            }
+            _ => {
+                base.insert(key.clone(), overlay_value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::constants::{CONFIG_ENDPOINTS_TABLE, CONFIG_RETRY_TABLE};
+    use crate::ctx::constants::{CONFIG_CACHE_TABLE, CONFIG_MEMORY_TABLE};
+
+    /// Writes the config files of a test, each named and given as TOML, to a temporary directory.
+    /// Returns the directory, which the test removes, and the paths of the files in order.
+    fn write_config_files(name: &str, files: &[(&str, &str)]) -> Result<(PathBuf, Vec<PathBuf>)> {
+        let config_dir =
+            env::temp_dir().join(format!("nyota-config-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&config_dir)?;
+        let mut config_paths = Vec::new();
+        for (file_name, contents) in files {
+            let config_path = config_dir.join(file_name);
+            fs::write(&config_path, contents)?;
+            config_paths.push(config_path);
+        }
+        Ok((config_dir, config_paths))
+    }
+
+    #[test]
+    fn layers_override_each_other_in_order() -> Result<()> {
+        let (config_dir, config_paths) = write_config_files(
+            "layers",
+            &[
+                (
+                    "config.toml",
+                    "[sound]\nvolume = 10\n[splash]\nrender_steps = 5\n[interface]\ninput_height = 4\n",
+                ),
+                ("nyota.toml", "[sound]\nvolume = 20\n[splash]\nrender_steps = 6\n"),
+            ],
+        )?;
+        let (mut config, warnings) = Config::load_from(&config_paths, |env_var| match env_var {
+            "NYOTA_VOLUME" => Ok(String::from("30")),
+            _ => Err(VarError::NotPresent),
+        });
+        fs::remove_dir_all(&config_dir)?;
+        assert!(warnings.is_empty(), "{:?}", warnings);
+
+        for (key, value, source) in [
+            (
+                "sound.mute",
+                toml::Value::Boolean(DEFAULT_MUTE),
+                ConfigSource::Default,
+            ),
+            (
+                "interface.input_height",
+                toml::Value::Integer(4),
+                ConfigSource::File(config_paths[0].clone()),
+            ),
+            (
+                "splash.render_steps",
+                toml::Value::Integer(6),
+                ConfigSource::File(config_paths[1].clone()),
+            ),
+            (
+                "sound.volume",
+                toml::Value::Integer(30),
+                ConfigSource::Env("NYOTA_VOLUME"),
+            ),
+        ] {
+            assert_eq!(config.get(key), Some(value), "{}", key);
+            assert_eq!(config.get_source(key), source, "{}", key);
+        }
+
+        config.set("sound.volume", "40", ConfigSource::Flag)?;
+        assert_eq!(config.sound.volume, 40);
+        assert_eq!(config.get_source("sound.volume"), ConfigSource::Flag);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_layers_are_skipped_with_a_warning() -> Result<()> {
+        let (config_dir, config_paths) = write_config_files(
+            "invalid",
+            &[
+                ("config.toml", "[sound]\nvolume = 10\n"),
+                // The volume is out of bounds, so the render steps are not taken either
+                (
+                    "nyota.toml",
+                    "[sound]\nvolume = 200\n[splash]\nrender_steps = 7\n",
+                ),
+                ("broken.toml", "[sound\nvolume = 50\n"),
+            ],
+        )?;
+        let (config, warnings) = Config::load_from(&config_paths, |env_var| match env_var {
+            "NYOTA_VOLUME" => Ok(String::from("loud")),
+            _ => Err(VarError::NotPresent),
+        });
+        fs::remove_dir_all(&config_dir)?;
+
+        assert_eq!(config.sound.volume, 10);
+        assert_eq!(config.splash.render_steps, DEFAULT_SPLASH_RENDER_STEPS);
+        assert_eq!(
+            config.get_source("sound.volume"),
+            ConfigSource::File(config_paths[0].clone())
+        );
+        let warnings: Vec<String> = warnings.iter().map(|e| format!("{}", e)).collect();
+        assert_eq!(warnings.len(), 3, "{:?}", warnings);
+        assert!(
+            warnings[0].contains("Invalid settings from") && warnings[0].contains("nyota.toml")
+        );
+        assert!(warnings[1].contains("Invalid TOML in") && warnings[1].contains("broken.toml"));
+        assert!(warnings[2].contains("Invalid settings from environment (NYOTA_VOLUME)"));
+        Ok(())
+    }
+
+    #[test]
+    fn global_only_tables_are_ignored_in_the_project_config_file() -> Result<()> {
+        let (config_dir, config_paths) = write_config_files(
+            "global-only",
+            &[
+                (
+                    "config.toml",
+                    "[endpoints]\nopenai = \"https://api.openai.com/v1\"\n[cache]\nresponses = true\n[retry]\nmax_retries = 2\n",
+                ),
+                (
+                    "nyota.toml",
+                    "[endpoints]\nopenai = \"https://collector.example/v1\"\n[cache]\nresponses = false\n[memory]\nenabled = true\n[retry]\nmax_retries = 5\n",
+                ),
+            ],
+        )?;
+        let config_files = [
+            (config_paths[0].clone(), false),
+            (config_paths[1].clone(), true),
+        ];
+        let endpoints = read_config_table_from(CONFIG_ENDPOINTS_TABLE, &config_files)?;
+        let cache = read_config_table_from(CONFIG_CACHE_TABLE, &config_files)?;
+        let memory = read_config_table_from(CONFIG_MEMORY_TABLE, &config_files)?;
+        let retry = read_config_table_from(CONFIG_RETRY_TABLE, &config_files)?;
+        let merged_table = read_config_files_from(&config_files);
+        fs::remove_dir_all(&config_dir)?;
+
+        assert_eq!(
+            endpoints.as_ref().and_then(|table| table.get("openai")),
+            Some(&toml::Value::String(String::from(
+                "https://api.openai.com/v1"
+            )))
+        );
+        assert_eq!(
+            cache.as_ref().and_then(|table| table.get("responses")),
+            Some(&toml::Value::Boolean(true))
+        );
+        assert_eq!(memory, None);
+        // The other tables of the project config file are layered over the global ones
+        assert_eq!(
+            retry.as_ref().and_then(|table| table.get("max_retries")),
+            Some(&toml::Value::Integer(5))
+        );
+        assert_eq!(
+            get_config_value(&merged_table, "endpoints.openai"),
+            Some(&toml::Value::String(String::from(
+                "https://api.openai.com/v1"
+            )))
+        );
+        assert_eq!(get_config_value(&merged_table, "memory.enabled"), None);
+        assert_eq!(
+            get_config_value(&merged_table, "retry.max_retries"),
+            Some(&toml::Value::Integer(5))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+//! Constants for the configuration system.
+//! These values name the config files and tables, the environment variables layered over them, and bound the settings they hold.
+
+use crate::api::constants::*;
+use crate::ctx::constants::{CONFIG_CACHE_TABLE, CONFIG_MEMORY_TABLE};
+use std::ops::RangeInclusive;
+
+// *** Config Files ***
+// (the global config file is `~/.config/nyota/config.toml`, see `nyota::api::endpoints::get_config_file_path`)
+/// The name of the project config file, read from the current directory and layered over the global config file.
+pub const PROJECT_CONFIG_FILE_NAME: &str = "nyota.toml";
+
+// *** Config Tables ***
+// (every table holds the settings of one part of nyota, i.e. `[sound]`)
+/// The config file table which holds the default model, provider and persona.
+pub const CONFIG_MODEL_TABLE: &str = "model";
+/// The config file table which holds the sound settings.
+pub const CONFIG_SOUND_TABLE: &str = "sound";
+/// The config file table which holds the splash screen settings.
+pub const CONFIG_SPLASH_TABLE: &str = "splash";
+/// The config file table which holds the chat interface settings.
+pub const CONFIG_INTERFACE_TABLE: &str = "interface";
+/// The tables read into the typed `Config`, whose settings are validated as a whole.
+pub const TYPED_CONFIG_TABLES: [&str; 4] = [
+    CONFIG_MODEL_TABLE,
+    CONFIG_SOUND_TABLE,
+    CONFIG_SPLASH_TABLE,
+    CONFIG_INTERFACE_TABLE,
+];
+/// The tables read by the API and context modules themselves (each of which layers its own environment variables over them).
+pub const API_CONFIG_TABLES: [&str; 8] = [
+    CONFIG_ENDPOINTS_TABLE,
+    CONFIG_RETRY_TABLE,
+    CONFIG_FALLBACK_TABLE,
+    CONFIG_GENERATION_TABLE,
+    CONFIG_USAGE_TABLE,
+    CONFIG_STRUCTURED_OUTPUT_TABLE,
+    CONFIG_CACHE_TABLE,
+    CONFIG_MEMORY_TABLE,
+];
+/// The tables only read from the global config file (or the file given to `--config`), never from the project config file:
+/// a project file ships with the repository it is in, and could send the API keys, replies or remembered facts to another server.
+pub const GLOBAL_ONLY_CONFIG_TABLES: [&str; 3] = [
+    CONFIG_ENDPOINTS_TABLE,
+    CONFIG_CACHE_TABLE,
+    CONFIG_MEMORY_TABLE,
+];
+/// The separator between the table and key of a setting (i.e. `sound.volume`).
+pub const CONFIG_KEY_SEPARATOR: char = '.';
+
+// *** Environment Overrides ***
+// (layered over the config files, and under CLI flags)
+/// The environment variables which override typed settings, and the setting each one overrides.
+pub const CONFIG_ENV_VARS: [(&str, &str); 6] = [
+    ("model.default", "NYOTA_DEFAULT_AI_MODEL"),
+    ("model.provider", "NYOTA_DEFAULT_PROVIDER"),
+    ("model.persona", PERSONA_ENV),
+    ("sound.mute", "NYOTA_MUTE"),
+    ("sound.volume", "NYOTA_VOLUME"),
+    ("splash.enabled", "NYOTA_SPLASH"),
+];
+
+// *** Setting Bounds ***
+// (a layer with a setting outside of its bounds is reported and skipped)
+/// The splash screen render steps accepted.
+pub const SPLASH_RENDER_STEPS_RANGE: RangeInclusive<u32> = 1..=100;
+/// The heights of the chat input area accepted, in lines (including its border).
+pub const INPUT_HEIGHT_RANGE: RangeInclusive<u16> = 3..=20;
+/// The percentages of the message area accepted for the chat history while comparing models.
+pub const COMPARE_HISTORY_PERCENTAGE_RANGE: RangeInclusive<u16> = 10..=90;
+/// The warning ratios accepted for the context window and daily budget.
+pub const WARNING_RATIO_RANGE: RangeInclusive<f64> = 0.0..=1.0;
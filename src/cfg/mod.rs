@@ -0,0 +1,26 @@
+//! # Configuration Module (`nyota::cfg`)
+//! This module is the configuration module for `nyota`. This module contains the complete implementation of the configuration system.
+//! It resolves every setting from layered sources, so that a project can override the user's global settings and a single run can override both.
+//!
+//! ## Implementation Details
+//! Settings are read from TOML files with the `toml` crate, and written back with `toml_edit` so that comments and formatting survive `nyota config set`.
+//! The layers are, from lowest to highest precedence: built-in defaults, the global config file (`~/.config/nyota/config.toml`),
+//! the project config file (`./nyota.toml`), environment variables, and CLI flags.
+//! The typed `Config` is consumed by the API adapter, the sound system, the splash screen and the chat interface.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use nyota::api::utilities::Adapter;
+//! use nyota::cfg::config::Config;
+//!
+//! let config = Config::load();
+//! let adapter = Adapter::with_config(&config);
+//! println!("{} at volume {}", adapter.get_current_model(), config.sound.volume);
+//! ```
+//!
+//! ## Modules
+//! - `config`: Config module for Nyota. This module provides the typed settings and the layering of the config files.
+//! - `constants`: Constants module for Nyota. This module provides the names of the config files, tables and environment variables.
+
+pub mod config;
+pub mod constants;
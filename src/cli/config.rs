@@ -0,0 +1,155 @@
+//! Configuration management for nyota.
+//! `nyota config` shows and changes the layered configuration (see `nyota::cfg::config`):
+//! `show` prints every setting and the layer it comes from, `get <key>` prints a single setting,
+//! and `set <key> <value>` writes a setting to the global config file, or to the project config file (`./nyota.toml`) with `--project`.
+//! Like task mode, results are written to stdout and diagnostics to stderr.
+
+use crate::cfg::config::*;
+use crate::cfg::constants::*;
+use crate::cli::constants::*;
+use anyhow::{Error, Result};
+use std::process::ExitCode;
+
+/// The `nyota config` subcommands.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigCommand {
+    /// Shows every setting, and the layer it comes from.
+    Show,
+    /// Shows the setting with the key (i.e. `sound.volume`).
+    Get(String),
+    /// Writes the setting to the global config file, or to the project config file if `project` is set.
+    Set {
+        key: String,
+        value: String,
+        project: bool,
+    },
+}
+
+/// Runs a `nyota config` subcommand.
+/// Returns the exit code for the process: `EXIT_SUCCESS`, or `EXIT_USAGE_ERROR` if the setting is unknown or invalid, or the config file cannot be written.
+pub fn run_config(config_command: &ConfigCommand) -> ExitCode {
+    let result = match config_command {
+        ConfigCommand::Show => {
+            show_config();
+            Ok(())
+        }
+        ConfigCommand::Get(key) => get_setting(key),
+        ConfigCommand::Set {
+            key,
+            value,
+            project,
+        } => set_setting(key, value, *project),
+    };
+    match result {
+        Ok(()) => ExitCode::from(EXIT_SUCCESS),
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::from(EXIT_USAGE_ERROR)
+        }
+    }
+}
+
+/// Prints the layers, then every typed setting with the layer it comes from, then the tables read by the API modules as they are in the config files.
+fn show_config() {
+    let config = Config::load();
+    let config_paths: Vec<String> = get_config_file_paths()
+        .iter()
+        .map(|config_path| config_path.display().to_string())
+        .collect();
+    println!(
+        "# Layers: defaults < {} < environment < flags",
+        config_paths.join(" < ")
+    );
+
+    let config_table = config.to_table();
+    for table_name in TYPED_CONFIG_TABLES {
+        println!("\n[{}]", table_name);
+        let Some(toml::Value::Table(table)) = config_table.get(table_name) else {
+            continue;
+        };
+        for (setting_name, value) in table {
+            let key = format!("{}{}{}", table_name, CONFIG_KEY_SEPARATOR, setting_name);
+            println!(
+                "{:<40} # {}",
+                format!("{} = {}", setting_name, value),
+                config.get_source(&key)
+            );
+        }
+    }
+
+    // The API modules layer their own environment variables over these tables when they read them
+    let file_table = read_config_files();
+    for (table_name, table) in &file_table {
+        let toml::Value::Table(table) = table else {
+            continue;
+        };
+        if TYPED_CONFIG_TABLES.contains(&table_name.as_str()) {
+            continue;
+        }
+        println!("\n[{}]", table_name);
+        for (setting_name, value) in table {
+            println!("{} = {}", setting_name, value);
+        }
+    }
+}
+
+/// Prints the value of the setting: typed settings as resolved from every layer, others as they are in the config files.
+fn get_setting(key: &str) -> Result<()> {
+    let (table_name, _) = split_config_key(key)?;
+    let value = if TYPED_CONFIG_TABLES.contains(&table_name) {
+        Config::load().get(key)
+    } else {
+        get_config_value(&read_config_files(), key).cloned()
+    };
+    match value {
+        Some(toml::Value::String(value)) => println!("{}", value),
+        Some(value) => println!("{}", value),
+        None => {
+            return Err(Error::msg(format!(
+                "🟡[CONFIG] 🔍⚙️ Warning: `{}` is not set.",
+                key
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// Writes the setting to the global config file, or to the project config file if `project` is set.
+/// Typed settings are checked before they are written, so that the config file stays loadable.
+fn set_setting(key: &str, value: &str, project: bool) -> Result<()> {
+    let (table_name, _) = split_config_key(key)?;
+    let config_value = if TYPED_CONFIG_TABLES.contains(&table_name) {
+        let mut config = Config::load();
+        config.set(key, value, ConfigSource::Flag)?;
+        config.get(key).unwrap_or_else(|| parse_config_value(value))
+    } else if API_CONFIG_TABLES.contains(&table_name) {
+        parse_config_value(value)
+    } else {
+        return Err(Error::msg(format!(
+            "🟡[CONFIG] 💔⚙️ Warning: `{}` is not a config table (known tables: {}).",
+            table_name,
+            TYPED_CONFIG_TABLES
+                .iter()
+                .chain(API_CONFIG_TABLES.iter())
+                .copied()
+                .collect::<Vec<&str>>()
+                .join(", ")
+        )));
+    };
+
+    let config_path = if project {
+        get_project_config_file_path()
+    } else {
+        get_global_config_file_path().ok_or_else(|| {
+            Error::msg("🔴[CONFIG] Error: Unable to find the config directory, use `--project`.")
+        })?
+    };
+    write_config_value(&config_path, key, &config_value)?;
+    println!(
+        "Set {} = {} in {}",
+        key,
+        config_value,
+        config_path.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! Constants for the command-line interface.
+//! These values are used by the command-line modes, such as the exit codes reported by task mode and the development REPL commands.
+
+/// Exit code reported when a task completes successfully.
+pub const EXIT_SUCCESS: u8 = 0;
+/// Exit code reported when the API provider fails to answer a task.
+pub const EXIT_API_FAILURE: u8 = 1;
+/// Exit code reported when no task prompt was given (neither as an argument nor through stdin).
+pub const EXIT_USAGE_ERROR: u8 = 2;
+
+/// The subcommand which manages the API keys in the key store (i.e. `nyota keys add anthropic`).
+pub const KEYS_COMMAND: &str = "keys";
+/// The `keys` subcommand which stores the API key of a provider.
+pub const KEYS_ADD_COMMAND: &str = "add";
+/// The `keys` subcommand which shows the API key of every provider, redacted.
+pub const KEYS_LIST_COMMAND: &str = "list";
+/// The `keys` subcommand which removes the stored API key of a provider.
+pub const KEYS_REMOVE_COMMAND: &str = "remove";
+/// The `keys` subcommand which checks that the API keys are accepted by their providers.
+pub const KEYS_TEST_COMMAND: &str = "test";
+/// The prompt shown when asking for the passphrase of a new key store.
+pub const KEYS_NEW_PASSPHRASE_PROMPT: &str = "🔐 New key store passphrase: ";
+/// The prompt shown when asking for the passphrase of a new key store again, to confirm it.
+pub const KEYS_CONFIRM_PASSPHRASE_PROMPT: &str = "🔐 Confirm the passphrase: ";
+
+/// The subcommand which shows and changes the settings of the config files (i.e. `nyota config set sound.volume 40`).
+pub const CONFIG_COMMAND: &str = "config";
+/// The `config` subcommand which shows every setting and the layer it comes from.
+pub const CONFIG_SHOW_COMMAND: &str = "show";
+/// The `config` subcommand which shows a single setting.
+pub const CONFIG_GET_COMMAND: &str = "get";
+/// The `config` subcommand which writes a setting to a config file.
+pub const CONFIG_SET_COMMAND: &str = "set";
+/// The separator between the key and value of a setting given to `--set` (i.e. `--set sound.mute=true`).
+pub const CONFIG_OVERRIDE_SEPARATOR: char = '=';
+
+/// The subcommand which lists the recorded chat sessions, or prints their messages and actions (i.e. `nyota history --search "monad"`).
+pub const HISTORY_COMMAND: &str = "history";
+/// The format of the times printed by `nyota history`.
+pub const HISTORY_TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+/// The format of the dates given to `nyota history --since` and `--until`.
+pub const HISTORY_DATE_FORMAT: &str = "%Y-%m-%d";
+/// The speaker printed by `nyota history` for the user's messages.
+pub const HISTORY_USER_SPEAKER: &str = "you";
+
+/// The subcommand which lists and manages the facts in the long-term memory (i.e. `nyota memory forget 12`).
+pub const MEMORY_COMMAND: &str = "memory";
+/// The `memory` subcommand which lists the remembered facts.
+pub const MEMORY_LIST_COMMAND: &str = "list";
+/// The `memory` subcommand which remembers a fact.
+pub const MEMORY_ADD_COMMAND: &str = "add";
+/// The `memory` subcommand which pins a fact, to be given to the model with every prompt.
+pub const MEMORY_PIN_COMMAND: &str = "pin";
+/// The `memory` subcommand which unpins a fact.
+pub const MEMORY_UNPIN_COMMAND: &str = "unpin";
+/// The `memory` subcommand which forgets a fact.
+pub const MEMORY_FORGET_COMMAND: &str = "forget";
+
+/// The separator placed between the task prompt and piped stdin content.
+pub const TASK_INPUT_SEPARATOR: &str = "\n\n";
+/// The task prompt which stands for the content piped through stdin (i.e. `nyota -t - < prompt.txt`).
+pub const TASK_STDIN_PROMPT: &str = "-";
+
+/// The prompt shown by the development REPL.
+pub const DEV_PROMPT: &str = "dev> ";
+/// The development REPL command which quits development mode.
+pub const DEV_EXIT_COMMAND: &str = "/exit";
+/// The development REPL command which clears the conversation.
+pub const DEV_RESET_COMMAND: &str = "/reset";
+/// The development REPL command which lists the models offered by the current provider.
+pub const DEV_MODELS_COMMAND: &str = "/models";
+/// The development REPL command which shows the metadata of the current model.
+pub const DEV_INFO_COMMAND: &str = "/info";
+/// The development REPL command which shows the tokens used and money spent today, per model.
+pub const DEV_USAGE_COMMAND: &str = "/usage";
+/// The development REPL command which lists the personas, or switches to the named persona (`/persona <name>`).
+pub const DEV_PERSONA_COMMAND: &str = "/persona";
+/// The development REPL command which shows the generation parameters, or changes one (`/set <key> <value>`).
+pub const DEV_SET_COMMAND: &str = "/set";
+/// The development REPL command which offers the built-in actions to the model as tools, or stops offering them.
+pub const DEV_TOOLS_COMMAND: &str = "/tools";
+/// The development REPL command which attaches a file to the next message (`/attach <path>`), or lists the files attached.
+pub const DEV_ATTACH_COMMAND: &str = "/attach";
+/// The `/attach` argument which detaches the files attached to the next message.
+pub const DEV_UNSET_ATTACHMENTS_VALUE: &str = "none";
@@ -0,0 +1,381 @@
+//! Development mode for nyota.
+//! Development mode is a line-based REPL like interactive mode, but every turn shows the raw exchange with the API provider:
+//! the exact JSON body produced by `formulate_request`, the HTTP status and headers returned, the raw provider JSON,
+//! the text extracted by the provider's response parser, the tools the model called, the tokens used, and the latency of the call.
+//! The conversation is multi-turn, so provider quirks with longer transcripts can be inspected too.
+
+use crate::act::{get_builtin_actions, ActionSchema};
+use crate::api::attachment::Attachment;
+use crate::api::conversation::ChatMessage;
+use crate::api::inspect::{RawExchange, RawResponse};
+use crate::api::persona::list_personas;
+use crate::api::utilities::{Adapter, ApiProvider};
+use crate::cli::constants::*;
+use anyhow::Result;
+use colored::*;
+use serde_json::Value;
+use std::io::{self, Write};
+
+/// Runs the development REPL until the user types `/exit` or closes stdin.
+pub async fn run_development(api_adapter: &mut Adapter) -> Result<()> {
+    println!(
+        "{} {} ({}) | {} to quit, {} to clear the conversation, {} to list the provider's models, {} to show the model's metadata, {} to show today's usage, {} to switch persona, {} to set generation parameters, {} to toggle the built-in tools, {} to attach a file to the next message",
+        "[DEV]".bright_magenta().bold(),
+        api_adapter.get_current_model().bright_cyan(),
+        api_adapter.get_current_provider(),
+        DEV_EXIT_COMMAND.yellow(),
+        DEV_RESET_COMMAND.yellow(),
+        DEV_MODELS_COMMAND.yellow(),
+        DEV_INFO_COMMAND.yellow(),
+        DEV_USAGE_COMMAND.yellow(),
+        DEV_PERSONA_COMMAND.yellow(),
+        DEV_SET_COMMAND.yellow(),
+        DEV_TOOLS_COMMAND.yellow(),
+        DEV_ATTACH_COMMAND.yellow()
+    );
+
+    let mut conversation: Vec<ChatMessage> = Vec::new();
+    let mut pending_attachments: Vec<Attachment> = Vec::new();
+    loop {
+        print!("{}", DEV_PROMPT.bright_magenta());
+        io::stdout().flush()?;
+
+        let mut input_line = String::new();
+        if io::stdin().read_line(&mut input_line)? == 0 {
+            break; // stdin closed
+        }
+        let input_line = input_line.trim();
+
+        match input_line {
+            "" => continue,
+            DEV_EXIT_COMMAND => break,
+            DEV_RESET_COMMAND => {
+                conversation.clear();
+                println!("{}", "Conversation cleared.".dimmed());
+                continue;
+            }
+            DEV_MODELS_COMMAND => {
+                print_models(api_adapter).await;
+                continue;
+            }
+            DEV_INFO_COMMAND => {
+                print_model_info(api_adapter);
+                continue;
+            }
+            DEV_USAGE_COMMAND => {
+                print_usage(api_adapter);
+                continue;
+            }
+            DEV_TOOLS_COMMAND => {
+                toggle_tools(api_adapter);
+                continue;
+            }
+            _ => {}
+        }
+        if let Some(persona_name) = get_command_argument(input_line, DEV_PERSONA_COMMAND) {
+            switch_persona(api_adapter, persona_name);
+            continue;
+        }
+        if let Some(param_setting) = get_command_argument(input_line, DEV_SET_COMMAND) {
+            set_generation_param(api_adapter, param_setting);
+            continue;
+        }
+
+        if let Some(path) = get_command_argument(input_line, DEV_ATTACH_COMMAND) {
+            attach_file(api_adapter, &mut pending_attachments, path);
+            continue;
+        }
+
+        conversation.push(
+            ChatMessage::user(input_line)
+                .with_attachments(std::mem::take(&mut pending_attachments)),
+        );
+        let exchange = api_adapter.inspect_conversation(&conversation).await;
+        print_exchange(&exchange);
+
+        // Only successful replies join the transcript - failed turns (and replies which only call tools) are dropped so they can be retried
+        match &exchange.outcome {
+            Ok(RawResponse {
+                parsed: Ok(reply), ..
+            }) if !reply.is_empty() => conversation.push(ChatMessage::assistant(reply)),
+            _ => {
+                // The attachments of a failed turn are kept for the retry
+                if let Some(failed_message) = conversation.pop() {
+                    pending_attachments = failed_message.attachments;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prints every stage of a raw exchange with an API provider.
+fn print_exchange(exchange: &RawExchange) {
+    print_section_header("REQUEST");
+    println!(
+        "POST {} | {} | {}",
+        exchange.url, exchange.provider, exchange.model
+    );
+    println!("{}", to_pretty_json(&exchange.request_body));
+
+    match &exchange.outcome {
+        Ok(response) => {
+            print_section_header("RESPONSE");
+            let status_line = format!("{}", response.status);
+            if response.status.is_success() {
+                println!("{}", status_line.green());
+            } else {
+                println!("{}", status_line.red());
+            }
+            for (header_name, header_value) in response.headers.iter() {
+                println!(
+                    "{}: {}",
+                    header_name.as_str().dimmed(),
+                    header_value.to_str().unwrap_or("<binary>")
+                );
+            }
+
+            print_section_header("RAW BODY");
+            match response.get_body_json() {
+                Some(json) => println!("{}", to_pretty_json(&json)),
+                None => println!("{}", response.raw_body),
+            }
+
+            print_section_header("PARSED");
+            match &response.parsed {
+                Ok(reply) => println!("{}", reply.bright_green()),
+                Err(e) => println!("{}", format!("{}", e).red()),
+            }
+
+            if !response.tool_calls.is_empty() {
+                print_section_header("TOOL CALLS");
+                for tool_call in &response.tool_calls {
+                    println!(
+                        "{}({}) {}",
+                        tool_call.name.bright_green(),
+                        tool_call.arguments,
+                        tool_call.id.dimmed()
+                    );
+                }
+            }
+
+            if let Some(usage) = &response.usage {
+                print_section_header("USAGE");
+                println!(
+                    "{} input + {} output = {} tokens",
+                    usage.input_tokens,
+                    usage.output_tokens,
+                    usage.get_total_tokens()
+                );
+            }
+
+            for warning in &response.warnings {
+                eprintln!("{}", warning);
+            }
+        }
+        Err(e) => {
+            print_section_header("ERROR");
+            println!("{}", format!("{}", e).red());
+        }
+    }
+
+    print_section_header("LATENCY");
+    println!("{} ms", exchange.latency.as_millis());
+}
+
+/// Fetches and prints the models offered by the current provider.
+async fn print_models(api_adapter: &mut Adapter) {
+    let provider = api_adapter.get_current_provider();
+    print_section_header("MODELS");
+    match api_adapter.refresh_models(provider).await {
+        Ok(models) => {
+            for model in models.iter() {
+                if model == api_adapter.get_current_model() {
+                    println!("{}", model.bright_green());
+                } else {
+                    println!("{}", model);
+                }
+            }
+            println!(
+                "{}",
+                format!("{} models from {}", models.len(), provider).dimmed()
+            );
+        }
+        Err(e) => println!("{}", format!("{}", e).red()),
+    }
+
+    // OpenRouter's model list also carries the model metadata
+    if provider == ApiProvider::OPENROUTER {
+        match api_adapter.refresh_model_info().await {
+            Ok(model_count) => println!(
+                "{}",
+                format!("Model info refreshed for {} models", model_count).dimmed()
+            ),
+            Err(e) => println!("{}", format!("{}", e).red()),
+        }
+    }
+}
+
+/// Prints the metadata of the current model.
+fn print_model_info(api_adapter: &Adapter) {
+    print_section_header("MODEL INFO");
+    match api_adapter.get_model_info(api_adapter.get_current_model()) {
+        Some(model_info) => match serde_json::to_value(model_info) {
+            Ok(json) => println!("{}", to_pretty_json(&json)),
+            Err(e) => println!("{}", format!("{}", e).red()),
+        },
+        None => println!(
+            "{}",
+            format!(
+                "No metadata for {} (OpenRouter models are added by {})",
+                api_adapter.get_current_model(),
+                DEV_MODELS_COMMAND
+            )
+            .dimmed()
+        ),
+    }
+}
+
+/// Prints the tokens used and money spent today, per model, against the daily budget if one is set.
+fn print_usage(api_adapter: &Adapter) {
+    print_section_header("USAGE TODAY");
+    let usage_ledger = api_adapter.get_usage_ledger();
+    let today = usage_ledger.get_today();
+    for (model, model_usage) in today.get_models() {
+        println!(
+            "{} | {} requests | {} input + {} output tokens | ${:.4}",
+            model.bright_cyan(),
+            model_usage.requests,
+            model_usage.input_tokens,
+            model_usage.output_tokens,
+            model_usage.cost
+        );
+    }
+    let total = today.get_total();
+    let spend = match usage_ledger.get_daily_budget() {
+        Some(daily_budget) => format!("${:.4} of ${:.2} budget", total.cost, daily_budget),
+        None => format!("${:.4}", total.cost),
+    };
+    println!(
+        "{}",
+        format!(
+            "{} requests | {} tokens | {}",
+            total.requests,
+            total.get_total_tokens(),
+            spend
+        )
+        .dimmed()
+    );
+}
+
+/// Offers the built-in actions to the model as tools, or stops offering them if they are offered, then prints the tools offered.
+fn toggle_tools(api_adapter: &mut Adapter) {
+    print_section_header("TOOLS");
+    if api_adapter.get_tools().is_empty() {
+        api_adapter.set_tools(
+            get_builtin_actions()
+                .iter()
+                .map(ActionSchema::to_tool_definition)
+                .collect(),
+        );
+    } else {
+        api_adapter.set_tools(Vec::new());
+    }
+    if api_adapter.get_tools().is_empty() {
+        println!("{}", "No tools offered.".dimmed());
+    }
+    for tool in api_adapter.get_tools() {
+        println!("{} {}", tool.name.bright_green(), tool.description.dimmed());
+    }
+}
+
+/// Attaches a file to the next message (or detaches them all with `none`), then lists the files attached.
+/// Unlike the chat, images are attached even if the current model has no vision, so the rejected request can still be inspected.
+fn attach_file(api_adapter: &Adapter, pending_attachments: &mut Vec<Attachment>, path: &str) {
+    print_section_header("ATTACHMENTS");
+    if path == DEV_UNSET_ATTACHMENTS_VALUE {
+        pending_attachments.clear();
+    } else if !path.is_empty() {
+        match Attachment::load(path) {
+            Ok(attachment) => {
+                if let Err(e) = api_adapter.check_attachments(std::slice::from_ref(&attachment)) {
+                    println!("{}", format!("{}", e).yellow());
+                }
+                pending_attachments.push(attachment);
+            }
+            Err(e) => println!("{}", format!("{}", e).red()),
+        }
+    }
+    if pending_attachments.is_empty() {
+        println!("{}", "No attachments for the next message.".dimmed());
+    }
+    for attachment in pending_attachments.iter() {
+        let kind = if attachment.is_image() {
+            "image"
+        } else {
+            "text"
+        };
+        println!("{} {}", attachment.get_name().bright_green(), kind.dimmed());
+    }
+}
+
+/// Lists the personas (marking the current one), or switches to the named persona.
+fn switch_persona(api_adapter: &mut Adapter, persona_name: &str) {
+    print_section_header("PERSONA");
+    if !persona_name.is_empty() {
+        if let Err(e) = api_adapter.use_persona(persona_name) {
+            println!("{}", format!("{}", e).red());
+            return;
+        }
+    }
+    for name in list_personas() {
+        if name == api_adapter.get_persona().name {
+            println!("{}", name.bright_green());
+        } else {
+            println!("{}", name);
+        }
+    }
+    println!("{}", api_adapter.get_persona().system_prompt.dimmed());
+}
+
+/// Sets a single generation parameter (i.e. `temperature 0.7`), then prints the generation parameters sent with requests.
+/// Unlike the chat, invalid values are kept so the request carrying them can still be inspected.
+fn set_generation_param(api_adapter: &mut Adapter, param_setting: &str) {
+    print_section_header("GENERATION");
+    if let Some((key, value)) = param_setting.split_once(char::is_whitespace) {
+        let mut generation_params = api_adapter.get_generation_params().clone();
+        match generation_params.set(key, value) {
+            Ok(()) => api_adapter.set_generation_params(generation_params),
+            Err(e) => println!("{}", format!("{}", e).red()),
+        }
+    } else if !param_setting.is_empty() {
+        println!(
+            "{}",
+            format!("Usage: {} <key> <value>", DEV_SET_COMMAND).red()
+        );
+    }
+    match serde_json::to_value(api_adapter.get_effective_generation_params()) {
+        Ok(json) => println!("{}", to_pretty_json(&json)),
+        Err(e) => println!("{}", format!("{}", e).red()),
+    }
+}
+
+/// Returns the argument of a command (i.e. `reviewer` in `/persona reviewer`), or `None` if the line is not that command.
+fn get_command_argument<'a>(input_line: &'a str, command: &str) -> Option<&'a str> {
+    let argument = input_line.strip_prefix(command)?;
+    if argument.is_empty() || argument.starts_with(char::is_whitespace) {
+        Some(argument.trim())
+    } else {
+        None
+    }
+}
+
+/// Prints a highlighted section header.
+fn print_section_header(title: &str) {
+    println!("{}", format!("── {} ──", title).bright_cyan().bold());
+}
+
+/// Formats a JSON value for display, falling back to compact JSON if pretty printing fails.
+fn to_pretty_json(json: &Value) -> String {
+    serde_json::to_string_pretty(json).unwrap_or_else(|_| json.to_string())
+}
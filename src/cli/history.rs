@@ -0,0 +1,125 @@
+//! History browsing for nyota.
+//! `nyota history` queries the context database (see `nyota::ctx::db`), which records every chat:
+//! without filters it lists the recorded sessions, and with `--session`, `--since`, `--until` or `--search` it prints the matching messages
+//! (or, with `--actions`, the actions run and their results), oldest first. `--json` prints them as JSON instead, for scripts.
+//! Like task mode, results are written to stdout and diagnostics to stderr.
+
+use crate::cli::constants::*;
+use crate::ctx::db::{ContextDb, HistoryAction, HistoryMessage, HistoryQuery, MessageRole};
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use std::process::ExitCode;
+
+/// The options given to `nyota history`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryOptions {
+    /// The filters and limit of the messages or actions to print.
+    pub query: HistoryQuery,
+    /// Whether to print the actions rather than the messages.
+    pub show_actions: bool,
+    /// Whether to print JSON rather than text.
+    pub as_json: bool,
+}
+
+/// Runs `nyota history`.
+/// Returns the exit code for the process: `EXIT_SUCCESS`, or `EXIT_USAGE_ERROR` if the context database cannot be opened or read.
+pub fn run_history(history_options: &HistoryOptions) -> ExitCode {
+    match show_history(history_options) {
+        Ok(()) => ExitCode::from(EXIT_SUCCESS),
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::from(EXIT_USAGE_ERROR)
+        }
+    }
+}
+
+/// Returns the start of the day in the local time zone, as the time to query the history from (or until, for the day after).
+pub fn get_local_day_start(date: NaiveDate) -> DateTime<Utc> {
+    let day_start = date.and_time(NaiveTime::MIN);
+    Local
+        .from_local_datetime(&day_start)
+        .earliest()
+        .map(|day_start| day_start.with_timezone(&Utc))
+        // Midnight can be skipped by a daylight saving change
+        .unwrap_or_else(|| day_start.and_utc())
+}
+
+/// Prints the recorded sessions, or the messages or actions which match the filters.
+fn show_history(history_options: &HistoryOptions) -> Result<()> {
+    let context_db = ContextDb::open()?;
+    let HistoryOptions {
+        query,
+        show_actions,
+        as_json,
+    } = history_options;
+
+    if *show_actions {
+        let actions = context_db.find_actions(query)?;
+        if *as_json {
+            println!("{}", serde_json::to_string_pretty(&actions)?);
+        } else {
+            actions.iter().for_each(print_action);
+        }
+    } else if query.is_filtered() {
+        let messages = context_db.find_messages(query)?;
+        if *as_json {
+            println!("{}", serde_json::to_string_pretty(&messages)?);
+        } else {
+            messages.iter().for_each(print_message);
+        }
+    } else {
+        let sessions = context_db.list_sessions()?;
+        if *as_json {
+            println!("{}", serde_json::to_string_pretty(&sessions)?);
+            return Ok(());
+        }
+        for session in sessions {
+            println!(
+                "{:<32} {} | {} | {} messages | {} actions",
+                session.name,
+                format_history_time(session.updated_at),
+                session.model,
+                session.message_count,
+                session.action_count
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Prints a message as `<time> [<session>] <speaker>: <content>`, the speaker being the model for a reply.
+fn print_message(message: &HistoryMessage) {
+    let speaker = match message.role {
+        MessageRole::User => HISTORY_USER_SPEAKER,
+        MessageRole::Assistant => message.model.as_str(),
+        MessageRole::Error | MessageRole::Notice => message.role.as_str(),
+    };
+    println!(
+        "{} [{}] {}: {}",
+        format_history_time(message.timestamp),
+        message.session,
+        speaker,
+        message.content
+    );
+}
+
+/// Prints an action as `<time> [<session>] <name> <arguments> -> <result>`, marking the result of a failed action.
+fn print_action(action: &HistoryAction) {
+    println!(
+        "{} [{}] {} {} -> {}{}",
+        format_history_time(action.timestamp),
+        action.session,
+        action.name,
+        action.arguments,
+        if action.succeeded { "" } else { "failed: " },
+        action.result
+    );
+}
+
+/// Formats a recorded time in the local time zone.
+pub fn format_history_time(timestamp: DateTime<Utc>) -> String {
+    timestamp
+        .with_timezone(&Local)
+        .format(HISTORY_TIME_FORMAT)
+        .to_string()
+}
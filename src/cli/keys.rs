@@ -0,0 +1,207 @@
+//! Key management for nyota.
+//! `nyota keys` manages the API keys kept in the encrypted key store (see `nyota::api::keystore`):
+//! `add <provider>` stores a key (piped through stdin, or else typed without echo), `list` shows the key of every provider and where it comes from,
+//! `remove <provider>` deletes a stored key, and `test [provider]` checks that the keys are accepted by fetching each provider's model list.
+//! Keys are only ever printed redacted. Like task mode, results are written to stdout and diagnostics to stderr.
+
+use crate::api::constants::{
+    KEYSTORE_PASSPHRASE_ENV, KEYSTORE_PASSPHRASE_PROMPT, SUPPORTED_PROVIDERS,
+};
+use crate::api::keystore::*;
+use crate::api::utilities::{Adapter, ApiProvider};
+use crate::cli::constants::*;
+use anyhow::{Error, Result};
+use std::env;
+use std::io::{self, IsTerminal, Read};
+use std::process::ExitCode;
+
+/// The `nyota keys` subcommands.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeysCommand {
+    /// Stores the API key of the provider in the key store.
+    Add(ApiProvider),
+    /// Shows the redacted API key of every provider, and where it comes from.
+    List,
+    /// Removes the API key of the provider from the key store.
+    Remove(ApiProvider),
+    /// Checks the API key of the provider, or of every provider with a key.
+    Test(Option<ApiProvider>),
+}
+
+/// Runs a `nyota keys` subcommand.
+/// Returns the exit code for the process: `EXIT_SUCCESS`, `EXIT_USAGE_ERROR` if a key is invalid or the key store cannot be opened or written,
+/// or `EXIT_API_FAILURE` if a provider rejected its key.
+pub async fn run_keys(keys_command: &KeysCommand) -> ExitCode {
+    let result = match keys_command {
+        KeysCommand::Add(provider) => add_key(*provider),
+        KeysCommand::List => list_keys(),
+        KeysCommand::Remove(provider) => remove_key(provider),
+        KeysCommand::Test(provider) => return test_keys(*provider).await,
+    };
+    match result {
+        Ok(()) => ExitCode::from(EXIT_SUCCESS),
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::from(EXIT_USAGE_ERROR)
+        }
+    }
+}
+
+/// Returns the providers which take an API key, in the order they are listed.
+pub fn get_keyed_providers() -> Vec<ApiProvider> {
+    SUPPORTED_PROVIDERS
+        .into_iter()
+        .filter(|provider| get_api_key_env_var(provider).is_some())
+        .collect()
+}
+
+/// Reads the API key of the provider, validates it, and stores it in the key store (creating the key store if there is none).
+fn add_key(provider: ApiProvider) -> Result<()> {
+    let api_key = read_api_key(&provider)?;
+    validate_api_key(&provider, &api_key)?;
+
+    let (mut key_store, passphrase) = if KeyStore::exists() {
+        let passphrase = read_passphrase(KEYSTORE_PASSPHRASE_PROMPT)?;
+        (KeyStore::open(&passphrase)?, passphrase)
+    } else {
+        (KeyStore::new(), read_new_passphrase()?)
+    };
+    key_store.set(provider, &api_key)?;
+    key_store.save(&passphrase)?;
+
+    println!(
+        "Stored the {} API key ({}){}",
+        provider,
+        redact_api_key(&api_key),
+        get_env_override_note(&provider)
+    );
+    Ok(())
+}
+
+/// Prints the redacted API key of every provider which takes one, and whether it comes from the environment or the key store.
+fn list_keys() -> Result<()> {
+    let key_store = if KeyStore::exists() {
+        KeyStore::unlock()?
+    } else {
+        KeyStore::new()
+    };
+
+    for provider in get_keyed_providers() {
+        let env_var = get_api_key_env_var(&provider).unwrap_or_default();
+        let env_key = env::var(env_var).ok().filter(|api_key| !api_key.is_empty());
+        let (api_key, source) = match (env_key, key_store.get(&provider)) {
+            (Some(env_key), Some(_)) => (
+                redact_api_key(&env_key),
+                format!("environment ({}), over the key store", env_var),
+            ),
+            (Some(env_key), None) => (
+                redact_api_key(&env_key),
+                format!("environment ({})", env_var),
+            ),
+            (None, Some(stored_key)) => (redact_api_key(stored_key), String::from("key store")),
+            (None, None) => (String::from("-"), String::new()),
+        };
+        println!("{:<18} {:<16} {}", provider.as_str(), api_key, source);
+    }
+    Ok(())
+}
+
+/// Removes the API key of the provider from the key store.
+fn remove_key(provider: &ApiProvider) -> Result<()> {
+    if !KeyStore::exists() {
+        return Err(Error::msg(format!(
+            "🔴[KEYSTORE] Error: No key store to remove the {} API key from.",
+            provider
+        )));
+    }
+    let passphrase = read_passphrase(KEYSTORE_PASSPHRASE_PROMPT)?;
+    let mut key_store = KeyStore::open(&passphrase)?;
+    if !key_store.remove(provider) {
+        return Err(Error::msg(format!(
+            "🔴[KEYSTORE] Error: No {} API key in the key store.",
+            provider
+        )));
+    }
+    key_store.save(&passphrase)?;
+    println!("Removed the {} API key", provider);
+    Ok(())
+}
+
+/// Checks the API key of the provider, or of every provider with a key, by fetching the provider's model list with it.
+async fn test_keys(provider: Option<ApiProvider>) -> ExitCode {
+    // The Adapter reads the keys from the environment and the key store, as every other mode does
+    let mut api_adapter = Adapter::new();
+    let providers: Vec<ApiProvider> = match provider {
+        Some(provider) => vec![provider],
+        None => get_keyed_providers()
+            .into_iter()
+            .filter(|provider| {
+                api_adapter
+                    .get_api_key(provider)
+                    .is_some_and(|api_key| !api_key.is_empty())
+            })
+            .collect(),
+    };
+    if providers.is_empty() {
+        eprintln!("🟡[KEYS] 🔍🔑 Warning: No API keys to test.");
+        return ExitCode::from(EXIT_USAGE_ERROR);
+    }
+
+    let mut all_accepted = true;
+    for provider in providers {
+        match api_adapter.refresh_models(provider).await {
+            Ok(models) => println!(
+                "🟢[KEYS] ✅🔑 Success: {:?} | API Key accepted ({} models).",
+                provider,
+                models.len()
+            ),
+            Err(e) => {
+                all_accepted = false;
+                println!("🔴[KEYS] Error: {:?} | {}", provider, e);
+            }
+        }
+    }
+    if all_accepted {
+        ExitCode::from(EXIT_SUCCESS)
+    } else {
+        ExitCode::from(EXIT_API_FAILURE)
+    }
+}
+
+/// Reads the API key from piped stdin, or else prompts for it on the terminal without echoing it.
+fn read_api_key(provider: &ApiProvider) -> Result<String> {
+    let stdin = io::stdin();
+    let api_key = if stdin.is_terminal() {
+        rpassword::prompt_password(format!("🔑 {} API key: ", provider))?
+    } else {
+        let mut piped_input = String::new();
+        stdin.lock().read_to_string(&mut piped_input)?;
+        piped_input
+    };
+    Ok(String::from(api_key.trim()))
+}
+
+/// Reads the passphrase of a new key store from the environment, or else prompts for it twice on the terminal.
+fn read_new_passphrase() -> Result<String> {
+    if env::var_os(KEYSTORE_PASSPHRASE_ENV).is_some() {
+        return read_passphrase(KEYS_NEW_PASSPHRASE_PROMPT);
+    }
+    let passphrase = read_passphrase(KEYS_NEW_PASSPHRASE_PROMPT)?;
+    let confirmation = read_passphrase(KEYS_CONFIRM_PASSPHRASE_PROMPT)?;
+    if passphrase != confirmation {
+        return Err(Error::msg(
+            "🔴[KEYSTORE] Error: The passphrases do not match, nothing was stored.",
+        ));
+    }
+    Ok(passphrase)
+}
+
+/// Notes that a key set in the environment takes precedence over the stored key of the provider, if there is one.
+fn get_env_override_note(provider: &ApiProvider) -> String {
+    match get_api_key_env_var(provider) {
+        Some(env_var) if env::var(env_var).is_ok_and(|api_key| !api_key.is_empty()) => {
+            format!(" - {} in the environment takes precedence over it", env_var)
+        }
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,100 @@
+//! Long-term memory management for nyota.
+//! `nyota memory` manages the facts remembered from the chats (see `nyota::ctx::memory`):
+//! `list` prints them, the pinned ones first (or as JSON with `--json`), `add <fact>` remembers a fact,
+//! `pin <id>` has a fact given to the model with every prompt (`unpin <id>` undoes it), and `forget <id>` deletes it.
+//! Like task mode, results are written to stdout and diagnostics to stderr.
+
+use crate::api::utilities::Adapter;
+use crate::cli::constants::*;
+use crate::cli::history::format_history_time;
+use crate::ctx::memory::{LongTermMemory, MemorySettings};
+use anyhow::Result;
+use std::process::ExitCode;
+
+/// The `nyota memory` subcommands.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemoryCommand {
+    /// Prints every memory, the pinned ones first, as JSON if set.
+    List { as_json: bool },
+    /// Remembers the fact.
+    Add(String),
+    /// Pins the memory with the id.
+    Pin(i64),
+    /// Unpins the memory with the id.
+    Unpin(i64),
+    /// Forgets the memory with the id.
+    Forget(i64),
+}
+
+/// Runs a `nyota memory` subcommand.
+/// Returns the exit code for the process: `EXIT_SUCCESS`, `EXIT_USAGE_ERROR` if there is no memory with the id or the context database cannot be opened,
+/// or `EXIT_API_FAILURE` if the fact to add could not be embedded.
+pub async fn run_memory(memory_command: &MemoryCommand) -> ExitCode {
+    let memory = match LongTermMemory::open(MemorySettings::load()) {
+        Ok(memory) => memory,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(EXIT_USAGE_ERROR);
+        }
+    };
+    let result = match memory_command {
+        MemoryCommand::List { as_json } => list_memories(&memory, *as_json),
+        MemoryCommand::Add(fact) => return add_memory(&memory, fact).await,
+        MemoryCommand::Pin(memory_id) => memory
+            .set_pinned(*memory_id, true)
+            .map(|_| println!("Pinned memory {}", memory_id)),
+        MemoryCommand::Unpin(memory_id) => memory
+            .set_pinned(*memory_id, false)
+            .map(|_| println!("Unpinned memory {}", memory_id)),
+        MemoryCommand::Forget(memory_id) => memory
+            .forget(*memory_id)
+            .map(|_| println!("Forgot memory {}", memory_id)),
+    };
+    match result {
+        Ok(()) => ExitCode::from(EXIT_SUCCESS),
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::from(EXIT_USAGE_ERROR)
+        }
+    }
+}
+
+/// Prints every memory as `<id> [pinned] <time> <fact>`, or as JSON.
+fn list_memories(memory: &LongTermMemory, as_json: bool) -> Result<()> {
+    let memories = memory.list()?;
+    if as_json {
+        println!("{}", serde_json::to_string_pretty(&memories)?);
+        return Ok(());
+    }
+    for memory in memories {
+        println!(
+            "{:>5} {} {} {}",
+            memory.id,
+            if memory.pinned { "📌" } else { "  " },
+            format_history_time(memory.created_at),
+            memory.content
+        );
+    }
+    Ok(())
+}
+
+/// Embeds the fact with the embedding model of the memory settings and remembers it, printing its id.
+async fn add_memory(memory: &LongTermMemory, fact: &str) -> ExitCode {
+    let api_adapter = Adapter::new();
+    match memory
+        .remember(&api_adapter, None, &[String::from(fact)])
+        .await
+    {
+        Ok(memories) => {
+            match memories.first() {
+                Some(new_memory) => println!("Remembered as memory {}", new_memory.id),
+                None => println!("Already remembered"),
+            }
+            ExitCode::from(EXIT_SUCCESS)
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::from(EXIT_API_FAILURE)
+        }
+    }
+}
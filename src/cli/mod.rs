@@ -2,4 +2,11 @@
 //! This module is the command-line interface module for `nyota`. This module contains the complete implementation of the command-line interface.
 //! It provides a minimal interface with a few modes/settings for user flags and options.
 
+pub mod config;
+pub mod constants;
+pub mod development;
+pub mod history;
+pub mod keys;
+pub mod memory;
 pub mod modes;
+pub mod task;
@@ -1,4 +1,21 @@
-use clap::{Arg, ArgAction, ArgGroup, Command};
+use crate::api::constants::SUPPORTED_PROVIDERS;
+use crate::api::generation::GenerationParams;
+use crate::api::utilities::ApiProvider;
+use crate::cli::config::ConfigCommand;
+use crate::cli::constants::*;
+use crate::cli::history::{get_local_day_start, HistoryOptions};
+use crate::cli::keys::{get_keyed_providers, KeysCommand};
+use crate::cli::memory::MemoryCommand;
+use crate::cli::task::TaskOptions;
+use crate::ctx::constants::DEFAULT_HISTORY_LIMIT;
+use crate::ctx::db::HistoryQuery;
+use crate::snd::constants::MAX_VOLUME;
+use chrono::{Days, NaiveDate};
+use clap::{
+    builder::{PossibleValuesParser, TypedValueParser},
+    value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command,
+};
+use std::{path::PathBuf, time::Duration};
 
 /// The different modes that nyota can be started in.
 /// This is used to determine the behavior of the program.
@@ -14,17 +31,94 @@ pub enum Mode {
     Task,
     /// Menu mode gives users access to nyota from the main menu. It is the default mode.
     Menu, // default mode
+    /// Keys mode manages the API keys in the encrypted key store (`nyota keys ...`) and quits.
+    Keys(KeysCommand),
+    /// Config mode shows or changes the settings of the config files (`nyota config ...`) and quits.
+    Config(ConfigCommand),
+    /// History mode lists the recorded chat sessions, or prints their messages and actions (`nyota history ...`), and quits.
+    History(HistoryOptions),
+    /// Memory mode lists the facts in the long-term memory, or adds, pins or forgets one (`nyota memory ...`), and quits.
+    Memory(MemoryCommand),
 }
 
 /// The settings for the current mode of nyota.
 pub struct ModeSettings {
     pub mode: Mode,
+    /// The prompt given to `--task`, if any. Piped stdin is read later by task mode itself.
+    pub task_prompt: Option<String>,
+    /// The attachments, JSON Schema and models to compare given for the task.
+    pub task_options: TaskOptions,
+    /// The retry count given to `--schema-retries`, which takes precedence over the config file.
+    pub schema_retries: Option<u32>,
+    /// The generation parameters given as flags, which take precedence over the config file and persona.
+    pub generation_params: GenerationParams,
+    /// The settings given to `--set` as key and value pairs, which take precedence over the config files and environment variables.
+    pub config_overrides: Vec<(String, String)>,
+    /// The config file given to `--config`, read in place of the global config file.
+    pub config_path: Option<PathBuf>,
+    /// The model given to `--model`.
+    pub model: Option<String>,
+    /// The provider given to `--provider`, which the equivalent of the model is used on.
+    pub provider: Option<ApiProvider>,
+    /// The system prompt given to `--system`, which replaces the persona's.
+    pub system_prompt: Option<String>,
+    /// Whether `--mute` was given.
+    pub mute: bool,
+    /// The volume given to `--volume`.
+    pub volume: Option<u8>,
+    /// Whether the splash screen is shown (off with `--no-splash`).
+    pub show_splash: bool,
+    /// Whether the banner and version plaque are printed (off with `--no-banner`).
+    pub show_banner: bool,
+    /// Whether the output is colored (off with `--no-color`).
+    pub use_colors: bool,
+    /// The name given to `--session`: the chat session to reopen, or to start under that name.
+    pub session_name: Option<String>,
+    /// Whether `--resume` was given, to reopen the most recent chat session.
+    pub resume: bool,
+    /// Whether `--no-cache` was given, to send every request rather than answer any from the response cache.
+    pub no_cache: bool,
+    /// The time given to `--cache-ttl` to keep replies for, which turns the response cache on.
+    pub cache_ttl: Option<Duration>,
+}
+
+impl ModeSettings {
+    /// Returns the typed settings given as their own flags (i.e. `--volume 40` for `sound.volume`), as key and value pairs,
+    /// to be layered over the config files and environment variables (see `nyota::cfg::config::Config::set`).
+    pub fn get_config_flags(&self) -> Vec<(&'static str, String)> {
+        let mut config_flags = Vec::new();
+        if let Some(model) = &self.model {
+            config_flags.push(("model.default", model.clone()));
+        }
+        if let Some(provider) = &self.provider {
+            config_flags.push(("model.provider", String::from(provider.as_str())));
+        }
+        if self.mute {
+            config_flags.push(("sound.mute", String::from("true")));
+        }
+        if let Some(volume) = self.volume {
+            config_flags.push(("sound.volume", volume.to_string()));
+        }
+        if !self.show_splash {
+            config_flags.push(("splash.enabled", String::from("false")));
+        }
+        if !self.use_colors {
+            config_flags.push(("splash.use_colors", String::from("false")));
+            config_flags.push(("interface.use_colors", String::from("false")));
+        }
+        config_flags
+    }
 }
 
 /// Parses the user input flags to determine the mode to start nyota in. If no flags are provided, the default mode is `Menu`.
 pub fn get_mode_input() -> ModeSettings {
     // Parse User Input Flags
-    let flag_input = Command::new("nyota")
+    let mut flag_input = Command::new("nyota")
+        .subcommand(get_keys_subcommand())
+        .subcommand(get_config_subcommand())
+        .subcommand(get_history_subcommand())
+        .subcommand(get_memory_subcommand())
+        .args_conflicts_with_subcommands(true)
         .arg(
             Arg::new("interactive")
                 .short('i')
@@ -43,8 +137,203 @@ pub fn get_mode_input() -> ModeSettings {
             Arg::new("task")
                 .short('t')
                 .long("task")
+                .value_name("PROMPT")
+                .num_args(0..=1)
+                .default_missing_value("")
+                .help("Execute a single task and print the reply (prompt, or piped stdin with no prompt or `-`)"),
+        )
+        .arg(
+            Arg::new("stdin")
+                .long("stdin")
                 .action(ArgAction::SetTrue)
-                .help("Execute a single task"),
+                .requires("task")
+                .help("Append the content piped through stdin to the task prompt"),
+        )
+        .arg(
+            Arg::new("attach")
+                .short('a')
+                .long("attach")
+                .value_name("FILE")
+                .value_parser(value_parser!(PathBuf))
+                .action(ArgAction::Append)
+                .requires("task")
+                .help("Attach an image or text file to the task (repeatable)"),
+        )
+        .arg(
+            Arg::new("json_schema")
+                .long("json-schema")
+                .value_name("FILE")
+                .value_parser(value_parser!(PathBuf))
+                .requires("task")
+                .help("Reply with JSON conforming to the JSON Schema in FILE"),
+        )
+        .arg(
+            Arg::new("schema_retries")
+                .long("schema-retries")
+                .value_name("RETRIES")
+                .value_parser(value_parser!(u32))
+                .requires("json_schema")
+                .help("Times to ask again for a reply which does not conform to the schema"),
+        )
+        .arg(
+            Arg::new("compare")
+                .long("compare")
+                .value_name("MODELS")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .requires("task")
+                .conflicts_with("json_schema")
+                .help("Send the task to several models at once and print their replies as JSON (comma-separated)"),
+        )
+        .arg(
+            Arg::new("temperature")
+                .long("temperature")
+                .value_name("TEMPERATURE")
+                .value_parser(value_parser!(f64))
+                .help("Sampling temperature"),
+        )
+        .arg(
+            Arg::new("top_p")
+                .long("top-p")
+                .value_name("TOP_P")
+                .value_parser(value_parser!(f64))
+                .help("Nucleus sampling probability mass"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_name("SEED")
+                .value_parser(value_parser!(u64))
+                .help("Seed for deterministic sampling"),
+        )
+        .arg(
+            Arg::new("max_tokens")
+                .long("max-tokens")
+                .value_name("TOKENS")
+                .value_parser(value_parser!(u64))
+                .help("Maximum number of tokens in a reply"),
+        )
+        .arg(
+            Arg::new("stop")
+                .long("stop")
+                .value_name("SEQUENCE")
+                .action(ArgAction::Append)
+                .help("Sequence which ends the reply (repeatable)"),
+        )
+        .arg(
+            Arg::new("presence_penalty")
+                .long("presence-penalty")
+                .value_name("PENALTY")
+                .value_parser(value_parser!(f64))
+                .help("Penalty for tokens already in the reply"),
+        )
+        .arg(
+            Arg::new("frequency_penalty")
+                .long("frequency-penalty")
+                .value_name("PENALTY")
+                .value_parser(value_parser!(f64))
+                .help("Penalty for tokens by how often they are in the reply"),
+        )
+        .arg(
+            Arg::new("model")
+                .short('m')
+                .long("model")
+                .value_name("MODEL")
+                .help("The model to use (i.e. `claude-3-5-haiku-20241022`, `ollama/llama3.2`)"),
+        )
+        .arg(
+            Arg::new("provider")
+                .short('p')
+                .long("provider")
+                .value_name("PROVIDER")
+                .value_parser(
+                    PossibleValuesParser::new(SUPPORTED_PROVIDERS.map(|provider| provider.as_str()))
+                        .try_map(|name| name.parse::<ApiProvider>()),
+                )
+                .help("The provider to use, with the model equivalent to the default one"),
+        )
+        .arg(
+            Arg::new("system")
+                .short('s')
+                .long("system")
+                .value_name("PROMPT")
+                .help("The system prompt, in place of the persona's"),
+        )
+        .arg(
+            Arg::new("mute")
+                .long("mute")
+                .action(ArgAction::SetTrue)
+                .help("Mute the sound effects"),
+        )
+        .arg(
+            Arg::new("volume")
+                .long("volume")
+                .value_name("VOLUME")
+                .value_parser(value_parser!(u8).range(0..=i64::from(MAX_VOLUME)))
+                .help("The volume of the sound effects, from 0 to 100"),
+        )
+        .arg(
+            Arg::new("no_splash")
+                .long("no-splash")
+                .action(ArgAction::SetTrue)
+                .help("Skip the splash screen"),
+        )
+        .arg(
+            Arg::new("no_banner")
+                .long("no-banner")
+                .action(ArgAction::SetTrue)
+                .help("Skip the banner and version plaque"),
+        )
+        .arg(
+            Arg::new("no_color")
+                .long("no-color")
+                .action(ArgAction::SetTrue)
+                .help("Draw everything without colors"),
+        )
+        .arg(
+            Arg::new("config")
+                .short('c')
+                .long("config")
+                .value_name("FILE")
+                .value_parser(value_parser!(PathBuf))
+                .global(true)
+                .help("Read settings from FILE in place of ~/.config/nyota/config.toml"),
+        )
+        .arg(
+            Arg::new("session")
+                .long("session")
+                .value_name("NAME")
+                .conflicts_with_all(["task", "development"])
+                .help("Reopen the chat session NAME, or start a new one named NAME"),
+        )
+        .arg(
+            Arg::new("resume")
+                .long("resume")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["session", "task", "development"])
+                .help("Reopen the most recent chat session"),
+        )
+        .arg(
+            Arg::new("no_cache")
+                .long("no-cache")
+                .action(ArgAction::SetTrue)
+                .help("Send every request, without answering any from the response cache"),
+        )
+        .arg(
+            Arg::new("cache_ttl")
+                .long("cache-ttl")
+                .value_name("SECONDS")
+                .value_parser(value_parser!(u64))
+                .conflicts_with("no_cache")
+                .help("Cache the replies and keep them for SECONDS"),
+        )
+        .arg(
+            Arg::new("set")
+                .long("set")
+                .value_name("KEY=VALUE")
+                .value_parser(parse_config_override)
+                .action(ArgAction::Append)
+                .help("Override a setting of the config files for this run, i.e. `sound.mute=true` (repeatable)"),
         )
         .group(
             ArgGroup::new("modes")
@@ -58,16 +347,354 @@ pub fn get_mode_input() -> ModeSettings {
     // println!("{:?}",flag_input);
 
     // Set and Return Mode Settings
+    let (keys_command, config_command, history_options, memory_command) =
+        match flag_input.remove_subcommand() {
+            Some((subcommand, keys_input)) if subcommand == KEYS_COMMAND => {
+                (get_keys_command(keys_input), None, None, None)
+            }
+            Some((subcommand, config_input)) if subcommand == CONFIG_COMMAND => {
+                (None, get_config_command(config_input), None, None)
+            }
+            Some((subcommand, history_input)) if subcommand == HISTORY_COMMAND => {
+                (None, None, Some(get_history_options(history_input)), None)
+            }
+            Some((subcommand, memory_input)) if subcommand == MEMORY_COMMAND => {
+                (None, None, None, get_memory_command(memory_input))
+            }
+            _ => (None, None, None, None),
+        };
+    let task_prompt = flag_input.remove_one::<String>("task");
+    let task_options = TaskOptions {
+        attachment_paths: flag_input
+            .remove_many("attach")
+            .map(Iterator::collect)
+            .unwrap_or_default(),
+        json_schema_path: flag_input.remove_one("json_schema"),
+        compare_models: flag_input
+            .remove_many("compare")
+            .map(Iterator::collect)
+            .unwrap_or_default(),
+        read_stdin: flag_input.get_flag("stdin"),
+    };
+    let schema_retries = flag_input.remove_one("schema_retries");
+    let generation_params = get_generation_params(&mut flag_input);
+    let config_overrides = flag_input
+        .remove_many("set")
+        .map(Iterator::collect)
+        .unwrap_or_default();
     ModeSettings {
-        mode: if flag_input.get_flag("interactive") {
+        mode: if let Some(keys_command) = keys_command {
+            Mode::Keys(keys_command)
+        } else if let Some(config_command) = config_command {
+            Mode::Config(config_command)
+        } else if let Some(history_options) = history_options {
+            Mode::History(history_options)
+        } else if let Some(memory_command) = memory_command {
+            Mode::Memory(memory_command)
+        } else if flag_input.get_flag("interactive")
+            || flag_input.contains_id("session")
+            || flag_input.get_flag("resume")
+        {
+            // A chat session can only be reopened in interactive mode
             Mode::Interactive
         } else if flag_input.get_flag("development") {
             Mode::Development
-        } else if flag_input.get_flag("task") {
+        } else if task_prompt.is_some() {
             Mode::Task
         } else {
             //if no Flag detected, default to Menu
             Mode::Menu
         },
+        task_prompt,
+        task_options,
+        schema_retries,
+        generation_params,
+        config_overrides,
+        config_path: flag_input.remove_one("config"),
+        model: flag_input.remove_one("model"),
+        provider: flag_input.remove_one("provider"),
+        system_prompt: flag_input.remove_one("system"),
+        mute: flag_input.get_flag("mute"),
+        volume: flag_input.remove_one("volume"),
+        show_splash: !flag_input.get_flag("no_splash"),
+        show_banner: !flag_input.get_flag("no_banner"),
+        use_colors: !flag_input.get_flag("no_color"),
+        session_name: flag_input.remove_one("session"),
+        resume: flag_input.get_flag("resume"),
+        no_cache: flag_input.get_flag("no_cache"),
+        cache_ttl: flag_input.remove_one("cache_ttl").map(Duration::from_secs),
+    }
+}
+
+/// Builds the `keys` subcommand, which manages the API keys in the key store.
+fn get_keys_subcommand() -> Command {
+    let provider_names: Vec<&'static str> = get_keyed_providers()
+        .iter()
+        .map(ApiProvider::as_str)
+        .collect();
+    let provider_arg = Arg::new("provider").value_name("PROVIDER").value_parser(
+        PossibleValuesParser::new(provider_names).try_map(|name| name.parse::<ApiProvider>()),
+    );
+    Command::new(KEYS_COMMAND)
+        .about("Manage the API keys in the encrypted key store")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new(KEYS_ADD_COMMAND)
+                .about("Store the API key of a provider (piped through stdin, or typed)")
+                .arg(provider_arg.clone().required(true)),
+        )
+        .subcommand(
+            Command::new(KEYS_LIST_COMMAND)
+                .about("Show the API key of every provider, redacted, and where it comes from"),
+        )
+        .subcommand(
+            Command::new(KEYS_REMOVE_COMMAND)
+                .about("Remove the stored API key of a provider")
+                .arg(provider_arg.clone().required(true)),
+        )
+        .subcommand(
+            Command::new(KEYS_TEST_COMMAND)
+                .about("Check that the API keys are accepted by their providers")
+                .arg(provider_arg),
+        )
+}
+
+/// Reads the `keys` subcommand given, if clap matched one.
+fn get_keys_command(mut keys_input: ArgMatches) -> Option<KeysCommand> {
+    let (subcommand, mut subcommand_input) = keys_input.remove_subcommand()?;
+    // Only some subcommands take a provider
+    let provider = subcommand_input
+        .try_remove_one::<ApiProvider>("provider")
+        .ok()
+        .flatten();
+    match (subcommand.as_str(), provider) {
+        (KEYS_ADD_COMMAND, Some(provider)) => Some(KeysCommand::Add(provider)),
+        (KEYS_LIST_COMMAND, _) => Some(KeysCommand::List),
+        (KEYS_REMOVE_COMMAND, Some(provider)) => Some(KeysCommand::Remove(provider)),
+        (KEYS_TEST_COMMAND, provider) => Some(KeysCommand::Test(provider)),
+        _ => None,
+    }
+}
+
+/// Builds the `config` subcommand, which shows and changes the settings of the config files.
+fn get_config_subcommand() -> Command {
+    let key_arg = Arg::new("key")
+        .value_name("KEY")
+        .required(true)
+        .help("The setting, as `table.key` (i.e. `sound.volume`)");
+    Command::new(CONFIG_COMMAND)
+        .about("Show or change the settings of the config files")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new(CONFIG_SHOW_COMMAND)
+                .about("Show every setting, and the layer it comes from"),
+        )
+        .subcommand(
+            Command::new(CONFIG_GET_COMMAND)
+                .about("Show a single setting")
+                .arg(key_arg.clone()),
+        )
+        .subcommand(
+            Command::new(CONFIG_SET_COMMAND)
+                .about("Write a setting to the global config file")
+                .arg(key_arg)
+                .arg(Arg::new("value").value_name("VALUE").required(true))
+                .arg(
+                    Arg::new("project")
+                        .long("project")
+                        .action(ArgAction::SetTrue)
+                        .help("Write to the project config file (./nyota.toml) instead"),
+                ),
+        )
+}
+
+/// Reads the `config` subcommand given, if clap matched one.
+fn get_config_command(mut config_input: ArgMatches) -> Option<ConfigCommand> {
+    let (subcommand, mut subcommand_input) = config_input.remove_subcommand()?;
+    // Only some subcommands take a key
+    let key = subcommand_input
+        .try_remove_one::<String>("key")
+        .ok()
+        .flatten();
+    match (subcommand.as_str(), key) {
+        (CONFIG_SHOW_COMMAND, _) => Some(ConfigCommand::Show),
+        (CONFIG_GET_COMMAND, Some(key)) => Some(ConfigCommand::Get(key)),
+        (CONFIG_SET_COMMAND, Some(key)) => Some(ConfigCommand::Set {
+            key,
+            value: subcommand_input.remove_one("value")?,
+            project: subcommand_input.get_flag("project"),
+        }),
+        _ => None,
+    }
+}
+
+/// Builds the `memory` subcommand, which lists and manages the facts in the long-term memory.
+fn get_memory_subcommand() -> Command {
+    let id_arg = Arg::new("id")
+        .value_name("ID")
+        .value_parser(value_parser!(i64))
+        .required(true)
+        .help("The id of the memory, as listed by `nyota memory list`");
+    Command::new(MEMORY_COMMAND)
+        .about("List the facts remembered from the chats, or add, pin or forget one")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new(MEMORY_LIST_COMMAND)
+                .about("List the remembered facts, the pinned ones first")
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .action(ArgAction::SetTrue)
+                        .help("Print JSON rather than text"),
+                ),
+        )
+        .subcommand(
+            Command::new(MEMORY_ADD_COMMAND)
+                .about("Remember a fact")
+                .arg(Arg::new("fact").value_name("FACT").required(true)),
+        )
+        .subcommand(
+            Command::new(MEMORY_PIN_COMMAND)
+                .about("Give a fact to the model with every prompt")
+                .arg(id_arg.clone()),
+        )
+        .subcommand(
+            Command::new(MEMORY_UNPIN_COMMAND)
+                .about("Only give a fact to the model with the prompts it is relevant to")
+                .arg(id_arg.clone()),
+        )
+        .subcommand(
+            Command::new(MEMORY_FORGET_COMMAND)
+                .about("Forget a fact")
+                .arg(id_arg),
+        )
+}
+
+/// Reads the `memory` subcommand given, if clap matched one.
+fn get_memory_command(mut memory_input: ArgMatches) -> Option<MemoryCommand> {
+    let (subcommand, mut subcommand_input) = memory_input.remove_subcommand()?;
+    // Only some subcommands take an id
+    let memory_id = subcommand_input.try_remove_one::<i64>("id").ok().flatten();
+    match (subcommand.as_str(), memory_id) {
+        (MEMORY_LIST_COMMAND, _) => Some(MemoryCommand::List {
+            as_json: subcommand_input.get_flag("json"),
+        }),
+        (MEMORY_ADD_COMMAND, _) => Some(MemoryCommand::Add(subcommand_input.remove_one("fact")?)),
+        (MEMORY_PIN_COMMAND, Some(memory_id)) => Some(MemoryCommand::Pin(memory_id)),
+        (MEMORY_UNPIN_COMMAND, Some(memory_id)) => Some(MemoryCommand::Unpin(memory_id)),
+        (MEMORY_FORGET_COMMAND, Some(memory_id)) => Some(MemoryCommand::Forget(memory_id)),
+        _ => None,
+    }
+}
+
+/// Builds the `history` subcommand, which queries the context database.
+fn get_history_subcommand() -> Command {
+    Command::new(HISTORY_COMMAND)
+        .about("List the recorded chat sessions, or print their messages and actions")
+        .arg(
+            Arg::new("session")
+                .long("session")
+                .value_name("NAME")
+                .help("Only the messages of the session NAME"),
+        )
+        .arg(
+            Arg::new("since")
+                .long("since")
+                .value_name("DATE")
+                .value_parser(parse_history_date)
+                .help("Only the messages from DATE on (YYYY-MM-DD)"),
+        )
+        .arg(
+            Arg::new("until")
+                .long("until")
+                .value_name("DATE")
+                .value_parser(parse_history_date)
+                .help("Only the messages up to and including DATE (YYYY-MM-DD)"),
+        )
+        .arg(
+            Arg::new("search")
+                .long("search")
+                .value_name("TEXT")
+                .help("Only the messages containing every word of TEXT"),
+        )
+        .arg(
+            Arg::new("limit")
+                .long("limit")
+                .value_name("COUNT")
+                .value_parser(value_parser!(usize))
+                .help(format!(
+                    "The most messages to print, the latest ones ({} by default)",
+                    DEFAULT_HISTORY_LIMIT
+                )),
+        )
+        .arg(
+            Arg::new("actions")
+                .long("actions")
+                .action(ArgAction::SetTrue)
+                .help("Print the actions run (i.e. chat commands) and their results, rather than the messages"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .action(ArgAction::SetTrue)
+                .help("Print JSON rather than text"),
+        )
+}
+
+/// Reads the options given to the `history` subcommand. Dates are whole days in the local time zone.
+fn get_history_options(mut history_input: ArgMatches) -> HistoryOptions {
+    let until = history_input
+        .remove_one::<NaiveDate>("until")
+        .map(|date| get_local_day_start(date.checked_add_days(Days::new(1)).unwrap_or(date)));
+    HistoryOptions {
+        query: HistoryQuery {
+            session: history_input.remove_one("session"),
+            since: history_input
+                .remove_one::<NaiveDate>("since")
+                .map(get_local_day_start),
+            until,
+            search: history_input.remove_one("search"),
+            limit: history_input
+                .remove_one("limit")
+                .unwrap_or(DEFAULT_HISTORY_LIMIT),
+        },
+        show_actions: history_input.get_flag("actions"),
+        as_json: history_input.get_flag("json"),
+    }
+}
+
+/// Parses a date given to `nyota history --since` or `--until` (i.e. `2025-01-31`).
+fn parse_history_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, HISTORY_DATE_FORMAT)
+        .map_err(|_| format!("`{}` is not a date (i.e. 2025-01-31)", date))
+}
+
+/// Parses a setting given to `--set` into its key and value (i.e. `sound.mute=true`).
+fn parse_config_override(config_override: &str) -> Result<(String, String), String> {
+    match config_override.split_once(CONFIG_OVERRIDE_SEPARATOR) {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((String::from(key.trim()), String::from(value.trim())))
+        }
+        _ => Err(format!(
+            "`{}` is not a setting (i.e. `sound.mute=true`)",
+            config_override
+        )),
+    }
+}
+
+/// Reads the generation parameters given as flags.
+fn get_generation_params(flag_input: &mut ArgMatches) -> GenerationParams {
+    GenerationParams {
+        temperature: flag_input.remove_one("temperature"),
+        top_p: flag_input.remove_one("top_p"),
+        seed: flag_input.remove_one("seed"),
+        max_tokens: flag_input.remove_one("max_tokens"),
+        stop: flag_input
+            .remove_many("stop")
+            .map(Iterator::collect)
+            .unwrap_or_default(),
+        presence_penalty: flag_input.remove_one("presence_penalty"),
+        frequency_penalty: flag_input.remove_one("frequency_penalty"),
+        ..GenerationParams::new()
     }
 }
@@ -0,0 +1,234 @@
+//! Task mode for nyota.
+//! Task mode sends a single prompt through the API adapter, prints the reply to stdout and exits.
+//! The prompt is given as the argument of `--task`, piped through stdin (`nyota -t` or `nyota -t -`), or both with `--stdin`
+//! (i.e. `cat log | nyota -t "explain" --stdin`), in which case the piped content is appended to the prompt. Stdin is not read
+//! when a prompt is given without `--stdin`, as scripts, CI jobs and `ssh` often leave it open with nothing written to it. Images and text files given to `--attach` are sent along with it
+//! (see `nyota::api::attachment`). With `--json-schema`, the reply is JSON validated against the schema (see `nyota::api::structured`).
+//! With `--compare`, the task is sent to several models at once and their replies are printed as a JSON array (see `nyota::api::compare`).
+//! If replies are cached, a task sent before is answered from the response cache (see `nyota::api::response_cache`), which is noted on stderr.
+//! If the long-term memory is on, the facts it recalls for the task are given to the model ahead of it (see `nyota::ctx::memory`).
+//! Diagnostics are written to stderr so that stdout only holds the reply.
+
+use crate::api::attachment::Attachment;
+use crate::api::compare::{compare_models, ComparisonEntry};
+use crate::api::conversation::{ChatMessage, LlmResponse};
+use crate::api::structured::ResponseSchema;
+use crate::api::utilities::Adapter;
+use crate::cli::constants::*;
+use crate::ctx::memory::{add_memories_to_conversation, LongTermMemory, MemorySettings};
+use anyhow::{Error, Result};
+use serde_json::Value;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// The inputs of a task besides its prompt.
+#[derive(Debug, Clone, Default)]
+pub struct TaskOptions {
+    /// The files given to `--attach`, sent along with the task prompt.
+    pub attachment_paths: Vec<PathBuf>,
+    /// The JSON Schema file given to `--json-schema`, which the reply must conform to.
+    pub json_schema_path: Option<PathBuf>,
+    /// The models given to `--compare`, which the task is sent to at once.
+    pub compare_models: Vec<String>,
+    /// Whether `--stdin` was given, to append the content piped through stdin to the prompt.
+    pub read_stdin: bool,
+}
+
+impl TaskOptions {
+    /// Creates a new TaskOptions with no attachments, schema or models to compare.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Runs a single task through the API adapter and prints the reply to stdout.
+/// If a JSON Schema file is given, the reply is printed as JSON which conforms to it;
+/// if models to compare are given, the replies of every model are printed as a JSON array.
+/// Returns the exit code for the process: `EXIT_SUCCESS`, `EXIT_USAGE_ERROR` if no prompt was given or an attachment or schema cannot be read,
+/// or `EXIT_API_FAILURE` if the API provider failed to answer (or the model cannot take the attachments, or no reply conformed to the schema,
+/// or none of the compared models answered).
+pub async fn run_task(
+    api_adapter: &Adapter,
+    task_prompt: Option<&str>,
+    task_options: &TaskOptions,
+) -> ExitCode {
+    let response_schema = match task_options
+        .json_schema_path
+        .as_ref()
+        .map(ResponseSchema::load)
+        .transpose()
+    {
+        Ok(response_schema) => response_schema,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(EXIT_USAGE_ERROR);
+        }
+    };
+    let task_message =
+        match read_task_input(task_prompt, task_options.read_stdin).and_then(|task_input| {
+            let attachments = task_options
+                .attachment_paths
+                .iter()
+                .map(Attachment::load)
+                .collect::<Result<Vec<Attachment>, Error>>()?;
+            Ok(ChatMessage::user(&task_input).with_attachments(attachments))
+        }) {
+            Ok(task_message) => task_message,
+            Err(e) => {
+                eprintln!("{}", e);
+                return ExitCode::from(EXIT_USAGE_ERROR);
+            }
+        };
+
+    let task_messages = with_recalled_memories(api_adapter, task_message).await;
+
+    if !task_options.compare_models.is_empty() {
+        let entries =
+            compare_models(api_adapter, &task_options.compare_models, &task_messages).await;
+        let json_entries = Value::Array(entries.iter().map(ComparisonEntry::to_json).collect());
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&json_entries)
+                .unwrap_or_else(|_| json_entries.to_string())
+        );
+        for reply in entries
+            .iter()
+            .filter_map(|entry| entry.outcome.as_ref().ok())
+        {
+            note_reply_warnings(reply);
+        }
+        return if entries.iter().any(|entry| entry.outcome.is_ok()) {
+            ExitCode::from(EXIT_SUCCESS)
+        } else {
+            ExitCode::from(EXIT_API_FAILURE)
+        };
+    }
+
+    let reply = match &response_schema {
+        Some(response_schema) => api_adapter
+            .send_structured(&task_messages, response_schema)
+            .await
+            .map(|structured_response| {
+                note_reply_warnings(&structured_response.response);
+                note_cached_reply(api_adapter, structured_response.response.cached);
+                serde_json::to_string_pretty(&structured_response.value)
+                    .unwrap_or_else(|_| structured_response.value.to_string())
+            }),
+        None => api_adapter
+            .send_conversation(&task_messages)
+            .await
+            .map(|response| {
+                note_reply_warnings(&response);
+                note_cached_reply(api_adapter, response.cached);
+                response.content
+            }),
+    };
+    match reply {
+        Ok(reply) => {
+            println!("{}", reply);
+            ExitCode::from(EXIT_SUCCESS)
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::from(EXIT_API_FAILURE)
+        }
+    }
+}
+
+/// Returns the messages of the task: the task message, after the facts the long-term memory recalls for it if the memory is on.
+/// A memory which cannot be recalled is reported as a warning, and the task is sent without it.
+async fn with_recalled_memories(
+    api_adapter: &Adapter,
+    task_message: ChatMessage,
+) -> Vec<ChatMessage> {
+    let memory_settings = MemorySettings::load();
+    let task_prompt = task_message.content.clone();
+    let mut task_messages = vec![task_message];
+    if !memory_settings.enabled {
+        return task_messages;
+    }
+    let recalled = match LongTermMemory::open(memory_settings) {
+        Ok(mut memory) => memory.recall(api_adapter, &task_prompt).await,
+        Err(e) => Err(e),
+    };
+    match recalled {
+        Ok(memories) => add_memories_to_conversation(
+            &mut task_messages,
+            &api_adapter.get_persona().system_prompt,
+            &memories,
+        ),
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("🟡[MEMORY] 💔🧠 Warning: The task is sent without memories.");
+        }
+    }
+    task_messages
+}
+
+/// Notes on stderr the problems which did not prevent the reply (i.e. a response cache which could not be written).
+fn note_reply_warnings(reply: &LlmResponse) {
+    for warning in &reply.warnings {
+        eprintln!("{}", warning);
+    }
+}
+
+/// Notes on stderr that the reply was given from the response cache, with the stats of the cache.
+fn note_cached_reply(api_adapter: &Adapter, cached: bool) {
+    if let (true, Some(response_cache)) = (cached, api_adapter.get_response_cache()) {
+        eprintln!(
+            "🟢[CACHE] ✅🗃️ Success: Reply from the {} cache | {}",
+            response_cache.get_backend_name(),
+            response_cache.get_stats()
+        );
+    }
+}
+
+/// Builds the task input from the prompt argument and, if it is missing or `-` or `--stdin` was given, the content piped through stdin.
+/// If neither is given and stdin is a terminal, the user is asked to type the task.
+fn read_task_input(task_prompt: Option<&str>, read_stdin: bool) -> Result<String, Error> {
+    let task_prompt = task_prompt
+        .map(str::trim)
+        .filter(|task_prompt| *task_prompt != TASK_STDIN_PROMPT)
+        .unwrap_or_default();
+    let stdin = io::stdin();
+
+    let task_input = if stdin.is_terminal() {
+        if task_prompt.is_empty() {
+            prompt_for_task()?
+        } else {
+            String::from(task_prompt)
+        }
+    } else if task_prompt.is_empty() || read_stdin {
+        let mut piped_input = String::new();
+        stdin.lock().read_to_string(&mut piped_input)?;
+        join_task_input(task_prompt, &piped_input)
+    } else {
+        String::from(task_prompt)
+    };
+
+    if task_input.trim().is_empty() {
+        return Err(Error::msg(
+            "🔴[TASK] Error: No task given. Usage: nyota -t \"<prompt>\", <command> | nyota -t or <command> | nyota -t \"<prompt>\" --stdin",
+        ));
+    }
+    Ok(task_input)
+}
+
+/// Appends the content piped through stdin to the task prompt, if both are given.
+fn join_task_input(task_prompt: &str, piped_input: &str) -> String {
+    match (task_prompt.is_empty(), piped_input.trim().is_empty()) {
+        (true, _) => String::from(piped_input),
+        (false, true) => String::from(task_prompt),
+        (false, false) => format!("{}{}{}", task_prompt, TASK_INPUT_SEPARATOR, piped_input),
+    }
+}
+
+/// Asks the user to type a single-line task on the terminal.
+fn prompt_for_task() -> Result<String, Error> {
+    eprint!("Task: ");
+    io::stderr().flush()?;
+    let mut task_line = String::new();
+    io::stdin().read_line(&mut task_line)?;
+    Ok(String::from(task_line.trim()))
+}
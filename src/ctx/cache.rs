@@ -1,3 +1,715 @@
-// Cache for the application using redis
+//! # Fast Cache
+//! This module contains the fast cache of nyota, which keeps frequently used data for quick responses:
+//! the recent prompts, the model lists of the providers, and the replies to requests already sent (see `nyota::api::response_cache`).
+//! Every entry expires after a time to live (TTL), which depends on what it holds.
+//!
+//! The cache is kept in Redis when a Redis server is reachable, so that it is shared between nyota processes and kept across runs,
+//! and otherwise in an in-process least-recently-used (LRU) cache. `FastCache` picks the backend, and falls back to memory
+//! if Redis stops answering. Both backends implement the `Cache` trait, which stores strings; `get_json` and `set_json` store any serializable value.
+//!
+//! The Redis server is set by the `redis_url` of the `[cache]` table of the nyota config file or by `NYOTA_REDIS_URL`
+//! (an empty URL keeps the cache in memory), and the capacity of the in-memory cache and the TTLs by the `[cache]` table.
+//! Replies are only cached if `responses = true` is set in the `[cache]` table (or `--cache-ttl` is given).
+//!
+//! ## Example
+//! ```rust,no_run
+//! use nyota::api::utilities::ApiProvider;
+//! use nyota::ctx::cache::{get_json, set_json, Cache, CacheKey, CacheSettings, FastCache};
+//!
+//! # fn example() -> anyhow::Result<()> {
+//! let (cache_settings, warnings) = CacheSettings::load();
+//! for warning in &warnings {
+//!     eprintln!("{}", warning);
+//! }
+//! let mut cache = FastCache::connect(&cache_settings);
+//! if let Some(fallback_reason) = cache.get_fallback_reason() {
+//!     eprintln!("{}", fallback_reason);
+//! }
+//! println!("cache kept in {}", cache.get_backend_name());
+//!
+//! let request = serde_json::json!({
+//!     "model": "gpt-4o-mini",
+//!     "messages": [{"role": "user", "content": "What is the capital of Kenya?"}]
+//! });
+//! let chat_url = "https://api.openai.com/v1/chat/completions";
+//! let key = CacheKey::response(ApiProvider::OPENAI, chat_url, "gpt-4o-mini", &request);
+//! if let Some(reply) = get_json::<String>(&mut cache, &key)? {
+//!     println!("{}", reply);
+//! } else {
+//!     set_json(&mut cache, &key, &"Nairobi", cache_settings.get_ttl(&key))?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ```toml
+//! [cache]
+//! redis_url = "redis://cache-box.local:6379/"
+//! memory_capacity = 500
+//! responses = true
+//! response_ttl_secs = 600
+//! ```
 
+use crate::api::utilities::ApiProvider;
+use crate::cfg::config::read_config_table;
+use crate::ctx::constants::*;
+use anyhow::{Error, Result};
+use lru::LruCache;
 use redis::Commands;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::{
+    env::{self, VarError},
+    num::NonZeroUsize,
+    time::{Duration, Instant},
+};
+
+/// Cache is a store of string values, each kept until its time to live runs out (or it is evicted).
+pub trait Cache {
+    /// Returns the value kept under the key, or `None` if there is none or it has expired.
+    fn get(&mut self, key: &str) -> Result<Option<String>>;
+    /// Keeps the value under the key for the time to live, replacing any value kept under it.
+    fn set(&mut self, key: &str, value: &str, ttl: Duration) -> Result<()>;
+    /// Removes the value kept under the key, if there is one.
+    fn delete(&mut self, key: &str) -> Result<()>;
+    /// Returns the name of the backend the values are kept in (i.e. `redis`).
+    fn get_backend_name(&self) -> String;
+}
+
+/// CacheSettings holds the `[cache]` table: where the fast cache is kept, and how long its entries are kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheSettings {
+    /// The URL of the Redis server the cache is kept in, or `None` to keep it in memory.
+    pub redis_url: Option<String>,
+    /// The time allowed to connect to the Redis server, and for each of its answers.
+    pub redis_timeout: Duration,
+    /// The number of entries the in-memory cache keeps.
+    pub memory_capacity: NonZeroUsize,
+    /// Whether the replies of the models are cached, and given again for identical requests.
+    pub cache_responses: bool,
+    /// The time a cached reply is kept.
+    pub response_ttl: Duration,
+    /// The time a cached model list is kept.
+    pub model_list_ttl: Duration,
+    /// The time the recent prompts are kept after the last one.
+    pub recent_prompts_ttl: Duration,
+}
+
+impl CacheSettings {
+    /// Creates a new CacheSettings with the default Redis server, capacity and TTLs, without caching replies.
+    pub fn new() -> Self {
+        Self {
+            redis_url: Some(String::from(DEFAULT_REDIS_URL)),
+            redis_timeout: Duration::from_millis(DEFAULT_REDIS_TIMEOUT_MS),
+            memory_capacity: NonZeroUsize::new(DEFAULT_MEMORY_CACHE_CAPACITY)
+                .unwrap_or(NonZeroUsize::MIN),
+            cache_responses: false,
+            response_ttl: Duration::from_secs(DEFAULT_RESPONSE_TTL_SECS),
+            model_list_ttl: Duration::from_secs(DEFAULT_MODEL_LIST_TTL_SECS),
+            recent_prompts_ttl: Duration::from_secs(DEFAULT_RECENT_PROMPTS_TTL_SECS),
+        }
+    }
+
+    /// Creates a new CacheSettings with the overrides from the config file and environment variables applied.
+    /// Precedence (lowest to highest): built-in defaults < config file < environment variables.
+    /// Unreadable overrides are skipped, keeping the lower layer, and returned as warnings for the caller to report.
+    pub fn load() -> (Self, Vec<Error>) {
+        let mut cache_settings = Self::new();
+        let mut warnings = Vec::new();
+
+        if let Err(e) = cache_settings.apply_config_file() {
+            warnings.push(e);
+        }
+
+        match env::var(REDIS_URL_ENV) {
+            Ok(redis_url) => cache_settings.redis_url = get_redis_url(&redis_url),
+            Err(VarError::NotPresent) => {}
+            Err(VarError::NotUnicode(_)) => warnings.push(Error::msg(format!(
+                "🟡[ENV] 🔍🗃️ Warning: `{}` unreadable.",
+                REDIS_URL_ENV
+            ))),
+        }
+
+        (cache_settings, warnings)
+    }
+
+    /// Creates a new CacheSettings like `load`, but returns the first unreadable override as an error
+    /// (i.e. for the chat interface, which shows it as a notice).
+    pub fn read() -> Result<Self> {
+        let (cache_settings, warnings) = Self::load();
+        match warnings.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(cache_settings),
+        }
+    }
+
+    /// Returns the time an entry under the key is kept.
+    pub fn get_ttl(&self, key: &CacheKey) -> Duration {
+        match key {
+            CacheKey::Response { .. } => self.response_ttl,
+            CacheKey::ModelList(_) => self.model_list_ttl,
+            CacheKey::RecentPrompts => self.recent_prompts_ttl,
+        }
+    }
+
+    /// Applies the overrides from the `[cache]` table of the nyota config file.
+    fn apply_config_file(&mut self) -> Result<(), Error> {
+        let Some(cache_table) = read_config_table(CONFIG_CACHE_TABLE)? else {
+            return Ok(());
+        };
+
+        for (setting, value) in cache_table {
+            if setting == "redis_url" {
+                let redis_url = value.as_str().ok_or_else(|| {
+                    Error::msg(
+                        "🟡[CONFIG] 💔🗃️ Warning: Cache setting `redis_url` must be a string.",
+                    )
+                })?;
+                self.redis_url = get_redis_url(redis_url);
+                continue;
+            }
+            if setting == "responses" {
+                self.cache_responses = value.as_bool().ok_or_else(|| {
+                    Error::msg(
+                        "🟡[CONFIG] 💔🗃️ Warning: Cache setting `responses` must be true or false.",
+                    )
+                })?;
+                continue;
+            }
+            let value = value
+                .as_integer()
+                .and_then(|value| u64::try_from(value).ok())
+                .ok_or_else(|| {
+                    Error::msg(format!(
+                        "🟡[CONFIG] 💔🗃️ Warning: Cache setting `{}` must be a positive integer.",
+                        setting
+                    ))
+                })?;
+            match setting.as_str() {
+                "redis_timeout_ms" => self.redis_timeout = Duration::from_millis(value),
+                "memory_capacity" => {
+                    self.memory_capacity = usize::try_from(value)
+                        .ok()
+                        .and_then(NonZeroUsize::new)
+                        .ok_or_else(|| {
+                            Error::msg("🟡[CONFIG] 💔🗃️ Warning: Cache setting `memory_capacity` must be at least 1.")
+                        })?
+                }
+                "response_ttl_secs" => self.response_ttl = Duration::from_secs(value),
+                "model_list_ttl_secs" => self.model_list_ttl = Duration::from_secs(value),
+                "recent_prompts_ttl_secs" => self.recent_prompts_ttl = Duration::from_secs(value),
+                _ => {
+                    return Err(Error::msg(format!(
+                        "🟡[CONFIG] 💔🗃️ Warning: Unknown cache setting `{}`",
+                        setting
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// CacheKey is what an entry of the fast cache holds, which decides its key and time to live.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CacheKey {
+    /// The reply of a model to a request, identified by the hash of the endpoint and the request.
+    Response {
+        provider: ApiProvider,
+        model: String,
+        request_hash: String,
+    },
+    /// The models offered by a provider.
+    ModelList(ApiProvider),
+    /// The prompts the user sent last, most recent first.
+    RecentPrompts,
+}
+
+impl CacheKey {
+    /// Creates the key of the reply of the model to a request, as formulated for the provider and sent to the chat URL.
+    /// The request holds the messages sent, including the system prompt, and the generation parameters and tools;
+    /// any difference in it, or another endpoint (i.e. another OpenAI-compatible server), gives another key.
+    pub fn response(provider: ApiProvider, chat_url: &str, model: &str, request: &Value) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(chat_url.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(to_canonical_json(request).as_bytes());
+        let request_hash = hasher.finalize();
+        Self::Response {
+            provider,
+            model: String::from(model),
+            request_hash: to_hex(&request_hash),
+        }
+    }
+
+    /// Returns the key the entry is kept under (i.e. `nyota:response:openai:gpt-4o-mini:<hash>`).
+    pub fn to_key_string(&self) -> String {
+        match self {
+            CacheKey::Response {
+                provider,
+                model,
+                request_hash,
+            } => format!(
+                "{}:response:{}:{}:{}",
+                CACHE_KEY_PREFIX,
+                provider.as_str(),
+                model,
+                request_hash
+            ),
+            CacheKey::ModelList(provider) => {
+                format!("{}:models:{}", CACHE_KEY_PREFIX, provider.as_str())
+            }
+            CacheKey::RecentPrompts => format!("{}:prompts:recent", CACHE_KEY_PREFIX),
+        }
+    }
+}
+
+/// MemoryCache keeps the cache in the process, evicting the least recently used entry once it is full.
+pub struct MemoryCache {
+    entries: LruCache<String, MemoryEntry>,
+}
+
+/// An entry of the in-memory cache, with the time it expires.
+struct MemoryEntry {
+    value: String,
+    expires_at: Instant,
+}
+
+impl MemoryCache {
+    /// Creates a new, empty in-memory cache which keeps up to `capacity` entries.
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            entries: LruCache::new(capacity),
+        }
+    }
+
+    /// Returns the number of entries kept, including any which have expired but not yet been read or evicted.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if no entries are kept.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Cache for MemoryCache {
+    fn get(&mut self, key: &str) -> Result<Option<String>> {
+        match self.entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Ok(Some(entry.value.clone())),
+            Some(_) => {
+                self.entries.pop(key);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str, ttl: Duration) -> Result<()> {
+        self.entries.put(
+            String::from(key),
+            MemoryEntry {
+                value: String::from(value),
+                expires_at: Instant::now() + ttl,
+            },
+        );
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &str) -> Result<()> {
+        self.entries.pop(key);
+        Ok(())
+    }
+
+    fn get_backend_name(&self) -> String {
+        String::from("memory")
+    }
+}
+
+/// RedisCache keeps the cache in a Redis server, which expires the entries itself.
+pub struct RedisCache {
+    connection: redis::Connection,
+}
+
+impl RedisCache {
+    /// Connects to the Redis server at the URL (i.e. `redis://127.0.0.1:6379/`), allowing it the timeout to connect and for each answer.
+    /// Returns an error if the URL is invalid or the server cannot be reached.
+    pub fn connect(redis_url: &str, timeout: Duration) -> Result<Self> {
+        let connect = || -> redis::RedisResult<redis::Connection> {
+            let connection =
+                redis::Client::open(redis_url)?.get_connection_with_timeout(timeout)?;
+            connection.set_read_timeout(Some(timeout))?;
+            connection.set_write_timeout(Some(timeout))?;
+            Ok(connection)
+        };
+        let mut connection = connect().map_err(|e| {
+            Error::msg(format!(
+                "🟡[CACHE] 🚫🗃️ Warning: Unable to connect to Redis at {} | {}",
+                redis_url, e
+            ))
+        })?;
+        redis::cmd("PING")
+            .query::<String>(&mut connection)
+            .map_err(|e| {
+                Error::msg(format!(
+                    "🟡[CACHE] 🚫🗃️ Warning: Redis at {} does not answer | {}",
+                    redis_url, e
+                ))
+            })?;
+        Ok(Self { connection })
+    }
+}
+
+impl Cache for RedisCache {
+    fn get(&mut self, key: &str) -> Result<Option<String>> {
+        Ok(self.connection.get(key)?)
+    }
+
+    fn set(&mut self, key: &str, value: &str, ttl: Duration) -> Result<()> {
+        // Redis expires keys after whole seconds, and rejects a TTL of 0
+        let ttl_secs = ttl.as_secs().max(1);
+        self.connection.set_ex::<_, _, ()>(key, value, ttl_secs)?;
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &str) -> Result<()> {
+        self.connection.del::<_, ()>(key)?;
+        Ok(())
+    }
+
+    fn get_backend_name(&self) -> String {
+        String::from("redis")
+    }
+}
+
+/// FastCache keeps the cache in Redis if it can be reached, and otherwise in memory.
+/// If Redis stops answering, the cache falls back to memory for the rest of the run.
+pub struct FastCache {
+    redis: Option<RedisCache>,
+    memory: MemoryCache,
+    /// Why the cache is kept in memory rather than Redis, if a Redis server was set.
+    fallback_reason: Option<String>,
+}
+
+impl FastCache {
+    /// Connects to the Redis server of the settings, falling back to an in-memory cache if none is set or it cannot be reached
+    /// (see `get_fallback_reason`).
+    pub fn connect(cache_settings: &CacheSettings) -> Self {
+        let mut fast_cache = Self::in_memory(cache_settings.memory_capacity);
+        if let Some(redis_url) = &cache_settings.redis_url {
+            match RedisCache::connect(redis_url, cache_settings.redis_timeout) {
+                Ok(redis_cache) => fast_cache.redis = Some(redis_cache),
+                Err(e) => fast_cache.fallback_reason = Some(format!("{}", e)),
+            }
+        }
+        fast_cache
+    }
+
+    /// Creates a cache kept in memory only, which keeps up to `capacity` entries.
+    pub fn in_memory(capacity: NonZeroUsize) -> Self {
+        Self {
+            redis: None,
+            memory: MemoryCache::new(capacity),
+            fallback_reason: None,
+        }
+    }
+
+    /// Returns why the cache is kept in memory rather than Redis, if a Redis server was set but could not be used.
+    pub fn get_fallback_reason(&self) -> Option<&str> {
+        self.fallback_reason.as_deref()
+    }
+
+    /// Stops using Redis after it failed, keeping the cache in memory from then on.
+    fn fall_back(&mut self, error: Error) {
+        self.redis = None;
+        self.fallback_reason = Some(format!(
+            "🟡[CACHE] 🚫🗃️ Warning: Redis stopped answering | {}",
+            error
+        ));
+    }
+}
+
+impl Cache for FastCache {
+    fn get(&mut self, key: &str) -> Result<Option<String>> {
+        if let Some(redis_cache) = &mut self.redis {
+            match redis_cache.get(key) {
+                Ok(value) => return Ok(value),
+                Err(e) => self.fall_back(e),
+            }
+        }
+        self.memory.get(key)
+    }
+
+    fn set(&mut self, key: &str, value: &str, ttl: Duration) -> Result<()> {
+        if let Some(redis_cache) = &mut self.redis {
+            match redis_cache.set(key, value, ttl) {
+                Ok(()) => return Ok(()),
+                Err(e) => self.fall_back(e),
+            }
+        }
+        self.memory.set(key, value, ttl)
+    }
+
+    fn delete(&mut self, key: &str) -> Result<()> {
+        if let Some(redis_cache) = &mut self.redis {
+            match redis_cache.delete(key) {
+                Ok(()) => return Ok(()),
+                Err(e) => self.fall_back(e),
+            }
+        }
+        self.memory.delete(key)
+    }
+
+    fn get_backend_name(&self) -> String {
+        match &self.redis {
+            Some(redis_cache) => redis_cache.get_backend_name(),
+            None => self.memory.get_backend_name(),
+        }
+    }
+}
+
+/// Returns the value kept under the key, deserialized from JSON, or `None` if there is none or it has expired.
+/// A value which cannot be deserialized (i.e. kept by another version of nyota) is treated as missing.
+pub fn get_json<T: DeserializeOwned>(cache: &mut dyn Cache, key: &CacheKey) -> Result<Option<T>> {
+    Ok(cache
+        .get(&key.to_key_string())?
+        .and_then(|value| serde_json::from_str(&value).ok()))
+}
+
+/// Keeps the value under the key as JSON for the time to live.
+pub fn set_json<T: Serialize + ?Sized>(
+    cache: &mut dyn Cache,
+    key: &CacheKey,
+    value: &T,
+    ttl: Duration,
+) -> Result<()> {
+    cache.set(&key.to_key_string(), &serde_json::to_string(value)?, ttl)
+}
+
+/// Adds the prompt to the recent prompts, moving it to the front if it was already among them.
+pub fn remember_prompt(
+    cache: &mut dyn Cache,
+    prompt: &str,
+    cache_settings: &CacheSettings,
+) -> Result<()> {
+    let prompt = prompt.trim();
+    if prompt.is_empty() {
+        return Ok(());
+    }
+    let mut recent_prompts = get_recent_prompts(cache)?;
+    recent_prompts.retain(|recent_prompt| recent_prompt != prompt);
+    recent_prompts.insert(0, String::from(prompt));
+    recent_prompts.truncate(MAX_RECENT_PROMPTS);
+    let key = CacheKey::RecentPrompts;
+    set_json(cache, &key, &recent_prompts, cache_settings.get_ttl(&key))
+}
+
+/// Returns the prompts the user sent last, most recent first.
+pub fn get_recent_prompts(cache: &mut dyn Cache) -> Result<Vec<String>> {
+    Ok(get_json(cache, &CacheKey::RecentPrompts)?.unwrap_or_default())
+}
+
+/// Reads a Redis URL setting: an empty URL keeps the cache in memory.
+fn get_redis_url(redis_url: &str) -> Option<String> {
+    Some(String::from(redis_url.trim())).filter(|redis_url| !redis_url.is_empty())
+}
+
+/// Serializes the JSON value with the keys of every object sorted, so that the same request always hashes the same
+/// whatever order its keys were inserted in.
+fn to_canonical_json(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            let entries: Vec<String> = entries
+                .into_iter()
+                .map(|(key, value)| {
+                    format!("{}:{}", Value::from(key.as_str()), to_canonical_json(value))
+                })
+                .collect();
+            format!("{{{}}}", entries.join(","))
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(to_canonical_json).collect();
+            format!("[{}]", items.join(","))
+        }
+        scalar => scalar.to_string(),
+    }
+}
+
+/// Encodes the bytes as lowercase hexadecimal.
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    fn get_capacity(capacity: usize) -> NonZeroUsize {
+        NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN)
+    }
+
+    #[test]
+    fn memory_cache_expires_entries_after_their_ttl() -> Result<()> {
+        let mut cache = MemoryCache::new(get_capacity(10));
+        cache.set("short", "gone", Duration::from_millis(20))?;
+        cache.set("long", "kept", Duration::from_secs(60))?;
+        assert_eq!(cache.get("short")?.as_deref(), Some("gone"));
+
+        sleep(Duration::from_millis(40));
+        assert_eq!(cache.get("short")?, None);
+        assert_eq!(cache.get("long")?.as_deref(), Some("kept"));
+        // An expired entry is dropped once it is read
+        assert_eq!(cache.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn memory_cache_evicts_the_least_recently_used_entry() -> Result<()> {
+        let ttl = Duration::from_secs(60);
+        let mut cache = MemoryCache::new(get_capacity(2));
+        cache.set("a", "1", ttl)?;
+        cache.set("b", "2", ttl)?;
+        // Reading `a` makes `b` the least recently used
+        assert_eq!(cache.get("a")?.as_deref(), Some("1"));
+        cache.set("c", "3", ttl)?;
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("b")?, None);
+        assert_eq!(cache.get("a")?.as_deref(), Some("1"));
+        assert_eq!(cache.get("c")?.as_deref(), Some("3"));
+        Ok(())
+    }
+
+    #[test]
+    fn memory_cache_replaces_and_deletes_entries() -> Result<()> {
+        let ttl = Duration::from_secs(60);
+        let mut cache = MemoryCache::new(get_capacity(2));
+        cache.set("a", "1", ttl)?;
+        cache.set("a", "2", ttl)?;
+        assert_eq!(cache.get("a")?.as_deref(), Some("2"));
+        cache.delete("a")?;
+        assert_eq!(cache.get("a")?, None);
+        assert!(cache.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn fast_cache_falls_back_to_memory_when_redis_is_unreachable() -> Result<()> {
+        let mut cache_settings = CacheSettings::new();
+        // Nothing listens on port 1, so the connection is refused at once
+        cache_settings.redis_url = Some(String::from("redis://127.0.0.1:1/"));
+        cache_settings.redis_timeout = Duration::from_millis(100);
+        let mut cache = FastCache::connect(&cache_settings);
+
+        assert_eq!(cache.get_backend_name(), "memory");
+        assert!(cache
+            .get_fallback_reason()
+            .is_some_and(|reason| reason.contains("127.0.0.1:1")));
+        let key = CacheKey::ModelList(ApiProvider::OPENAI);
+        set_json(&mut cache, &key, &["gpt-4o-mini"], Duration::from_secs(60))?;
+        assert_eq!(
+            get_json::<Vec<String>>(&mut cache, &key)?,
+            Some(vec![String::from("gpt-4o-mini")])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fast_cache_without_redis_has_no_fallback_reason() {
+        let mut cache_settings = CacheSettings::new();
+        cache_settings.redis_url = None;
+        let cache = FastCache::connect(&cache_settings);
+        assert_eq!(cache.get_backend_name(), "memory");
+        assert_eq!(cache.get_fallback_reason(), None);
+    }
+
+    #[test]
+    fn remember_prompt_keeps_the_latest_first_without_repeats() -> Result<()> {
+        let cache_settings = CacheSettings::new();
+        let mut cache = FastCache::in_memory(get_capacity(10));
+        for prompt in ["first", "second", "  first  ", "", "third"] {
+            remember_prompt(&mut cache, prompt, &cache_settings)?;
+        }
+        assert_eq!(
+            get_recent_prompts(&mut cache)?,
+            vec!["third", "first", "second"]
+        );
+
+        for index in 0..MAX_RECENT_PROMPTS + 5 {
+            remember_prompt(&mut cache, &format!("prompt {}", index), &cache_settings)?;
+        }
+        let recent_prompts = get_recent_prompts(&mut cache)?;
+        assert_eq!(recent_prompts.len(), MAX_RECENT_PROMPTS);
+        assert_eq!(
+            recent_prompts.first().map(String::as_str),
+            Some(format!("prompt {}", MAX_RECENT_PROMPTS + 4).as_str())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn cache_keys_are_namespaced() {
+        assert_eq!(
+            CacheKey::ModelList(ApiProvider::ANTHROPIC).to_key_string(),
+            "nyota:models:anthropic"
+        );
+        assert_eq!(
+            CacheKey::RecentPrompts.to_key_string(),
+            "nyota:prompts:recent"
+        );
+    }
+
+    #[test]
+    fn response_keys_ignore_the_key_order() {
+        let chat_url = "https://api.openai.com/v1/chat/completions";
+        let request: Value = serde_json::from_str(
+            r#"{"model": "gpt-4o-mini", "messages": [{"role": "user", "content": "Hi"}], "temperature": 0.2}"#,
+        )
+        .unwrap_or_default();
+        let reordered: Value = serde_json::from_str(
+            r#"{"temperature": 0.2, "messages": [{"content": "Hi", "role": "user"}], "model": "gpt-4o-mini"}"#,
+        )
+        .unwrap_or_default();
+        assert_eq!(
+            CacheKey::response(ApiProvider::OPENAI, chat_url, "gpt-4o-mini", &request),
+            CacheKey::response(ApiProvider::OPENAI, chat_url, "gpt-4o-mini", &reordered)
+        );
+        assert_eq!(
+            to_canonical_json(&reordered),
+            r#"{"messages":[{"content":"Hi","role":"user"}],"model":"gpt-4o-mini","temperature":0.2}"#
+        );
+    }
+
+    #[test]
+    fn response_keys_differ_by_endpoint() {
+        let request = serde_json::json!({"model": "llama3", "messages": []});
+        let local = CacheKey::response(
+            ApiProvider::OPENAI_COMPATIBLE,
+            "http://localhost:8080/v1/chat/completions",
+            "llama3",
+            &request,
+        );
+        let remote = CacheKey::response(
+            ApiProvider::OPENAI_COMPATIBLE,
+            "https://llm.example.com/v1/chat/completions",
+            "llama3",
+            &request,
+        );
+        assert_ne!(local, remote);
+        assert_ne!(local.to_key_string(), remote.to_key_string());
+        assert!(local
+            .to_key_string()
+            .starts_with("nyota:response:openai-compatible:llama3:"));
+    }
+}
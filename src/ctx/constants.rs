@@ -0,0 +1,68 @@
+//! Constants for the context system.
+//! These values name the files where the context of nyota is kept, such as the saved chat sessions and the context database,
+//! and tune the fast cache of recent prompts, model lists and replies, and the long-term memory.
+
+// *** Chat Sessions ***
+// (saved under the user's data directory, i.e. `~/.local/share/nyota/sessions/`)
+/// The name of the directory which holds the saved chat sessions.
+pub const SESSIONS_DIR_NAME: &str = "sessions";
+/// The file extension of a saved chat session.
+pub const SESSION_FILE_EXTENSION: &str = "json";
+/// The longest name a chat session can be given.
+pub const MAX_SESSION_NAME_LENGTH: usize = 64;
+/// The prefix of the names generated for unnamed chat sessions (i.e. `chat-2025-01-31-142501`).
+pub const SESSION_NAME_PREFIX: &str = "chat";
+/// The format of the time in the names generated for unnamed chat sessions.
+pub const SESSION_NAME_TIME_FORMAT: &str = "%Y-%m-%d-%H%M%S";
+
+// *** Context Database ***
+// (an SQLite file under the user's data directory, i.e. `~/.local/share/nyota/context.db`)
+/// The file name of the context database, which records the history of every chat.
+pub const DB_FILE_NAME: &str = "context.db";
+/// How long to wait for another nyota process to finish writing to the context database, in milliseconds.
+pub const DB_BUSY_TIMEOUT_MS: u64 = 5000;
+/// The most messages or actions a history query returns, unless it asks for another limit.
+pub const DEFAULT_HISTORY_LIMIT: usize = 50;
+
+// *** Fast Cache ***
+// (overridable via the `[cache]` table of the config file or env, see `nyota::ctx::cache`)
+/// The config file table which holds the fast cache settings.
+pub const CONFIG_CACHE_TABLE: &str = "cache";
+/// Environment variable which sets the URL of the Redis server the fast cache is kept in (empty keeps it in memory).
+pub const REDIS_URL_ENV: &str = "NYOTA_REDIS_URL";
+/// The default URL of the Redis server the fast cache is kept in.
+pub const DEFAULT_REDIS_URL: &str = "redis://127.0.0.1:6379/";
+/// The default time allowed to connect to (and then to answer from) the Redis server, before the in-memory cache is used instead.
+pub const DEFAULT_REDIS_TIMEOUT_MS: u64 = 250;
+/// The default number of entries the in-memory cache keeps, the least recently used being evicted first.
+pub const DEFAULT_MEMORY_CACHE_CAPACITY: usize = 1000;
+/// The default time a cached reply is kept.
+pub const DEFAULT_RESPONSE_TTL_SECS: u64 = 60 * 60;
+/// The default time a cached model list is kept.
+pub const DEFAULT_MODEL_LIST_TTL_SECS: u64 = 24 * 60 * 60;
+/// The default time the recent prompts are kept after the last one.
+pub const DEFAULT_RECENT_PROMPTS_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+/// The most recent prompts kept.
+pub const MAX_RECENT_PROMPTS: usize = 20;
+/// The prefix of every key nyota keeps in the cache, so that it can share a Redis server (i.e. `nyota:models:openai`).
+pub const CACHE_KEY_PREFIX: &str = "nyota";
+
+// *** Long-Term Memory ***
+// (off unless enabled in the `[memory]` table of the config file, see `nyota::ctx::memory`)
+/// The config file table which holds the long-term memory settings.
+pub const CONFIG_MEMORY_TABLE: &str = "memory";
+/// The default number of memories, besides the pinned ones, given to the model with each prompt.
+pub const DEFAULT_MEMORY_TOP_K: usize = 5;
+/// The default least cosine similarity a memory must have to a prompt to be given with it.
+pub const DEFAULT_MEMORY_MIN_SIMILARITY: f32 = 0.3;
+/// The cosine similarity at or above which a new fact is taken to be one already remembered, and is not remembered again.
+pub const MEMORY_DUPLICATE_SIMILARITY: f32 = 0.92;
+/// The most messages of a chat, the latest ones, that facts are extracted from.
+pub const MAX_MEMORY_EXTRACTION_MESSAGES: usize = 40;
+/// The name of the JSON Schema the extracted facts are asked for with.
+pub const MEMORY_SCHEMA_NAME: &str = "memories";
+/// The instruction which asks the model for the facts worth remembering from a chat, which follows it.
+pub const MEMORY_EXTRACTION_PROMPT: &str = "Extract the lasting facts about the user from the conversation below: their preferences, projects, environment, plans and decisions, which would help in later conversations. Write each fact as a short sentence which stands on its own (i.e. \"The user deploys their services to Fly.io\"). Leave out small talk, one-off questions and anything only true within this conversation. Reply with an empty list if there is nothing worth remembering.";
+/// The header of the memories given to the model with a prompt, followed by one memory per line.
+pub const MEMORY_CONTEXT_HEADER: &str =
+    "Facts remembered from earlier conversations with the user (use them where they are relevant):";
@@ -0,0 +1,955 @@
+//! # Context Database
+//! This module contains the context database, the local memory of the user's interactions with nyota.
+//! It is an embedded SQLite file (`~/.local/share/nyota/context.db`) which records every chat session,
+//! the messages sent and received in it, and the actions run in it (i.e. chat commands) with their results.
+//! Unlike the session files (see `nyota::ctx::session`), which hold the chat as it is now, the database keeps the history:
+//! it can be queried by session, by date and by full-text search over the messages, across every chat.
+//! It also keeps the long-term memory (see `nyota::ctx::memory`): the facts remembered from the chats, with their embeddings.
+//!
+//! The schema is created and upgraded by the migrations below when the database is opened;
+//! the number of migrations applied is kept in the `user_version` of the database.
+
+use crate::api::constants::CONFIG_DIR_NAME;
+use crate::ctx::constants::*;
+use crate::ctx::session::{Session, SessionMessage};
+use anyhow::{Error, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{
+    params, params_from_iter,
+    types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef},
+    Connection, OptionalExtension, ToSql,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+/// The migrations of the schema, in the order they are applied. Each one is applied once, in a transaction of its own.
+/// Migrations are never changed once released - the schema is changed by adding a migration.
+const MIGRATIONS: &[&str] = &[
+    // 1: sessions, their messages (with a full-text index) and their actions
+    "CREATE TABLE sessions (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE,
+        model TEXT NOT NULL,
+        provider TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE TABLE messages (
+        id INTEGER PRIMARY KEY,
+        session_id INTEGER NOT NULL REFERENCES sessions (id) ON DELETE CASCADE,
+        role TEXT NOT NULL,
+        content TEXT NOT NULL,
+        model TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    CREATE INDEX messages_by_session ON messages (session_id, created_at);
+    CREATE INDEX messages_by_date ON messages (created_at);
+    CREATE TABLE actions (
+        id INTEGER PRIMARY KEY,
+        session_id INTEGER NOT NULL REFERENCES sessions (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        arguments TEXT NOT NULL,
+        result TEXT NOT NULL,
+        succeeded INTEGER NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    CREATE INDEX actions_by_session ON actions (session_id, created_at);
+    CREATE VIRTUAL TABLE messages_fts USING fts5 (content, content = 'messages', content_rowid = 'id');
+    CREATE TRIGGER messages_fts_insert AFTER INSERT ON messages BEGIN
+        INSERT INTO messages_fts (rowid, content) VALUES (new.id, new.content);
+    END;
+    CREATE TRIGGER messages_fts_delete AFTER DELETE ON messages BEGIN
+        INSERT INTO messages_fts (messages_fts, rowid, content) VALUES ('delete', old.id, old.content);
+    END;
+    CREATE TRIGGER messages_fts_update AFTER UPDATE OF content ON messages BEGIN
+        INSERT INTO messages_fts (messages_fts, rowid, content) VALUES ('delete', old.id, old.content);
+        INSERT INTO messages_fts (rowid, content) VALUES (new.id, new.content);
+    END;",
+    // 2: the long-term memory, each fact with the embedding it was given by the embedding model
+    "CREATE TABLE memories (
+        id INTEGER PRIMARY KEY,
+        content TEXT NOT NULL UNIQUE,
+        embedding BLOB NOT NULL,
+        embedding_model TEXT NOT NULL,
+        pinned INTEGER NOT NULL DEFAULT 0,
+        session_id INTEGER REFERENCES sessions (id) ON DELETE SET NULL,
+        created_at TEXT NOT NULL,
+        recalled_at TEXT,
+        recall_count INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE INDEX memories_by_model ON memories (embedding_model);",
+];
+
+/// MessageRole is who a recorded message is from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageRole {
+    /// A message from the user.
+    User,
+    /// A reply from the AI.
+    Assistant,
+    /// An error reported in place of a reply.
+    Error,
+    /// A notice from nyota (i.e. a command result).
+    Notice,
+}
+
+impl MessageRole {
+    /// Returns the role of a chat session message.
+    pub fn of(message: &SessionMessage) -> Self {
+        match (message.is_user, message.is_notice, message.is_error) {
+            (true, _, _) => MessageRole::User,
+            (false, true, _) => MessageRole::Notice,
+            (false, false, true) => MessageRole::Error,
+            (false, false, false) => MessageRole::Assistant,
+        }
+    }
+
+    /// Returns the name the role is recorded as.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageRole::User => "user",
+            MessageRole::Assistant => "assistant",
+            MessageRole::Error => "error",
+            MessageRole::Notice => "notice",
+        }
+    }
+}
+
+impl fmt::Display for MessageRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for MessageRole {
+    type Err = Error;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role {
+            "user" => Ok(MessageRole::User),
+            "assistant" => Ok(MessageRole::Assistant),
+            "error" => Ok(MessageRole::Error),
+            "notice" => Ok(MessageRole::Notice),
+            _ => Err(Error::msg(format!(
+                "🟡[CONTEXT] 💔🗄️ Warning: Unknown message role `{}`.",
+                role
+            ))),
+        }
+    }
+}
+
+impl ToSql for MessageRole {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for MessageRole {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: Error| FromSqlError::Other(e.into()))
+    }
+}
+
+/// HistoryMessage is a message recorded in the context database.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryMessage {
+    /// The id of the message in the database.
+    pub id: i64,
+    /// The name of the session the message was sent or received in.
+    pub session: String,
+    /// Who the message is from.
+    pub role: MessageRole,
+    /// The text of the message.
+    pub content: String,
+    /// The model the session used when the message was recorded.
+    pub model: String,
+    /// When the message was sent, or its reply started.
+    pub timestamp: DateTime<Utc>,
+}
+
+/// HistoryAction is an action recorded in the context database, with its result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryAction {
+    /// The id of the action in the database.
+    pub id: i64,
+    /// The name of the session the action was run in.
+    pub session: String,
+    /// The name of the action (i.e. `/set`).
+    pub name: String,
+    /// The arguments the action was run with, as they were given (i.e. `temperature 0.7`).
+    pub arguments: String,
+    /// What the action answered, or the error it failed with.
+    pub result: String,
+    /// Whether the action succeeded.
+    pub succeeded: bool,
+    /// When the action was run.
+    pub timestamp: DateTime<Utc>,
+}
+
+/// HistorySession is a session recorded in the context database, with the number of messages and actions recorded in it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistorySession {
+    /// The name of the session.
+    pub name: String,
+    /// The model the session last used.
+    pub model: String,
+    /// The API provider the session last used.
+    pub provider: String,
+    /// When the session was first recorded.
+    pub created_at: DateTime<Utc>,
+    /// When a message or action was last recorded in the session.
+    pub updated_at: DateTime<Utc>,
+    /// The number of messages recorded in the session.
+    pub message_count: u64,
+    /// The number of actions recorded in the session.
+    pub action_count: u64,
+}
+
+/// Memory is a fact remembered in the context database (see `nyota::ctx::memory`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Memory {
+    /// The id of the memory in the database, by which it is pinned or forgotten.
+    pub id: i64,
+    /// The fact, as a sentence which stands on its own.
+    pub content: String,
+    /// Whether the memory is pinned, to be given to the model with every prompt.
+    pub pinned: bool,
+    /// The name of the session the fact was remembered from, if it was remembered from a chat.
+    pub session: Option<String>,
+    /// The model which embedded the fact. Only memories embedded by the same model as a prompt can be compared to it.
+    pub embedding_model: String,
+    /// When the fact was remembered.
+    pub created_at: DateTime<Utc>,
+    /// When the memory was last given to the model, if it has been.
+    pub recalled_at: Option<DateTime<Utc>>,
+    /// The number of times the memory was given to the model.
+    pub recall_count: u64,
+}
+
+/// HistoryQuery selects the messages or actions to return from the context database. Every filter which is set must match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryQuery {
+    /// Only those of the session with this name.
+    pub session: Option<String>,
+    /// Only those recorded at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only those recorded before this time.
+    pub until: Option<DateTime<Utc>>,
+    /// Only those containing every word of this text (full-text search for messages, substring search for actions).
+    pub search: Option<String>,
+    /// The most to return - the latest ones are returned, oldest first.
+    pub limit: usize,
+}
+
+impl HistoryQuery {
+    /// Creates a query for the latest `DEFAULT_HISTORY_LIMIT` messages or actions of every session.
+    pub fn new() -> Self {
+        Self {
+            session: None,
+            since: None,
+            until: None,
+            search: None,
+            limit: DEFAULT_HISTORY_LIMIT,
+        }
+    }
+
+    /// Returns true if the query has a filter, rather than selecting from the whole history.
+    pub fn is_filtered(&self) -> bool {
+        self.session.is_some()
+            || self.since.is_some()
+            || self.until.is_some()
+            || self.search.is_some()
+    }
+}
+
+impl Default for HistoryQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// ContextDb is an open connection to the context database.
+pub struct ContextDb {
+    connection: Connection,
+}
+
+impl ContextDb {
+    /// Opens the context database in the user's data directory, creating it if there is none, and brings its schema up to date.
+    pub fn open() -> Result<Self> {
+        let db_path = get_db_file_path().ok_or_else(|| {
+            Error::msg(
+                "🟡[CONTEXT] 🔍🗄️ Warning: No data directory to keep the context database in.",
+            )
+        })?;
+        Self::open_at(&db_path)
+    }
+
+    /// Opens the context database at the specified path, creating it if there is none, and brings its schema up to date.
+    pub fn open_at(db_path: &Path) -> Result<Self> {
+        if let Some(db_dir) = db_path.parent() {
+            fs::create_dir_all(db_dir)?;
+        }
+        let connection = Connection::open(db_path).map_err(|e| {
+            Error::msg(format!(
+                "🔴[CONTEXT] Error: Unable to open {} | {}",
+                db_path.display(),
+                e
+            ))
+        })?;
+        Self::with_connection(connection).map_err(|e| {
+            Error::msg(format!(
+                "🔴[CONTEXT] Error: Unable to migrate {} | {}",
+                db_path.display(),
+                e
+            ))
+        })
+    }
+
+    /// Sets up the open connection to the context database, and brings its schema up to date.
+    fn with_connection(connection: Connection) -> Result<Self> {
+        // Several nyota processes may write to the database at once
+        connection.busy_timeout(Duration::from_millis(DB_BUSY_TIMEOUT_MS))?;
+        connection.pragma_update(None, "foreign_keys", true)?;
+        let mut context_db = Self { connection };
+        context_db.migrate()?;
+        Ok(context_db)
+    }
+
+    /// Returns the version of the schema, which is the number of migrations applied.
+    pub fn get_schema_version(&self) -> Result<usize> {
+        let schema_version: i64 =
+            self.connection
+                .pragma_query_value(None, "user_version", |row| row.get(0))?;
+        Ok(usize::try_from(schema_version)?)
+    }
+
+    /// Applies the migrations which have not been applied yet, each in a transaction of its own.
+    /// Returns an error if the database was created by a newer nyota, whose schema this one does not know.
+    fn migrate(&mut self) -> Result<()> {
+        let schema_version = self.get_schema_version()?;
+        if schema_version > MIGRATIONS.len() {
+            return Err(Error::msg(format!(
+                "the schema (version {}) is newer than this nyota knows (version {})",
+                schema_version,
+                MIGRATIONS.len()
+            )));
+        }
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(schema_version) {
+            let transaction = self.connection.transaction()?;
+            transaction.execute_batch(migration)?;
+            transaction.pragma_update(None, "user_version", index + 1)?;
+            transaction.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Records the session, or notes the model it now uses if it is already recorded, and returns its id.
+    pub fn record_session(&self, session: &Session) -> Result<i64> {
+        let session_id = self.connection.query_row(
+            "INSERT INTO sessions (name, model, provider, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT (name) DO UPDATE SET model = excluded.model, provider = excluded.provider, updated_at = excluded.updated_at
+            RETURNING id",
+            params![
+                session.name,
+                session.model,
+                session.provider.as_str(),
+                session.created_at,
+                Utc::now()
+            ],
+            |row| row.get(0),
+        )?;
+        Ok(session_id)
+    }
+
+    /// Records the messages of the session, in a single transaction.
+    pub fn record_messages(
+        &mut self,
+        session: &Session,
+        messages: &[SessionMessage],
+    ) -> Result<()> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+        let session_id = self.record_session(session)?;
+        let transaction = self.connection.transaction()?;
+        {
+            let mut statement = transaction.prepare(
+                "INSERT INTO messages (session_id, role, content, model, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for message in messages {
+                statement.execute(params![
+                    session_id,
+                    MessageRole::of(message),
+                    message.content,
+                    session.model,
+                    message.timestamp
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Records an action run in the session, with its arguments, and what it answered or the error it failed with.
+    pub fn record_action(
+        &self,
+        session: &Session,
+        name: &str,
+        arguments: &str,
+        outcome: &Result<String>,
+    ) -> Result<()> {
+        let session_id = self.record_session(session)?;
+        let (result, succeeded) = match outcome {
+            Ok(result) => (result.clone(), true),
+            Err(e) => (format!("{}", e), false),
+        };
+        self.connection.execute(
+            "INSERT INTO actions (session_id, name, arguments, result, succeeded, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![session_id, name, arguments, result, succeeded, Utc::now()],
+        )?;
+        Ok(())
+    }
+
+    /// Moves the history of a session to its new name, once the session has been renamed.
+    /// If a session with the new name is already recorded (i.e. a deleted session which had it), the histories are merged.
+    pub fn rename_session(&mut self, old_name: &str, new_name: &str) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        let get_session_id = |name: &str| {
+            transaction
+                .query_row("SELECT id FROM sessions WHERE name = ?1", [name], |row| {
+                    row.get::<_, i64>(0)
+                })
+                .optional()
+        };
+        match (get_session_id(old_name)?, get_session_id(new_name)?) {
+            (Some(old_id), Some(new_id)) if old_id != new_id => {
+                transaction.execute(
+                    "UPDATE messages SET session_id = ?2 WHERE session_id = ?1",
+                    [old_id, new_id],
+                )?;
+                transaction.execute(
+                    "UPDATE actions SET session_id = ?2 WHERE session_id = ?1",
+                    [old_id, new_id],
+                )?;
+                transaction.execute("DELETE FROM sessions WHERE id = ?1", [old_id])?;
+            }
+            (Some(old_id), None) => {
+                transaction.execute(
+                    "UPDATE sessions SET name = ?2 WHERE id = ?1",
+                    params![old_id, new_name],
+                )?;
+            }
+            _ => {} // Nothing recorded under the old name
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Returns the recorded sessions, the most recently updated first.
+    pub fn list_sessions(&self) -> Result<Vec<HistorySession>> {
+        let mut statement = self.connection.prepare(
+            "SELECT sessions.name, sessions.model, sessions.provider, sessions.created_at, sessions.updated_at,
+                (SELECT COUNT(*) FROM messages WHERE messages.session_id = sessions.id),
+                (SELECT COUNT(*) FROM actions WHERE actions.session_id = sessions.id)
+            FROM sessions ORDER BY sessions.updated_at DESC",
+        )?;
+        let sessions = statement
+            .query_map([], |row| {
+                Ok(HistorySession {
+                    name: row.get(0)?,
+                    model: row.get(1)?,
+                    provider: row.get(2)?,
+                    created_at: row.get(3)?,
+                    updated_at: row.get(4)?,
+                    message_count: row.get(5)?,
+                    action_count: row.get(6)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<HistorySession>>>()?;
+        Ok(sessions)
+    }
+
+    /// Returns the latest recorded messages which match the query, oldest first.
+    /// The search text matches messages containing every one of its words (or word prefixes), in any case.
+    pub fn find_messages(&self, query: &HistoryQuery) -> Result<Vec<HistoryMessage>> {
+        let mut sql = String::from(
+            "SELECT messages.id, sessions.name, messages.role, messages.content, messages.model, messages.created_at
+            FROM messages JOIN sessions ON sessions.id = messages.session_id",
+        );
+        let mut conditions: Vec<String> = Vec::new();
+        let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+        if let Some(search) = query
+            .search
+            .as_deref()
+            .filter(|search| !search.trim().is_empty())
+        {
+            sql.push_str(" JOIN messages_fts ON messages_fts.rowid = messages.id");
+            conditions.push(String::from("messages_fts MATCH ?"));
+            values.push(Box::new(to_fts_query(search)));
+        }
+        add_common_filters(query, "messages", &mut conditions, &mut values);
+        let sql = finish_history_sql(sql, "messages", &conditions);
+        values.push(Box::new(query.limit));
+
+        let mut statement = self.connection.prepare(&sql)?;
+        let mut messages = statement
+            .query_map(params_from_iter(values.iter()), |row| {
+                Ok(HistoryMessage {
+                    id: row.get(0)?,
+                    session: row.get(1)?,
+                    role: row.get(2)?,
+                    content: row.get(3)?,
+                    model: row.get(4)?,
+                    timestamp: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<HistoryMessage>>>()?;
+        messages.reverse();
+        Ok(messages)
+    }
+
+    /// Returns the latest recorded actions which match the query, oldest first.
+    /// The search text matches actions whose name, arguments or result contain it, in any case.
+    pub fn find_actions(&self, query: &HistoryQuery) -> Result<Vec<HistoryAction>> {
+        let sql = String::from(
+            "SELECT actions.id, sessions.name, actions.name, actions.arguments, actions.result, actions.succeeded, actions.created_at
+            FROM actions JOIN sessions ON sessions.id = actions.session_id",
+        );
+        let mut conditions: Vec<String> = Vec::new();
+        let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+        if let Some(search) = query
+            .search
+            .as_deref()
+            .filter(|search| !search.trim().is_empty())
+        {
+            conditions.push(String::from(
+                "instr(lower(actions.name || ' ' || actions.arguments || ' ' || actions.result), lower(?)) > 0",
+            ));
+            values.push(Box::new(String::from(search.trim())));
+        }
+        add_common_filters(query, "actions", &mut conditions, &mut values);
+        let sql = finish_history_sql(sql, "actions", &conditions);
+        values.push(Box::new(query.limit));
+
+        let mut statement = self.connection.prepare(&sql)?;
+        let mut actions = statement
+            .query_map(params_from_iter(values.iter()), |row| {
+                Ok(HistoryAction {
+                    id: row.get(0)?,
+                    session: row.get(1)?,
+                    name: row.get(2)?,
+                    arguments: row.get(3)?,
+                    result: row.get(4)?,
+                    succeeded: row.get(5)?,
+                    timestamp: row.get(6)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<HistoryAction>>>()?;
+        actions.reverse();
+        Ok(actions)
+    }
+
+    /// Remembers a fact with the embedding the embedding model gave it, from the session if it was remembered from a chat.
+    /// Returns the id of the memory, or `None` if the fact is already remembered.
+    pub fn add_memory(
+        &self,
+        session: Option<&Session>,
+        content: &str,
+        embedding_model: &str,
+        embedding: &[f32],
+    ) -> Result<Option<i64>> {
+        let session_id = session
+            .map(|session| self.record_session(session))
+            .transpose()?;
+        let memory_id = self
+            .connection
+            .query_row(
+                "INSERT INTO memories (content, embedding, embedding_model, session_id, created_at) VALUES (?1, ?2, ?3, ?4, ?5)
+                ON CONFLICT (content) DO NOTHING
+                RETURNING id",
+                params![
+                    content,
+                    to_embedding_blob(embedding),
+                    embedding_model,
+                    session_id,
+                    Utc::now()
+                ],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(memory_id)
+    }
+
+    /// Returns every memory, the pinned ones first, then the most recently remembered first.
+    pub fn list_memories(&self) -> Result<Vec<Memory>> {
+        let mut statement = self.connection.prepare(&format!(
+            "{} ORDER BY memories.pinned DESC, memories.created_at DESC, memories.id DESC",
+            MEMORY_SELECT_SQL
+        ))?;
+        let memories = statement
+            .query_map([], read_memory)?
+            .collect::<rusqlite::Result<Vec<Memory>>>()?;
+        Ok(memories)
+    }
+
+    /// Returns the memories embedded by the embedding model, with their embeddings.
+    pub fn get_memory_embeddings(&self, embedding_model: &str) -> Result<Vec<(Memory, Vec<f32>)>> {
+        let mut statement = self.connection.prepare(&format!(
+            "{} WHERE memories.embedding_model = ?1",
+            MEMORY_SELECT_SQL
+        ))?;
+        let memories = statement
+            .query_map([embedding_model], |row| {
+                let embedding: Vec<u8> = row.get(8)?;
+                Ok((read_memory(row)?, from_embedding_blob(&embedding)))
+            })?
+            .collect::<rusqlite::Result<Vec<(Memory, Vec<f32>)>>>()?;
+        Ok(memories)
+    }
+
+    /// Pins the memory, to be given to the model with every prompt, or unpins it.
+    /// Returns an error if there is no memory with the id.
+    pub fn set_memory_pinned(&self, memory_id: i64, pinned: bool) -> Result<()> {
+        let updated = self.connection.execute(
+            "UPDATE memories SET pinned = ?2 WHERE id = ?1",
+            params![memory_id, pinned],
+        )?;
+        check_memory_found(memory_id, updated)
+    }
+
+    /// Forgets the memory. Returns an error if there is no memory with the id.
+    pub fn forget_memory(&self, memory_id: i64) -> Result<()> {
+        let deleted = self
+            .connection
+            .execute("DELETE FROM memories WHERE id = ?1", [memory_id])?;
+        check_memory_found(memory_id, deleted)
+    }
+
+    /// Notes that the memories were given to the model, in a single transaction.
+    pub fn mark_memories_recalled(&mut self, memory_ids: &[i64]) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        {
+            let mut statement = transaction.prepare(
+                "UPDATE memories SET recalled_at = ?2, recall_count = recall_count + 1 WHERE id = ?1",
+            )?;
+            let recalled_at = Utc::now();
+            for memory_id in memory_ids {
+                statement.execute(params![memory_id, recalled_at])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+/// The query of the memories, with the name of their session, and their embedding last.
+const MEMORY_SELECT_SQL: &str = "SELECT memories.id, memories.content, memories.pinned, sessions.name, memories.embedding_model,
+        memories.created_at, memories.recalled_at, memories.recall_count, memories.embedding
+    FROM memories LEFT JOIN sessions ON sessions.id = memories.session_id";
+
+/// Reads a memory from a row of `MEMORY_SELECT_SQL`.
+fn read_memory(row: &rusqlite::Row<'_>) -> rusqlite::Result<Memory> {
+    Ok(Memory {
+        id: row.get(0)?,
+        content: row.get(1)?,
+        pinned: row.get(2)?,
+        session: row.get(3)?,
+        embedding_model: row.get(4)?,
+        created_at: row.get(5)?,
+        recalled_at: row.get(6)?,
+        recall_count: row.get(7)?,
+    })
+}
+
+/// Returns an error if no memory was changed, there being none with the id.
+fn check_memory_found(memory_id: i64, changed: usize) -> Result<()> {
+    match changed {
+        0 => Err(Error::msg(format!(
+            "🟡[CONTEXT] 🔍🧠 Warning: No memory with the id {}.",
+            memory_id
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Encodes an embedding as the little-endian bytes of its numbers, as it is kept in the database.
+fn to_embedding_blob(embedding: &[f32]) -> Vec<u8> {
+    embedding
+        .iter()
+        .flat_map(|number| number.to_le_bytes())
+        .collect()
+}
+
+/// Decodes an embedding kept in the database.
+fn from_embedding_blob(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
+
+/// Returns the path of the context database (i.e. `~/.local/share/nyota/context.db`), if the user's data directory is known.
+pub fn get_db_file_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|data_dir| data_dir.join(CONFIG_DIR_NAME).join(DB_FILE_NAME))
+}
+
+/// Adds the session and date filters of the query, which messages and actions share, to the conditions of the table's query.
+fn add_common_filters(
+    query: &HistoryQuery,
+    table_name: &str,
+    conditions: &mut Vec<String>,
+    values: &mut Vec<Box<dyn ToSql>>,
+) {
+    if let Some(session) = &query.session {
+        conditions.push(String::from("sessions.name = ?"));
+        values.push(Box::new(session.clone()));
+    }
+    if let Some(since) = query.since {
+        conditions.push(format!("{}.created_at >= ?", table_name));
+        values.push(Box::new(since));
+    }
+    if let Some(until) = query.until {
+        conditions.push(format!("{}.created_at < ?", table_name));
+        values.push(Box::new(until));
+    }
+}
+
+/// Adds the conditions to the query of the table, and orders it to return the latest rows up to the limit (the last value to bind).
+fn finish_history_sql(mut sql: String, table_name: &str, conditions: &[String]) -> String {
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(&format!(
+        " ORDER BY {0}.created_at DESC, {0}.id DESC LIMIT ?",
+        table_name
+    ));
+    sql
+}
+
+/// Turns search text into a full-text query matching every word as a prefix (i.e. `rust err` matches "Rust errors"),
+/// quoting each word so that characters which are FTS5 syntax (`"`, `-`, `*`, `:`...) are searched for rather than parsed.
+fn to_fts_query(search: &str) -> String {
+    search
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::utilities::ApiProvider;
+    use chrono::TimeZone;
+    use std::env;
+
+    /// Opens a context database in memory, with its schema up to date.
+    fn get_test_db() -> Result<ContextDb> {
+        ContextDb::with_connection(Connection::open_in_memory()?)
+    }
+
+    /// Creates a user message with the content, sent at the time.
+    fn get_message(content: &str, timestamp: DateTime<Utc>) -> SessionMessage {
+        SessionMessage {
+            content: String::from(content),
+            timestamp,
+            is_user: true,
+            is_error: false,
+            is_notice: false,
+            attachments: Vec::new(),
+        }
+    }
+
+    /// Returns the contents of the messages, oldest first.
+    fn get_contents(messages: &[HistoryMessage]) -> Vec<&str> {
+        messages
+            .iter()
+            .map(|message| message.content.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn migrations_are_applied_from_version_0() -> Result<()> {
+        let context_db = get_test_db()?;
+        assert_eq!(context_db.get_schema_version()?, MIGRATIONS.len());
+        let table_count: i64 = context_db.connection.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name IN ('sessions', 'messages', 'actions', 'memories')",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(table_count, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn only_the_migrations_not_applied_yet_are_applied() -> Result<()> {
+        let connection = Connection::open_in_memory()?;
+        connection.execute_batch(MIGRATIONS[0])?;
+        connection.pragma_update(None, "user_version", 1)?;
+        connection.execute(
+            "INSERT INTO sessions (name, model, provider, created_at, updated_at) VALUES ('kept', 'gpt-4o-mini', 'openai', ?1, ?1)",
+            [Utc::now()],
+        )?;
+
+        let context_db = ContextDb::with_connection(connection)?;
+        assert_eq!(context_db.get_schema_version()?, MIGRATIONS.len());
+        assert_eq!(context_db.list_sessions()?.len(), 1);
+        assert!(context_db.list_memories()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn a_newer_schema_is_not_opened() -> Result<()> {
+        let connection = Connection::open_in_memory()?;
+        connection.pragma_update(None, "user_version", MIGRATIONS.len() + 1)?;
+        let error = ContextDb::with_connection(connection)
+            .err()
+            .map(|e| e.to_string());
+        assert!(error.is_some_and(|error| error.contains("newer than this nyota knows")));
+        Ok(())
+    }
+
+    #[test]
+    fn an_existing_database_is_reopened_with_its_history() -> Result<()> {
+        let db_dir = env::temp_dir().join(format!("nyota-db-reopen-{}", std::process::id()));
+        let db_path = db_dir.join(DB_FILE_NAME);
+        let session = Session::new("reopened", "gpt-4o-mini", ApiProvider::OPENAI);
+        let reopened = ContextDb::open_at(&db_path).and_then(|mut context_db| {
+            context_db.record_messages(&session, &[get_message("Hello", Utc::now())])?;
+            drop(context_db);
+            ContextDb::open_at(&db_path)
+        });
+        let history = reopened.and_then(|context_db| {
+            Ok((
+                context_db.get_schema_version()?,
+                context_db.find_messages(&HistoryQuery::new())?,
+            ))
+        });
+        fs::remove_dir_all(&db_dir)?;
+
+        let (schema_version, messages) = history?;
+        assert_eq!(schema_version, MIGRATIONS.len());
+        assert_eq!(get_contents(&messages), ["Hello"]);
+        assert_eq!(messages[0].session, "reopened");
+        Ok(())
+    }
+
+    #[test]
+    fn search_words_are_quoted_for_full_text_search() -> Result<()> {
+        for (search, fts_query) in [
+            ("rust err", r#""rust"* "err"*"#),
+            (r#"say "hi""#, r#""say"* """hi"""*"#),
+            ("build*", r#""build*"*"#),
+            ("-rf", r#""-rf"*"#),
+            ("NEAR", r#""NEAR"*"#),
+        ] {
+            assert_eq!(to_fts_query(search), fts_query, "{}", search);
+        }
+
+        let mut context_db = get_test_db()?;
+        let session = Session::new("search", "gpt-4o-mini", ApiProvider::OPENAI);
+        let now = Utc::now();
+        context_db.record_messages(
+            &session,
+            &[
+                get_message("rm -rf build", now),
+                get_message(r#"Just say "hi" to them"#, now),
+                get_message("The station is NEAR the end of the line", now),
+            ],
+        )?;
+        for (search, expected) in [
+            ("-rf", vec!["rm -rf build"]),
+            (r#""hi""#, vec![r#"Just say "hi" to them"#]),
+            ("NEAR end", vec!["The station is NEAR the end of the line"]),
+            ("build*", vec!["rm -rf build"]),
+            ("OR", vec![]),
+        ] {
+            let query = HistoryQuery {
+                search: Some(String::from(search)),
+                ..HistoryQuery::new()
+            };
+            let messages = context_db.find_messages(&query)?;
+            assert_eq!(get_contents(&messages), expected, "{}", search);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn messages_are_filtered_by_date() -> Result<()> {
+        let mut context_db = get_test_db()?;
+        let session = Session::new("dates", "gpt-4o-mini", ApiProvider::OPENAI);
+        let times: Vec<DateTime<Utc>> = (1..=3)
+            .filter_map(|day| Utc.with_ymd_and_hms(2026, 3, day, 12, 0, 0).single())
+            .collect();
+        let messages: Vec<SessionMessage> = times
+            .iter()
+            .zip(["first", "second", "third"])
+            .map(|(timestamp, content)| get_message(content, *timestamp))
+            .collect();
+        context_db.record_messages(&session, &messages)?;
+
+        for (since, until, expected) in [
+            (Some(times[1]), None, vec!["second", "third"]),
+            (None, Some(times[1]), vec!["first"]),
+            (Some(times[0]), Some(times[2]), vec!["first", "second"]),
+            (Some(times[2]), Some(times[0]), vec![]),
+        ] {
+            let query = HistoryQuery {
+                since,
+                until,
+                ..HistoryQuery::new()
+            };
+            let messages = context_db.find_messages(&query)?;
+            assert_eq!(get_contents(&messages), expected, "{:?} {:?}", since, until);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn renaming_a_session_moves_or_merges_its_history() -> Result<()> {
+        let mut context_db = get_test_db()?;
+        let now = Utc::now();
+        for (name, content) in [("draft", "Hello"), ("kept", "Hi"), ("notes", "Hey")] {
+            let session = Session::new(name, "gpt-4o-mini", ApiProvider::OPENAI);
+            context_db.record_messages(&session, &[get_message(content, now)])?;
+        }
+        let session = Session::new("draft", "gpt-4o-mini", ApiProvider::OPENAI);
+        context_db.record_action(&session, "/set", "temperature 0.7", &Ok(String::new()))?;
+
+        // A name already recorded has the histories merged, and a new name takes the history over
+        context_db.rename_session("draft", "kept")?;
+        context_db.rename_session("notes", "ideas")?;
+        let mut sessions: Vec<(String, u64, u64)> = context_db
+            .list_sessions()?
+            .into_iter()
+            .map(|session| (session.name, session.message_count, session.action_count))
+            .collect();
+        sessions.sort();
+        assert_eq!(
+            sessions,
+            [(String::from("ideas"), 1, 0), (String::from("kept"), 2, 1)]
+        );
+
+        let query = HistoryQuery {
+            session: Some(String::from("kept")),
+            ..HistoryQuery::new()
+        };
+        let mut contents: Vec<String> = context_db
+            .find_messages(&query)?
+            .into_iter()
+            .map(|message| message.content)
+            .collect();
+        contents.sort();
+        assert_eq!(contents, ["Hello", "Hi"]);
+        Ok(())
+    }
+}
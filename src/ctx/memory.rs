@@ -0,0 +1,546 @@
+//! # Long-Term Memory
+//! This module contains the long-term memory of nyota, which remembers facts about the user across chats.
+//! When a chat is closed, the model is asked for the lasting facts in it (the user's preferences, projects, environment...),
+//! and each fact is embedded by an embedding model (see `nyota::api::embeddings`) and kept in the context database (see `nyota::ctx::db`).
+//! With each new prompt, the facts most similar to it in meaning are recalled and given to the model ahead of the conversation,
+//! along with the pinned facts, which are given with every prompt.
+//!
+//! The memory is off unless `enabled = true` is set in the `[memory]` table of the nyota config file.
+//! Facts are embedded on the current provider if it offers embeddings (OpenAI, Ollama and OpenAI-compatible servers), or on the `provider` set;
+//! a fact is only compared to prompts embedded by the same `embedding_model`. `nyota memory` lists, adds, pins and forgets the facts.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use nyota::api::conversation::ChatMessage;
+//! use nyota::api::utilities::Adapter;
+//! use nyota::ctx::memory::{add_memories_to_conversation, LongTermMemory, MemorySettings};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let adapter = Adapter::new();
+//! let mut memory = LongTermMemory::open(MemorySettings::load())?;
+//! memory.remember(&adapter, None, &[String::from("The user deploys their services to Fly.io")]).await?;
+//!
+//! let mut conversation = vec![ChatMessage::user("How do I add a health check?")];
+//! let memories = memory.recall(&adapter, "How do I add a health check?").await?;
+//! add_memories_to_conversation(&mut conversation, &adapter.get_persona().system_prompt, &memories);
+//! let reply = adapter.send_conversation(&conversation).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ```toml
+//! [memory]
+//! enabled = true
+//! provider = "ollama"
+//! embedding_model = "nomic-embed-text"
+//! top_k = 5
+//! min_similarity = 0.3
+//! ```
+
+use crate::api::conversation::{ChatMessage, Role};
+use crate::api::embeddings::{cosine_similarity, get_default_embedding_model};
+use crate::api::structured::ResponseSchema;
+use crate::api::utilities::{Adapter, ApiProvider};
+use crate::cfg::config::read_config_table;
+use crate::ctx::constants::*;
+use crate::ctx::db::{ContextDb, Memory};
+use crate::ctx::session::Session;
+use anyhow::{Error, Result};
+use serde_json::json;
+use std::cmp::Ordering;
+
+/// MemorySettings holds the `[memory]` table: whether facts are remembered, how they are embedded, and how many are recalled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemorySettings {
+    /// Whether facts are remembered from chats, and recalled with prompts.
+    pub enabled: bool,
+    /// The provider which embeds the facts, or `None` for the current provider.
+    pub provider: Option<ApiProvider>,
+    /// The model which embeds the facts, or `None` for the default embedding model of the provider.
+    pub embedding_model: Option<String>,
+    /// The number of memories, besides the pinned ones, recalled with each prompt.
+    pub top_k: usize,
+    /// The least cosine similarity a memory must have to a prompt to be recalled with it.
+    pub min_similarity: f32,
+}
+
+impl MemorySettings {
+    /// Creates a new MemorySettings with the memory off, embedding on the current provider.
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            provider: None,
+            embedding_model: None,
+            top_k: DEFAULT_MEMORY_TOP_K,
+            min_similarity: DEFAULT_MEMORY_MIN_SIMILARITY,
+        }
+    }
+
+    /// Creates a new MemorySettings with the settings from the `[memory]` table of the config file applied.
+    /// Unreadable settings are reported as warnings and the defaults are kept.
+    pub fn load() -> Self {
+        let mut memory_settings = Self::new();
+        if let Err(e) = memory_settings.apply_config_file() {
+            eprintln!("{:?}", e);
+        }
+        memory_settings
+    }
+
+    /// Creates a new MemorySettings with the settings from the `[memory]` table of the config file applied.
+    /// Unlike `load`, unreadable settings are returned as an error (i.e. for the chat interface, which shows them as notices).
+    pub fn read() -> Result<Self> {
+        let mut memory_settings = Self::new();
+        memory_settings.apply_config_file()?;
+        Ok(memory_settings)
+    }
+
+    /// Returns the provider and model which embed the facts: those set, or the current provider of the Adapter and its default embedding model.
+    /// Returns an error if the provider offers no embeddings and no model is set.
+    pub fn get_embedder(&self, api_adapter: &Adapter) -> Result<(ApiProvider, String)> {
+        let provider = self
+            .provider
+            .unwrap_or_else(|| api_adapter.get_current_provider());
+        let embedding_model = match &self.embedding_model {
+            Some(embedding_model) => embedding_model.clone(),
+            None => get_default_embedding_model(provider)
+                .map(String::from)
+                .ok_or_else(|| {
+                    Error::msg(format!(
+                        "🟡[MEMORY] 💔🧠 Warning: {} offers no embeddings; set `provider` in the `[{}]` table of the config file.",
+                        provider, CONFIG_MEMORY_TABLE
+                    ))
+                })?,
+        };
+        Ok((provider, embedding_model))
+    }
+
+    /// Applies the settings from the `[memory]` table of the nyota config file.
+    fn apply_config_file(&mut self) -> Result<(), Error> {
+        let Some(memory_table) = read_config_table(CONFIG_MEMORY_TABLE)? else {
+            return Ok(());
+        };
+
+        for (setting, value) in memory_table {
+            let invalid_setting = |expected: &str| {
+                Error::msg(format!(
+                    "🟡[CONFIG] 💔🧠 Warning: Memory setting `{}` must be {}.",
+                    setting, expected
+                ))
+            };
+            match setting.as_str() {
+                "enabled" => {
+                    self.enabled = value
+                        .as_bool()
+                        .ok_or_else(|| invalid_setting("true or false"))?
+                }
+                "provider" => {
+                    self.provider = Some(
+                        value
+                            .as_str()
+                            .ok_or_else(|| invalid_setting("a provider name"))?
+                            .parse()?,
+                    )
+                }
+                "embedding_model" => {
+                    self.embedding_model = Some(String::from(
+                        value
+                            .as_str()
+                            .ok_or_else(|| invalid_setting("a model name"))?,
+                    ))
+                }
+                "top_k" => {
+                    self.top_k = value
+                        .as_integer()
+                        .and_then(|top_k| usize::try_from(top_k).ok())
+                        .ok_or_else(|| invalid_setting("a positive integer"))?
+                }
+                "min_similarity" => {
+                    self.min_similarity = value
+                        .as_float()
+                        .or_else(|| value.as_integer().map(|similarity| similarity as f64))
+                        .filter(|similarity| (-1.0..=1.0).contains(similarity))
+                        .ok_or_else(|| invalid_setting("a number from -1 to 1"))?
+                        as f32
+                }
+                _ => {
+                    return Err(Error::msg(format!(
+                        "🟡[CONFIG] 💔🧠 Warning: Unknown memory setting `{}`",
+                        setting
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for MemorySettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// LongTermMemory remembers facts in the context database, and recalls those relevant to a prompt.
+pub struct LongTermMemory {
+    context_db: ContextDb,
+    settings: MemorySettings,
+}
+
+impl LongTermMemory {
+    /// Opens the long-term memory in the context database, with the settings.
+    pub fn open(settings: MemorySettings) -> Result<Self> {
+        Ok(Self {
+            context_db: ContextDb::open()?,
+            settings,
+        })
+    }
+
+    /// Returns the settings of the memory.
+    pub fn get_settings(&self) -> &MemorySettings {
+        &self.settings
+    }
+
+    /// Returns the memories to give to the model with the prompt: the pinned ones, then up to `top_k` of the others
+    /// which are the most similar to it (and at least `min_similarity`), the most similar first. The recalled memories are noted as such.
+    pub async fn recall(&mut self, api_adapter: &Adapter, prompt: &str) -> Result<Vec<Memory>> {
+        let mut memories: Vec<Memory> = self
+            .context_db
+            .list_memories()?
+            .into_iter()
+            .filter(|memory| memory.pinned)
+            .collect();
+        let (provider, embedding_model) = self.settings.get_embedder(api_adapter)?;
+        let candidates = self.context_db.get_memory_embeddings(&embedding_model)?;
+        if self.settings.top_k > 0 && candidates.iter().any(|(memory, _)| !memory.pinned) {
+            let prompt_embedding = api_adapter
+                .embed(provider, &embedding_model, &[String::from(prompt)])
+                .await?
+                .pop()
+                .unwrap_or_default();
+            let mut scored: Vec<(f32, Memory)> = candidates
+                .into_iter()
+                .filter(|(memory, _)| !memory.pinned)
+                .map(|(memory, embedding)| {
+                    (cosine_similarity(&prompt_embedding, &embedding), memory)
+                })
+                .filter(|(similarity, _)| *similarity >= self.settings.min_similarity)
+                .collect();
+            scored.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+            memories.extend(
+                scored
+                    .into_iter()
+                    .take(self.settings.top_k)
+                    .map(|(_, memory)| memory),
+            );
+        }
+        let memory_ids: Vec<i64> = memories.iter().map(|memory| memory.id).collect();
+        self.context_db.mark_memories_recalled(&memory_ids)?;
+        Ok(memories)
+    }
+
+    /// Remembers the facts, from the session if they were remembered from a chat.
+    /// Facts already remembered, or as good as the same as one already remembered, are skipped.
+    /// Returns the new memories.
+    pub async fn remember(
+        &self,
+        api_adapter: &Adapter,
+        session: Option<&Session>,
+        facts: &[String],
+    ) -> Result<Vec<Memory>> {
+        let facts: Vec<String> = facts
+            .iter()
+            .map(|fact| String::from(fact.trim()))
+            .filter(|fact| !fact.is_empty())
+            .collect();
+        if facts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let (provider, embedding_model) = self.settings.get_embedder(api_adapter)?;
+        let embeddings = api_adapter
+            .embed(provider, &embedding_model, &facts)
+            .await?;
+
+        let mut known_embeddings: Vec<Vec<f32>> = self
+            .context_db
+            .get_memory_embeddings(&embedding_model)?
+            .into_iter()
+            .map(|(_, embedding)| embedding)
+            .collect();
+        let mut new_memory_ids = Vec::new();
+        for (fact, embedding) in facts.iter().zip(embeddings) {
+            let is_duplicate = known_embeddings.iter().any(|known_embedding| {
+                cosine_similarity(known_embedding, &embedding) >= MEMORY_DUPLICATE_SIMILARITY
+            });
+            if is_duplicate {
+                continue;
+            }
+            if let Some(memory_id) =
+                self.context_db
+                    .add_memory(session, fact, &embedding_model, &embedding)?
+            {
+                new_memory_ids.push(memory_id);
+            }
+            known_embeddings.push(embedding);
+        }
+        Ok(self
+            .context_db
+            .list_memories()?
+            .into_iter()
+            .filter(|memory| new_memory_ids.contains(&memory.id))
+            .collect())
+    }
+
+    /// Asks the model for the lasting facts in the chat messages (the latest `MAX_MEMORY_EXTRACTION_MESSAGES` of them) and remembers them.
+    /// Returns the new memories.
+    pub async fn extract(
+        &self,
+        api_adapter: &Adapter,
+        session: Option<&Session>,
+        messages: &[ChatMessage],
+    ) -> Result<Vec<Memory>> {
+        let mut transcript: Vec<String> = messages
+            .iter()
+            .filter(|message| message.role != Role::System)
+            .map(|message| format!("{}: {}", message.role.as_str(), message.get_text_content()))
+            .collect();
+        transcript.drain(
+            ..transcript
+                .len()
+                .saturating_sub(MAX_MEMORY_EXTRACTION_MESSAGES),
+        );
+        if transcript.is_empty() {
+            return Ok(Vec::new());
+        }
+        let response_schema = ResponseSchema::new(
+            MEMORY_SCHEMA_NAME,
+            json!({
+                "type": "object",
+                "properties": {
+                    "facts": {"type": "array", "items": {"type": "string"}}
+                },
+                "required": ["facts"]
+            }),
+        );
+        let extraction = api_adapter
+            .send_structured(
+                &[
+                    ChatMessage::system(MEMORY_EXTRACTION_PROMPT),
+                    ChatMessage::user(&transcript.join("\n\n")),
+                ],
+                &response_schema,
+            )
+            .await?;
+        let facts: Vec<String> = extraction.value["facts"]
+            .as_array()
+            .map(|facts| {
+                facts
+                    .iter()
+                    .filter_map(|fact| fact.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.remember(api_adapter, session, &facts).await
+    }
+
+    /// Returns every memory, the pinned ones first.
+    pub fn list(&self) -> Result<Vec<Memory>> {
+        self.context_db.list_memories()
+    }
+
+    /// Pins the memory, to be given to the model with every prompt, or unpins it.
+    pub fn set_pinned(&self, memory_id: i64, pinned: bool) -> Result<()> {
+        self.context_db.set_memory_pinned(memory_id, pinned)
+    }
+
+    /// Forgets the memory.
+    pub fn forget(&self, memory_id: i64) -> Result<()> {
+        self.context_db.forget_memory(memory_id)
+    }
+}
+
+/// Gives the memories to the model ahead of the conversation, after the system prompt.
+/// They are added to the system message the conversation starts with, if it has one; otherwise a system message is added
+/// with the system prompt (i.e. the persona's) and the memories, as a system message in the conversation takes the place of the persona's.
+pub fn add_memories_to_conversation(
+    conversation: &mut Vec<ChatMessage>,
+    system_prompt: &str,
+    memories: &[Memory],
+) {
+    if memories.is_empty() {
+        return;
+    }
+    let memory_context = format!(
+        "{}\n{}",
+        MEMORY_CONTEXT_HEADER,
+        memories
+            .iter()
+            .map(|memory| format!("- {}", memory.content))
+            .collect::<Vec<String>>()
+            .join("\n")
+    );
+    match conversation.first_mut() {
+        Some(message) if message.role == Role::System => {
+            message.content = format!("{}\n\n{}", message.content, memory_context)
+        }
+        _ => {
+            let system_content = if system_prompt.is_empty() {
+                memory_context
+            } else {
+                format!("{}\n\n{}", system_prompt, memory_context)
+            };
+            conversation.insert(0, ChatMessage::system(&system_content));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::utilities::tests::{get_response, get_test_adapter, serve_responses};
+    use std::{env, fs, path::PathBuf};
+
+    /// The model which embeds the memories of the tests.
+    const TEST_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+
+    /// Opens a long-term memory in an empty temporary context database, embedding on OpenAI.
+    /// Returns the memory and the temporary directory of the database.
+    fn get_test_memory(name: &str, top_k: usize) -> Result<(LongTermMemory, PathBuf)> {
+        let db_dir = env::temp_dir().join(format!("nyota-memory-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&db_dir);
+        let memory = LongTermMemory {
+            context_db: ContextDb::open_at(&db_dir.join("context.db"))?,
+            settings: MemorySettings {
+                enabled: true,
+                provider: Some(ApiProvider::OPENAI),
+                embedding_model: Some(String::from(TEST_EMBEDDING_MODEL)),
+                top_k,
+                min_similarity: 0.3,
+            },
+        };
+        Ok((memory, db_dir))
+    }
+
+    /// Remembers the fact with the embedding, and returns the id of the memory.
+    fn add_test_memory(
+        memory: &LongTermMemory,
+        fact: &str,
+        embedding_model: &str,
+        embedding: &[f32],
+    ) -> Result<i64> {
+        memory
+            .context_db
+            .add_memory(None, fact, embedding_model, embedding)?
+            .ok_or_else(|| Error::msg(format!("`{}` is already remembered", fact)))
+    }
+
+    /// Returns the contents of the memories, in order.
+    fn get_contents(memories: &[Memory]) -> Vec<&str> {
+        memories
+            .iter()
+            .map(|memory| memory.content.as_str())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn recall_gives_the_pinned_memories_then_the_most_similar() -> Result<()> {
+        let (base_url, server) = serve_responses(vec![get_response(
+            "200 OK",
+            "",
+            r#"{"data": [{"index": 0, "embedding": [1.0, 0.0, 0.0]}]}"#,
+        )])?;
+        let adapter = get_test_adapter(&base_url)?;
+        let (mut memory, db_dir) = get_test_memory("recall", 3)?;
+        add_test_memory(
+            &memory,
+            "The user writes Rust",
+            TEST_EMBEDDING_MODEL,
+            &[0.8, 0.6, 0.0],
+        )?;
+        add_test_memory(
+            &memory,
+            "The user deploys to Fly.io",
+            TEST_EMBEDDING_MODEL,
+            &[1.0, 0.0, 0.0],
+        )?;
+        add_test_memory(
+            &memory,
+            "The user has two cats",
+            TEST_EMBEDDING_MODEL,
+            &[0.0, 0.0, 1.0],
+        )?;
+        add_test_memory(
+            &memory,
+            "The user deploys to Render",
+            "nomic-embed-text",
+            &[1.0, 0.0, 0.0],
+        )?;
+        let pinned_id = add_test_memory(
+            &memory,
+            "The user speaks Swahili",
+            TEST_EMBEDDING_MODEL,
+            &[0.0, 1.0, 0.0],
+        )?;
+        memory.set_pinned(pinned_id, true)?;
+
+        // Memories below the least similarity, or embedded by another model, are not recalled
+        let memories = memory.recall(&adapter, "Where should I deploy?").await?;
+        assert_eq!(
+            get_contents(&memories),
+            [
+                "The user speaks Swahili",
+                "The user deploys to Fly.io",
+                "The user writes Rust"
+            ]
+        );
+        assert_eq!(server.join().ok().map(|requests| requests.len()), Some(1));
+
+        let recall_counts: Vec<(String, u64)> = memory
+            .list()?
+            .into_iter()
+            .map(|memory| (memory.content, memory.recall_count))
+            .collect();
+        for (content, recall_count) in recall_counts {
+            let expected = u64::from(get_contents(&memories).contains(&content.as_str()));
+            assert_eq!(recall_count, expected, "{}", content);
+        }
+
+        fs::remove_dir_all(&db_dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pinned_memories_are_recalled_until_unpinned_or_forgotten() -> Result<()> {
+        // No memory is recalled by similarity, so no prompt is embedded
+        let adapter = get_test_adapter("http://127.0.0.1:9/v1")?;
+        let (mut memory, db_dir) = get_test_memory("pin", 0)?;
+        let rust_id = add_test_memory(
+            &memory,
+            "The user writes Rust",
+            TEST_EMBEDDING_MODEL,
+            &[1.0, 0.0],
+        )?;
+        let swahili_id = add_test_memory(
+            &memory,
+            "The user speaks Swahili",
+            TEST_EMBEDDING_MODEL,
+            &[0.0, 1.0],
+        )?;
+        assert!(memory.recall(&adapter, "Hello").await?.is_empty());
+
+        memory.set_pinned(rust_id, true)?;
+        memory.set_pinned(swahili_id, true)?;
+        assert_eq!(memory.recall(&adapter, "Hello").await?.len(), 2);
+
+        memory.set_pinned(swahili_id, false)?;
+        memory.forget(rust_id)?;
+        assert!(memory.recall(&adapter, "Hello").await?.is_empty());
+        assert_eq!(get_contents(&memory.list()?), ["The user speaks Swahili"]);
+
+        // A memory which is gone cannot be pinned or forgotten again
+        assert!(memory.set_pinned(rust_id, true).is_err());
+        assert!(memory.forget(rust_id).is_err());
+
+        fs::remove_dir_all(&db_dir)?;
+        Ok(())
+    }
+}
@@ -3,5 +3,40 @@
 //! This module is the context module for `nyota`. This module contains the complete implementation of the context system.
 //! It provides functions for managing the context of the program, including the user's state, preferences, and history.
 //! The context system is used by `nyota` to provide a persistent and flexible interface for storing and retrieving user data.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use nyota::ctx::db::{ContextDb, HistoryQuery};
+//! use nyota::ctx::session::{list_sessions, Session};
+//!
+//! # fn example() -> anyhow::Result<()> {
+//! for session in list_sessions()? {
+//!     println!("{} | {} messages", session.name, session.messages.len());
+//! }
+//! let session = Session::load("release-notes")?;
+//! session.fork("release-notes-v2")?;
+//!
+//! let context_db = ContextDb::open()?;
+//! let query = HistoryQuery {
+//!     search: Some(String::from("changelog")),
+//!     ..HistoryQuery::new()
+//! };
+//! for message in context_db.find_messages(&query)? {
+//!     println!("[{}] {}: {}", message.session, message.role, message.content);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Modules
+//! - `cache`: Cache module for Nyota. This module provides the fast cache of recent prompts, model lists and replies, kept in Redis or in memory.
+//! - `constants`: Constants module for Nyota. This module provides the names of the files where the context is kept.
+//! - `db`: Context Database module for Nyota. This module provides the local memory of user interactions: an SQLite history of every chat, queryable by session, date and full text.
+//! - `memory`: Long-Term Memory module for Nyota. This module provides the facts remembered from chats, embedded and recalled by their similarity to new prompts.
+//! - `session`: Session module for Nyota. This module provides the chat sessions saved to disk, which can be reopened, renamed, deleted and forked.
 
 pub mod cache;
+pub mod constants;
+pub mod db;
+pub mod memory;
+pub mod session;
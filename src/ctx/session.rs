@@ -0,0 +1,403 @@
+//! # Chat Sessions
+//! This module contains the chat sessions of interactive mode, saved to disk so that they can be reopened later.
+//! Every session is a JSON file in the sessions directory (`~/.local/share/nyota/sessions/<name>.json`), named after the session.
+//! It holds the messages of the chat (with their timestamps and attachments), the model and provider it last used,
+//! and the tokens and money its replies have used.
+//! Sessions are saved as the chat goes, and can be reopened (`--session <name>`, or `--resume` for the latest one),
+//! renamed, deleted and forked from the session browser of the menu (see `nyota::tui::sessions`).
+
+use crate::api::attachment::Attachment;
+use crate::api::constants::CONFIG_DIR_NAME;
+use crate::api::usage::UsageTotals;
+use crate::api::utilities::ApiProvider;
+use crate::ctx::constants::*;
+use anyhow::{Error, Result};
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+/// SessionMessage is a message of a chat session, as it is shown in the chat.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionMessage {
+    /// The text of the message.
+    pub content: String,
+    /// When the message was sent, or its reply started.
+    pub timestamp: DateTime<Utc>,
+    /// Whether the message is from the user (otherwise it is from the AI, or a notice from nyota).
+    pub is_user: bool,
+    /// Whether the message reports an error rather than a reply.
+    #[serde(default)]
+    pub is_error: bool,
+    /// Whether the message is a notice from nyota (i.e. a command result) rather than part of the conversation.
+    #[serde(default)]
+    pub is_notice: bool,
+    /// The files attached to a user message.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+}
+
+/// Session is a chat session, saved to the sessions directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// The name of the session (the file name of the session, without the `.json` extension).
+    pub name: String,
+    /// When the session was created (or forked).
+    pub created_at: DateTime<Utc>,
+    /// When the session was last saved.
+    pub updated_at: DateTime<Utc>,
+    /// The model the session last used.
+    pub model: String,
+    /// The API provider the session last used.
+    pub provider: ApiProvider,
+    /// The tokens used and money spent by the replies of the session, per model.
+    #[serde(default)]
+    pub usage: UsageTotals,
+    /// The messages of the session, oldest first.
+    #[serde(default)]
+    pub messages: Vec<SessionMessage>,
+}
+
+impl Session {
+    /// Creates a new, empty session with the specified name, model and provider. The session is not saved until `save` is called.
+    pub fn new(name: &str, model: &str, provider: ApiProvider) -> Self {
+        let now = Utc::now();
+        Self {
+            name: String::from(name),
+            created_at: now,
+            updated_at: now,
+            model: String::from(model),
+            provider,
+            usage: UsageTotals::new(),
+            messages: Vec::new(),
+        }
+    }
+
+    /// Loads the session with the specified name from the sessions directory.
+    /// Returns an error if the name is invalid, or the session is not saved or cannot be read.
+    pub fn load(name: &str) -> Result<Self, Error> {
+        Self::load_from(&get_sessions_dir()?, name)
+    }
+
+    /// Loads the session with the specified name from the sessions directory at the path (see `load`).
+    fn load_from(sessions_dir: &Path, name: &str) -> Result<Self, Error> {
+        let session_path = get_session_file_path_in(sessions_dir, name)?;
+        let contents = fs::read_to_string(&session_path).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => Error::msg(format!(
+                "🟡[SESSION] 🔍💬 Warning: No session named `{}`.",
+                name
+            )),
+            _ => Error::msg(format!(
+                "🟡[SESSION] 🔍💬 Warning: Unable to read {} | {}",
+                session_path.display(),
+                e
+            )),
+        })?;
+        let mut session: Session = serde_json::from_str(&contents).map_err(|e| {
+            Error::msg(format!(
+                "🟡[SESSION] 💔💬 Warning: Invalid session in {} | {}",
+                session_path.display(),
+                e
+            ))
+        })?;
+        // The file name is the name of the session, even if the file was renamed by hand
+        session.name = String::from(name);
+        Ok(session)
+    }
+
+    /// Returns true if a session with the specified name is saved.
+    pub fn exists(name: &str) -> bool {
+        get_session_file_path(name).is_ok_and(|session_path| session_path.exists())
+    }
+
+    /// Writes the session to its file in the sessions directory, marking it as updated now.
+    pub fn save(&mut self) -> Result<()> {
+        self.save_to(&get_sessions_dir()?)
+    }
+
+    /// Writes the session to its file in the sessions directory at the path (see `save`).
+    fn save_to(&mut self, sessions_dir: &Path) -> Result<()> {
+        let session_path = get_session_file_path_in(sessions_dir, &self.name)?;
+        self.updated_at = Utc::now();
+        if let Some(sessions_dir) = session_path.parent() {
+            fs::create_dir_all(sessions_dir)?;
+        }
+        fs::write(&session_path, serde_json::to_string_pretty(self)?).map_err(|e| {
+            Error::msg(format!(
+                "🔴[SESSION] Error: Unable to write {} | {}",
+                session_path.display(),
+                e
+            ))
+        })
+    }
+
+    /// Renames the session, moving its file if it has been saved.
+    /// Returns an error, leaving the session as it was, if the new name is invalid or another session has it.
+    pub fn rename(&mut self, new_name: &str) -> Result<()> {
+        if new_name == self.name {
+            return Ok(());
+        }
+        check_session_name_free(new_name)?;
+        let old_path = get_session_file_path(&self.name)?;
+        if old_path.exists() {
+            fs::rename(&old_path, get_session_file_path(new_name)?).map_err(|e| {
+                Error::msg(format!(
+                    "🔴[SESSION] Error: Unable to rename {} | {}",
+                    old_path.display(),
+                    e
+                ))
+            })?;
+        }
+        self.name = String::from(new_name);
+        Ok(())
+    }
+
+    /// Saves a copy of the session under the specified name, which continues separately from this one, and returns the copy.
+    /// Returns an error if the name is invalid or another session has it.
+    pub fn fork(&self, new_name: &str) -> Result<Session> {
+        check_session_name_free(new_name)?;
+        let mut forked_session = Session {
+            name: String::from(new_name),
+            created_at: Utc::now(),
+            ..self.clone()
+        };
+        forked_session.save()?;
+        Ok(forked_session)
+    }
+
+    /// Deletes the saved session with the specified name.
+    pub fn delete(name: &str) -> Result<()> {
+        let session_path = get_session_file_path(name)?;
+        fs::remove_file(&session_path).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => Error::msg(format!(
+                "🟡[SESSION] 🔍💬 Warning: No session named `{}`.",
+                name
+            )),
+            _ => Error::msg(format!(
+                "🔴[SESSION] Error: Unable to delete {} | {}",
+                session_path.display(),
+                e
+            )),
+        })
+    }
+}
+
+/// Returns the saved sessions, the most recently updated first.
+/// Files which cannot be read as sessions are skipped (so that the session browser is not drawn over); a missing sessions directory yields no sessions.
+pub fn list_sessions() -> Result<Vec<Session>> {
+    let Some(sessions_dir) = get_sessions_dir_path() else {
+        return Ok(Vec::new());
+    };
+    let dir_entries = match fs::read_dir(&sessions_dir) {
+        Ok(dir_entries) => dir_entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(Error::msg(format!(
+                "🟡[SESSION] 🔍💬 Warning: Unable to read {} | {}",
+                sessions_dir.display(),
+                e
+            )))
+        }
+    };
+
+    let mut sessions: Vec<Session> = dir_entries
+        .filter_map(|dir_entry| dir_entry.ok().map(|dir_entry| dir_entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == SESSION_FILE_EXTENSION)
+        })
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_str()?;
+            Session::load(name).ok()
+        })
+        .collect();
+    sessions.sort_by_key(|session| Reverse(session.updated_at));
+    Ok(sessions)
+}
+
+/// Loads the most recently updated session, for `--resume`.
+/// Returns an error if no session is saved.
+pub fn load_latest_session() -> Result<Session> {
+    list_sessions()?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::msg("🟡[SESSION] 🔍💬 Warning: No saved session to resume."))
+}
+
+/// Generates a name for an unnamed session from the current time (i.e. `chat-2025-01-31-142501`), which no saved session has.
+pub fn generate_session_name() -> String {
+    let session_name = format!(
+        "{}-{}",
+        SESSION_NAME_PREFIX,
+        Local::now().format(SESSION_NAME_TIME_FORMAT)
+    );
+    (1..)
+        .map(|suffix| match suffix {
+            1 => session_name.clone(),
+            _ => format!("{}-{}", session_name, suffix),
+        })
+        .find(|session_name| !Session::exists(session_name))
+        .unwrap_or(session_name)
+}
+
+/// Checks that the name can name a session: it must not be empty or too long,
+/// and may only hold letters, digits, `-` and `_` (so that it is a safe file name).
+pub fn validate_session_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name.chars().count() > MAX_SESSION_NAME_LENGTH
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(Error::msg(format!(
+            "🟡[SESSION] 💔💬 Warning: Invalid session name `{}` (up to {} letters, digits, `-` or `_`).",
+            name, MAX_SESSION_NAME_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+/// Returns the path of the sessions directory (i.e. `~/.local/share/nyota/sessions/`), if the user's data directory is known.
+pub fn get_sessions_dir_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|data_dir| data_dir.join(CONFIG_DIR_NAME).join(SESSIONS_DIR_NAME))
+}
+
+/// Returns the path of the sessions directory, or an error if the user's data directory is unknown.
+fn get_sessions_dir() -> Result<PathBuf> {
+    get_sessions_dir_path().ok_or_else(|| {
+        Error::msg("🟡[SESSION] 🔍💬 Warning: No data directory to save the sessions in.")
+    })
+}
+
+/// Returns the path of the file of the session with the specified name.
+/// Returns an error if the name is invalid, or the user's data directory is unknown.
+fn get_session_file_path(name: &str) -> Result<PathBuf> {
+    get_session_file_path_in(&get_sessions_dir()?, name)
+}
+
+/// Returns the path of the file of the session with the specified name in the sessions directory at the path.
+/// Returns an error if the name is invalid.
+fn get_session_file_path_in(sessions_dir: &Path, name: &str) -> Result<PathBuf> {
+    validate_session_name(name)?;
+    Ok(sessions_dir.join(format!("{}.{}", name, SESSION_FILE_EXTENSION)))
+}
+
+/// Checks that the name can name a new session: it must be valid, and no saved session may have it.
+fn check_session_name_free(name: &str) -> Result<()> {
+    validate_session_name(name)?;
+    if Session::exists(name) {
+        return Err(Error::msg(format!(
+            "🟡[SESSION] 💔💬 Warning: A session named `{}` already exists.",
+            name
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    /// Returns an empty temporary sessions directory for a test.
+    fn get_test_sessions_dir(name: &str) -> Result<PathBuf> {
+        let sessions_dir =
+            env::temp_dir().join(format!("nyota-session-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&sessions_dir);
+        fs::create_dir_all(&sessions_dir)?;
+        Ok(sessions_dir)
+    }
+
+    #[test]
+    fn session_names_are_validated() {
+        let longest_name = "a".repeat(MAX_SESSION_NAME_LENGTH);
+        let too_long_name = "a".repeat(MAX_SESSION_NAME_LENGTH + 1);
+        for (name, is_valid) in [
+            ("chat-2025-01-31-142501", true),
+            ("release_notes", true),
+            ("maandishi", true),
+            (longest_name.as_str(), true),
+            ("", false),
+            (too_long_name.as_str(), false),
+            ("../secrets", false),
+            ("notes/today", false),
+            ("two words", false),
+            ("notes.json", false),
+        ] {
+            assert_eq!(validate_session_name(name).is_ok(), is_valid, "{}", name);
+        }
+    }
+
+    #[test]
+    fn saved_sessions_are_loaded_as_they_were() -> Result<()> {
+        let sessions_dir = get_test_sessions_dir("round-trip")?;
+        let mut session = Session::new("kenya", "gpt-4o-mini", ApiProvider::OPENAI);
+        session.messages = vec![
+            SessionMessage {
+                content: String::from("What is wrong in this file?"),
+                timestamp: Utc::now(),
+                is_user: true,
+                is_error: false,
+                is_notice: false,
+                attachments: vec![Attachment::Text {
+                    name: String::from("main.rs"),
+                    content: String::from("fn main() {}"),
+                }],
+            },
+            SessionMessage {
+                content: String::from("Nothing."),
+                timestamp: Utc::now(),
+                is_user: false,
+                is_error: false,
+                is_notice: false,
+                attachments: Vec::new(),
+            },
+        ];
+
+        session.save_to(&sessions_dir)?;
+        assert!(sessions_dir.join("kenya.json").exists());
+        assert_eq!(Session::load_from(&sessions_dir, "kenya")?, session);
+
+        // The file name is the name of the session
+        fs::rename(
+            sessions_dir.join("kenya.json"),
+            sessions_dir.join("nairobi.json"),
+        )?;
+        assert_eq!(
+            Session::load_from(&sessions_dir, "nairobi")?.name,
+            "nairobi"
+        );
+
+        fs::remove_dir_all(&sessions_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn sessions_which_cannot_be_loaded_are_reported() -> Result<()> {
+        let sessions_dir = get_test_sessions_dir("errors")?;
+        fs::write(sessions_dir.join("broken.json"), "{\"name\": ")?;
+
+        for (name, expected) in [
+            ("missing", "No session named `missing`"),
+            ("broken", "Invalid session in"),
+            ("../kenya", "Invalid session name"),
+        ] {
+            let error = Session::load_from(&sessions_dir, name)
+                .err()
+                .map(|e| e.to_string())
+                .unwrap_or_default();
+            assert!(error.contains(expected), "{}: {}", name, error);
+        }
+        // A session with an invalid name is not written
+        let mut session = Session::new("../kenya", "gpt-4o-mini", ApiProvider::OPENAI);
+        assert!(session.save_to(&sessions_dir).is_err());
+        assert!(!sessions_dir.join("kenya.json").exists());
+
+        fs::remove_dir_all(&sessions_dir)?;
+        Ok(())
+    }
+}
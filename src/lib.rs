@@ -1,5 +1,6 @@
 pub mod act;
 pub mod api;
+pub mod cfg;
 pub mod cli;
 pub mod ctx;
 pub mod snd;
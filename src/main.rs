@@ -1,50 +1,231 @@
+use nyota::api::response_cache::ResponseCache;
 use nyota::api::utilities::*;
+use nyota::cfg::config::{use_config_file, Config, ConfigSource};
+use nyota::cli::config::run_config;
+use nyota::cli::constants::EXIT_USAGE_ERROR;
+use nyota::cli::development::run_development;
+use nyota::cli::history::run_history;
+use nyota::cli::keys::run_keys;
+use nyota::cli::memory::run_memory;
 use nyota::cli::modes::*;
-use nyota::snd::constants::{DEFAULT_MUTE, DEFAULT_VOLUME};
+use nyota::cli::task::{run_task, TaskOptions};
+use nyota::ctx::cache::CacheSettings;
+use nyota::ctx::session::{load_latest_session, validate_session_name, Session};
 use nyota::snd::control::AudioControl;
 use nyota::tui::banner::*;
 use nyota::tui::interactive::*;
 use nyota::tui::menu::*;
+use nyota::tui::sessions::SessionBrowser;
 use nyota::tui::splash::*;
 
 use anyhow::Result;
 use ratatui::{backend::CrosstermBackend, Terminal};
-use std::{io::Stdout, time::Duration};
+use std::{io::Stdout, process::ExitCode, time::Duration};
 use tokio::time::sleep;
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     dotenv::dotenv().ok();
 
-    println!("{}", get_banner());
-    println!("{}", get_version_plaque());
+    let mode_input = get_mode_input();
+
+    // The config file given to `--config` is read in place of the global one by every mode
+    if let Some(config_path) = mode_input.config_path.clone() {
+        if let Err(e) = use_config_file(config_path) {
+            eprintln!("{}", e);
+            return Ok(ExitCode::from(EXIT_USAGE_ERROR));
+        }
+    }
 
-    AudioControl::init(DEFAULT_MUTE, DEFAULT_VOLUME).await?;
+    // Key, config and memory management, and history queries, are meant for scripts, and quit once done
+    match &mode_input.mode {
+        Mode::Keys(keys_command) => return Ok(run_keys(keys_command).await),
+        Mode::Config(config_command) => return Ok(run_config(config_command)),
+        Mode::History(history_options) => return Ok(run_history(history_options)),
+        Mode::Memory(memory_command) => return Ok(run_memory(memory_command).await),
+        _ => {}
+    }
 
-    let default_adapter = Adapter::new();
-    let mode_input = get_mode_input();
+    let config = load_config(&mode_input);
+
+    // Task mode is meant for scripts: no banner, splash or audio, and the reply alone on stdout
+    if let Mode::Task = mode_input.mode {
+        let default_adapter = create_adapter(&config, &mode_input);
+        return Ok(handle_task(
+            default_adapter,
+            mode_input.task_prompt.as_deref(),
+            &mode_input.task_options,
+        )
+        .await);
+    }
+
+    if !config.interface.use_colors {
+        colored::control::set_override(false);
+    }
+    if mode_input.show_banner {
+        println!("{}", get_banner());
+        println!("{}", get_version_plaque());
+    }
+
+    AudioControl::init(config.sound.mute, config.sound.volume).await?;
+
+    let default_adapter = create_adapter(&config, &mode_input);
     match mode_input.mode {
-        Mode::Development => handle_development(default_adapter),
-        Mode::Interactive => handle_interactive(default_adapter).await,
-        Mode::Task => handle_task(default_adapter),
-        Mode::Menu => handle_menu(default_adapter).await,
+        Mode::Development => handle_development(default_adapter).await?,
+        Mode::Interactive => {
+            // The session is looked up before any TUI setup, so that a wrong name is reported on the terminal
+            let session = match get_session(&mode_input, &default_adapter) {
+                Ok(session) => session,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return Ok(ExitCode::from(EXIT_USAGE_ERROR));
+                }
+            };
+            handle_interactive(default_adapter, &config, session).await?
+        }
+        Mode::Task | Mode::Keys(_) | Mode::Config(_) | Mode::History(_) | Mode::Memory(_) => {} // handled above, before any TUI setup
+        Mode::Menu => handle_menu(default_adapter, &config).await?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Loads the layered config, with the settings given to `--set`, then those given as their own flags (i.e. `--volume`),
+/// taking precedence over the config files and environment variables. Invalid settings are reported and skipped.
+fn load_config(mode_input: &ModeSettings) -> Config {
+    let mut config = Config::load();
+    let config_overrides = mode_input
+        .config_overrides
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.clone()))
+        .chain(mode_input.get_config_flags());
+    for (key, value) in config_overrides {
+        if let Err(e) = config.set(key, &value, ConfigSource::Flag) {
+            eprintln!("{:?}", e);
+        }
     }
+    config
 }
 
-async fn display_splash_screen(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
-    let splash = SplashScreen::new();
+/// Creates the Adapter from the config, with the provider, system prompt, generation parameters and schema retries given as flags
+/// taking precedence over the config file and persona.
+fn create_adapter(config: &Config, mode_input: &ModeSettings) -> Adapter {
+    let mut api_adapter = Adapter::with_config(config);
+    // A model given to `--model` decides the provider, otherwise `--provider` switches to the equivalent of the default model
+    match (&mode_input.model, mode_input.provider) {
+        (Some(model), Some(provider)) if api_adapter.get_current_provider() != provider => {
+            eprintln!(
+                "🟡[ADAPTER] 🧩 Warning: {:#?} is not offered by {}, using {}.",
+                model,
+                provider,
+                api_adapter.get_current_provider()
+            );
+        }
+        (None, Some(provider)) => {
+            if let Err(e) = api_adapter.use_provider(provider) {
+                eprintln!("{:?}", e);
+            }
+        }
+        _ => {}
+    }
+    if let Some(system_prompt) = &mode_input.system_prompt {
+        let mut persona = api_adapter.get_persona().clone();
+        persona.system_prompt = system_prompt.clone();
+        api_adapter.set_persona(persona);
+    }
+    let generation_params = api_adapter
+        .get_generation_params()
+        .merge(&mode_input.generation_params);
+    api_adapter.set_generation_params(generation_params);
+    if let Some(schema_retries) = mode_input.schema_retries {
+        api_adapter.set_schema_retries(schema_retries);
+    }
+    // `--cache-ttl` turns the response cache on if the config file leaves it off
+    if mode_input.no_cache {
+        api_adapter.set_response_cache(None);
+    } else if let Some(cache_ttl) = mode_input.cache_ttl {
+        match api_adapter.get_response_cache() {
+            Some(response_cache) => response_cache.set_ttl(cache_ttl),
+            None => {
+                let cache_settings = CacheSettings {
+                    response_ttl: cache_ttl,
+                    ..api_adapter.get_cache_settings().clone()
+                };
+                api_adapter.set_response_cache(Some(ResponseCache::connect(&cache_settings)));
+            }
+        }
+    }
+    if let Some(fallback_reason) = api_adapter
+        .get_response_cache()
+        .and_then(ResponseCache::get_fallback_reason)
+    {
+        eprintln!("{}", fallback_reason);
+        eprintln!("🟡[CACHE] 🗃️ Warning: Replies are cached in memory for this run only.");
+    }
+    api_adapter
+}
+
+/// Returns the chat session given to `--session` (reopened if it is saved, otherwise started under that name),
+/// or the most recent session for `--resume`. Returns `None` if neither was given, for the chat to start a new session.
+fn get_session(mode_input: &ModeSettings, api_adapter: &Adapter) -> Result<Option<Session>> {
+    match (&mode_input.session_name, mode_input.resume) {
+        (Some(session_name), _) if Session::exists(session_name) => {
+            Session::load(session_name).map(Some)
+        }
+        (Some(session_name), _) => {
+            validate_session_name(session_name)?;
+            Ok(Some(Session::new(
+                session_name,
+                api_adapter.get_current_model(),
+                api_adapter.get_current_provider(),
+            )))
+        }
+        (None, true) => load_latest_session().map(Some),
+        (None, false) => Ok(None),
+    }
+}
+
+async fn display_splash_screen(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    config: &Config,
+) -> Result<()> {
+    let splash = SplashScreen::with_settings(&config.splash);
     splash.show(terminal)
 }
 
 async fn display_main_menu(
     terminal: &mut Terminal<CrosstermBackend<Stdout>>,
     api_adapter: Adapter,
+    config: &Config,
 ) -> Result<MenuAction> {
     let mut menu = Menu::new();
-    match menu.run(terminal).await? {
-        MenuAction::Interactive => handle_interactive(api_adapter).await?,
-        MenuAction::Task => handle_task(api_adapter)?,
-        MenuAction::Development => handle_development(api_adapter)?,
+    menu.set_use_colors(config.interface.use_colors);
+    let menu_action = loop {
+        match menu.run(terminal).await? {
+            MenuAction::Sessions => {
+                let mut session_browser = SessionBrowser::new();
+                session_browser.set_use_colors(config.interface.use_colors);
+                // Going back from the browser returns to the menu
+                if let Some(session) = session_browser.run(terminal).await? {
+                    handle_interactive(api_adapter, config, Some(session)).await?;
+                    return Ok(MenuAction::Exit);
+                }
+            }
+            menu_action => break menu_action,
+        }
+    };
+    match menu_action {
+        MenuAction::Interactive => handle_interactive(api_adapter, config, None).await?,
+        MenuAction::Sessions => {} // handled above, where the browser can go back to the menu
+        MenuAction::Task => {
+            // Leave the TUI so the task can be typed and its reply printed on the terminal
+            ratatui::restore();
+            handle_task(api_adapter, None, &TaskOptions::new()).await;
+        }
+        MenuAction::Development => {
+            // Leave the TUI so the raw exchanges can be printed on the terminal
+            ratatui::restore();
+            handle_development(api_adapter).await?
+        }
         MenuAction::Help => {
             todo!("  /* TODO: Implement help */ ")
         }
@@ -56,31 +237,44 @@ async fn display_main_menu(
     Ok(MenuAction::Exit)
 }
 
-async fn handle_menu(api_adapter: Adapter) -> Result<()> {
+async fn handle_menu(api_adapter: Adapter, config: &Config) -> Result<()> {
     AudioControl::play_welcome_chirp().await?;
     sleep(Duration::from_millis(500)).await; // DEBUG - sleep so we can read initialisation messages
 
     // Initialize terminal once
     let mut terminal = ratatui::init();
 
-    display_splash_screen(&mut terminal).await?;
-    display_main_menu(&mut terminal, api_adapter).await?;
+    if config.splash.enabled {
+        display_splash_screen(&mut terminal, config).await?;
+    }
+    display_main_menu(&mut terminal, api_adapter, config).await?;
 
     // Cleanup
     ratatui::restore();
     Ok(())
 }
 
-fn handle_task(_api_adapter: Adapter) -> Result<()> {
-    todo!("TODO:implement handle task");
+async fn handle_task(
+    api_adapter: Adapter,
+    task_prompt: Option<&str>,
+    task_options: &TaskOptions,
+) -> ExitCode {
+    run_task(&api_adapter, task_prompt, task_options).await
 }
 
-async fn handle_interactive(api_adapter: Adapter) -> Result<()> {
+async fn handle_interactive(
+    api_adapter: Adapter,
+    config: &Config,
+    session: Option<Session>,
+) -> Result<()> {
     // Initialize terminal
     let mut terminal = ratatui::init();
 
     // Create and run chat interface
-    let mut chat = ChatInterface::new(api_adapter).await;
+    let mut chat = ChatInterface::new(api_adapter, config.interface.clone()).await;
+    if let Some(session) = session {
+        chat.open_session(session);
+    }
     let result = chat.run(&mut terminal).await;
 
     // Cleanup
@@ -89,6 +283,6 @@ async fn handle_interactive(api_adapter: Adapter) -> Result<()> {
     result
 }
 
-fn handle_development(_api_adapter: Adapter) -> Result<()> {
-    todo!("TODO:implement handle development");
+async fn handle_development(mut api_adapter: Adapter) -> Result<()> {
+    run_development(&mut api_adapter).await
 }
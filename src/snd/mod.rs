@@ -13,24 +13,27 @@
 //! The `Source` is played asynchronously using the `rodio` crate.
 //!
 //! ## Example
-//! ```rust
+//! ```rust,no_run
 //! use nyota::snd::control::AudioControl;
 //! use nyota::snd::constants::{DEFAULT_MUTE, DEFAULT_VOLUME};
 //!
+//! # async fn example() -> anyhow::Result<()> {
 //! // Initialize the audio system with sane defaults
 //! // It is a global singleton and should be initialized only once at the start of the program.
-//! AudioControl::init(DEFAULT_MUTE, DEFAULT_VOLUME);
+//! AudioControl::init(DEFAULT_MUTE, DEFAULT_VOLUME).await?;
 //!
 //! // Play a sound effect
-//! AudioControl.play_welcome_chirp().await;
+//! AudioControl::play_welcome_chirp().await?;
 //!
 //! // Increase the volume
-//! let current_volume = AudioControl.get_volume();
-//! AudioControl.set_volume(current_volume + 10);
+//! let audio = AudioControl::global()?;
+//! let current_volume = audio.get_volume();
+//! audio.set_volume(current_volume + 10);
 //!
 //! // Mute the audio
-//! AudioControl.set_mute(true);
-//!
+//! audio.set_muted(true);
+//! # Ok(())
+//! # }
 //! ```
 //!
 //! ## Modules
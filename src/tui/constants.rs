@@ -0,0 +1,53 @@
+//! Constants for the textual user interface.
+//! These values tune how the chat interface reports its state, such as when the status line warns about the context window or the daily budget,
+//! and name the commands which can be typed into the chat.
+
+// *** Interface Defaults ***
+// (overridable via the `[interface]` and `[splash]` tables of the config file, see `nyota::cfg::config`)
+/// Share of the model's context window above which the status line shows the context usage as a warning.
+pub const DEFAULT_CONTEXT_WARNING_RATIO: f64 = 0.8;
+/// Share of the daily budget above which the status line shows today's spend as a warning.
+pub const DEFAULT_BUDGET_WARNING_RATIO: f64 = 0.8;
+/// Percentage of the message area kept for the chat history when the replies of compared models are shown below it.
+pub const DEFAULT_COMPARE_HISTORY_PERCENTAGE: u16 = 35;
+/// Height of the chat input area in lines, including its border.
+pub const DEFAULT_INPUT_HEIGHT: u16 = 5;
+/// Number of steps the splash screen reveals the logo in.
+pub const DEFAULT_SPLASH_RENDER_STEPS: u32 = 6;
+/// Whether the chat interface and the menu are drawn in color.
+pub const DEFAULT_USE_COLORS: bool = true;
+/// Whether the splash screen draws the logo in color.
+pub const DEFAULT_SPLASH_USE_COLORS: bool = true;
+
+// *** Chat Commands ***
+// (typed into the chat input, see `nyota::tui::interactive`)
+/// The prefix which marks chat input as a command rather than a message.
+pub const COMMAND_PREFIX: &str = "/";
+/// The chat command which shows or changes the generation parameters (i.e. `/set temperature 0.7`).
+pub const SET_COMMAND: &str = "/set";
+/// The chat command which lists the personas or switches to one (i.e. `/persona reviewer`).
+pub const PERSONA_COMMAND: &str = "/persona";
+/// The chat command which attaches a file to the next message (i.e. `/attach screenshot.png`).
+pub const ATTACH_COMMAND: &str = "/attach";
+/// The chat command which sends each message to several models at once, side by side (i.e. `/compare gpt-4o-mini claude-3-5-haiku-20241022`).
+pub const COMPARE_COMMAND: &str = "/compare";
+/// The chat command which searches the messages of every chat recorded in the context database (i.e. `/history monad`), or lists the latest chats.
+pub const HISTORY_COMMAND: &str = "/history";
+/// The chat command which lists the facts remembered from the chats, or pins, unpins or forgets one (i.e. `/memory pin 3`).
+pub const MEMORY_COMMAND: &str = "/memory";
+/// The `/memory` argument which pins a memory, to be given to the model with every prompt.
+pub const MEMORY_PIN_ARGUMENT: &str = "pin";
+/// The `/memory` argument which unpins a memory.
+pub const MEMORY_UNPIN_ARGUMENT: &str = "unpin";
+/// The `/memory` argument which forgets a memory.
+pub const MEMORY_FORGET_ARGUMENT: &str = "forget";
+/// The argument which clears what a command set (i.e. `/attach none` detaches the files, `/compare none` stops comparing).
+pub const NONE_ARGUMENT: &str = "none";
+/// The most models which can be compared side by side.
+pub const MAX_COMPARE_MODELS: usize = 4;
+/// The most messages `/history <text>` shows, or chats `/history` lists.
+pub const HISTORY_RESULT_LIMIT: usize = 5;
+/// The most memories `/memory` lists.
+pub const MEMORY_RESULT_LIMIT: usize = 20;
+/// The most characters of each message `/history <text>` shows.
+pub const HISTORY_SNIPPET_LENGTH: usize = 80;
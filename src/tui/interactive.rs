@@ -2,10 +2,35 @@
 //! This module provides an interactive chat interface using the features from the `ratatui` crate.
 //! The chat interface allows the user to send messages to an AI model and receive responses.
 //! The chat interface is designed to be interactive and user-friendly.
+//! Every chat is a session which is saved as it goes, and can be reopened later (see `nyota::ctx::session`).
+//! Its messages and commands are also recorded in the context database (see `nyota::ctx::db`), whose history `/history` searches.
+//! If the long-term memory is on, the facts it recalls are given to the model with each message, and the lasting facts of the chat
+//! are remembered when it is closed (see `nyota::ctx::memory`); `/memory` lists, pins, unpins and forgets them.
+//! The prompts sent are kept in the fast cache (see `nyota::ctx::cache`), and Up and Down on an empty input recall them.
 
-use crate::{api::utilities::Adapter, snd::control::AudioControl};
+use crate::{
+    api::{
+        attachment::Attachment,
+        compare::{compare_models, ComparisonEntry},
+        conversation::ChatMessage,
+        error::ApiError,
+        model_info::estimate_tokens,
+        persona::list_personas,
+        usage::UsageTotals,
+        utilities::{Adapter, ApiProvider},
+    },
+    cfg::config::InterfaceSettings,
+    ctx::{
+        cache::{get_recent_prompts, remember_prompt, CacheSettings, FastCache},
+        db::{ContextDb, HistoryQuery},
+        memory::{add_memories_to_conversation, LongTermMemory, MemorySettings},
+        session::{generate_session_name, Session, SessionMessage},
+    },
+    snd::control::AudioControl,
+    tui::{constants::*, style::remove_colors},
+};
 use anyhow::Result;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Local, Utc};
 use crossterm::event::{self, Event, KeyCode};
 use ratatui::{
     backend::CrosstermBackend,
@@ -17,24 +42,20 @@ use ratatui::{
 };
 use std::collections::HashMap;
 use std::io::Stdout;
+use tokio::task::JoinHandle;
 use tui_textarea::TextArea;
 
 #[derive(Hash, Eq, PartialEq, Clone, Debug)]
 pub struct ChatId(String);
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum InputMode {
+    #[default]
     Normal,
     Insert,
     Visual,
 }
 
-impl Default for InputMode {
-    fn default() -> Self {
-        Self::Normal
-    }
-}
-
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 enum ConnectionStatus {
     #[default]
@@ -44,12 +65,34 @@ enum ConnectionStatus {
     Error,
 }
 
+impl ConnectionStatus {
+    /// Classifies a failed API call: `Disconnected` if the provider could not be reached at all, otherwise `Error`.
+    fn from_error(error: &anyhow::Error) -> Self {
+        match error.downcast_ref::<ApiError>() {
+            Some(api_error) if api_error.is_connection_error() => ConnectionStatus::Disconnected,
+            _ => ConnectionStatus::Error,
+        }
+    }
+}
+
 #[derive(Default)]
 struct StatusLine {
     mode: InputMode,
     connection_status: ConnectionStatus,
     message_count: usize,
     current_model: String,
+    /// The provider which answered the last reply (which may be a fallback provider).
+    current_provider: ApiProvider,
+    /// True if the last reply was answered by a fallback provider rather than the configured one.
+    is_fallback: bool,
+    /// Share of the model's context window taken up by the conversation, if the context window is known.
+    context_usage: Option<f64>,
+    /// Tokens used and money spent by the replies of this chat.
+    session_usage: UsageTotals,
+    /// Money spent today across every chat (from the usage ledger).
+    today_cost: f64,
+    /// The daily budget in USD, if one is set.
+    daily_budget: Option<f64>,
 }
 
 /// Represents a single message in the chat
@@ -57,7 +100,39 @@ struct StatusLine {
 pub struct Message {
     content: String,
     timestamp: DateTime<Utc>,
-    is_user: bool, // true if from user, false if from bot
+    is_user: bool,                // true if from user, false if from bot
+    is_error: bool,               // true if the bot message is an error report rather than a reply
+    is_notice: bool, // true if the message is a notice from nyota (i.e. a command result) rather than part of the conversation
+    attachments: Vec<Attachment>, // files attached to a user message
+}
+
+impl Message {
+    /// Converts the message into a role-tagged `ChatMessage` for the API adapter.
+    /// Error reports are not part of the conversation and return `None`.
+    fn to_chat_message(&self) -> Option<ChatMessage> {
+        if self.is_notice {
+            return None;
+        }
+        match (self.is_user, self.is_error) {
+            (true, _) => {
+                Some(ChatMessage::user(&self.content).with_attachments(self.attachments.clone()))
+            }
+            (false, false) => Some(ChatMessage::assistant(&self.content)),
+            (false, true) => None,
+        }
+    }
+
+    /// Creates a notice from nyota, which is shown in the chat but never sent to the AI.
+    fn notice(content: &str, is_error: bool) -> Self {
+        Self {
+            content: String::from(content),
+            timestamp: Utc::now(),
+            is_user: false,
+            is_error,
+            is_notice: true,
+            attachments: Vec::new(),
+        }
+    }
 }
 
 /// Main chat interface state
@@ -72,10 +147,35 @@ pub struct ChatInterface<'a> {
     api_adapter: Adapter,
     /// Status information
     status: StatusLine,
+    /// Files attached with `/attach`, sent with the next message
+    pending_attachments: Vec<Attachment>,
+    /// Models set with `/compare`, which each message is sent to side by side (empty when not comparing)
+    compare_models: Vec<String>,
+    /// The replies of the compared models to the last message
+    comparison: Vec<ComparisonEntry>,
+    /// Layout and warning settings, from the `[interface]` table of the config
+    interface: InterfaceSettings,
+    /// The session the chat is saved to, whose name is shown in the title of the message area
+    session: Session,
+    /// The context database the messages and commands of the chat are recorded in (`None` if it cannot be opened)
+    context_db: Option<ContextDb>,
+    /// The number of messages of the session already recorded in the context database
+    recorded_message_count: usize,
+    /// The long-term memory, which recalls facts with each message and remembers those of the chat (`None` if it is off)
+    memory: Option<LongTermMemory>,
+    /// The number of messages of the session whose facts were already remembered
+    remembered_message_count: usize,
+    /// The fast cache the recent prompts are kept in, and its settings
+    prompt_cache: FastCache,
+    cache_settings: CacheSettings,
+    /// The position among the recent prompts of the one recalled into the input (`None` if the input was typed)
+    recalled_prompt: Option<usize>,
+    /// The refresh of the provider's model list started with the chat, until it is stored in the catalogue
+    model_refresh: Option<(ApiProvider, JoinHandle<Result<Vec<String>>>)>,
 }
 
 impl<'a> ChatInterface<'a> {
-    pub async fn new(api_adapter: Adapter) -> Self {
+    pub async fn new(api_adapter: Adapter, interface: InterfaceSettings) -> Self {
         let mut input = TextArea::default();
         input.set_block(
             Block::default()
@@ -85,36 +185,278 @@ impl<'a> ChatInterface<'a> {
         );
 
         let adapter_default_model = String::from(api_adapter.get_current_model());
+        let adapter_default_provider = api_adapter.get_current_provider();
         let connection_status = Self::test_init_status(&api_adapter).await;
+        let usage_ledger = api_adapter.get_usage_ledger();
+        let today_cost = usage_ledger.get_today().get_total().cost;
+        let daily_budget = usage_ledger.get_daily_budget();
+        // Refresh the provider's model list in the background if the cached one is stale, so the chat is drawn without waiting
+        let mut refresh_adapter = api_adapter.clone();
+        let model_refresh =
+            tokio::spawn(
+                async move { refresh_adapter.list_models(adapter_default_provider).await },
+            );
+        // The chat works without its history being recorded, which is noted in the chat
+        let (context_db, mut messages) = match ContextDb::open() {
+            Ok(context_db) => (Some(context_db), Vec::new()),
+            Err(e) => (
+                None,
+                vec![Message::notice(
+                    &format!("The chat is not recorded in the history: {}", e),
+                    true,
+                )],
+            ),
+        };
+        // Likewise without the long-term memory
+        let memory = match MemorySettings::read() {
+            Ok(memory_settings) if memory_settings.enabled => {
+                LongTermMemory::open(memory_settings).map(Some)
+            }
+            Ok(_) => Ok(None),
+            Err(e) => Err(e),
+        }
+        .unwrap_or_else(|e| {
+            messages.push(Message::notice(
+                &format!("The long-term memory is off: {}", e),
+                true,
+            ));
+            None
+        });
+        // The recent prompts are kept in Redis if it can be reached, and otherwise in memory for this chat
+        let cache_settings = CacheSettings::read().unwrap_or_else(|e| {
+            messages.push(Message::notice(
+                &format!(
+                    "The recent prompts are kept with the default cache settings: {}",
+                    e
+                ),
+                true,
+            ));
+            CacheSettings::new()
+        });
+        let prompt_cache = FastCache::connect(&cache_settings);
+        if let Some(fallback_reason) = prompt_cache.get_fallback_reason() {
+            messages.push(Message::notice(
+                &format!(
+                    "The recent prompts are kept in memory for this chat: {}",
+                    fallback_reason
+                ),
+                true,
+            ));
+        }
         Self {
-            messages: Vec::new(),
+            messages,
             input,
             should_quit: false,
             api_adapter,
             status: StatusLine {
                 mode: InputMode::Normal,
-                connection_status: connection_status,
+                connection_status,
                 message_count: 0,
-                current_model: adapter_default_model,
+                current_model: adapter_default_model.clone(),
+                current_provider: adapter_default_provider,
+                is_fallback: false,
+                context_usage: None,
+                session_usage: UsageTotals::new(),
+                today_cost,
+                daily_budget,
             },
+            pending_attachments: Vec::new(),
+            compare_models: Vec::new(),
+            comparison: Vec::new(),
+            interface,
+            session: Session::new(
+                &generate_session_name(),
+                &adapter_default_model,
+                adapter_default_provider,
+            ),
+            context_db,
+            recorded_message_count: 0,
+            memory,
+            remembered_message_count: 0,
+            prompt_cache,
+            cache_settings,
+            recalled_prompt: None,
+            model_refresh: Some((adapter_default_provider, model_refresh)),
         }
     }
 
+    /// Stores the model list refreshed in the background in the catalogue, once it has been fetched.
+    /// Failures leave the catalogue as it was.
+    async fn finish_model_refresh(&mut self) {
+        if !self
+            .model_refresh
+            .as_ref()
+            .is_some_and(|(_, model_refresh)| model_refresh.is_finished())
+        {
+            return;
+        }
+        if let Some((provider, model_refresh)) = self.model_refresh.take() {
+            if let Ok(Ok(models)) = model_refresh.await {
+                self.api_adapter
+                    .get_catalogue_mut()
+                    .set_models(provider, models);
+            }
+        }
+    }
+
+    /// Continues the session: its messages and usage are restored, and its model is used if it is still available.
+    /// The chat is saved to the session from then on.
+    pub fn open_session(&mut self, session: Session) {
+        // The messages of the session were recorded as they were sent, and their facts remembered when it was closed
+        self.recorded_message_count = session.messages.len();
+        self.remembered_message_count = session.messages.len();
+        self.messages = session
+            .messages
+            .iter()
+            .cloned()
+            .map(Message::from)
+            .collect();
+        self.status.message_count = self.messages.len();
+        self.status.session_usage = session.usage.clone();
+        if session.model != self.api_adapter.get_current_model() {
+            match self.api_adapter.use_model(&session.model) {
+                Ok(()) => {
+                    self.status.current_model = String::from(self.api_adapter.get_current_model());
+                    self.status.current_provider = self.api_adapter.get_current_provider();
+                }
+                Err(e) => self.messages.push(Message::notice(
+                    &format!(
+                        "Continuing with {}: {}",
+                        self.api_adapter.get_current_model(),
+                        e
+                    ),
+                    true,
+                )),
+            }
+        }
+        let conversation: Vec<ChatMessage> = self
+            .messages
+            .iter()
+            .filter_map(Message::to_chat_message)
+            .collect();
+        self.status.context_usage = self.get_context_usage(&conversation);
+        self.session = session;
+    }
+
+    /// Saves the chat to its session, with the model last used and the usage of its replies,
+    /// and records the messages added since it was last saved in the context database.
+    /// A chat without messages is not saved; a failure to save or record is shown as a notice.
+    fn save_session(&mut self) {
+        if self.messages.is_empty() {
+            return;
+        }
+        self.session.messages = self.messages.iter().map(SessionMessage::from).collect();
+        self.session.model = String::from(self.api_adapter.get_current_model());
+        self.session.provider = self.api_adapter.get_current_provider();
+        self.session.usage = self.status.session_usage.clone();
+        if let Err(e) = self.session.save() {
+            self.messages.push(Message::notice(&format!("{}", e), true));
+        }
+        if let Some(context_db) = &mut self.context_db {
+            let new_messages = self
+                .session
+                .messages
+                .get(self.recorded_message_count..)
+                .unwrap_or_default();
+            match context_db.record_messages(&self.session, new_messages) {
+                Ok(()) => self.recorded_message_count = self.session.messages.len(),
+                Err(e) => self.messages.push(Message::notice(
+                    &format!("The chat is not recorded in the history: {}", e),
+                    true,
+                )),
+            }
+        }
+    }
+
+    /// Remembers the lasting facts of the messages added since the chat was opened, if the long-term memory is on.
+    /// The facts remembered, or the failure to remember them, are shown as a notice.
+    async fn remember_chat(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    ) -> Result<()> {
+        if self.memory.is_none() {
+            return Ok(());
+        }
+        let new_messages: Vec<ChatMessage> = self
+            .messages
+            .get(self.remembered_message_count..)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(Message::to_chat_message)
+            .collect();
+        if new_messages.is_empty() {
+            return Ok(());
+        }
+        self.status.connection_status = ConnectionStatus::Thinking;
+        self.draw(terminal)?;
+        let Some(memory) = &self.memory else {
+            return Ok(());
+        };
+        let remembered = memory
+            .extract(&self.api_adapter, Some(&self.session), &new_messages)
+            .await;
+        self.status.connection_status = ConnectionStatus::Connected;
+        self.remembered_message_count = self.messages.len();
+        match remembered {
+            Ok(memories) if memories.is_empty() => {}
+            Ok(memories) => self.messages.push(Message::notice(
+                &format!(
+                    "Remembered: {}",
+                    memories
+                        .iter()
+                        .map(|memory| memory.content.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(" | ")
+                ),
+                false,
+            )),
+            Err(e) => self.messages.push(Message::notice(
+                &format!("The facts of the chat are not remembered: {}", e),
+                true,
+            )),
+        }
+        Ok(())
+    }
+
+    /// Returns the conversation with the facts the long-term memory recalls for the prompt, if it is on.
+    /// A memory which cannot be recalled is shown as a notice, and the conversation is sent without it.
+    async fn with_recalled_memories(
+        &mut self,
+        mut conversation: Vec<ChatMessage>,
+        prompt: &str,
+    ) -> Vec<ChatMessage> {
+        let Some(memory) = &mut self.memory else {
+            return conversation;
+        };
+        match memory.recall(&self.api_adapter, prompt).await {
+            Ok(memories) => add_memories_to_conversation(
+                &mut conversation,
+                &self.api_adapter.get_persona().system_prompt,
+                &memories,
+            ),
+            Err(e) => self.messages.push(Message::notice(
+                &format!("Sent without memories: {}", e),
+                true,
+            )),
+        }
+        conversation
+    }
+
     async fn test_init_status(api_adapter: &Adapter) -> ConnectionStatus {
         match api_adapter.send_test_request("test").await {
             Ok(_) => ConnectionStatus::Connected,
-            Err(_) => ConnectionStatus::Error,
+            Err(e) => ConnectionStatus::from_error(&e),
         }
     }
 
     /// Creates the main layout divisions with status line
-    fn create_layout(area: Rect) -> (Rect, Rect, Rect) {
+    fn create_layout(&self, area: Rect) -> (Rect, Rect, Rect) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Min(1),    // Message area (expands)
-                Constraint::Length(5), // Input area (fixed)
-                Constraint::Length(1), // Status line (single line)
+                Constraint::Min(1),                              // Message area (expands)
+                Constraint::Length(self.interface.input_height), // Input area (fixed)
+                Constraint::Length(1),                           // Status line (single line)
             ])
             .split(area);
 
@@ -145,7 +487,20 @@ impl<'a> ChatInterface<'a> {
             Style::default().fg(Color::Magenta),
         );
 
-        let status_line = Line::from(vec![
+        // Highlight the provider when a fallback provider answered the last reply
+        let provider = if self.status.is_fallback {
+            Span::styled(
+                format!("Fallback: {}", self.status.current_provider),
+                Style::default().fg(Color::Yellow),
+            )
+        } else {
+            Span::styled(
+                format!("Provider: {}", self.status.current_provider),
+                Style::default().fg(Color::Magenta),
+            )
+        };
+
+        let mut status_spans = vec![
             Span::raw(" "),
             mode_indicator,
             Span::raw(" | "),
@@ -153,8 +508,73 @@ impl<'a> ChatInterface<'a> {
             Span::raw(" | "),
             msg_count,
             Span::raw(" | "),
-            model,
-        ]);
+        ];
+        if self.compare_models.is_empty() {
+            status_spans.extend([provider, Span::raw(" | "), model]);
+        } else {
+            status_spans.push(Span::styled(
+                format!("Comparing: {} models", self.compare_models.len()),
+                Style::default().fg(Color::Magenta),
+            ));
+        }
+
+        // Warn as the conversation approaches the model's context window
+        if let Some(context_usage) = self.status.context_usage {
+            let context_color = if context_usage >= 1.0 {
+                Color::Red
+            } else if context_usage >= self.interface.context_warning_ratio {
+                Color::Yellow
+            } else {
+                Color::Cyan
+            };
+            status_spans.push(Span::raw(" | "));
+            status_spans.push(Span::styled(
+                format!("Context: {:.0}%", context_usage * 100.0),
+                Style::default().fg(context_color),
+            ));
+        }
+
+        // Show what this chat has used, and what has been spent today against the budget
+        let session_total = self.status.session_usage.get_total();
+        if session_total.requests > 0 {
+            status_spans.push(Span::raw(" | "));
+            status_spans.push(Span::styled(
+                format!(
+                    "Tokens: {} (${:.4})",
+                    session_total.get_total_tokens(),
+                    session_total.cost
+                ),
+                Style::default().fg(Color::Cyan),
+            ));
+        }
+        let today_spend = match self.status.daily_budget {
+            Some(daily_budget) => {
+                let budget_color = if self.status.today_cost >= daily_budget {
+                    Color::Red
+                } else if self.status.today_cost
+                    >= daily_budget * self.interface.budget_warning_ratio
+                {
+                    Color::Yellow
+                } else {
+                    Color::Cyan
+                };
+                Some(Span::styled(
+                    format!("Today: ${:.2}/${:.2}", self.status.today_cost, daily_budget),
+                    Style::default().fg(budget_color),
+                ))
+            }
+            None if self.status.today_cost > 0.0 => Some(Span::styled(
+                format!("Today: ${:.2}", self.status.today_cost),
+                Style::default().fg(Color::Cyan),
+            )),
+            None => None,
+        };
+        if let Some(today_spend) = today_spend {
+            status_spans.push(Span::raw(" | "));
+            status_spans.push(today_spend);
+        }
+
+        let status_line = Line::from(status_spans);
 
         let status_widget = Paragraph::new(status_line)
             .style(Style::default().bg(Color::Black))
@@ -163,46 +583,546 @@ impl<'a> ChatInterface<'a> {
         frame.render_widget(status_widget, area);
     }
 
-    async fn submit_message(&mut self) {
+    async fn submit_message(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    ) -> Result<()> {
         let input_content = self.input.lines().join("\n");
+        self.recalled_prompt = None;
+        if input_content.trim().starts_with(COMMAND_PREFIX) {
+            self.input.select_all();
+            self.input.delete_char();
+            self.run_command(input_content.trim());
+            self.status.message_count = self.messages.len();
+            return Ok(());
+        }
+        if !input_content.trim().is_empty() {
+            self.keep_recent_prompt(&input_content);
+        }
+        if !self.compare_models.is_empty() && !input_content.trim().is_empty() {
+            self.input.select_all();
+            self.input.delete_char();
+            return self.submit_comparison(&input_content, terminal).await;
+        }
         if !input_content.trim().is_empty() {
             // Add user message
             self.messages.push(Message {
                 content: input_content.to_string(),
                 timestamp: Utc::now(),
                 is_user: true,
+                is_error: false,
+                is_notice: false,
+                attachments: std::mem::take(&mut self.pending_attachments),
             });
 
             // Clear the input
             self.input.select_all();
             self.input.delete_char();
 
-            // Update message count in status
-            self.status.message_count = self.messages.len();
+            // Send the whole transcript so the AI keeps the context of earlier turns
+            let conversation: Vec<ChatMessage> = self
+                .messages
+                .iter()
+                .filter_map(Message::to_chat_message)
+                .collect();
 
-            // TODO: Here you would typically send the message to the AI
-            // and add its response
-            // For now, let's just add a mock response
+            // Don't send a conversation which would overflow the model's context window - give the input back instead
+            let context_length = self.get_context_length();
+            self.status.context_usage = self.get_context_usage(&conversation);
+            if let Some(context_length) = context_length {
+                let conversation_tokens = estimate_tokens(&conversation);
+                if conversation_tokens >= context_length {
+                    if let Some(unsent) = self.messages.pop() {
+                        self.pending_attachments = unsent.attachments;
+                    }
+                    self.input.insert_str(&input_content);
+                    self.messages.push(Message {
+                        content: format!(
+                            "Not sent: the conversation (~{} tokens) would overflow the context window of {} ({} tokens).",
+                            conversation_tokens,
+                            self.api_adapter.get_current_model(),
+                            context_length
+                        ),
+                        timestamp: Utc::now(),
+                        is_user: false,
+                        is_error: true,
+                        is_notice: false,
+                        attachments: Vec::new(),
+                    });
+                    self.status.message_count = self.messages.len();
+                    return Ok(());
+                }
+            }
 
-            let llm_talk = match self.api_adapter.send_to_llm(&input_content).await {
-                Ok(response) => response,
-                Err(e) => format!("{}", e),
-            };
+            let conversation = self
+                .with_recalled_memories(conversation, &input_content)
+                .await;
 
+            // Add an empty AI message which grows in place as the reply streams in
             self.messages.push(Message {
-                content: llm_talk,
+                content: String::new(),
                 timestamp: Utc::now(),
                 is_user: false,
+                is_error: false,
+                is_notice: false,
+                attachments: Vec::new(),
             });
+
+            // Update message count in status
+            self.status.message_count = self.messages.len();
+            self.status.connection_status = ConnectionStatus::Thinking;
+            self.draw(terminal)?;
+
+            match self.stream_reply(&conversation, terminal).await {
+                Ok(()) => {
+                    self.status.connection_status = ConnectionStatus::Connected;
+                    let conversation: Vec<ChatMessage> = self
+                        .messages
+                        .iter()
+                        .filter_map(Message::to_chat_message)
+                        .collect();
+                    self.status.context_usage = self.get_context_usage(&conversation);
+                }
+                Err(e) => {
+                    self.status.connection_status = ConnectionStatus::from_error(&e);
+                    if let Some(reply) = self.messages.last_mut() {
+                        if !reply.content.is_empty() {
+                            reply.content.push('\n');
+                        }
+                        reply.content.push_str(&format!("{}", e));
+                        reply.is_error = true;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the prompt to every compared model at once, and shows their replies side by side.
+    /// Compared prompts and replies are noted in the chat but are not part of the conversation.
+    /// Adds the prompt to the recent prompts; a failure to keep it is shown as a notice.
+    fn keep_recent_prompt(&mut self, prompt: &str) {
+        if let Err(e) = remember_prompt(&mut self.prompt_cache, prompt, &self.cache_settings) {
+            self.messages.push(Message::notice(
+                &format!("The prompt is not kept among the recent prompts: {}", e),
+                true,
+            ));
         }
     }
 
+    /// Replaces the input with the recent prompt before (`is_earlier`) or after the one recalled, or with an empty input past the latest.
+    /// Returns false, leaving the input alone, if it holds text which was typed rather than recalled.
+    fn recall_prompt(&mut self, is_earlier: bool) -> bool {
+        let is_input_empty = self.input.lines().iter().all(|line| line.is_empty());
+        if self.recalled_prompt.is_none() && !is_input_empty {
+            return false;
+        }
+        let recent_prompts = match get_recent_prompts(&mut self.prompt_cache) {
+            Ok(recent_prompts) => recent_prompts,
+            Err(e) => {
+                self.messages.push(Message::notice(
+                    &format!("The recent prompts cannot be recalled: {}", e),
+                    true,
+                ));
+                return true;
+            }
+        };
+        let recalled_prompt = match (self.recalled_prompt, is_earlier) {
+            (None, false) => return true,
+            (None, true) => 0,
+            (Some(index), true) => (index + 1).min(recent_prompts.len().saturating_sub(1)),
+            (Some(0), false) => {
+                self.input.select_all();
+                self.input.delete_char();
+                self.recalled_prompt = None;
+                return true;
+            }
+            (Some(index), false) => index - 1,
+        };
+        if let Some(prompt) = recent_prompts.get(recalled_prompt) {
+            self.input.select_all();
+            self.input.delete_char();
+            self.input.insert_str(prompt);
+            self.recalled_prompt = Some(recalled_prompt);
+        }
+        true
+    }
+
+    async fn submit_comparison(
+        &mut self,
+        prompt: &str,
+        terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    ) -> Result<()> {
+        let message = ChatMessage::user(prompt)
+            .with_attachments(std::mem::take(&mut self.pending_attachments));
+        self.messages.push(Message::notice(
+            &format!("Compared {}: {}", self.compare_models.join(", "), prompt),
+            false,
+        ));
+        self.comparison.clear();
+        self.status.message_count = self.messages.len();
+        self.status.connection_status = ConnectionStatus::Thinking;
+        self.draw(terminal)?;
+
+        self.comparison = compare_models(&self.api_adapter, &self.compare_models, &[message]).await;
+        for reply in self
+            .comparison
+            .iter()
+            .filter_map(|entry| entry.outcome.as_ref().ok())
+        {
+            if let Some(usage) = reply.usage {
+                self.status
+                    .session_usage
+                    .add(&reply.model, usage, reply.cost);
+            }
+            for warning in &reply.warnings {
+                self.messages.push(Message::notice(warning, true));
+            }
+        }
+        self.status.today_cost = self
+            .api_adapter
+            .get_usage_ledger()
+            .get_today()
+            .get_total()
+            .cost;
+        self.status.connection_status =
+            match self.comparison.iter().find(|entry| entry.outcome.is_ok()) {
+                Some(_) => ConnectionStatus::Connected,
+                None => ConnectionStatus::Error,
+            };
+        Ok(())
+    }
+
+    /// Runs a chat command, answering with a notice:
+    /// `/set` shows the generation parameters, `/set <key> <value>` changes one (`none` unsets it, `options.<name>` sets an Ollama option),
+    /// `/persona` lists the personas and `/persona <name>` switches to one,
+    /// `/attach` lists the files attached to the next message and `/attach <path>` attaches one (`none` detaches them),
+    /// `/compare` shows the compared models and `/compare <model> <model>...` sends each message to them side by side (`none` stops comparing),
+    /// `/history` lists the latest chats and `/history <text>` searches the messages of every chat,
+    /// `/memory` lists the remembered facts and `/memory pin|unpin|forget <id>` changes one.
+    /// The command and its result are recorded in the context database.
+    fn run_command(&mut self, command_line: &str) {
+        let mut command_parts = command_line.splitn(3, char::is_whitespace);
+        let command = command_parts.next().unwrap_or_default();
+        let first_argument = command_parts.next().map(str::trim).unwrap_or_default();
+        let rest = command_parts.next().map(str::trim).unwrap_or_default();
+
+        let notice = match (command, first_argument) {
+            (SET_COMMAND, "") => {
+                let generation_params = self.api_adapter.get_effective_generation_params();
+                Ok(serde_json::to_string(&generation_params).unwrap_or_default())
+            }
+            (SET_COMMAND, key) => self.set_generation_param(key, rest),
+            (PERSONA_COMMAND, "") => Ok(format!(
+                "Persona: {} (available: {})",
+                self.api_adapter.get_persona().name,
+                list_personas().join(", ")
+            )),
+            (PERSONA_COMMAND, persona_name) => self
+                .api_adapter
+                .use_persona(persona_name)
+                .map(|_| format!("Persona: {}", persona_name)),
+            (ATTACH_COMMAND, "") => Ok(self.get_pending_attachments_notice()),
+            (ATTACH_COMMAND, NONE_ARGUMENT) if rest.is_empty() => {
+                self.pending_attachments.clear();
+                Ok(self.get_pending_attachments_notice())
+            }
+            (ATTACH_COMMAND, _) => {
+                // Paths may contain spaces
+                let path = command_line[ATTACH_COMMAND.len()..].trim();
+                self.attach_file(path)
+            }
+            (COMPARE_COMMAND, "") => Ok(self.get_compare_models_notice()),
+            (COMPARE_COMMAND, NONE_ARGUMENT) if rest.is_empty() => {
+                self.compare_models.clear();
+                self.comparison.clear();
+                Ok(self.get_compare_models_notice())
+            }
+            (COMPARE_COMMAND, _) => self.set_compare_models(&command_line[COMPARE_COMMAND.len()..]),
+            (HISTORY_COMMAND, "") => self.get_history_notice(),
+            (HISTORY_COMMAND, _) => {
+                self.search_history(command_line[HISTORY_COMMAND.len()..].trim())
+            }
+            (MEMORY_COMMAND, "") => self.get_memory_notice(),
+            (MEMORY_COMMAND, action) => self.change_memory(action, rest),
+            _ => Err(anyhow::Error::msg(format!(
+                "Unknown command `{}` (commands: {}, {}, {}, {}, {}, {})",
+                command,
+                SET_COMMAND,
+                PERSONA_COMMAND,
+                ATTACH_COMMAND,
+                COMPARE_COMMAND,
+                HISTORY_COMMAND,
+                MEMORY_COMMAND
+            ))),
+        };
+        if let Some(context_db) = &self.context_db {
+            let arguments = command_line[command.len()..].trim();
+            if let Err(e) = context_db.record_action(&self.session, command, arguments, &notice) {
+                self.messages.push(Message::notice(
+                    &format!("The command is not recorded in the history: {}", e),
+                    true,
+                ));
+            }
+        }
+        self.messages.push(match notice {
+            Ok(notice) => Message::notice(&notice, false),
+            Err(e) => Message::notice(&format!("{}", e), true),
+        });
+    }
+
+    /// Lists the latest chats recorded in the context database, with their number of messages.
+    fn get_history_notice(&self) -> Result<String> {
+        let sessions = self.get_context_db()?.list_sessions()?;
+        if sessions.is_empty() {
+            return Ok(String::from("No chats in the history yet."));
+        }
+        let session_list: Vec<String> = sessions
+            .iter()
+            .take(HISTORY_RESULT_LIMIT)
+            .map(|session| format!("{} ({} messages)", session.name, session.message_count))
+            .collect();
+        Ok(format!(
+            "Latest chats: {} | {} <text> searches them",
+            session_list.join(", "),
+            HISTORY_COMMAND
+        ))
+    }
+
+    /// Searches the messages of every chat recorded in the context database, showing the latest matches.
+    fn search_history(&self, search: &str) -> Result<String> {
+        let query = HistoryQuery {
+            search: Some(String::from(search)),
+            limit: HISTORY_RESULT_LIMIT,
+            ..HistoryQuery::new()
+        };
+        let messages = self.get_context_db()?.find_messages(&query)?;
+        if messages.is_empty() {
+            return Ok(format!("No messages in the history match `{}`.", search));
+        }
+        let matches: Vec<String> = messages
+            .iter()
+            .map(|message| {
+                let snippet: String = message
+                    .content
+                    .split_whitespace()
+                    .collect::<Vec<&str>>()
+                    .join(" ")
+                    .chars()
+                    .take(HISTORY_SNIPPET_LENGTH)
+                    .collect();
+                format!(
+                    "[{} {}] {}: {}",
+                    message.session,
+                    message
+                        .timestamp
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M"),
+                    message.role,
+                    snippet
+                )
+            })
+            .collect();
+        Ok(format!(
+            "History matching `{}`:\n{}",
+            search,
+            matches.join("\n")
+        ))
+    }
+
+    /// Lists the facts remembered from the chats, the pinned ones first.
+    fn get_memory_notice(&self) -> Result<String> {
+        let memories = self.get_context_db()?.list_memories()?;
+        if memories.is_empty() {
+            return Ok(String::from("No memories yet."));
+        }
+        let memory_list: Vec<String> = memories
+            .iter()
+            .take(MEMORY_RESULT_LIMIT)
+            .map(|memory| {
+                format!(
+                    "{} {}{}",
+                    memory.id,
+                    if memory.pinned { "📌 " } else { "" },
+                    memory.content
+                )
+            })
+            .collect();
+        Ok(format!(
+            "Memories:\n{}\n{} {}|{}|{} <id> changes them",
+            memory_list.join("\n"),
+            MEMORY_COMMAND,
+            MEMORY_PIN_ARGUMENT,
+            MEMORY_UNPIN_ARGUMENT,
+            MEMORY_FORGET_ARGUMENT
+        ))
+    }
+
+    /// Pins, unpins or forgets the memory with the id.
+    fn change_memory(&self, action: &str, memory_id: &str) -> Result<String> {
+        let usage = || {
+            anyhow::Error::msg(format!(
+                "Usage: {} {}|{}|{} <id>",
+                MEMORY_COMMAND, MEMORY_PIN_ARGUMENT, MEMORY_UNPIN_ARGUMENT, MEMORY_FORGET_ARGUMENT
+            ))
+        };
+        let context_db = self.get_context_db()?;
+        let memory_id: i64 = memory_id.parse().map_err(|_| usage())?;
+        match action {
+            MEMORY_PIN_ARGUMENT => context_db
+                .set_memory_pinned(memory_id, true)
+                .map(|_| format!("Pinned memory {}", memory_id)),
+            MEMORY_UNPIN_ARGUMENT => context_db
+                .set_memory_pinned(memory_id, false)
+                .map(|_| format!("Unpinned memory {}", memory_id)),
+            MEMORY_FORGET_ARGUMENT => context_db
+                .forget_memory(memory_id)
+                .map(|_| format!("Forgot memory {}", memory_id)),
+            _ => Err(usage()),
+        }
+    }
+
+    /// Returns the context database, or an error if it could not be opened.
+    fn get_context_db(&self) -> Result<&ContextDb> {
+        self.context_db.as_ref().ok_or_else(|| {
+            anyhow::Error::msg(
+                "The history is not available (the context database could not be opened).",
+            )
+        })
+    }
+
+    /// Sets a single generation parameter, keeping the previous value if the new one is not valid for the current model.
+    fn set_generation_param(&mut self, key: &str, value: &str) -> Result<String> {
+        let mut generation_params = self.api_adapter.get_generation_params().clone();
+        generation_params.set(key, value)?;
+        self.api_adapter
+            .get_persona()
+            .generation_params
+            .merge(&generation_params)
+            .validate(
+                self.api_adapter.get_current_provider(),
+                self.api_adapter.get_current_model(),
+            )?;
+        self.api_adapter.set_generation_params(generation_params);
+        Ok(format!("{} = {}", key, value))
+    }
+
+    /// Attaches a file to the next message, unless it cannot be read or sent to the current model.
+    fn attach_file(&mut self, path: &str) -> Result<String> {
+        let attachment = Attachment::load(path)?;
+        self.api_adapter
+            .check_attachments(std::slice::from_ref(&attachment))?;
+        self.pending_attachments.push(attachment);
+        Ok(self.get_pending_attachments_notice())
+    }
+
+    /// Describes the files attached to the next message.
+    fn get_pending_attachments_notice(&self) -> String {
+        if self.pending_attachments.is_empty() {
+            return String::from("No attachments for the next message.");
+        }
+        format!(
+            "Attached to the next message: {}",
+            get_attachment_names(&self.pending_attachments)
+        )
+    }
+
+    /// Sets the models to compare, given separated by spaces or commas, unless any of them is not offered by a known provider.
+    fn set_compare_models(&mut self, model_list: &str) -> Result<String> {
+        let models: Vec<String> = model_list
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|model| !model.is_empty())
+            .map(String::from)
+            .collect();
+        if !(2..=MAX_COMPARE_MODELS).contains(&models.len()) {
+            return Err(anyhow::Error::msg(format!(
+                "Compare between 2 and {} models (i.e. {} gpt-4o-mini claude-3-5-haiku-20241022)",
+                MAX_COMPARE_MODELS, COMPARE_COMMAND
+            )));
+        }
+        let catalogue = self.api_adapter.get_catalogue();
+        for model in &models {
+            catalogue.get_provider_for_model(model)?;
+        }
+        self.compare_models = models;
+        self.comparison.clear();
+        Ok(self.get_compare_models_notice())
+    }
+
+    /// Describes the models each message is compared between.
+    fn get_compare_models_notice(&self) -> String {
+        if self.compare_models.is_empty() {
+            return String::from("Not comparing models.");
+        }
+        format!("Comparing: {}", self.compare_models.join(", "))
+    }
+
+    /// Returns the context window of the current model in tokens, if known.
+    fn get_context_length(&self) -> Option<u64> {
+        self.api_adapter
+            .get_model_info(self.api_adapter.get_current_model())
+            .and_then(|model_info| model_info.context_length)
+    }
+
+    /// Returns the share of the current model's context window taken up by the conversation, if the context window is known.
+    fn get_context_usage(&self, conversation: &[ChatMessage]) -> Option<f64> {
+        self.get_context_length()
+            .filter(|context_length| *context_length > 0)
+            .map(|context_length| estimate_tokens(conversation) as f64 / context_length as f64)
+    }
+
+    /// Streams the AI reply to the conversation into the last message, redrawing the interface as each chunk arrives.
+    async fn stream_reply(
+        &mut self,
+        conversation: &[ChatMessage],
+        terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    ) -> Result<()> {
+        let mut stream = self.api_adapter.stream_conversation(conversation).await?;
+        self.status.current_provider = stream.get_provider();
+        self.status.current_model = String::from(stream.get_model());
+        self.status.is_fallback = stream.get_provider() != self.api_adapter.get_current_provider();
+        while let Some(chunk) = stream.next_chunk().await? {
+            if let Some(reply) = self.messages.last_mut() {
+                reply.content.push_str(&chunk);
+            }
+            self.draw(terminal)?;
+        }
+
+        // The finished stream has recorded its usage to the ledger
+        if let Some(usage) = stream.get_usage() {
+            self.status
+                .session_usage
+                .add(stream.get_model(), usage, stream.get_cost());
+        }
+        for warning in stream.get_warnings() {
+            self.messages.push(Message::notice(warning, true));
+        }
+        self.status.today_cost = self
+            .api_adapter
+            .get_usage_ledger()
+            .get_today()
+            .get_total()
+            .cost;
+        Ok(())
+    }
+
+    /// Draws the chat interface to the whole terminal area.
+    fn draw(&mut self, terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+        terminal.draw(|frame| {
+            self.render(frame, frame.area());
+            if !self.interface.use_colors {
+                remove_colors(frame);
+            }
+        })?;
+        Ok(())
+    }
+
     pub fn render(&mut self, frame: &mut Frame, area: Rect) {
-        let (message_area, input_area, status_area) = Self::create_layout(area);
+        let (message_area, input_area, status_area) = self.create_layout(area);
 
         // Render message history area
         let messages_block = Block::default()
-            .title(" Messages ")
+            .title(format!(" Messages | {} ", self.session.name))
             .borders(Borders::ALL)
             .border_type(BorderType::Rounded)
             .border_style(Style::default().fg(Color::Cyan));
@@ -212,18 +1132,26 @@ impl<'a> ChatInterface<'a> {
             .messages
             .iter()
             .map(|msg| {
-                let prefix = if msg.is_user { "You: " } else { "AI: " };
-                Line::from(vec![
+                let (prefix, prefix_color) = match (msg.is_user, msg.is_notice) {
+                    (true, _) => ("You: ", Color::Green),
+                    (false, true) => ("nyota: ", Color::Magenta),
+                    (false, false) => ("AI: ", Color::Blue),
+                };
+                let mut message_spans = vec![
                     Span::styled(
-                        prefix,
-                        Style::default().fg(if msg.is_user {
-                            Color::Green
-                        } else {
-                            Color::Blue
-                        }),
+                        format!("[{}] ", msg.timestamp.format("%H:%M")),
+                        Style::default().fg(Color::DarkGray),
                     ),
+                    Span::styled(prefix, Style::default().fg(prefix_color)),
                     Span::raw(msg.content.clone()),
-                ])
+                ];
+                if !msg.attachments.is_empty() {
+                    message_spans.push(Span::styled(
+                        format!(" 📎 {}", get_attachment_names(&msg.attachments)),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                Line::from(message_spans)
             })
             .collect();
 
@@ -231,7 +1159,20 @@ impl<'a> ChatInterface<'a> {
             .block(messages_block)
             .wrap(ratatui::widgets::Wrap { trim: true });
 
-        frame.render_widget(messages_paragraph, message_area);
+        // When comparing, the replies of the compared models take the lower part of the message area, side by side
+        if self.compare_models.is_empty() {
+            frame.render_widget(messages_paragraph, message_area);
+        } else {
+            let comparison_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Percentage(self.interface.compare_history_percentage),
+                    Constraint::Min(1),
+                ])
+                .split(message_area);
+            frame.render_widget(messages_paragraph, comparison_chunks[0]);
+            self.render_comparison(frame, comparison_chunks[1]);
+        }
 
         // Render input area
         frame.render_widget(&self.input, input_area);
@@ -240,28 +1181,97 @@ impl<'a> ChatInterface<'a> {
         self.render_status_line(frame, status_area);
     }
 
+    /// Renders one pane per compared model, each titled with the provider, latency, tokens and cost of its reply.
+    fn render_comparison(&self, frame: &mut Frame, area: Rect) {
+        let pane_count = self.compare_models.len() as u32;
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(
+                self.compare_models
+                    .iter()
+                    .map(|_| Constraint::Ratio(1, pane_count)),
+            )
+            .split(area);
+
+        for (index, model) in self.compare_models.iter().enumerate() {
+            let entry = self
+                .comparison
+                .get(index)
+                .filter(|entry| entry.model == *model);
+            let mut title = format!(" {} ", model);
+            let body = match entry {
+                Some(entry) => {
+                    if let Some(provider) = entry.provider {
+                        title.push_str(&format!("| {} ", provider));
+                    }
+                    title.push_str(&format!("| {:.1}s ", entry.latency.as_secs_f64()));
+                    match &entry.outcome {
+                        Ok(reply) => {
+                            if let Some(usage) = &reply.usage {
+                                title.push_str(&format!("| {} tokens ", usage.get_total_tokens()));
+                            }
+                            if let Some(cost) = reply.cost {
+                                title.push_str(&format!("| ${:.4} ", cost));
+                            }
+                            Paragraph::new(reply.content.clone())
+                        }
+                        Err(e) => {
+                            Paragraph::new(format!("{}", e)).style(Style::default().fg(Color::Red))
+                        }
+                    }
+                }
+                None if self.status.connection_status == ConnectionStatus::Thinking => {
+                    Paragraph::new("Waiting for a reply…")
+                        .style(Style::default().fg(Color::DarkGray))
+                }
+                None => Paragraph::new(""),
+            };
+            let pane_block = Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(Color::Magenta));
+            frame.render_widget(
+                body.block(pane_block)
+                    .wrap(ratatui::widgets::Wrap { trim: true }),
+                panes[index],
+            );
+        }
+    }
+
     pub async fn run(&mut self, terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
-        loop {
-            terminal.draw(|frame| {
-                self.render(frame, frame.area());
-            })?;
+        while !self.should_quit {
+            self.draw(terminal)?;
 
             if let Event::Key(key) = event::read()? {
+                self.finish_model_refresh().await;
                 match key.code {
                     KeyCode::Esc => {
-                        break;
+                        // Recorded before its facts are remembered, so that the memories can refer to the chat
+                        self.save_session();
+                        self.remember_chat(terminal).await?;
+                        self.save_session();
+                        self.should_quit = true;
                     }
                     KeyCode::Enter => {
                         AudioControl::play_message_sent_noise().await?; // Play sound when sending message
-                        self.submit_message().await;
-                        // Handle message sending
+                        self.submit_message(terminal).await?;
+                        // Save the chat once each message (or command) has been answered
+                        self.save_session();
+                    }
+                    KeyCode::Up | KeyCode::Down => {
+                        if !self.recall_prompt(key.code == KeyCode::Up) {
+                            self.input.input(key);
+                        }
                     }
                     KeyCode::Backspace => {
                         // AudioControl::play_backspace_noise().await?; // Play sound when deleting
+                        self.recalled_prompt = None;
                         self.input.input(key);
                     }
                     _ => {
                         // AudioControl::play_keystroke_noise().await?; // Play sound for normal keystrokes
+                        self.recalled_prompt = None;
                         self.input.input(key);
                     }
                 }
@@ -271,6 +1281,41 @@ impl<'a> ChatInterface<'a> {
     }
 }
 
+impl From<&Message> for SessionMessage {
+    fn from(message: &Message) -> Self {
+        Self {
+            content: message.content.clone(),
+            timestamp: message.timestamp,
+            is_user: message.is_user,
+            is_error: message.is_error,
+            is_notice: message.is_notice,
+            attachments: message.attachments.clone(),
+        }
+    }
+}
+
+impl From<SessionMessage> for Message {
+    fn from(session_message: SessionMessage) -> Self {
+        Self {
+            content: session_message.content,
+            timestamp: session_message.timestamp,
+            is_user: session_message.is_user,
+            is_error: session_message.is_error,
+            is_notice: session_message.is_notice,
+            attachments: session_message.attachments,
+        }
+    }
+}
+
+/// Returns the file names of the attachments as a comma-separated list.
+fn get_attachment_names(attachments: &[Attachment]) -> String {
+    attachments
+        .iter()
+        .map(Attachment::get_name)
+        .collect::<Vec<&str>>()
+        .join(", ")
+}
+
 /// Manages multiple chat interfaces
 pub struct _ChatManager<'a> {
     /// All active chat sessions
@@ -278,30 +1323,38 @@ pub struct _ChatManager<'a> {
     /// Currently focused chat
     active_chat: ChatId,
     /// Input mode (Normal, Insert, Visual - vim-like)
-    mode: InputMode,
+    _mode: InputMode,
+    /// The API adapter new chats start from, built before the terminal entered raw mode (so it never prompts for the key store passphrase)
+    api_adapter: Adapter,
+    /// Layout and warning settings of new chats
+    interface: InterfaceSettings,
 }
 
 impl<'a> _ChatManager<'a> {
-    pub async fn _new() -> Self {
+    pub async fn _new(api_adapter: Adapter, interface: InterfaceSettings) -> Self {
         let default_chat = ChatId("main".to_string());
         let mut chats = HashMap::new();
         chats.insert(
             default_chat.clone(),
-            ChatInterface::new(Adapter::new()).await,
+            ChatInterface::new(api_adapter.clone(), interface.clone()).await,
         );
 
         Self {
             chats,
             active_chat: default_chat,
-            mode: InputMode::Normal,
+            _mode: InputMode::Normal,
+            api_adapter,
+            interface,
         }
     }
 
     /// Switch to a different chat
     pub async fn _switch_chat(&mut self, id: ChatId) {
         if !self.chats.contains_key(&id) {
-            self.chats
-                .insert(id.clone(), ChatInterface::new(Adapter::new()).await);
+            self.chats.insert(
+                id.clone(),
+                ChatInterface::new(self.api_adapter.clone(), self.interface.clone()).await,
+            );
         }
         self.active_chat = id;
     }
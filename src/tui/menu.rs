@@ -1,6 +1,7 @@
 //! A simple menu for the nyota tui
 
 use crate::snd::control::AudioControl;
+use crate::tui::style::remove_colors;
 use anyhow::Result;
 use crossterm::event::{self, Event, KeyCode, KeyEventKind};
 use ratatui::{
@@ -24,10 +25,12 @@ struct MenuItem {
 pub struct Menu {
     items: Vec<MenuItem>,
     state: ListState, // Keeps track of which item is selected
+    use_colors: bool, // Draw the menu in color (off with `--no-color`)
 }
 
 pub enum MenuAction {
     Interactive,
+    Sessions,
     Task,
     Development,
     Help,
@@ -46,6 +49,10 @@ impl Menu {
                     "Start an interactive REPL session (Multi-turn Conversation)",
                 ),
             },
+            MenuItem {
+                title: String::from("Sessions"),
+                description: String::from("Reopen, rename, fork or delete a past chat"),
+            },
             MenuItem {
                 title: String::from("Task Mode"),
                 description: String::from("Execute an isolated task (Single-turn Conversation)"),
@@ -72,6 +79,7 @@ impl Menu {
         let mut menu = Self {
             items,
             state: ListState::default(),
+            use_colors: true,
         };
 
         // Set the initial selection to the first item
@@ -80,6 +88,11 @@ impl Menu {
         menu
     }
 
+    /// Sets whether the menu is drawn in color
+    pub fn set_use_colors(&mut self, use_colors: bool) {
+        self.use_colors = use_colors;
+    }
+
     /// Move the selection down
     pub fn next(&mut self) {
         let i = match self.state.selected() {
@@ -167,6 +180,9 @@ impl Menu {
             terminal.draw(|frame| {
                 // Use the entire screen area
                 self.draw(frame, frame.area());
+                if !self.use_colors {
+                    remove_colors(frame);
+                }
             })?;
 
             // Handle keyboard input
@@ -192,11 +208,12 @@ impl Menu {
                             if let Some(selected) = self.state.selected() {
                                 return Ok(match selected {
                                     0 => MenuAction::Interactive,
-                                    1 => MenuAction::Task,
-                                    2 => MenuAction::Development,
-                                    3 => MenuAction::Help,
-                                    4 => MenuAction::About,
-                                    5 => MenuAction::Exit,
+                                    1 => MenuAction::Sessions,
+                                    2 => MenuAction::Task,
+                                    3 => MenuAction::Development,
+                                    4 => MenuAction::Help,
+                                    5 => MenuAction::About,
+                                    6 => MenuAction::Exit,
                                     _ => MenuAction::Exit,
                                 });
                             }
@@ -213,3 +230,9 @@ impl Menu {
         Ok(MenuAction::Exit)
     }
 }
+
+impl Default for Menu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
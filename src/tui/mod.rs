@@ -11,24 +11,35 @@
 //!  The `tui` is encapsulated and can be avoided if a user prefers a command-line interface.
 
 //! ## Example
-//! ```rust
+//! ```rust,no_run
 //! use nyota::tui::banner::get_banner;
-//! use nyota::tui::splash::Splash;
+//! use nyota::tui::splash::SplashScreen;
 //!
+//! # fn example() -> anyhow::Result<()> {
 //! // Display a banner
 //! println!("{}", get_banner());
 //!
 //! // Display a splash screen
-//! SplashScreen::new().show();
+//! let mut terminal = ratatui::init();
+//! SplashScreen::new().show(&mut terminal)?;
+//! ratatui::restore();
+//! # Ok(())
+//! # }
 //!```
 //!
 //! ## Modules
 //! - `banner`: Banner module for Nyota. This module provides functions that return decorative ASCII art banners and plaques for the Nyota program.
+//! - `constants`: Constants module for Nyota. This module provides the values which tune the textual user interface.
 //! - `interactive`: Interactive module for Nyota. This module provides functions for creating interactive interfaces in Nyota.
 //! - `menu`: Menu module for Nyota. This module provides functions for creating menus in Nyota.
+//! - `sessions`: Sessions module for Nyota. This module provides the browser for the saved chat sessions, opened from the menu.
 //! - `splash`: Splash module for Nyota. This module provides functions for displaying splash screens in Nyota.
+//! - `style`: Style module for Nyota. This module provides helpers shared by the interfaces to adjust how they are drawn, such as without colors.
 
 pub mod banner;
+pub mod constants;
 pub mod interactive;
 pub mod menu;
+pub mod sessions;
 pub mod splash;
+pub mod style;
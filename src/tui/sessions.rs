@@ -0,0 +1,315 @@
+//! A browser for the saved chat sessions in the nyota tui.
+//! The browser lists the sessions saved by the chat interface (see `nyota::ctx::session`), the most recently updated first,
+//! and reopens, renames, forks or deletes the selected one. Deleting a session keeps its history in the context database (see `nyota::ctx::db`).
+
+use crate::ctx::db::ContextDb;
+use crate::ctx::session::{list_sessions, Session};
+use crate::snd::control::AudioControl;
+use crate::tui::style::remove_colors;
+use anyhow::Result;
+use chrono::Local;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    prelude::{Alignment, Modifier, Stylize},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, List, ListItem, ListState, Paragraph},
+    Frame, Terminal,
+};
+use std::io::Stdout;
+
+/// What the browser is waiting for
+enum BrowserPrompt {
+    /// Moving through the sessions
+    Browsing,
+    /// Typing the new name of the selected session
+    Renaming(String),
+    /// Typing the name of the copy of the selected session
+    Forking(String),
+    /// Confirming that the selected session is to be deleted
+    ConfirmingDelete,
+}
+
+/// The session browser
+pub struct SessionBrowser {
+    sessions: Vec<Session>,
+    state: ListState, // Keeps track of which session is selected
+    prompt: BrowserPrompt,
+    notice: Option<(String, bool)>, // The result of the last action, and whether it failed
+    use_colors: bool,               // Draw the browser in color (off with `--no-color`)
+}
+
+impl SessionBrowser {
+    /// Create a new browser with the saved sessions
+    pub fn new() -> Self {
+        let mut browser = Self {
+            sessions: Vec::new(),
+            state: ListState::default(),
+            prompt: BrowserPrompt::Browsing,
+            notice: None,
+            use_colors: true,
+        };
+        browser.reload();
+        browser
+    }
+
+    /// Sets whether the browser is drawn in color
+    pub fn set_use_colors(&mut self, use_colors: bool) {
+        self.use_colors = use_colors;
+    }
+
+    /// Reads the saved sessions again, keeping the selection in range
+    fn reload(&mut self) {
+        match list_sessions() {
+            Ok(sessions) => self.sessions = sessions,
+            Err(e) => self.notice = Some((format!("{}", e), true)),
+        }
+        let selected = match self.sessions.len() {
+            0 => None,
+            session_count => Some(self.state.selected().unwrap_or(0).min(session_count - 1)),
+        };
+        self.state.select(selected);
+    }
+
+    /// Returns the selected session, if there is one
+    fn get_selected(&self) -> Option<&Session> {
+        self.sessions.get(self.state.selected()?)
+    }
+
+    /// Move the selection down
+    fn next(&mut self) {
+        if self.sessions.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) if i + 1 < self.sessions.len() => i + 1,
+            _ => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    /// Move the selection up
+    fn previous(&mut self) {
+        if self.sessions.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(0) | None => self.sessions.len() - 1,
+            Some(i) => i - 1,
+        };
+        self.state.select(Some(i));
+    }
+
+    /// Renames, forks or deletes the selected session, as the prompt asked, and notes the result
+    fn apply_prompt(&mut self, prompt: BrowserPrompt) {
+        let Some(mut session) = self.get_selected().cloned() else {
+            return;
+        };
+        let old_name = session.name.clone();
+        let result = match &prompt {
+            BrowserPrompt::Renaming(new_name) => session
+                .rename(new_name.trim())
+                // The history recorded in the context database follows the session
+                .and_then(|_| ContextDb::open()?.rename_session(&old_name, &session.name))
+                .map(|_| format!("Renamed `{}` to `{}`", old_name, session.name)),
+            BrowserPrompt::Forking(new_name) => {
+                session.fork(new_name.trim()).map(|forked_session| {
+                    format!("Forked `{}` as `{}`", old_name, forked_session.name)
+                })
+            }
+            BrowserPrompt::ConfirmingDelete => {
+                Session::delete(&old_name).map(|_| format!("Deleted `{}`", old_name))
+            }
+            BrowserPrompt::Browsing => return,
+        };
+        self.notice = Some(match result {
+            Ok(notice) => (notice, false),
+            Err(e) => (format!("{}", e), true),
+        });
+        self.reload();
+    }
+
+    /// Returns the name of the selected session
+    fn get_selected_name(&self) -> String {
+        self.get_selected()
+            .map(|session| session.name.clone())
+            .unwrap_or_default()
+    }
+
+    /// Draw the browser on the screen
+    pub fn draw(&mut self, frame: &mut Frame, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(1),    // Sessions (expands)
+                Constraint::Length(1), // Prompt or help line (single line)
+            ])
+            .split(area);
+
+        let title_block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Double)
+            .border_style(Style::default().fg(Color::Cyan).bold())
+            .title(" Sessions ")
+            .title_alignment(Alignment::Center);
+
+        // Each session shows its name, then when it was last updated, its model, its length and what it cost
+        let items: Vec<ListItem> = self
+            .sessions
+            .iter()
+            .map(|session| {
+                let title = Line::from(vec![Span::styled(
+                    session.name.clone(),
+                    Style::default().fg(Color::White).bold(),
+                )]);
+                let description = Line::from(vec![Span::styled(
+                    format!(
+                        "{} | {} | {} messages | ${:.4}",
+                        session
+                            .updated_at
+                            .with_timezone(&Local)
+                            .format("%Y-%m-%d %H:%M"),
+                        session.model,
+                        session.messages.len(),
+                        session.usage.get_total().cost
+                    ),
+                    Style::default().fg(Color::Gray),
+                )]);
+                ListItem::new(vec![title, description, Line::from("")])
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(title_block)
+            .highlight_style(
+                Style::default()
+                    .bg(Color::Cyan)
+                    .fg(Color::Black)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol("> 💬 ");
+        frame.render_stateful_widget(list, chunks[0], &mut self.state);
+
+        let prompt_line = match (&self.prompt, &self.notice) {
+            (BrowserPrompt::Renaming(new_name), _) => Span::styled(
+                format!(" Rename to: {}▏", new_name),
+                Style::default().fg(Color::Yellow),
+            ),
+            (BrowserPrompt::Forking(new_name), _) => Span::styled(
+                format!(" Fork as: {}▏", new_name),
+                Style::default().fg(Color::Yellow),
+            ),
+            (BrowserPrompt::ConfirmingDelete, _) => Span::styled(
+                format!(" Delete `{}`? (y/n)", self.get_selected_name()),
+                Style::default().fg(Color::Red),
+            ),
+            (BrowserPrompt::Browsing, Some((notice, is_error))) => Span::styled(
+                format!(" {}", notice),
+                Style::default().fg(if *is_error { Color::Red } else { Color::Green }),
+            ),
+            (BrowserPrompt::Browsing, None) if self.sessions.is_empty() => Span::styled(
+                " No saved sessions yet | Esc back",
+                Style::default().fg(Color::Gray),
+            ),
+            (BrowserPrompt::Browsing, None) => Span::styled(
+                " Enter open | r rename | f fork | d delete | Esc back",
+                Style::default().fg(Color::Gray),
+            ),
+        };
+        frame.render_widget(Paragraph::new(Line::from(prompt_line)), chunks[1]);
+    }
+
+    /// Runs the browser until a session is chosen (which is returned to be reopened) or the user goes back (`None`).
+    pub async fn run(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    ) -> Result<Option<Session>> {
+        loop {
+            terminal.draw(|frame| {
+                self.draw(frame, frame.area());
+                if !self.use_colors {
+                    remove_colors(frame);
+                }
+            })?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let prompt = std::mem::replace(&mut self.prompt, BrowserPrompt::Browsing);
+            self.prompt = match (prompt, key.code) {
+                // Typing a name
+                (BrowserPrompt::Renaming(mut new_name), KeyCode::Char(c)) => {
+                    new_name.push(c);
+                    BrowserPrompt::Renaming(new_name)
+                }
+                (BrowserPrompt::Forking(mut new_name), KeyCode::Char(c)) => {
+                    new_name.push(c);
+                    BrowserPrompt::Forking(new_name)
+                }
+                (BrowserPrompt::Renaming(mut new_name), KeyCode::Backspace) => {
+                    new_name.pop();
+                    BrowserPrompt::Renaming(new_name)
+                }
+                (BrowserPrompt::Forking(mut new_name), KeyCode::Backspace) => {
+                    new_name.pop();
+                    BrowserPrompt::Forking(new_name)
+                }
+                (
+                    prompt @ (BrowserPrompt::Renaming(_) | BrowserPrompt::Forking(_)),
+                    KeyCode::Enter,
+                )
+                | (prompt @ BrowserPrompt::ConfirmingDelete, KeyCode::Char('y')) => {
+                    self.apply_prompt(prompt);
+                    BrowserPrompt::Browsing
+                }
+                // Any other key cancels the prompt
+                (BrowserPrompt::Renaming(_) | BrowserPrompt::Forking(_), KeyCode::Esc)
+                | (BrowserPrompt::ConfirmingDelete, _) => BrowserPrompt::Browsing,
+                (prompt @ (BrowserPrompt::Renaming(_) | BrowserPrompt::Forking(_)), _) => prompt,
+
+                // Browsing
+                (BrowserPrompt::Browsing, KeyCode::Char('q') | KeyCode::Esc) => return Ok(None),
+                (BrowserPrompt::Browsing, KeyCode::Down | KeyCode::Char('j')) => {
+                    AudioControl::play_menu_toggle().await?;
+                    self.next();
+                    BrowserPrompt::Browsing
+                }
+                (BrowserPrompt::Browsing, KeyCode::Up | KeyCode::Char('k')) => {
+                    AudioControl::play_menu_toggle().await?;
+                    self.previous();
+                    BrowserPrompt::Browsing
+                }
+                (BrowserPrompt::Browsing, KeyCode::Enter) => {
+                    if let Some(session) = self.get_selected() {
+                        return Ok(Some(session.clone()));
+                    }
+                    BrowserPrompt::Browsing
+                }
+                (BrowserPrompt::Browsing, KeyCode::Char('r')) if self.get_selected().is_some() => {
+                    self.notice = None;
+                    BrowserPrompt::Renaming(self.get_selected_name())
+                }
+                (BrowserPrompt::Browsing, KeyCode::Char('f')) if self.get_selected().is_some() => {
+                    self.notice = None;
+                    BrowserPrompt::Forking(format!("{}-fork", self.get_selected_name()))
+                }
+                (BrowserPrompt::Browsing, KeyCode::Char('d')) if self.get_selected().is_some() => {
+                    self.notice = None;
+                    BrowserPrompt::ConfirmingDelete
+                }
+                (BrowserPrompt::Browsing, _) => BrowserPrompt::Browsing,
+            };
+        }
+    }
+}
+
+impl Default for SessionBrowser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -2,6 +2,7 @@
 //! This module provides the `SplashScreen` struct which is used to display a splash screen with the logo file when the application is launched.
 //! It is currently hard-coded to display the current version of the logo file. Loading anything else could produce errors.
 //! In theory, the splash screen can be used throughout the program.
+use crate::cfg::config::SplashSettings;
 use anyhow::Result;
 use ratatui::backend::CrosstermBackend;
 use ratatui::Terminal;
@@ -18,12 +19,17 @@ impl SplashScreen {
     /// Creates a new `SplashScreen` instance with the default configuration.
     /// The default configuration includes the logo file and the number of render steps.
     pub fn new() -> Self {
+        Self::with_settings(&SplashSettings::new())
+    }
+
+    /// Creates a new `SplashScreen` instance with the number of render steps and colors of the `[splash]` settings (see `nyota::cfg::config`).
+    pub fn with_settings(settings: &SplashSettings) -> Self {
         Self {
             config: SplashConfig {
                 image_data: include_bytes!("../../logo_with_text_small.png"),
                 sha256sum: Some("5750a49fe90b697f5f2e1be2a522f8eaad92310e9056ce74e5c090bb9d0b42c9"),
-                render_steps: 6,
-                use_colors: true,
+                render_steps: i32::try_from(settings.render_steps).unwrap_or(i32::MAX),
+                use_colors: settings.use_colors,
             },
         }
     }
@@ -43,3 +49,9 @@ impl SplashScreen {
         Ok(())
     }
 }
+
+impl Default for SplashScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
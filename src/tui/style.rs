@@ -0,0 +1,12 @@
+//! Styling helpers for the nyota tui.
+//! This module provides the functions shared by the chat interface and the menu to adjust how a frame is drawn, such as drawing it without colors.
+
+use ratatui::{style::Color, Frame};
+
+/// Resets the foreground and background colors of every cell drawn to the frame, keeping their modifiers (i.e. bold).
+/// This is called after a frame is rendered, so that the interface can be drawn without colors (`--no-color`).
+pub fn remove_colors(frame: &mut Frame) {
+    for cell in frame.buffer_mut().content.iter_mut() {
+        cell.set_fg(Color::Reset).set_bg(Color::Reset);
+    }
+}